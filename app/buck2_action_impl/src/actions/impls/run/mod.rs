@@ -35,6 +35,7 @@ use buck2_build_api::interpreter::rule_defs::cmd_args::DefaultCommandLineContext
 use buck2_build_api::interpreter::rule_defs::cmd_args::SimpleCommandLineArtifactVisitor;
 use buck2_build_api::interpreter::rule_defs::provider::builtin::worker_info::WorkerInfo;
 use buck2_core::category::Category;
+use buck2_core::execution_types::executor_config::LocalResourceLimits;
 use buck2_core::fs::buck_out_path::BuckOutPath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_events::dispatch::span_async;
@@ -167,6 +168,8 @@ pub(crate) struct UnregisteredRunAction {
     pub(crate) allow_dep_file_cache_upload: bool,
     pub(crate) force_full_hybrid_if_capable: bool,
     pub(crate) unique_input_inodes: bool,
+    pub(crate) no_sandbox: bool,
+    pub(crate) local_resource_limits: LocalResourceLimits,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -638,7 +641,10 @@ impl IncrementalActionExecutable for RunAction {
             .with_outputs_cleanup(!self.inner.no_outputs_cleanup)
             .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions())
             .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
-            .with_unique_input_inodes(self.inner.unique_input_inodes);
+            .with_unique_input_inodes(self.inner.unique_input_inodes)
+            .with_disable_sandbox(self.inner.no_sandbox)
+            .with_local_resource_limits(self.inner.local_resource_limits)
+            .with_allow_cache_upload(self.inner.allow_cache_upload);
 
         let (mut dep_file_bundle, req) = if let Some(visitor) = dep_file_visitor {
             let bundle = make_dep_file_bundle(ctx, visitor, cmdline_digest, req.paths())?;
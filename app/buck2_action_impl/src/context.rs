@@ -39,6 +39,7 @@ use buck2_build_api::interpreter::rule_defs::transitive_set::TransitiveSet;
 use buck2_build_api::interpreter::rule_defs::transitive_set::TransitiveSetDefinition;
 use buck2_common::cas_digest::CasDigest;
 use buck2_core::category::Category;
+use buck2_core::execution_types::executor_config::LocalResourceLimits;
 use buck2_core::execution_types::executor_config::RemoteExecutorUseCase;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_execute::execute::request::OutputType;
@@ -124,6 +125,8 @@ enum RunActionError {
     InvalidWeight(i32),
     #[error("`weight` and `weight_percentage` cannot both be passed")]
     DuplicateWeightsSpecified,
+    #[error("`{0}` must be a positive integer, got `{1}`")]
+    InvalidResourceLimit(&'static str, i32),
     #[error("`dep_files` value with key `{}` has an invalid count of associated outputs. Expected 1, got {}.", .key, .count)]
     InvalidDepFileOutputs { key: String, count: usize },
     #[error("`dep_files` with keys `{}` and {} are using the same tag", .first, .second)]
@@ -621,6 +624,12 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
     ///     and `--local-only` CLI flags. The CLI flags take precedence.
     ///     * The `force_full_hybrid_if_capable` option overrides the `use_limited_hybrid` hybrid.
     ///     The options listed above take precedence if set.
+    /// * `no_sandbox`: if local actions are configured to run in a sandbox (with
+    ///   `buck2.sandbox_local_actions`), run this one without it. This is an escape hatch for
+    ///   actions that need to access files they cannot declare as inputs.
+    /// * `local_memory_limit_mebibytes` and `local_cpu_limit_percent`: limits on the memory and CPU
+    ///   (as a percentage of one CPU) the action may use when it runs locally. Those override the
+    ///   limits set on the executor (see `CommandExecutorConfig`).
     ///
     /// When actions execute, they'll do so from the root of the repository. As they execute,
     /// actions have exclusive access to their output directory.
@@ -656,6 +665,9 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             Either<ValueOf<'v, &'v WorkerRunInfo<'v>>, ValueOf<'v, &'v RunInfo<'v>>>,
         >,
         #[starlark(require = named, default = false)] unique_input_inodes: bool,
+        #[starlark(require = named, default = false)] no_sandbox: bool,
+        #[starlark(require = named)] local_memory_limit_mebibytes: Option<i32>,
+        #[starlark(require = named)] local_cpu_limit_percent: Option<i32>,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<NoneType> {
         struct RunCommandArtifactVisitor {
@@ -747,6 +759,16 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             }
         };
 
+        let positive = |name, v: Option<i32>| match v {
+            Some(v) if v < 1 => Err(RunActionError::InvalidResourceLimit(name, v)),
+            v => Ok(v.map(|v| v as u32)),
+        };
+        let local_resource_limits = LocalResourceLimits {
+            memory_bytes: positive("local_memory_limit_mebibytes", local_memory_limit_mebibytes)?
+                .map(|v| u64::from(v) * 1024 * 1024),
+            cpu_percent: positive("local_cpu_limit_percent", local_cpu_limit_percent)?,
+        };
+
         let starlark_env = match env {
             None => Value::new_none(),
             Some(env) => {
@@ -835,6 +857,8 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             allow_dep_file_cache_upload,
             force_full_hybrid_if_capable,
             unique_input_inodes,
+            no_sandbox,
+            local_resource_limits,
         };
         this.state().register_action(
             artifacts.inputs,
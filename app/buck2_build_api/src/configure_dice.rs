@@ -57,6 +57,22 @@ pub async fn configure_dice_for_buck(
     dice.set_io_provider(io);
     dice.set_digest_config(digest_config);
 
+    let memory_budget_mebibytes = root_config
+        .map(|c| c.parse::<usize>("buck2", "dice_memory_budget_mebibytes"))
+        .transpose()?
+        .flatten();
+    if let Some(mebibytes) = memory_budget_mebibytes {
+        match which_dice {
+            WhichDice::Legacy => {
+                // legacy dice keeps every value until it is invalidated
+                tracing::warn!(
+                    "Ignoring `buck2.dice_memory_budget_mebibytes`, which requires `buck2.dice = modern`"
+                );
+            }
+            WhichDice::Modern => dice.set_memory_budget(mebibytes * 1024 * 1024)?,
+        }
+    }
+
     let dice = dice.build(detect_cycles);
     let mut dice_ctx = dice.updater();
     dice_ctx.set_none_cell_resolver()?;
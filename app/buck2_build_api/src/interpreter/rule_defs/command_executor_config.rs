@@ -17,6 +17,7 @@ use buck2_core::execution_types::executor_config::CommandGenerationOptions;
 use buck2_core::execution_types::executor_config::Executor;
 use buck2_core::execution_types::executor_config::HybridExecutionLevel;
 use buck2_core::execution_types::executor_config::LocalExecutorOptions;
+use buck2_core::execution_types::executor_config::LocalResourceLimits;
 use buck2_core::execution_types::executor_config::PathSeparatorKind;
 use buck2_core::execution_types::executor_config::RemoteEnabledExecutor;
 use buck2_core::execution_types::executor_config::RemoteExecutorOptions;
@@ -41,6 +42,8 @@ enum CommandExecutorConfigErrors {
     MissingField(&'static str),
     #[error("invalid value in `{0}`")]
     InvalidField(&'static str),
+    #[error("`{0}` must be a positive integer, got `{1}`")]
+    InvalidResourceLimit(&'static str, i32),
     #[error(
         "executor config must specify at least `local_enabled = True` or `remote_enabled = True`"
     )]
@@ -71,6 +74,8 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
     /// * `remote_execution_max_input_files_mebibytes`: The maximum input file size (in bytes) that remote execution can support
     /// * `remote_execution_queue_time_threshold_s`: The maximum time in seconds we are willing to wait
     /// in the RE queue for remote execution to start running our action
+    /// * `remote_execution_priority`: The priority to request for actions executed remotely. How
+    /// this is interpreted is up to the RE backend, but lower values generally run sooner
     /// * `remote_execution_use_case`: The use case to use when communicating with RE
     /// * `use_limited_hybrid`: Whether to use the limited hybrid executor
     /// * `allow_limited_hybrid_fallbacks`: Whether to allow fallbacks
@@ -81,6 +86,11 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
     /// * `max_cache_upload_mebibytes`: Maximum size to upload in cache uploads
     /// * `experimental_low_pass_filter`: Whether to use the experimental low pass filter
     /// * `remote_output_paths`: How to express output paths to RE
+    /// * `local_memory_limit_mebibytes`: The maximum amount of memory a local action may use. This
+    /// is enforced when `buck2.local_action_cgroups` is set, and counts towards the local memory
+    /// budget (`buck2.local_action_memory_budget_mebibytes`) regardless
+    /// * `local_cpu_limit_percent`: The maximum amount of CPU a local action may use, as a
+    /// percentage of one CPU. This is enforced when `buck2.local_action_cgroups` is set
     #[starlark(as_type = StarlarkCommandExecutorConfig)]
     fn CommandExecutorConfig<'v>(
         #[starlark(require = named)] local_enabled: bool,
@@ -93,6 +103,7 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
         remote_execution_max_input_files_mebibytes: NoneOr<i32>,
         #[starlark(default = NoneOr::None, require = named)]
         remote_execution_queue_time_threshold_s: NoneOr<i32>,
+        #[starlark(default = NoneOr::None, require = named)] remote_execution_priority: NoneOr<i32>,
         #[starlark(default = NoneType, require = named)] remote_execution_use_case: Value<'v>,
         #[starlark(default = false, require = named)] use_limited_hybrid: bool,
         #[starlark(default = false, require = named)] allow_limited_hybrid_fallbacks: bool,
@@ -105,6 +116,10 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
         >,
         #[starlark(default = false, require = named)] experimental_low_pass_filter: bool,
         #[starlark(default = NoneOr::None, require = named)] remote_output_paths: NoneOr<&str>,
+        #[starlark(default = NoneOr::None, require = named)] local_memory_limit_mebibytes: NoneOr<
+            i32,
+        >,
+        #[starlark(default = NoneOr::None, require = named)] local_cpu_limit_percent: NoneOr<i32>,
     ) -> anyhow::Result<StarlarkCommandExecutorConfig> {
         let command_executor_config = {
             let remote_execution_max_input_files_mebibytes =
@@ -153,8 +168,23 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
             };
 
             let local_options = if local_enabled {
+                let positive = |name, v: NoneOr<i32>| match v.into_option() {
+                    Some(v) if v < 1 => {
+                        Err(CommandExecutorConfigErrors::InvalidResourceLimit(name, v))
+                    }
+                    v => Ok(v.map(|v| v as u32)),
+                };
+                let memory_bytes =
+                    positive("local_memory_limit_mebibytes", local_memory_limit_mebibytes)?
+                        .map(|v| u64::from(v) * 1024 * 1024);
+                let cpu_percent = positive("local_cpu_limit_percent", local_cpu_limit_percent)?;
+
                 Some(LocalExecutorOptions {
                     use_persistent_workers,
+                    resource_limits: LocalResourceLimits {
+                        memory_bytes,
+                        cpu_percent,
+                    },
                 })
             } else {
                 None
@@ -175,6 +205,7 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
                 Some(RemoteExecutorOptions {
                     re_max_input_files_bytes,
                     re_max_queue_time_ms,
+                    re_priority: remote_execution_priority.into_option(),
                 })
             } else {
                 None
@@ -16,6 +16,8 @@ use buck2_build_api_derive::internal_provider;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_interpreter::types::configured_providers_label::StarlarkConfiguredProvidersLabel;
 use either::Either;
+use host_sharing::WeightClass;
+use host_sharing::WeightPercentage;
 use indexmap::IndexMap;
 use starlark::any::ProvidesStaticType;
 use starlark::coerce::Coerce;
@@ -98,6 +100,23 @@ pub struct ExternalRunnerTestInfoGen<V> {
     /// should be ignored when executing tests even if those are passed as required from test runner.
     #[provider(field_type = DictType<String, Option<StarlarkConfiguredProvidersLabel>>)]
     local_resources: V,
+
+    /// How heavy the test is, in permits of the host running it, like the `weight` of
+    /// `ctx.actions.run`. Tests which don't set this or `weight_percentage` have a weight of 1.
+    #[provider(field_type = NoneOr<i32>)]
+    weight: V,
+
+    /// How heavy the test is, as a percentage of the host running it. Cannot be used with
+    /// `weight`.
+    #[provider(field_type = NoneOr<i32>)]
+    weight_percentage: V,
+
+    /// Whether the result of running this test only depends on its inputs, so that a passing
+    /// run can be cached and reused while they don't change.
+    ///
+    /// Defaults to `None`, which is the same as `False`.
+    #[provider(field_type = NoneOr<bool>)]
+    cacheable: V,
 }
 
 // NOTE: All the methods here unwrap because we validate at freeze time.
@@ -152,6 +171,18 @@ impl FrozenExternalRunnerTestInfo {
         unwrap_all(iter_local_resources(self.local_resources.to_value())).collect()
     }
 
+    pub fn cacheable(&self) -> bool {
+        NoneOr::<bool>::unpack_value(self.cacheable.to_value())
+            .unwrap()
+            .into_option()
+            .unwrap_or(false)
+    }
+
+    /// The weight the test declared, if any.
+    pub fn weight_class(&self) -> Option<WeightClass> {
+        unpack_weight_class(self.weight.to_value(), self.weight_percentage.to_value()).unwrap()
+    }
+
     pub fn visit_artifacts(
         &self,
         visitor: &mut dyn CommandLineArtifactVisitor,
@@ -397,6 +428,37 @@ fn unpack_opt_executor<'v>(
     Ok(Some(executor))
 }
 
+fn unpack_weight_class<'v>(
+    weight: Value<'v>,
+    weight_percentage: Value<'v>,
+) -> anyhow::Result<Option<WeightClass>> {
+    let weight = NoneOr::<i32>::unpack_value(weight)
+        .context("`weight` must be an int if provided")?
+        .into_option();
+    let weight_percentage = NoneOr::<i32>::unpack_value(weight_percentage)
+        .context("`weight_percentage` must be an int if provided")?
+        .into_option();
+    match (weight, weight_percentage) {
+        (None, None) => Ok(None),
+        (Some(v), None) => {
+            if v < 1 {
+                Err(anyhow::anyhow!(
+                    "`weight` must be a positive integer, got `{}`",
+                    v
+                ))
+            } else {
+                Ok(Some(WeightClass::Permits(v as usize)))
+            }
+        }
+        (None, Some(v)) => Ok(Some(WeightClass::Percentage(
+            WeightPercentage::try_new(v).context("Invalid `weight_percentage`")?,
+        ))),
+        (Some(..), Some(..)) => Err(anyhow::anyhow!(
+            "`weight` and `weight_percentage` cannot both be passed"
+        )),
+    }
+}
+
 fn check_all<I, T>(it: I) -> anyhow::Result<()>
 where
     I: IntoIterator<Item = anyhow::Result<T>>,
@@ -430,7 +492,10 @@ where
         .context("`use_project_relative_paths` must be a bool if provided")?;
     NoneOr::<bool>::unpack_value(info.run_from_project_root.to_value())
         .context("`run_from_project_root` must be a bool if provided")?;
+    NoneOr::<bool>::unpack_value(info.cacheable.to_value())
+        .context("`cacheable` must be a bool if provided")?;
     unpack_opt_executor(info.default_executor.to_value()).context("Invalid `default_executor`")?;
+    unpack_weight_class(info.weight.to_value(), info.weight_percentage.to_value())?;
     info.test_type
         .to_value()
         .unpack_str()
@@ -452,6 +517,9 @@ fn external_runner_test_info_creator(globals: &mut GlobalsBuilder) {
         #[starlark(default = NoneType)] default_executor: Value<'v>,
         #[starlark(default = NoneType)] executor_overrides: Value<'v>,
         #[starlark(default = NoneType)] local_resources: Value<'v>,
+        #[starlark(default = NoneType)] weight: Value<'v>,
+        #[starlark(default = NoneType)] weight_percentage: Value<'v>,
+        #[starlark(default = NoneType)] cacheable: Value<'v>,
     ) -> anyhow::Result<ExternalRunnerTestInfo<'v>> {
         let res = ExternalRunnerTestInfo {
             test_type: r#type,
@@ -464,6 +532,9 @@ fn external_runner_test_info_creator(globals: &mut GlobalsBuilder) {
             default_executor,
             executor_overrides,
             local_resources,
+            weight,
+            weight_percentage,
+            cacheable,
         };
         validate_external_runner_test_info(&res)?;
         Ok(res)
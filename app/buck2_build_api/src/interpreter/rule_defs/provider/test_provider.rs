@@ -13,6 +13,8 @@ use buck2_core::cells::name::CellName;
 use buck2_test_api::data::ConfiguredTarget;
 use buck2_test_api::data::ExternalRunnerSpec;
 use buck2_test_api::data::ExternalRunnerSpecValue;
+use buck2_test_api::data::LocalResourceType;
+use buck2_test_api::data::TestNameFilter;
 use buck2_test_api::protocol::TestExecutor;
 use futures::future::BoxFuture;
 use futures::future::FutureExt;
@@ -33,6 +35,7 @@ pub trait TestProvider {
         target: ConfiguredTarget,
         executor: Arc<dyn TestExecutor + 'exec>,
         working_dir_cell: CellName,
+        test_filter: TestNameFilter,
     ) -> BoxFuture<'exec, anyhow::Result<()>>;
 }
 
@@ -50,6 +53,7 @@ impl TestProvider for FrozenExternalRunnerTestInfo {
         target: ConfiguredTarget,
         executor: Arc<dyn TestExecutor + 'exec>,
         working_dir_cell: CellName,
+        test_filter: TestNameFilter,
     ) -> BoxFuture<'exec, anyhow::Result<()>> {
         let mut handle_index = 0;
 
@@ -87,6 +91,18 @@ impl TestProvider for FrozenExternalRunnerTestInfo {
             contacts: self.contacts().map(|l| l.to_owned()).collect(),
             oncall: self.contacts().exactly_one().ok().map(str::to_owned),
             working_dir_cell,
+            test_filter,
+            weight_class: self.weight_class(),
+            // Types mapped to no target are ignored when a runner requires them, so there is
+            // no point asking for them.
+            local_resources: self
+                .local_resources()
+                .into_iter()
+                .filter(|(_, target)| target.is_some())
+                .map(|(name, _)| LocalResourceType {
+                    name: name.to_owned(),
+                })
+                .collect(),
         };
 
         async move { executor.external_runner_spec(spec).await }.boxed()
@@ -34,6 +34,11 @@ fn test_construction() -> anyhow::Result<()> {
             ExternalRunnerTestInfo(type = "foo", labels = ("foo",))
             ExternalRunnerTestInfo(type = "foo", use_project_relative_paths = True)
             ExternalRunnerTestInfo(type = "foo", run_from_project_root = True)
+            ExternalRunnerTestInfo(type = "foo", weight = 4)
+            ExternalRunnerTestInfo(type = "foo", weight_percentage = 50)
+            ExternalRunnerTestInfo(type = "foo", cacheable = True)
+            ExternalRunnerTestInfo(type = "foo", cacheable = None)
+            assert_eq(None, ExternalRunnerTestInfo(type = "foo").cacheable)
         "#
     );
     let mut tester = tester();
@@ -164,6 +169,16 @@ fn test_validation() -> anyhow::Result<()> {
         "`run_from_project_root`",
     );
 
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", cacheable = "foo")
+        "#
+        ),
+        "`cacheable`",
+    );
+
     tester.run_starlark_bzl_test_expecting_error(
         indoc!(
             r#"
@@ -184,6 +199,26 @@ fn test_validation() -> anyhow::Result<()> {
         "`executor_overrides`",
     );
 
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", weight = 0)
+        "#
+        ),
+        "`weight`",
+    );
+
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", weight = 2, weight_percentage = 50)
+        "#
+        ),
+        "cannot both be passed",
+    );
+
     Ok(())
 }
 
@@ -0,0 +1,301 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Explains why DICE keys were recomputed in a command, by linking the keys DICE evaluated to the
+//! keys the command changed, through the deps of the evaluated keys.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use dice::ChangedKey;
+use dice::EvaluatedKey;
+use dice::KeyId;
+
+/// The types of the keys whose recomputation we explain. Other recomputed keys are only reported
+/// when they are on the way from a changed key to one of those.
+const REPORTED_KEY_TYPES: &[&str] = &["AnalysisKey", "BuildKey"];
+
+/// The maximum number of nodes in a report.
+const MAX_REPORT_NODES: usize = 1000;
+
+/// A key evaluated by DICE, along with the ids of its deps. Keys are only displayed when they make
+/// it into the report.
+pub(crate) struct KeyEvaluatedSignal {
+    pub key: EvaluatedKey,
+    pub deps: Vec<KeyId>,
+}
+
+struct Node {
+    type_name: &'static str,
+    key: Arc<dyn Display + Send + Sync>,
+    /// The dep closest to a changed key among those that caused this one to be recomputed, or
+    /// `None` for changed keys.
+    parent: Option<usize>,
+    /// The other deps that caused this key to be recomputed.
+    other_parents: Vec<usize>,
+    /// The number of keys between this one and a changed key.
+    depth: u32,
+    injected: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct InvalidationTracker {
+    /// Parents always come before their children.
+    nodes: Vec<Node>,
+    by_key: HashMap<KeyId, usize>,
+    changed_keys: u64,
+    /// Whether some evaluations weren't tracked.
+    truncated: bool,
+}
+
+impl InvalidationTracker {
+    pub(crate) fn keys_changed(&mut self, changed: Vec<ChangedKey>) {
+        for changed in changed {
+            if self.by_key.contains_key(&changed.id) {
+                continue;
+            }
+            self.changed_keys += 1;
+            self.push(
+                changed.id,
+                Node {
+                    type_name: changed.type_name,
+                    key: Arc::new(changed.key),
+                    parent: None,
+                    other_parents: Vec::new(),
+                    depth: 0,
+                    injected: changed.injected,
+                },
+            );
+        }
+    }
+
+    /// Record an evaluated key if it was recomputed because some of its deps changed or were
+    /// themselves recomputed. Keys computed for the first time are ignored.
+    pub(crate) fn key_evaluated(&mut self, signal: KeyEvaluatedSignal) {
+        let KeyEvaluatedSignal { key, deps } = signal;
+        if !key.recomputed || self.by_key.contains_key(&key.id) {
+            return;
+        }
+
+        let mut parents = deps
+            .iter()
+            .filter_map(|dep| self.by_key.get(dep).copied())
+            .collect::<Vec<_>>();
+        // Attribute the recomputation to the dep closest to a changed key, so that the report
+        // shows the shortest path from a change to this key.
+        let closest = parents
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, p)| self.nodes[**p].depth)
+            .map(|(i, _)| i);
+        let parent = match closest {
+            Some(closest) => parents.remove(closest),
+            None => return,
+        };
+
+        self.push(
+            key.id,
+            Node {
+                type_name: key.type_name,
+                key: key.key,
+                parent: Some(parent),
+                other_parents: parents,
+                depth: self.nodes[parent].depth + 1,
+                injected: false,
+            },
+        );
+    }
+
+    pub(crate) fn evaluations_truncated(&mut self) {
+        self.truncated = true;
+    }
+
+    fn push(&mut self, id: KeyId, node: Node) {
+        self.by_key.insert(id, self.nodes.len());
+        self.nodes.push(node);
+    }
+
+    /// The report for this command, or `None` if it changed no keys.
+    pub(crate) fn report(&self) -> Option<buck2_data::DiceInvalidationReport> {
+        if self.changed_keys == 0 {
+            return None;
+        }
+
+        // Keep the reported keys along with the keys that lead to them.
+        let mut keep = vec![false; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !REPORTED_KEY_TYPES.contains(&node.type_name) {
+                continue;
+            }
+            let mut current = Some(i);
+            while let Some(c) = current {
+                if keep[c] {
+                    break;
+                }
+                keep[c] = true;
+                current = self.nodes[c].parent;
+            }
+        }
+
+        // Since parents come before their children, any prefix of the kept nodes includes the
+        // parents of its nodes. Other parents are only reported if they were kept.
+        let mut truncated = self.truncated;
+        let mut new_index = HashMap::new();
+        let mut nodes = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !keep[i] {
+                continue;
+            }
+            if nodes.len() == MAX_REPORT_NODES {
+                truncated = true;
+                break;
+            }
+            new_index.insert(i, nodes.len() as u64);
+            nodes.push(buck2_data::dice_invalidation_report::Node {
+                key: node.key.to_string(),
+                key_type: node.type_name.to_owned(),
+                parent: node.parent.map(|p| new_index[&p]),
+                injected: node.injected,
+                other_parents: node
+                    .other_parents
+                    .iter()
+                    .filter_map(|p| new_index.get(p).copied())
+                    .collect(),
+            });
+        }
+
+        Some(buck2_data::DiceInvalidationReport {
+            nodes,
+            changed_keys: self.changed_keys,
+            recomputed_keys: self.nodes.len() as u64 - self.changed_keys,
+            truncated,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changed(type_name: &'static str, key: &'static str) -> ChangedKey {
+        ChangedKey {
+            type_name,
+            id: KeyId::new(&key),
+            key: key.to_owned(),
+            injected: false,
+        }
+    }
+
+    fn evaluated(
+        key: (&'static str, &'static str),
+        deps: &[&'static str],
+        recomputed: bool,
+    ) -> KeyEvaluatedSignal {
+        KeyEvaluatedSignal {
+            key: EvaluatedKey {
+                type_name: key.0,
+                id: KeyId::new(&key.1),
+                key: Arc::new(key.1),
+                recomputed,
+            },
+            deps: deps.iter().map(KeyId::new).collect(),
+        }
+    }
+
+    fn recomputed(key: (&'static str, &'static str), deps: &[&'static str]) -> KeyEvaluatedSignal {
+        evaluated(key, deps, true)
+    }
+
+    #[test]
+    fn test_nothing_changed() {
+        let mut tracker = InvalidationTracker::default();
+        tracker.keys_changed(Vec::new());
+        tracker.key_evaluated(recomputed(("AnalysisKey", "root//:a"), &[]));
+        assert_eq!(tracker.report(), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut tracker = InvalidationTracker::default();
+        tracker.keys_changed(vec![
+            changed("ReadFileKey", "root//a/BUCK"),
+            changed("ReadFileKey", "root//b/BUCK"),
+        ]);
+        // Recomputed because of a changed file, and leads to an analysis key.
+        tracker.key_evaluated(recomputed(
+            ("InterpreterResultsKey", "root//a"),
+            &["root//a/BUCK"],
+        ));
+        // Recomputed, but doesn't lead to an analysis or action key.
+        tracker.key_evaluated(recomputed(
+            ("InterpreterResultsKey", "root//b"),
+            &["root//b/BUCK"],
+        ));
+        tracker.key_evaluated(recomputed(
+            ("AnalysisKey", "root//a:a"),
+            &["root//a:b", "root//a"],
+        ));
+        // Computed for the first time, even though one of its deps was recomputed.
+        tracker.key_evaluated(evaluated(("AnalysisKey", "root//b:b"), &["root//b"], false));
+
+        let report = tracker.report().unwrap();
+        assert_eq!(report.changed_keys, 2);
+        assert_eq!(report.recomputed_keys, 3);
+        assert!(!report.truncated);
+        assert_eq!(
+            report
+                .nodes
+                .iter()
+                .map(|n| (n.key.as_str(), n.parent))
+                .collect::<Vec<_>>(),
+            vec![
+                ("root//a/BUCK", None),
+                ("root//a", Some(0)),
+                ("root//a:a", Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_several_parents() {
+        let mut tracker = InvalidationTracker::default();
+        tracker.keys_changed(vec![
+            changed("ReadFileKey", "root//a/BUCK"),
+            changed("ReadFileKey", "root//a/src.txt"),
+        ]);
+        tracker.key_evaluated(recomputed(
+            ("InterpreterResultsKey", "root//a"),
+            &["root//a/BUCK"],
+        ));
+        tracker.key_evaluated(recomputed(("AnalysisKey", "root//a:b"), &["root//a"]));
+        // Attributed to the changed file, which is closer than the recomputed dep.
+        tracker.key_evaluated(recomputed(
+            ("AnalysisKey", "root//a:a"),
+            &["root//a:b", "root//a/src.txt"],
+        ));
+
+        let report = tracker.report().unwrap();
+        assert_eq!(report.recomputed_keys, 3);
+        assert_eq!(
+            report
+                .nodes
+                .iter()
+                .map(|n| (n.key.as_str(), n.parent, n.other_parents.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("root//a/BUCK", None, vec![]),
+                ("root//a/src.txt", None, vec![]),
+                ("root//a", Some(0), vec![]),
+                ("root//a:b", Some(2), vec![]),
+                ("root//a:a", Some(1), vec![3]),
+            ]
+        );
+    }
+}
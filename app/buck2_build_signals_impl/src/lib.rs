@@ -13,6 +13,9 @@ use std::any::Any;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
@@ -55,6 +58,9 @@ use buck2_node::nodes::eval_result::EvaluationResult;
 use derive_more::From;
 use dice::ActivationData;
 use dice::ActivationTracker;
+use dice::ChangedKey;
+use dice::EvaluatedKey;
+use dice::KeyId;
 use dupe::Dupe;
 use dupe::OptionDupedExt;
 use itertools::Itertools;
@@ -69,8 +75,15 @@ use tokio_stream::StreamExt;
 use crate::backend::backend::BuildListenerBackend;
 use crate::backend::default::DefaultBackend;
 use crate::backend::longest_path_graph::LongestPathGraphBackend;
+use crate::invalidation::InvalidationTracker;
+use crate::invalidation::KeyEvaluatedSignal;
 
 mod backend;
+mod invalidation;
+
+/// The maximum number of recomputed keys we track to explain why they were recomputed. This bounds
+/// the memory held by the tracked keys and their deps.
+const MAX_TRACKED_EVALUATIONS: usize = 100_000;
 
 /// A node in our critical path graph.
 #[derive(Hash, Eq, PartialEq, Clone, Dupe, Debug, From)]
@@ -173,6 +186,10 @@ enum BuildSignal {
     Evaluation(Evaluation),
     TopLevelTarget(TopLevelTargetSignal),
     FinalMaterialization(FinalMaterializationSignal),
+    KeysChanged(Vec<ChangedKey>),
+    KeyEvaluated(KeyEvaluatedSignal),
+    /// We stopped tracking evaluations after `MAX_TRACKED_EVALUATIONS`.
+    EvaluationsTruncated,
     BuildFinished,
 }
 
@@ -200,6 +217,10 @@ pub struct Evaluation {
 
 pub struct BuildSignalSender {
     sender: UnboundedSender<BuildSignal>,
+    /// Whether the transaction changed any keys, in which case we track evaluated keys to explain
+    /// why they were recomputed.
+    track_evaluations: AtomicBool,
+    tracked_evaluations: AtomicUsize,
 }
 
 impl BuildSignals for BuildSignalSender {
@@ -306,6 +327,35 @@ impl ActivationTracker for BuildSignalSender {
 
         let _ignored = self.sender.send(signal.into());
     }
+
+    fn keys_changed(&self, changed: &[ChangedKey]) {
+        if changed.is_empty() {
+            return;
+        }
+        self.track_evaluations.store(true, Ordering::Relaxed);
+        let _ignored = self.sender.send(BuildSignal::KeysChanged(changed.to_vec()));
+    }
+
+    fn key_evaluated(&self, key: EvaluatedKey, deps: &mut dyn Iterator<Item = KeyId>) {
+        if !key.recomputed || !self.track_evaluations.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let tracked = self.tracked_evaluations.fetch_add(1, Ordering::Relaxed);
+        if tracked >= MAX_TRACKED_EVALUATIONS {
+            if tracked == MAX_TRACKED_EVALUATIONS {
+                let _ignored = self.sender.send(BuildSignal::EvaluationsTruncated);
+            }
+            return;
+        }
+
+        let _ignored = self
+            .sender
+            .send(BuildSignal::KeyEvaluated(KeyEvaluatedSignal {
+                key,
+                deps: deps.collect(),
+            }));
+    }
 }
 
 pub struct DeferredBuildSignalsImpl {
@@ -371,6 +421,7 @@ struct BuildSignalReceiver<T> {
     // is how we discovered its existence.
     first_edge_to_load: HashMap<PackageLabel, PackageLabel>,
     backend: T,
+    invalidations: InvalidationTracker,
 }
 
 impl<T> BuildSignalReceiver<T>
@@ -382,6 +433,7 @@ where
             receiver: UnboundedReceiverStream::new(receiver),
             backend,
             first_edge_to_load: HashMap::new(),
+            invalidations: InvalidationTracker::default(),
         }
     }
 
@@ -395,10 +447,17 @@ where
                 BuildSignal::FinalMaterialization(final_materialization) => {
                     self.process_final_materialization(final_materialization)?
                 }
+                BuildSignal::KeysChanged(changed) => self.invalidations.keys_changed(changed),
+                BuildSignal::KeyEvaluated(evaluated) => self.invalidations.key_evaluated(evaluated),
+                BuildSignal::EvaluationsTruncated => self.invalidations.evaluations_truncated(),
                 BuildSignal::BuildFinished => break,
             }
         }
 
+        if let Some(report) = self.invalidations.report() {
+            instant_event(report);
+        }
+
         let now = Instant::now();
 
         let BuildInfo {
@@ -628,7 +687,11 @@ assert_eq_size!(NodeData, [usize; 8]);
 fn create_build_signals() -> (BuildSignalsInstaller, Box<dyn DeferredBuildSignals>) {
     let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
 
-    let sender = Arc::new(BuildSignalSender { sender });
+    let sender = Arc::new(BuildSignalSender {
+        sender,
+        track_evaluations: AtomicBool::new(false),
+        tracked_evaluations: AtomicUsize::new(0),
+    });
     let installer = BuildSignalsInstaller {
         build_signals: sender.dupe() as _,
         activation_tracker: sender.dupe() as _,
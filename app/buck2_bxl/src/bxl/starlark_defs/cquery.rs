@@ -14,6 +14,9 @@ use buck2_build_api::query::oneshot::CqueryOwnerBehavior;
 use buck2_build_api::query::oneshot::QUERY_FRONTEND;
 use buck2_core::target::label::TargetLabel;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::cycles::cycles_report;
+use buck2_query::query::cycles::representative_cycle;
+use buck2_query::query::cycles::strongly_connected_components;
 use buck2_query::query::syntax::simple::eval::set::TargetSetExt;
 use buck2_query::query::syntax::simple::functions::helpers::CapturedExpr;
 use derivative::Derivative;
@@ -217,6 +220,113 @@ fn cquery_methods(builder: &mut MethodsBuilder) {
         })
     }
 
+    /// The scc query, which returns the strongly connected components with a cycle among the
+    /// given targets, ignoring dependencies on targets outside them. Each component is a target set.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_scc(ctx):
+    ///     for component in ctx.cquery().scc(ctx.cquery().deps("root//bin:the_binary")):
+    ///         ctx.output.print(component)
+    /// ```
+    fn scc<'v>(
+        this: &StarlarkCQueryCtx<'v>,
+        targets: ConfiguredTargetListExprArg<'v>,
+    ) -> anyhow::Result<Vec<StarlarkTargetSet<ConfiguredTargetNode>>> {
+        this.ctx.via_dice(|mut dice, ctx| {
+            dice.via(|dice| {
+                async {
+                    let targets = filter_incompatible(
+                        TargetListExpr::<'v, ConfiguredTargetNode>::unpack(
+                            targets,
+                            &this.target_platform,
+                            ctx,
+                            dice,
+                        )
+                        .await?
+                        .get(dice)
+                        .await?
+                        .into_iter(),
+                        ctx,
+                    )?;
+                    Ok(strongly_connected_components(&targets)
+                        .into_iter()
+                        .map(StarlarkTargetSet::from)
+                        .collect())
+                }
+                .boxed_local()
+            })
+        })
+    }
+
+    /// The cycles query, which returns one shortest cycle per strongly connected component among
+    /// the given targets. Each cycle is a target set in dependency order: each target depends on
+    /// the next, and the last on the first.
+    fn cycles<'v>(
+        this: &StarlarkCQueryCtx<'v>,
+        targets: ConfiguredTargetListExprArg<'v>,
+    ) -> anyhow::Result<Vec<StarlarkTargetSet<ConfiguredTargetNode>>> {
+        this.ctx.via_dice(|mut dice, ctx| {
+            dice.via(|dice| {
+                async {
+                    let targets = filter_incompatible(
+                        TargetListExpr::<'v, ConfiguredTargetNode>::unpack(
+                            targets,
+                            &this.target_platform,
+                            ctx,
+                            dice,
+                        )
+                        .await?
+                        .get(dice)
+                        .await?
+                        .into_iter(),
+                        ctx,
+                    )?;
+                    Ok(strongly_connected_components(&targets)
+                        .iter()
+                        .map(|c| StarlarkTargetSet::from(representative_cycle(c)))
+                        .collect())
+                }
+                .boxed_local()
+            })
+        })
+    }
+
+    /// A textual report of the cycles query, with one cycle per line like
+    /// `root//a:a -> root//b:b -> root//a:a`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_cycles_report(ctx):
+    ///     ctx.output.print(ctx.cquery().cycles_report(ctx.cquery().deps("root//bin:the_binary")))
+    /// ```
+    fn cycles_report<'v>(
+        this: &StarlarkCQueryCtx<'v>,
+        targets: ConfiguredTargetListExprArg<'v>,
+    ) -> anyhow::Result<String> {
+        this.ctx.via_dice(|mut dice, ctx| {
+            dice.via(|dice| {
+                async {
+                    let targets = filter_incompatible(
+                        TargetListExpr::<'v, ConfiguredTargetNode>::unpack(
+                            targets,
+                            &this.target_platform,
+                            ctx,
+                            dice,
+                        )
+                        .await?
+                        .get(dice)
+                        .await?
+                        .into_iter(),
+                        ctx,
+                    )?;
+                    Ok(cycles_report(&targets))
+                }
+                .boxed_local()
+            })
+        })
+    }
+
     /// The attrfilter query for rule attribute filtering.
     fn attrfilter<'v>(
         this: &StarlarkCQueryCtx<'v>,
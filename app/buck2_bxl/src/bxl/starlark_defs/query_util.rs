@@ -11,6 +11,7 @@ use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationValue;
 use starlark::eval::Evaluator;
 use starlark::values::dict::Dict;
+use starlark::values::list::AllocList;
 use starlark::values::Value;
 
 use super::targetset::NodeLike;
@@ -27,6 +28,9 @@ pub(crate) fn parse_query_evaluation_result<'v, T: NodeLike>(
                 eval.heap().alloc(StarlarkTargetSet::from(targets))
             }
             QueryEvaluationValue::FileSet(files) => eval.heap().alloc(StarlarkFileSet::from(files)),
+            QueryEvaluationValue::TargetSetList(sets) => eval
+                .heap()
+                .alloc(AllocList(sets.into_iter().map(StarlarkTargetSet::from))),
         },
         QueryEvaluationResult::Multiple(multi) => eval.heap().alloc(Dict::new(
             multi
@@ -42,6 +46,9 @@ pub(crate) fn parse_query_evaluation_result<'v, T: NodeLike>(
                             QueryEvaluationValue::FileSet(files) => {
                                 eval.heap().alloc(StarlarkFileSet::from(files))
                             }
+                            QueryEvaluationValue::TargetSetList(sets) => eval
+                                .heap()
+                                .alloc(AllocList(sets.into_iter().map(StarlarkTargetSet::from))),
                         },
                     ))
                 })
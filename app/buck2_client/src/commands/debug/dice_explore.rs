@@ -0,0 +1,282 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+
+use anyhow::Context as _;
+use bincode::Options;
+use buck2_cli_proto::unstable_dice_dump_request::DiceDumpFormat;
+use buck2_cli_proto::UnstableDiceDumpRequest;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::daemon::client::connect::BuckdConnectOptions;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use dice::introspection::explore::DirtyReason;
+use dice::introspection::explore::GraphExplorer;
+use dice::introspection::explore::KeyState;
+use dice::introspection::graph::KeyID;
+use dice::introspection::graph::SerializedGraphNodesForKey;
+use dice::introspection::graph::VersionNumber;
+use flate2::read::GzDecoder;
+
+/// How many matching keys we list when a query is ambiguous.
+const MAX_CANDIDATES: usize = 20;
+
+const HELP: &str = "\
+Queries:
+  find <pattern>            List the keys whose name contains <pattern>
+  deps <key>                List the keys <key> depended on when it was last computed
+  rdeps <key>               List the keys that depend on <key>, closest first
+  why-dirty <key>           Show the invalidated deps that made <key> dirty
+  recomputed [<version>]    List the keys computed at <version> (default: the latest)
+  help                      Show this message
+  quit                      Exit
+
+A <key> is the name of a key as displayed by `find`, or a unique part of it.";
+
+/// Explore a DICE graph, answering questions like why a key is dirty or what depends on it.
+///
+/// Without a query, queries are read interactively from stdin.
+#[derive(Debug, clap::Parser)]
+pub struct DiceExploreCommand {
+    /// A dump written by `buck2 debug dice-dump --serde` or `--serde-pretty`. Without it, the
+    /// graph of the running daemon is dumped and explored.
+    #[clap(long, value_name = "PATH")]
+    dump: Option<PathArg>,
+    /// Whether the dump was written with `--serde-pretty`.
+    #[clap(long, requires = "dump")]
+    serde_pretty: bool,
+    /// A query to answer, instead of reading queries from stdin. Run the `help` query to list
+    /// them.
+    query: Vec<String>,
+}
+
+impl DiceExploreCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        let (path, format) = match &self.dump {
+            Some(dump) => {
+                let format = if self.serde_pretty {
+                    DiceDumpFormat::JsonPretty
+                } else {
+                    DiceDumpFormat::Bincode
+                };
+                (dump.resolve(&ctx.working_dir), format)
+            }
+            None => (
+                ctx.with_runtime(async move |ctx| dump_daemon_graph(ctx).await)?,
+                DiceDumpFormat::Bincode,
+            ),
+        };
+
+        let explorer = GraphExplorer::new(
+            load_dump(&path, format)
+                .with_context(|| format!("Error loading DICE dump `{}`", path.display()))?,
+        );
+
+        if !self.query.is_empty() {
+            buck2_client_ctx::print!("{}", run_query(&explorer, &self.query.join(" "))?)?;
+            return ExitResult::success();
+        }
+
+        buck2_client_ctx::eprintln!(
+            "Loaded {} DICE keys. Type `help` for a list of queries.",
+            explorer.key_count()
+        )?;
+        let stdin = std::io::stdin();
+        let mut stdin = stdin.lock();
+        let mut line = String::new();
+        loop {
+            buck2_client_ctx::eprint!("> ")?;
+            line.clear();
+            if stdin.read_line(&mut line)? == 0 {
+                break;
+            }
+            let query = line.trim();
+            if query == "quit" || query == "exit" {
+                break;
+            }
+            match run_query(&explorer, query) {
+                Ok(output) => buck2_client_ctx::print!("{}", output)?,
+                Err(e) => buck2_client_ctx::eprintln!("{:#}", e)?,
+            }
+        }
+        ExitResult::success()
+    }
+}
+
+async fn dump_daemon_graph(ctx: ClientCommandContext<'_>) -> anyhow::Result<AbsPathBuf> {
+    let dump_dir = ctx.paths()?.dice_dump_dir();
+    fs_util::create_dir_all(&dump_dir)?;
+    let path = dump_dir.as_abs_path().join("explore.gz");
+
+    let mut buckd = ctx
+        .connect_buckd(BuckdConnectOptions::existing_only_no_console())
+        .await?;
+    buckd
+        .with_flushing()
+        .unstable_dice_dump(UnstableDiceDumpRequest {
+            destination_path: path
+                .to_str()
+                .context("Non-UTF-8 DICE dump path")?
+                .to_owned(),
+            format: DiceDumpFormat::Bincode.into(),
+        })
+        .await
+        .context("Error dumping the DICE graph of the daemon")?;
+
+    Ok(path)
+}
+
+fn load_dump(
+    path: &Path,
+    format: DiceDumpFormat,
+) -> anyhow::Result<Vec<SerializedGraphNodesForKey>> {
+    let reader = BufReader::new(GzDecoder::new(BufReader::new(File::open(path)?)));
+    Ok(match format {
+        // Those need to match the options the dump was written with.
+        DiceDumpFormat::Bincode => bincode::config::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_from(reader)?,
+        DiceDumpFormat::JsonPretty => serde_json::from_reader(reader)?,
+        DiceDumpFormat::Tsv => {
+            return Err(anyhow::anyhow!(
+                "TSV DICE dumps don't contain version data, use `--serde`"
+            ));
+        }
+    })
+}
+
+fn run_query(explorer: &GraphExplorer, query: &str) -> anyhow::Result<String> {
+    let mut out = String::new();
+    let (command, arg) = match query.split_once(' ') {
+        Some((command, arg)) => (command, arg.trim()),
+        None => (query, ""),
+    };
+    match command {
+        "" => {}
+        "help" => writeln!(out, "{}", HELP)?,
+        "find" => {
+            for key in explorer.find(arg) {
+                writeln!(out, "{}", describe(explorer, key))?;
+            }
+        }
+        "deps" => {
+            for dep in explorer.deps(resolve_key(explorer, arg)?) {
+                writeln!(out, "{}", describe(explorer, dep))?;
+            }
+        }
+        "rdeps" => {
+            for (rdep, depth) in explorer.rdeps(resolve_key(explorer, arg)?) {
+                writeln!(out, "{}\t{}", depth, describe(explorer, rdep))?;
+            }
+        }
+        "why-dirty" => {
+            let key = resolve_key(explorer, arg)?;
+            match explorer.why_dirty(key) {
+                Some(reason) => write_dirty_reason(explorer, &reason, 0, &mut out)?,
+                None => writeln!(out, "Not dirty: {}", describe(explorer, key))?,
+            }
+        }
+        "recomputed" => {
+            let version = if arg.is_empty() {
+                explorer
+                    .latest_version()
+                    .context("The DICE graph has no versions")?
+            } else {
+                VersionNumber(
+                    arg.trim_start_matches('v')
+                        .parse()
+                        .with_context(|| format!("Invalid version `{}`", arg))?,
+                )
+            };
+            let keys = explorer.recomputed(version);
+            writeln!(out, "{} keys computed at v{}", keys.len(), version)?;
+            for key in keys {
+                writeln!(out, "{}", describe(explorer, key))?;
+            }
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown query `{}`. Type `help` for a list of queries.",
+                command
+            ));
+        }
+    }
+    Ok(out)
+}
+
+fn resolve_key(explorer: &GraphExplorer, pattern: &str) -> anyhow::Result<KeyID> {
+    if pattern.is_empty() {
+        return Err(anyhow::anyhow!("Missing key"));
+    }
+    match explorer.find(pattern).as_slice() {
+        [] => Err(anyhow::anyhow!("No key matches `{}`", pattern)),
+        [key] => Ok(*key),
+        keys => {
+            let mut msg = format!("{} keys match `{}`, including:", keys.len(), pattern);
+            for key in keys.iter().take(MAX_CANDIDATES) {
+                msg.push_str("\n  ");
+                msg.push_str(&describe(explorer, *key));
+            }
+            Err(anyhow::anyhow!(msg))
+        }
+    }
+}
+
+fn describe(explorer: &GraphExplorer, id: KeyID) -> String {
+    let state = match explorer.state(id) {
+        KeyState::Verified(version) => format!("verified at v{}", version),
+        KeyState::Dirty {
+            version,
+            forced: false,
+        } => format!("dirty at v{}", version),
+        KeyState::Dirty {
+            version,
+            forced: true,
+        } => format!("force-dirtied at v{}", version),
+        KeyState::Unknown => "unknown".to_owned(),
+    };
+    match explorer.key(id) {
+        Some(key) => format!("{} ({}, {})", key.key, key.type_name, state),
+        None => format!("<key {}> ({})", id.0, state),
+    }
+}
+
+fn write_dirty_reason(
+    explorer: &GraphExplorer,
+    reason: &DirtyReason,
+    depth: usize,
+    out: &mut String,
+) -> anyhow::Result<()> {
+    let note = if reason.repeated {
+        " (see above)"
+    } else if reason.dirty_deps.is_empty() {
+        " (changed)"
+    } else {
+        ""
+    };
+    writeln!(
+        out,
+        "{}{}{}",
+        "  ".repeat(depth),
+        describe(explorer, reason.key),
+        note
+    )?;
+    for dep in &reason.dirty_deps {
+        write_dirty_reason(explorer, dep, depth + 1, out)?;
+    }
+    Ok(())
+}
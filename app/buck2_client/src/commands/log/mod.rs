@@ -22,6 +22,7 @@ mod what_materialized;
 pub(crate) mod what_ran;
 mod what_up;
 mod what_uploaded;
+mod why_recomputed;
 
 use std::fmt::Debug;
 
@@ -85,6 +86,7 @@ pub enum LogCommand {
     Replay(replay::ReplayCommand),
     ShowUser(show_user_log::ShowUserLogCommand),
     Summary(summary::SummaryCommand),
+    WhyRecomputed(why_recomputed::WhyRecomputedCommand),
 }
 
 impl LogCommand {
@@ -102,6 +104,7 @@ impl LogCommand {
             Self::Replay(cmd) => cmd.exec(matches, ctx),
             Self::ShowUser(cmd) => cmd.exec(matches, ctx),
             Self::Summary(cmd) => cmd.exec(matches, ctx),
+            Self::WhyRecomputed(cmd) => cmd.exec(matches, ctx),
         }
     }
 
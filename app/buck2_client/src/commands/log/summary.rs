@@ -47,6 +47,9 @@ impl Stats {
                         Some(ActionExecutionKind::Local) => self.total_local_actions += 1,
                         Some(ActionExecutionKind::Remote) => self.total_remote_actions += 1,
                         Some(ActionExecutionKind::ActionCache) => self.total_remote_actions += 1,
+                        Some(ActionExecutionKind::LocalActionCache) => {
+                            self.total_local_actions += 1
+                        }
                         _ => self.total_other_actions += 1,
                     }
                 }
@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::stream_value::StreamValue;
+use tokio_stream::StreamExt;
+
+use crate::commands::log::options::EventLogOptions;
+
+/// Show why analysis and actions were recomputed in a selected command.
+///
+/// This prints a tree for each key that changed since the previous command (such as a file), whose
+/// children are the keys recomputed because of it, down to the analysis and action keys. A key
+/// recomputed because of several changes is shown once, under the change closest to it.
+#[derive(Debug, clap::Parser)]
+pub struct WhyRecomputedCommand {
+    #[clap(flatten)]
+    event_log: EventLogOptions,
+}
+
+impl WhyRecomputedCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        let Self { event_log } = self;
+
+        ctx.with_runtime(async move |ctx| {
+            let log_path = event_log.get(&ctx).await?;
+
+            let (invocation, mut events) = log_path.unpack_stream().await?;
+            buck2_client_ctx::eprintln!(
+                "Showing why keys were recomputed in: {}",
+                invocation.display_command_line()
+            )?;
+
+            let mut found = false;
+            while let Some(event) = events.try_next().await? {
+                match event {
+                    StreamValue::Event(event) => match event.data {
+                        Some(buck2_data::buck_event::Data::Instant(instant)) => {
+                            match instant.data {
+                                Some(buck2_data::instant_event::Data::DiceInvalidationReport(
+                                    report,
+                                )) => {
+                                    found = true;
+                                    log_invalidation_report(&report)?;
+                                }
+                                _ => {}
+                            }
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+
+            if !found {
+                buck2_client_ctx::eprintln!("No keys changed since the previous command")?;
+            }
+
+            anyhow::Ok(())
+        })?;
+
+        ExitResult::success()
+    }
+}
+
+fn log_invalidation_report(report: &buck2_data::DiceInvalidationReport) -> anyhow::Result<()> {
+    buck2_client_ctx::println!(
+        "{} keys changed, {} keys recomputed",
+        report.changed_keys,
+        report.recomputed_keys
+    )?;
+
+    let mut roots = Vec::new();
+    let mut children = vec![Vec::new(); report.nodes.len()];
+    for (i, node) in report.nodes.iter().enumerate() {
+        match node.parent {
+            Some(parent) => children
+                .get_mut(parent as usize)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid parent in invalidation report: {}", parent)
+                })?
+                .push(i),
+            None => roots.push(i),
+        }
+    }
+
+    // Every node has at most one parent, so this visits each node at most once.
+    let mut stack = roots.into_iter().rev().map(|i| (i, 0)).collect::<Vec<_>>();
+    while let Some((i, depth)) = stack.pop() {
+        let node = &report.nodes[i];
+        let note = match (node.parent, node.injected) {
+            (Some(..), _) => match node.other_parents.len() {
+                0 => String::new(),
+                1 => " (also caused by 1 other key)".to_owned(),
+                n => format!(" (also caused by {} other keys)", n),
+            },
+            (None, true) => " (injected)".to_owned(),
+            (None, false) => " (changed)".to_owned(),
+        };
+        buck2_client_ctx::println!(
+            "{}{} {}{}",
+            "  ".repeat(depth),
+            node.key_type,
+            node.key,
+            note
+        )?;
+        stack.extend(children[i].iter().rev().map(|c| (*c, depth + 1)));
+    }
+
+    if report.truncated {
+        buck2_client_ctx::println!("(some recomputed keys were omitted)")?;
+    }
+
+    Ok(())
+}
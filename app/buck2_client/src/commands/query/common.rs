@@ -25,6 +25,8 @@ enum QueryOutputFormatArg {
     Dot,
     Json,
     DotCompact,
+    Graphml,
+    Mermaid,
 }
 
 /// Args common to all the query commands
@@ -53,9 +55,11 @@ pub(crate) struct CommonQueryOptions {
         long_help = "Output format (default: list). \n
            dot -  dot graph format. \n
            dot_compact - compact alternative to dot format. \n
-           json - JSON format.
+           json - JSON format. \n
+           graphml - GraphML format, with attributes as node data. \n
+           mermaid - Mermaid flowchart format.
          ",
-        value_name = "dot|dot_compact|json",
+        value_name = "dot|dot_compact|json|graphml|mermaid",
         arg_enum
     )]
     output_format: Option<QueryOutputFormatArg>,
@@ -87,6 +91,8 @@ impl CommonQueryOptions {
             Some(QueryOutputFormatArg::Json) => QueryOutputFormat::Json,
             Some(QueryOutputFormatArg::Dot) => QueryOutputFormat::Dot,
             Some(QueryOutputFormatArg::DotCompact) => QueryOutputFormat::DotCompact,
+            Some(QueryOutputFormatArg::Graphml) => QueryOutputFormat::Graphml,
+            Some(QueryOutputFormatArg::Mermaid) => QueryOutputFormat::Mermaid,
             None => {
                 if self.json {
                     QueryOutputFormat::Json
@@ -148,6 +148,34 @@ If include patterns are present, regardless of whether exclude patterns are pres
     #[clap(long)]
     test_executor_stderr: Option<OutputDestinationArg>,
 
+    /// Writes every test result to the provided path as JUnit XML, with a test suite per
+    /// target. Each test case includes its duration, status and the end of its output.
+    #[clap(long, value_name = "PATH")]
+    junit_output: Option<PathArg>,
+
+    /// Writes every test result to the provided path as JSON, one result per line.
+    #[clap(long, value_name = "PATH")]
+    json_lines_output: Option<PathArg>,
+
+    /// Only run tests whose names match one of these regexes. A test's name is its target, or
+    /// `TARGET - CASE` for the test cases the test runner lists and runs individually.
+    /// Filtering is done by the test runner, after tests have been built.
+    #[clap(long, value_name = "REGEX")]
+    include_test_name: Vec<String>,
+
+    /// Don't run tests whose names match one of these regexes, even if included.
+    #[clap(long, value_name = "REGEX")]
+    exclude_test_name: Vec<String>,
+
+    /// Only run the tests in this shard, counting from zero. Tests are assigned to shards by a
+    /// hash of their name, so the same shard runs the same tests on every machine.
+    #[clap(long, requires = "shard_count", value_name = "INDEX")]
+    shard_index: Option<u32>,
+
+    /// How many shards to split the tests into, for `--shard-index`.
+    #[clap(long, requires = "shard_index", value_name = "COUNT")]
+    shard_count: Option<u32>,
+
     /// Additional arguments passed to the test executor.
     ///
     /// Test executor is expected to have `--env` flag to pass environment variables.
@@ -191,6 +219,20 @@ impl StreamingCommand for TestCommand {
                         force_use_project_relative_paths: self.unstable_allow_all_tests_on_re,
                         force_run_from_project_root: self.unstable_allow_all_tests_on_re,
                     }),
+                    junit_output: self
+                        .junit_output
+                        .as_ref()
+                        .map(|p| p.resolve(&ctx.working_dir).to_string())
+                        .unwrap_or_default(),
+                    json_lines_output: self
+                        .json_lines_output
+                        .as_ref()
+                        .map(|p| p.resolve(&ctx.working_dir).to_string())
+                        .unwrap_or_default(),
+                    included_test_names: self.include_test_name,
+                    excluded_test_names: self.exclude_test_name,
+                    shard_index: self.shard_index,
+                    shard_count: self.shard_count,
                 },
                 ctx.stdin()
                     .console_interaction_stream(&self.common_opts.console_opts),
@@ -211,6 +253,8 @@ impl StreamingCommand for TestCommand {
         let failed = statuses.failed.as_ref().context("Missing `failed`")?;
         let fatals = statuses.fatals.as_ref().context("Missing `fatals`")?;
         let skipped = statuses.skipped.as_ref().context("Missing `skipped`")?;
+        let flaky = statuses.flaky.as_ref().context("Missing `flaky`")?;
+        let reruns = statuses.reruns.as_ref().context("Missing `reruns`")?;
 
         let console = self.common_opts.console_opts.final_console();
         print_build_result(&console, &response.errors)?;
@@ -223,7 +267,13 @@ impl StreamingCommand for TestCommand {
         // TODO: also remove the duplicate information when the above is done.
 
         let mut line = Line::default();
-        line.push(Span::new_unstyled_lossy("Tests finished: "));
+        match (self.shard_index, self.shard_count) {
+            (Some(index), Some(count)) => line.push(Span::new_unstyled_lossy(format!(
+                "Tests finished for shard {} of {}: ",
+                index, count
+            ))),
+            _ => line.push(Span::new_unstyled_lossy("Tests finished: ")),
+        }
         if listing_failed.count > 0 {
             line.push(TestCounterColumn::LISTING_FAIL.to_span_from_test_statuses(statuses)?);
             line.push(Span::new_unstyled_lossy(". "));
@@ -238,13 +288,20 @@ impl StreamingCommand for TestCommand {
             line.push(column.to_span_from_test_statuses(statuses)?);
             line.push(Span::new_unstyled_lossy(". "));
         }
+        if reruns.count > 0 {
+            for column in [TestCounterColumn::FLAKY, TestCounterColumn::RERUN] {
+                line.push(column.to_span_from_test_statuses(statuses)?);
+                line.push(Span::new_unstyled_lossy(". "));
+            }
+        }
         line.push(span_from_build_failure_count(response.errors.len())?);
         eprint_line(&line)?;
 
         print_error_counter(&console, listing_failed, "LISTINGS FAILED", "⚠")?;
         print_error_counter(&console, failed, "TESTS FAILED", "✗")?;
         print_error_counter(&console, fatals, "TESTS FATALS", "⚠")?;
-        if passed.count + failed.count + fatals.count + skipped.count == 0 {
+        print_error_counter(&console, flaky, "TESTS FLAKY", "⚠")?;
+        if passed.count + failed.count + fatals.count + skipped.count + flaky.count == 0 {
             console.print_warning("NO TESTS RAN")?;
         }
 
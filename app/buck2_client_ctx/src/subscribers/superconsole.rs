@@ -802,7 +802,9 @@ fn lines_for_command_details(
                     )]));
                 }
             }
-            Some(Command::OmittedLocalCommand(..)) | None => {
+            Some(Command::OmittedLocalCommand(..))
+            | Some(Command::LocalActionCacheCommand(..))
+            | None => {
                 // Nothing to show in this case.
             }
             Some(Command::WorkerInitCommand(worker_init_command)) => {
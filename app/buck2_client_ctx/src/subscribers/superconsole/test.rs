@@ -68,6 +68,18 @@ impl TestCounterColumn {
         get_from_test_state: |test_state| test_state.skipped,
         get_from_test_statues: |test_statuses| &test_statuses.skipped,
     };
+    pub const FLAKY: TestCounterColumn = TestCounterColumn {
+        label: "Flaky",
+        color: Some(Color::Yellow),
+        get_from_test_state: |test_state| test_state.flaky,
+        get_from_test_statues: |test_statuses| &test_statuses.flaky,
+    };
+    pub const RERUN: TestCounterColumn = TestCounterColumn {
+        label: "Rerun",
+        color: None,
+        get_from_test_state: |test_state| test_state.retry,
+        get_from_test_statues: |test_statuses| &test_statuses.reruns,
+    };
     const TIMEOUT: TestCounterColumn = TestCounterColumn {
         label: "Timeout",
         color: Some(Color::Yellow),
@@ -128,6 +140,12 @@ impl TestCounterComponent {
         spans.push(". ".try_into()?);
         spans.push(TestCounterColumn::SKIP.to_span_from_test_state(test_state)?);
         spans.push(". ".try_into()?);
+        if test_state.retry > 0 {
+            spans.push(TestCounterColumn::FLAKY.to_span_from_test_state(test_state)?);
+            spans.push(". ".try_into()?);
+            spans.push(TestCounterColumn::RERUN.to_span_from_test_state(test_state)?);
+            spans.push(". ".try_into()?);
+        }
         spans.push(TestCounterColumn::TIMEOUT.to_span_from_test_state(test_state)?);
         Ok(Lines::from_iter([Line::from_iter(spans)]))
     }
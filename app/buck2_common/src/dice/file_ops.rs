@@ -281,6 +281,11 @@ impl FileChangeTracker {
         }
     }
 
+    /// The directories whose listings changed, i.e. that had entries added or removed.
+    pub fn changed_dirs(&self) -> HashSet<CellPath> {
+        self.dirs_to_dirty.iter().map(|k| k.0.clone()).collect()
+    }
+
     pub fn write_to_dice(self, ctx: &mut DiceTransactionUpdater) -> anyhow::Result<()> {
         ctx.changed(self.files_to_dirty)?;
         ctx.changed(self.dirs_to_dirty)?;
@@ -149,6 +149,17 @@ impl InvocationPaths {
             .join(self.materializer_state_dir_name())
     }
 
+    /// Subdirectory of `cache_dir` responsible for storing the local action cache
+    pub fn local_action_cache_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.local_action_cache_dir_name())
+    }
+
+    /// Subdirectory of `cache_dir` responsible for storing DICE state persisted across daemons
+    pub fn dice_state_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path().join(self.dice_state_dir_name())
+    }
+
     /// This is used by the forkserver to write the miniperf wrapper binary (if used), as well as
     /// temporary files used by miniperf. We put this in buck-out because that directory gets
     /// allowlisted for execution (because we write lots of tools there).
@@ -161,8 +172,20 @@ impl InvocationPaths {
         FileName::unchecked_new("materializer_state")
     }
 
+    pub fn local_action_cache_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("local_action_cache")
+    }
+
+    pub fn dice_state_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("dice_state")
+    }
+
     pub fn valid_cache_dirs(&self) -> Vec<&FileName> {
-        vec![self.materializer_state_dir_name()]
+        vec![
+            self.materializer_state_dir_name(),
+            self.local_action_cache_dir_name(),
+            self.dice_state_dir_name(),
+        ]
     }
 }
 
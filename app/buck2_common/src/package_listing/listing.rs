@@ -13,8 +13,11 @@ use allocative::Allocative;
 use buck2_core::fs::paths::file_name::FileName;
 use buck2_core::fs::paths::file_name::FileNameBuf;
 use buck2_core::package::package_relative_path::PackageRelativePath;
+use buck2_core::package::package_relative_path::PackageRelativePathBuf;
 use buck2_util::arc_str::ArcS;
 use dupe::Dupe;
+use serde::Deserialize;
+use serde::Serialize;
 use starlark_map::sorted_set::SortedSet;
 use starlark_map::sorted_vec::SortedVec;
 
@@ -101,6 +104,49 @@ impl PackageListing {
     }
 }
 
+/// A `PackageListing` in a form that can be written to disk, so that it can be reused by another
+/// daemon.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct PersistedPackageListing {
+    pub files: Vec<String>,
+    pub directories: Vec<String>,
+    pub subpackages: Vec<String>,
+    pub buildfile: String,
+}
+
+impl PersistedPackageListing {
+    pub fn new(listing: &PackageListing) -> Self {
+        fn strings<'a>(paths: impl Iterator<Item = &'a ArcS<PackageRelativePath>>) -> Vec<String> {
+            paths.map(|p| p.as_str().to_owned()).collect()
+        }
+
+        Self {
+            files: strings(listing.listing.files.files.iter()),
+            directories: strings(listing.listing.directories.iter()),
+            subpackages: strings(listing.listing.subpackages.iter()),
+            buildfile: listing.listing.buildfile.as_str().to_owned(),
+        }
+    }
+
+    pub fn into_listing(self) -> anyhow::Result<PackageListing> {
+        fn paths<C: FromIterator<ArcS<PackageRelativePath>>>(
+            paths: Vec<String>,
+        ) -> anyhow::Result<C> {
+            paths
+                .into_iter()
+                .map(|p| Ok(PackageRelativePathBuf::try_from(p)?.to_arc()))
+                .collect()
+        }
+
+        Ok(PackageListing::new(
+            paths(self.files)?,
+            paths(self.directories)?,
+            paths(self.subpackages)?,
+            FileNameBuf::try_from(self.buildfile)?,
+        ))
+    }
+}
+
 pub mod testing {
     use buck2_core::fs::paths::file_name::FileNameBuf;
     use buck2_core::package::package_relative_path::PackageRelativePathBuf;
@@ -25,6 +25,26 @@ use static_interner::Interner;
 #[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Dupe, Allocative)]
 pub struct LocalExecutorOptions {
     pub use_persistent_workers: bool,
+    pub resource_limits: LocalResourceLimits,
+}
+
+/// Limits on the resources a local command may use. Those are enforced using cgroups, when they
+/// are enabled.
+#[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Copy, Dupe, Allocative)]
+pub struct LocalResourceLimits {
+    pub memory_bytes: Option<u64>,
+    /// A percentage of one CPU (so, 200 means 2 CPUs).
+    pub cpu_percent: Option<u32>,
+}
+
+impl LocalResourceLimits {
+    /// Use limits from `self`, falling back to `other` for those that are not set.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            memory_bytes: self.memory_bytes.or(other.memory_bytes),
+            cpu_percent: self.cpu_percent.or(other.cpu_percent),
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Dupe, Display, Allocative)]
@@ -61,6 +81,7 @@ impl Hash for RemoteExecutorUseCase {
 pub struct RemoteExecutorOptions {
     pub re_max_input_files_bytes: Option<u64>,
     pub re_max_queue_time_ms: Option<u64>,
+    pub re_priority: Option<i32>,
 }
 
 /// The actual executor portion of a RemoteEnabled executor. It's possible for a RemoteEnabled
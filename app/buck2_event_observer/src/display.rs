@@ -435,6 +435,7 @@ pub fn display_executor_stage(
             match buck2_data::CacheType::from_i32(cache_query.cache_type).unwrap() {
                 buck2_data::CacheType::ActionCache => "re_action_cache",
                 buck2_data::CacheType::RemoteDepFileCache => "re_dep_file_cache",
+                buck2_data::CacheType::LocalActionCache => "local_action_cache",
             }
         }
         Stage::CacheHit(..) => "re_download",
@@ -526,6 +527,7 @@ pub fn format_test_result(test_result: &buck2_data::TestResult) -> anyhow::Resul
         TestStatus::UNKNOWN => Span::new_styled("? Unknown".to_owned().cyan()),
         TestStatus::RERUN => Span::new_styled("↻ Rerun".to_owned().cyan()),
         TestStatus::LISTING_FAILED => Span::new_styled("⚠ Listing failed".to_owned().red()),
+        TestStatus::FLAKY => Span::new_styled("⚠ Flaky".to_owned().yellow()),
     }?;
     let mut base = Line::from_iter([prefix, Span::new_unstyled(format!(": {}", name,))?]);
     if let Some(duration) = duration {
@@ -629,7 +631,9 @@ impl<'a> ActionErrorDisplay<'a> {
                         remote_command.action_digest
                     );
                 }
-                Some(Command::OmittedLocalCommand(..)) | None => {
+                Some(Command::OmittedLocalCommand(..))
+                | Some(Command::LocalActionCacheCommand(..))
+                | None => {
                     // Nothing to show in this case.
                 }
             };
@@ -716,6 +720,7 @@ fn failure_reason_for_command_execution(
             Some(Command::LocalCommand(..)) | Some(Command::OmittedLocalCommand(..)) => "Local ",
             Some(Command::WorkerInitCommand(..)) => "Local Worker Initialization ",
             Some(Command::WorkerCommand(..)) => "Local Worker ",
+            Some(Command::LocalActionCacheCommand(..)) => "Local Action Cache ",
             None => "",
         }
     } else {
@@ -0,0 +1,887 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A Sink that exports spans as OpenTelemetry traces, written as OTLP JSON.
+//!
+//! Each line of the output file is an OTLP `ExportTraceServiceRequest`, which is the format the
+//! OpenTelemetry Collector's `otlpjsonfile` receiver reads. From there, traces can be forwarded to
+//! Jaeger, Tempo or any other OTLP-compatible backend.
+//!
+//! Only commands, analysis, action executions and materializations are exported. Other spans are
+//! skipped, and exported spans nested under them are parented to their closest exported ancestor.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use buck2_data::span_end_event;
+use buck2_data::span_start_event;
+use gazebo::variants::VariantName;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::span::SpanId;
+use crate::BuckEvent;
+use crate::Event;
+use crate::EventSink;
+use crate::EventSinkStats;
+use crate::EventSinkWithStats;
+
+/// Number of finished spans we hold on to before writing them out, even if the command they
+/// belong to has not finished yet.
+const MAX_BUFFERED_SPANS: usize = 1000;
+
+/// Number of events we let queue up for the writer thread. If it falls further behind than this,
+/// e.g. because writes to the trace file are slow, we drop events rather than let the queue grow
+/// without bound.
+const MAX_QUEUED_EVENTS: usize = 10000;
+
+/// OTLP `Status.code` values.
+const STATUS_CODE_OK: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+/// OTLP `Span.kind` for spans that are neither clients nor servers.
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// Events are turned into spans and written on a background thread, so that sending them only
+/// costs a channel send on the thread dispatching them. Events sent while the channel is full are
+/// dropped.
+pub struct OtlpFileSink {
+    sender: Option<crossbeam_channel::Sender<BuckEvent>>,
+    writer_thread: Option<JoinHandle<()>>,
+    stats: Arc<Stats>,
+}
+
+#[derive(Default)]
+struct Stats {
+    written: AtomicU64,
+    failed: AtomicU64,
+    buffered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+struct State {
+    writer: Box<dyn Write + Send>,
+    /// The OTLP `Resource` the spans are from, which is the same for all of them.
+    resource: Value,
+    /// Spans that have started but not ended yet.
+    open: HashMap<SpanId, OpenSpan>,
+    /// Spans that have ended and are waiting to be written.
+    finished: Vec<Value>,
+    stats: Arc<Stats>,
+}
+
+enum OpenSpan {
+    Exported {
+        name: String,
+        trace_id: String,
+        start: SystemTime,
+        parent: Option<SpanId>,
+        attributes: Vec<Value>,
+    },
+    /// A span we don't export. We still track it so that its exported descendants can find their
+    /// closest exported ancestor.
+    Skipped {
+        trace_id: String,
+        parent: Option<SpanId>,
+    },
+}
+
+impl OpenSpan {
+    fn trace_id(&self) -> &str {
+        match self {
+            OpenSpan::Exported { trace_id, .. } | OpenSpan::Skipped { trace_id, .. } => trace_id,
+        }
+    }
+}
+
+impl OtlpFileSink {
+    /// Creates a sink that appends traces to the file at `path`.
+    pub fn create(path: &Path) -> anyhow::Result<OtlpFileSink> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Error opening OTLP trace file `{}`", path.display()))?;
+        OtlpFileSink::new(Box::new(file))
+    }
+
+    fn new(writer: Box<dyn Write + Send>) -> anyhow::Result<OtlpFileSink> {
+        OtlpFileSink::with_capacity(writer, MAX_QUEUED_EVENTS)
+    }
+
+    fn with_capacity(
+        writer: Box<dyn Write + Send>,
+        capacity: usize,
+    ) -> anyhow::Result<OtlpFileSink> {
+        let stats = Arc::new(Stats::default());
+        let mut state = State::new(writer, stats.clone());
+        let (sender, receiver) = crossbeam_channel::bounded::<BuckEvent>(capacity);
+        let writer_thread = thread::Builder::new()
+            .name("buck2-otlp".to_owned())
+            .spawn(move || {
+                for event in receiver {
+                    state.handle(&event);
+                }
+                state.flush();
+            })
+            .context("Cannot start OTLP writer thread")?;
+        Ok(OtlpFileSink {
+            sender: Some(sender),
+            writer_thread: Some(writer_thread),
+            stats,
+        })
+    }
+}
+
+impl State {
+    fn new(writer: Box<dyn Write + Send>, stats: Arc<Stats>) -> State {
+        let host = hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_default();
+        State {
+            writer,
+            resource: json!({
+                "attributes": [
+                    attribute("service.name", "buck2"),
+                    attribute("host.name", host),
+                ],
+            }),
+            open: HashMap::new(),
+            finished: Vec::new(),
+            stats,
+        }
+    }
+
+    /// The closest exported ancestor of a span whose parent is `parent`, if any.
+    fn exported_ancestor(&self, parent: Option<SpanId>) -> Option<SpanId> {
+        let parent = parent?;
+        match self.open.get(&parent)? {
+            OpenSpan::Exported { .. } => Some(parent),
+            OpenSpan::Skipped { parent, .. } => *parent,
+        }
+    }
+
+    fn handle(&mut self, event: &BuckEvent) {
+        let span_id = match event.span_id() {
+            Some(span_id) => span_id,
+            None => return,
+        };
+        let trace_id = event.event().trace_id.replace('-', "");
+
+        if let Some(start) = event.span_start_event() {
+            let parent = self.exported_ancestor(event.parent_id());
+            let span = match start.data.as_ref().and_then(start_span) {
+                Some((name, attributes)) => OpenSpan::Exported {
+                    name,
+                    trace_id,
+                    start: event.timestamp(),
+                    parent,
+                    attributes,
+                },
+                None => OpenSpan::Skipped { trace_id, parent },
+            };
+            self.open.insert(span_id, span);
+        } else if let Some(end) = event.span_end_event() {
+            let span = self.open.remove(&span_id);
+            if let Some(span_end_event::Data::Command(..)) = &end.data {
+                // Spans of this command that are still open won't end anymore, e.g. because they
+                // were cancelled without an end event. Stop tracking them so that they don't
+                // accumulate over the lifetime of the daemon.
+                self.open.retain(|_, span| span.trace_id() != trace_id);
+            }
+
+            let (name, start, parent, mut attributes) = match span {
+                Some(OpenSpan::Exported {
+                    name,
+                    trace_id: _,
+                    start,
+                    parent,
+                    attributes,
+                }) => (name, start, parent, attributes),
+                _ => return,
+            };
+
+            let error = end
+                .data
+                .as_ref()
+                .and_then(|data| end_span(data, &mut attributes));
+            let status = match error {
+                Some(message) => json!({ "code": STATUS_CODE_ERROR, "message": message }),
+                None => json!({ "code": STATUS_CODE_OK }),
+            };
+
+            let mut span = json!({
+                "traceId": trace_id,
+                "spanId": otlp_span_id(span_id),
+                "name": name,
+                "kind": SPAN_KIND_INTERNAL,
+                "startTimeUnixNano": unix_nanos(start),
+                "endTimeUnixNano": unix_nanos(event.timestamp()),
+                "attributes": attributes,
+                "status": status,
+            });
+            if let Some(parent) = parent {
+                span["parentSpanId"] = otlp_span_id(parent).into();
+            }
+            self.finished.push(span);
+
+            // Write spans out once the command they belong to is done, so that each trace
+            // mostly ends up on a single line.
+            if parent.is_none() || self.finished.len() >= MAX_BUFFERED_SPANS {
+                self.flush();
+            }
+            self.stats
+                .buffered
+                .store(self.finished.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.finished.is_empty() {
+            return;
+        }
+
+        let spans = std::mem::take(&mut self.finished);
+        let count = spans.len() as u64;
+        let request = json!({
+            "resourceSpans": [{
+                "resource": self.resource,
+                "scopeSpans": [{
+                    "scope": { "name": "buck2_events" },
+                    "spans": spans,
+                }],
+            }],
+        });
+
+        match write_line(&mut self.writer, &request) {
+            Ok(()) => {
+                self.stats.written.fetch_add(count, Ordering::Relaxed);
+            }
+            Err(e) => {
+                tracing::warn!("Error writing OTLP traces: {:#}", e);
+                self.stats.failed.fetch_add(count, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn write_line(writer: &mut dyn Write, value: &Value) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+impl EventSink for OtlpFileSink {
+    fn send(&self, event: Event) {
+        if let (Event::Buck(event), Some(sender)) = (event, &self.sender) {
+            // The writer thread only exits once we drop the sender, so the only way this can fail
+            // is if the channel is full.
+            if sender.try_send(event).is_err() {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl EventSinkWithStats for OtlpFileSink {
+    fn to_event_sync(self: Arc<Self>) -> Arc<dyn EventSink> {
+        self as _
+    }
+
+    fn stats(&self) -> Option<EventSinkStats> {
+        Some(EventSinkStats {
+            successes: self.stats.written.load(Ordering::Relaxed),
+            failures: self.stats.failed.load(Ordering::Relaxed),
+            buffered: self.stats.buffered.load(Ordering::Relaxed),
+            dropped: self.stats.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl Drop for OtlpFileSink {
+    fn drop(&mut self) {
+        // Let the writer thread drain the events that were already sent and write out what it
+        // has buffered.
+        drop(self.sender.take());
+        if let Some(writer_thread) = self.writer_thread.take() {
+            let _ignored = writer_thread.join();
+        }
+    }
+}
+
+/// Name and attributes of the span to export for this start event, or None if it should be
+/// skipped.
+fn start_span(data: &span_start_event::Data) -> Option<(String, Vec<Value>)> {
+    match data {
+        span_start_event::Data::Command(command) => {
+            let kind = command
+                .data
+                .as_ref()
+                .map_or_else(|| "unknown".to_owned(), |d| d.variant_name().to_lowercase());
+            let mut attributes = vec![attribute("buck2.command", kind.as_str())];
+            let mut metadata: Vec<_> = command.metadata.iter().collect();
+            metadata.sort();
+            attributes.extend(
+                metadata
+                    .into_iter()
+                    .map(|(k, v)| attribute(&format!("buck2.metadata.{}", k), v.as_str())),
+            );
+            Some((format!("command:{}", kind), attributes))
+        }
+        span_start_event::Data::Analysis(analysis) => {
+            let target = match &analysis.target {
+                Some(buck2_data::analysis_start::Target::StandardTarget(label)) => {
+                    configured_label(label)
+                }
+                Some(buck2_data::analysis_start::Target::AnonTarget(anon)) => anon_label(anon),
+                None => String::new(),
+            };
+            Some((
+                "analysis".to_owned(),
+                vec![
+                    attribute("buck2.target", target),
+                    attribute("buck2.rule", analysis.rule.as_str()),
+                ],
+            ))
+        }
+        span_start_event::Data::ActionExecution(action) => {
+            let name = action.name.clone().unwrap_or_default();
+            let mut attributes = vec![
+                attribute("buck2.action.category", name.category.as_str()),
+                attribute("buck2.action.identifier", name.identifier.as_str()),
+            ];
+            if let Some(kind) = buck2_data::ActionKind::from_i32(action.kind) {
+                attributes.push(attribute(
+                    "buck2.action.kind",
+                    kind.as_str_name().to_lowercase(),
+                ));
+            }
+            if let Some(target) = action.key.as_ref().and_then(action_owner) {
+                attributes.push(attribute("buck2.target", target));
+            }
+            Some((format!("action:{}", name.category), attributes))
+        }
+        span_start_event::Data::Materialization(materialization) => {
+            let mut attributes = Vec::new();
+            if let Some(digest) = &materialization.action_digest {
+                attributes.push(attribute("buck2.action.digest", digest.as_str()));
+            }
+            Some(("materialization".to_owned(), attributes))
+        }
+        _ => None,
+    }
+}
+
+/// Add the attributes we know from this end event to `attributes`, and return an error message
+/// if it indicates the span failed.
+fn end_span(data: &span_end_event::Data, attributes: &mut Vec<Value>) -> Option<String> {
+    match data {
+        span_end_event::Data::Command(command) => {
+            attributes.push(attribute("buck2.success", command.is_success));
+            if command.is_success {
+                None
+            } else {
+                Some(
+                    command
+                        .errors
+                        .first()
+                        .map_or_else(|| "Command failed".to_owned(), |e| e.message.clone()),
+                )
+            }
+        }
+        span_end_event::Data::ActionExecution(action) => {
+            if let Some(kind) = buck2_data::ActionExecutionKind::from_i32(action.execution_kind) {
+                let kind = kind.as_str_name();
+                attributes.push(attribute(
+                    "buck2.action.execution_kind",
+                    kind.trim_start_matches("ACTION_EXECUTION_KIND_")
+                        .to_lowercase(),
+                ));
+            }
+            attributes.push(attribute("buck2.action.output_size", action.output_size));
+            attributes.push(attribute(
+                "buck2.action.did_cache_upload",
+                action.did_cache_upload,
+            ));
+            if action.failed {
+                Some("Action failed".to_owned())
+            } else {
+                None
+            }
+        }
+        span_end_event::Data::Materialization(materialization) => {
+            attributes.push(attribute(
+                "buck2.materialization.path",
+                materialization.path.as_str(),
+            ));
+            attributes.push(attribute(
+                "buck2.materialization.file_count",
+                materialization.file_count,
+            ));
+            attributes.push(attribute(
+                "buck2.materialization.total_bytes",
+                materialization.total_bytes,
+            ));
+            if materialization.success {
+                None
+            } else {
+                Some(
+                    materialization
+                        .error
+                        .clone()
+                        .unwrap_or_else(|| "Materialization failed".to_owned()),
+                )
+            }
+        }
+        span_end_event::Data::SpanCancelled(..) => Some("Cancelled".to_owned()),
+        _ => None,
+    }
+}
+
+fn target_label(label: &Option<buck2_data::TargetLabel>) -> String {
+    match label {
+        Some(label) => format!("{}:{}", label.package, label.name),
+        None => String::new(),
+    }
+}
+
+fn configured_label(label: &buck2_data::ConfiguredTargetLabel) -> String {
+    match &label.configuration {
+        Some(cfg) => format!("{} ({})", target_label(&label.label), cfg.full_name),
+        None => target_label(&label.label),
+    }
+}
+
+fn anon_label(anon: &buck2_data::AnonTarget) -> String {
+    format!("{} (anon: {})", target_label(&anon.name), anon.hash)
+}
+
+fn action_owner(key: &buck2_data::ActionKey) -> Option<String> {
+    use buck2_data::action_key::Owner;
+
+    match key.owner.as_ref()? {
+        Owner::TargetLabel(label) | Owner::TestTargetLabel(label) => Some(configured_label(label)),
+        Owner::LocalResourceSetup(label) => Some(configured_label(label)),
+        Owner::AnonTarget(anon) => Some(anon_label(anon)),
+        Owner::BxlKey(_) => None,
+    }
+}
+
+/// An OTLP `KeyValue`. Integers are encoded as strings, as the protobuf JSON mapping wants for
+/// 64-bit values.
+fn attribute(key: &str, value: impl Into<AttributeValue>) -> Value {
+    let value = match value.into() {
+        AttributeValue::String(s) => json!({ "stringValue": s }),
+        AttributeValue::Bool(b) => json!({ "boolValue": b }),
+        AttributeValue::Int(i) => json!({ "intValue": i.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+enum AttributeValue {
+    String(String),
+    Bool(bool),
+    Int(u64),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(s: &str) -> Self {
+        AttributeValue::String(s.to_owned())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(s: String) -> Self {
+        AttributeValue::String(s)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(b: bool) -> Self {
+        AttributeValue::Bool(b)
+    }
+}
+
+impl From<u64> for AttributeValue {
+    fn from(i: u64) -> Self {
+        AttributeValue::Int(i)
+    }
+}
+
+fn otlp_span_id(span_id: SpanId) -> String {
+    format!("{:016x}", span_id.0.get())
+}
+
+/// OTLP timestamps are nanoseconds since the epoch, as strings since they don't fit in a double.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use buck2_data::ActionExecutionEnd;
+    use buck2_data::ActionExecutionStart;
+    use buck2_data::CommandEnd;
+    use buck2_data::CommandStart;
+    use buck2_data::FakeEnd;
+    use buck2_data::FakeStart;
+    use buck2_data::SpanEndEvent;
+    use buck2_data::SpanStartEvent;
+    use buck2_wrapper_common::invocation_id::TraceId;
+
+    use super::*;
+
+    /// A writer whose output the test can look at after handing it to the sink.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect()
+        }
+    }
+
+    fn event(
+        trace_id: &TraceId,
+        time: SystemTime,
+        span_id: SpanId,
+        parent_id: Option<SpanId>,
+        data: impl Into<buck2_data::buck_event::Data>,
+    ) -> BuckEvent {
+        BuckEvent::new(
+            time,
+            trace_id.clone(),
+            Some(span_id),
+            parent_id,
+            data.into(),
+        )
+    }
+
+    fn command_start(trace_id: &TraceId, span_id: SpanId) -> BuckEvent {
+        event(
+            trace_id,
+            SystemTime::now(),
+            span_id,
+            None,
+            SpanStartEvent {
+                data: Some(
+                    CommandStart {
+                        data: Some(buck2_data::BuildCommandStart::default().into()),
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+            },
+        )
+    }
+
+    fn command_end(trace_id: &TraceId, span_id: SpanId) -> BuckEvent {
+        event(
+            trace_id,
+            SystemTime::now(),
+            span_id,
+            None,
+            SpanEndEvent {
+                data: Some(CommandEnd::default().into()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn find_attribute<'a>(span: &'a Value, key: &str) -> &'a Value {
+        span["attributes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["key"] == key)
+            .map(|a| &a["value"])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_export_spans() {
+        let buffer = SharedBuffer::default();
+        let mut state = State::new(Box::new(buffer.clone()), Arc::new(Stats::default()));
+
+        let trace_id = TraceId::new();
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let command = SpanId::next();
+        let skipped = SpanId::next();
+        let action = SpanId::next();
+
+        state.handle(&event(
+            &trace_id,
+            t0,
+            command,
+            None,
+            SpanStartEvent {
+                data: Some(
+                    CommandStart {
+                        data: Some(buck2_data::BuildCommandStart::default().into()),
+                        metadata: HashMap::from([("client".to_owned(), "ci".to_owned())]),
+                    }
+                    .into(),
+                ),
+            },
+        ));
+        state.handle(&event(
+            &trace_id,
+            t0,
+            skipped,
+            Some(command),
+            SpanStartEvent {
+                data: Some(FakeStart::default().into()),
+            },
+        ));
+        state.handle(&event(
+            &trace_id,
+            t0 + Duration::from_secs(1),
+            action,
+            Some(skipped),
+            SpanStartEvent {
+                data: Some(
+                    ActionExecutionStart {
+                        name: Some(buck2_data::ActionName {
+                            category: "cxx_compile".to_owned(),
+                            identifier: "foo.cpp".to_owned(),
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+            },
+        ));
+        state.handle(&event(
+            &trace_id,
+            t0 + Duration::from_secs(2),
+            action,
+            Some(skipped),
+            SpanEndEvent {
+                data: Some(
+                    ActionExecutionEnd {
+                        failed: true,
+                        output_size: 42,
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+                ..Default::default()
+            },
+        ));
+
+        // Nothing is written until the command is done.
+        assert!(buffer.lines().is_empty());
+
+        state.handle(&event(
+            &trace_id,
+            t0 + Duration::from_secs(3),
+            skipped,
+            Some(command),
+            SpanEndEvent {
+                data: Some(FakeEnd::default().into()),
+                ..Default::default()
+            },
+        ));
+        state.handle(&event(
+            &trace_id,
+            t0 + Duration::from_secs(4),
+            command,
+            None,
+            SpanEndEvent {
+                data: Some(
+                    CommandEnd {
+                        is_success: true,
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+                ..Default::default()
+            },
+        ));
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        let spans = lines[0]["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        assert_eq!(spans.len(), 2);
+
+        let (action_span, command_span) = (&spans[0], &spans[1]);
+        let expected_trace_id = trace_id.to_string().replace('-', "");
+        assert_eq!(command_span["traceId"], expected_trace_id.as_str());
+        assert_eq!(command_span["name"], "command:build");
+        assert_eq!(command_span["startTimeUnixNano"], "100000000000");
+        assert_eq!(command_span["endTimeUnixNano"], "104000000000");
+        assert_eq!(command_span["status"]["code"], STATUS_CODE_OK);
+        assert!(command_span.get("parentSpanId").is_none());
+        assert_eq!(
+            find_attribute(command_span, "buck2.metadata.client")["stringValue"],
+            "ci"
+        );
+
+        // The action is parented to the command, skipping over the span we don't export.
+        assert_eq!(action_span["traceId"], expected_trace_id.as_str());
+        assert_eq!(action_span["name"], "action:cxx_compile");
+        assert_eq!(action_span["parentSpanId"], command_span["spanId"]);
+        assert_eq!(action_span["status"]["code"], STATUS_CODE_ERROR);
+        assert_eq!(
+            find_attribute(action_span, "buck2.action.identifier")["stringValue"],
+            "foo.cpp"
+        );
+        assert_eq!(
+            find_attribute(action_span, "buck2.action.output_size")["intValue"],
+            "42"
+        );
+
+        assert_eq!(state.stats.written.load(Ordering::Relaxed), 2);
+        assert_eq!(state.stats.buffered.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_evict_open_spans_on_command_end() {
+        let mut state = State::new(
+            Box::new(SharedBuffer::default()),
+            Arc::new(Stats::default()),
+        );
+
+        let trace_id = TraceId::new();
+        let other_trace_id = TraceId::new();
+        let command = SpanId::next();
+        let other_command = SpanId::next();
+
+        state.handle(&command_start(&trace_id, command));
+        state.handle(&command_start(&other_trace_id, other_command));
+        // A span that never ends.
+        state.handle(&event(
+            &trace_id,
+            SystemTime::now(),
+            SpanId::next(),
+            Some(command),
+            SpanStartEvent {
+                data: Some(FakeStart::default().into()),
+            },
+        ));
+        assert_eq!(state.open.len(), 3);
+
+        state.handle(&command_end(&trace_id, command));
+        assert_eq!(
+            state.open.keys().copied().collect::<Vec<_>>(),
+            vec![other_command]
+        );
+    }
+
+    /// A writer that tells the test when it is first written to, and then blocks until the test
+    /// lets it go.
+    struct BlockingWriter {
+        writing: Option<std::sync::mpsc::Sender<()>>,
+        release: std::sync::mpsc::Receiver<()>,
+    }
+
+    impl Write for BlockingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if let Some(writing) = self.writing.take() {
+                writing.send(()).unwrap();
+                self.release.recv().unwrap();
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sink_drops_events_when_full() -> anyhow::Result<()> {
+        let (writing_send, writing_recv) = std::sync::mpsc::channel();
+        let (release_send, release_recv) = std::sync::mpsc::channel();
+        let sink = OtlpFileSink::with_capacity(
+            Box::new(BlockingWriter {
+                writing: Some(writing_send),
+                release: release_recv,
+            }),
+            1,
+        )?;
+
+        let trace_id = TraceId::new();
+        let command = SpanId::next();
+        // Wait for the writer thread to pick up each event, so that none of these get dropped.
+        for event in [
+            command_start(&trace_id, command),
+            command_end(&trace_id, command),
+        ] {
+            sink.send(Event::Buck(event));
+            while !sink.sender.as_ref().unwrap().is_empty() {
+                thread::yield_now();
+            }
+        }
+        // The writer thread is now stuck writing the command span, and the channel is empty.
+        writing_recv.recv()?;
+
+        for _ in 0..3 {
+            sink.send(Event::Buck(command_start(&trace_id, SpanId::next())));
+        }
+        assert_eq!(sink.stats().unwrap().dropped, 2);
+
+        release_send.send(())?;
+        drop(sink);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sink_writes_spans_on_drop() -> anyhow::Result<()> {
+        let buffer = SharedBuffer::default();
+        let sink = OtlpFileSink::new(Box::new(buffer.clone()))?;
+
+        let trace_id = TraceId::new();
+        let command = SpanId::next();
+        sink.send(Event::Buck(command_start(&trace_id, command)));
+        sink.send(Event::Buck(command_end(&trace_id, command)));
+        drop(sink);
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0]["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["name"],
+            "command:build"
+        );
+        Ok(())
+    }
+}
@@ -53,6 +53,9 @@ pub enum CommandExecutionKind {
     RemoteDepFileCache {
         details: RemoteCommandExecutionDetails,
     },
+    /// This action was served by the local on-disk action cache and not executed.
+    #[display(fmt = "local_action_cache")]
+    LocalActionCache { digest: ActionDigest },
     /// This action would have executed via a local worker but failed during worker initialization.
     #[display(fmt = "worker_init")]
     LocalWorkerInit {
@@ -79,6 +82,7 @@ impl CommandExecutionKind {
             Self::Remote { .. } => buck2_data::ActionExecutionKind::Remote,
             Self::ActionCache { .. } => buck2_data::ActionExecutionKind::ActionCache,
             Self::RemoteDepFileCache { .. } => buck2_data::ActionExecutionKind::RemoteDepFileCache,
+            Self::LocalActionCache { .. } => buck2_data::ActionExecutionKind::LocalActionCache,
         }
     }
 
@@ -151,6 +155,12 @@ impl CommandExecutionKind {
                 })
             }
 
+            Self::LocalActionCache { digest } => {
+                Command::LocalActionCacheCommand(buck2_data::LocalActionCacheCommand {
+                    action_digest: digest.to_string(),
+                })
+            }
+
             Self::LocalWorkerInit { command, env } => {
                 Command::WorkerInitCommand(buck2_data::WorkerInitCommand {
                     argv: command.to_owned(),
@@ -17,6 +17,7 @@ use buck2_common::local_resource_state::LocalResourceState;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::directory::DirectoryIterator;
 use buck2_core::directory::FingerprintedDirectory;
+use buck2_core::execution_types::executor_config::LocalResourceLimits;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::buck_out_path::BuckOutPath;
 use buck2_core::fs::buck_out_path::BuckOutScratchPath;
@@ -311,12 +312,24 @@ pub struct CommandExecutionRequest {
     force_full_hybrid_if_capable: bool,
     /// Whether to disable capturing performance counters for this execution.
     disable_miniperf: bool,
+    /// Whether to opt out of sandboxing when this command runs locally.
+    disable_sandbox: bool,
+    /// Limits on the resources this command may use when it runs locally. Those take precedence
+    /// over the limits set on the executor.
+    local_resource_limits: LocalResourceLimits,
     required_local_resources: SortedSet<LocalResourceState>,
     /// Persistent worker to use for execution
     worker: Option<WorkerSpec>,
     /// Whether the executor should guarantee that the inodes for all inputs are unique (i.e. avoid
     /// hardlinking identical input files, for example)
     unique_input_inodes: bool,
+    /// Whether this is a test whose result may be served from and stored in caches. Unlike build
+    /// actions, tests are only cached when they opt in, since they commonly depend on things
+    /// that aren't declared as inputs.
+    cacheable_test: bool,
+    /// Whether the result of running this command locally may be stored in caches, like the
+    /// `allow_cache_upload` of `ctx.actions.run`.
+    allow_cache_upload: bool,
     /// Remote dep file key, if the action has a dep file.
     /// If this key is set and remote dep file caching is enabled, it will be used to query the cache.
     pub remote_dep_file_key: Option<DepFileDigest>,
@@ -344,9 +357,13 @@ impl CommandExecutionRequest {
             local_environment_inheritance: None,
             force_full_hybrid_if_capable: false,
             disable_miniperf: false,
+            disable_sandbox: false,
+            local_resource_limits: LocalResourceLimits::default(),
             required_local_resources: SortedSet::new(),
             worker: None,
             unique_input_inodes: false,
+            cacheable_test: false,
+            allow_cache_upload: false,
             remote_dep_file_key: None,
         }
     }
@@ -501,6 +518,27 @@ impl CommandExecutionRequest {
         self.disable_miniperf
     }
 
+    pub fn with_disable_sandbox(mut self, disable_sandbox: bool) -> Self {
+        self.disable_sandbox = disable_sandbox;
+        self
+    }
+
+    pub fn disable_sandbox(&self) -> bool {
+        self.disable_sandbox
+    }
+
+    pub fn with_local_resource_limits(
+        mut self,
+        local_resource_limits: LocalResourceLimits,
+    ) -> Self {
+        self.local_resource_limits = local_resource_limits;
+        self
+    }
+
+    pub fn local_resource_limits(&self) -> LocalResourceLimits {
+        self.local_resource_limits
+    }
+
     pub fn with_required_local_resources(
         mut self,
         required_local_resources: Vec<LocalResourceState>,
@@ -527,6 +565,24 @@ impl CommandExecutionRequest {
     pub fn unique_input_inodes(&self) -> bool {
         self.unique_input_inodes
     }
+
+    pub fn with_cacheable_test(mut self, cacheable_test: bool) -> Self {
+        self.cacheable_test = cacheable_test;
+        self
+    }
+
+    pub fn cacheable_test(&self) -> bool {
+        self.cacheable_test
+    }
+
+    pub fn with_allow_cache_upload(mut self, allow_cache_upload: bool) -> Self {
+        self.allow_cache_upload = allow_cache_upload;
+        self
+    }
+
+    pub fn allow_cache_upload(&self) -> bool {
+        self.allow_cache_upload
+    }
 }
 
 /// Is an output a file or a directory
@@ -340,6 +340,7 @@ mod tests {
                     time_enabled: 50,
                     time_running: 100,
                 }),
+                memory_peak: Some(1024),
             }),
             input_materialization_duration: Duration::from_secs(6),
             hashing_duration: Duration::from_secs(7),
@@ -388,6 +389,7 @@ mod tests {
                 time_enabled: 50,
                 time_running: 100,
             }),
+            memory_peak: Some(1024),
         };
         let command_execution_metadata = buck2_data::CommandExecutionMetadata {
             wall_time: Some(Duration {
@@ -17,4 +17,12 @@ pub struct ExecutorGlobalKnobs {
     /// Whether to emit action keys to execution logs (thos are pretty verbose and omitted by
     /// default).
     pub log_action_keys: bool,
+
+    /// Whether to run local actions in a sandbox that only exposes their declared inputs and
+    /// outputs (Linux only).
+    pub sandbox_local_actions: bool,
+
+    /// Whether to run local actions in a cgroup of their own, which lets us enforce resource limits
+    /// and report their peak memory usage (Linux only).
+    pub local_action_cgroups: bool,
 }
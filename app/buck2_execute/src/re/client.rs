@@ -109,6 +109,7 @@ impl RemoteExecutionClient {
         logs_dir_path: Option<&AbsNormPath>,
         buck_out_path: &AbsNormPath,
         is_paranoid_mode: bool,
+        digest_config: DigestConfig,
     ) -> anyhow::Result<Self> {
         let client = RemoteExecutionClientImpl::new(
             fb,
@@ -117,6 +118,7 @@ impl RemoteExecutionClient {
             logs_dir_path,
             buck_out_path,
             is_paranoid_mode,
+            digest_config,
         )
         .await?;
 
@@ -142,6 +144,7 @@ impl RemoteExecutionClient {
         logs_dir_path: Option<&AbsNormPath>,
         buck_out_path: &AbsNormPath,
         is_paranoid_mode: bool,
+        digest_config: DigestConfig,
     ) -> anyhow::Result<Self> {
         // Loop happens times-1 times at most
         for i in 1..times {
@@ -152,6 +155,7 @@ impl RemoteExecutionClient {
                 logs_dir_path,
                 buck_out_path,
                 is_paranoid_mode,
+                digest_config,
             )
             .await
             {
@@ -173,6 +177,7 @@ impl RemoteExecutionClient {
             logs_dir_path,
             buck_out_path,
             is_paranoid_mode,
+            digest_config,
         )
         .await
     }
@@ -255,6 +260,7 @@ impl RemoteExecutionClient {
         skip_cache_read: bool,
         skip_cache_write: bool,
         re_max_queue_time: Option<Duration>,
+        re_priority: Option<i32>,
         knobs: &ExecutorGlobalKnobs,
     ) -> anyhow::Result<ExecuteResponseOrCancelled> {
         self.data
@@ -271,6 +277,7 @@ impl RemoteExecutionClient {
                     skip_cache_read,
                     skip_cache_write,
                     re_max_queue_time,
+                    re_priority,
                     knobs,
                 )
                 .map_err(|e| self.decorate_error(e)))
@@ -421,6 +428,7 @@ impl RemoteExecutionClientImpl {
         maybe_logs_dir_path: Option<&AbsNormPath>,
         buck_out_path: &AbsNormPath,
         is_paranoid_mode: bool,
+        digest_config: DigestConfig,
     ) -> anyhow::Result<Self> {
         tracing::info!("Creating a new RE client");
 
@@ -435,6 +443,8 @@ impl RemoteExecutionClientImpl {
 
             #[cfg(fbcode_build)]
             let client = {
+                let _unused = digest_config;
+
                 use buck2_core::fs::fs_util;
                 use remote_execution::create_default_config;
                 use remote_execution::CASDaemonClientCfg;
@@ -655,9 +665,22 @@ impl RemoteExecutionClientImpl {
 
             #[cfg(not(fbcode_build))]
             let client = {
+                use buck2_common::cas_digest::DigestAlgorithm;
+                use remote_execution::DigestFunction;
+
                 let _unused = (fb, maybe_logs_dir_path, buck_out_path, is_paranoid_mode);
 
-                REClientBuilder::build_and_connect(&static_metadata.0).await?
+                let digest_function = match digest_config.cas_digest_config().preferred_algorithm()
+                {
+                    DigestAlgorithm::Sha1 => DigestFunction::Sha1,
+                    DigestAlgorithm::Sha256 => DigestFunction::Sha256,
+                    DigestAlgorithm::Blake3 => DigestFunction::Blake3,
+                    DigestAlgorithm::Blake3Keyed { .. } => Err(anyhow::anyhow!(
+                        "The BLAKE3-KEYED digest algorithm is not supported by remote execution"
+                    ))?,
+                };
+
+                REClientBuilder::build_and_connect(&static_metadata.0, digest_function).await?
             };
 
             Self {
@@ -943,6 +966,7 @@ impl RemoteExecutionClientImpl {
         skip_cache_read: bool,
         skip_cache_write: bool,
         re_max_queue_time: Option<Duration>,
+        re_priority: Option<i32>,
         knobs: &ExecutorGlobalKnobs,
     ) -> anyhow::Result<ExecuteResponseOrCancelled> {
         let metadata = RemoteExecutionMetadata {
@@ -966,7 +990,10 @@ impl RemoteExecutionClientImpl {
         };
         let request = ExecuteRequest {
             skip_cache_lookup: self.skip_remote_cache || skip_cache_read,
-            execution_policy: Some(TExecutionPolicy::default()),
+            execution_policy: Some(TExecutionPolicy {
+                priority: re_priority.unwrap_or_default(),
+                ..Default::default()
+            }),
             action_digest: action_digest.to_re(),
             ..Default::default()
         };
@@ -91,6 +91,8 @@ struct RemoteExecutionConfig {
     buck_out_path: AbsNormPathBuf,
     /// Whether Buck is running in paranoid mode.
     is_paranoid_mode: bool,
+    /// The digests the client sends are computed with this.
+    digest_config: DigestConfig,
 }
 
 impl RemoteExecutionConfig {
@@ -103,6 +105,7 @@ impl RemoteExecutionConfig {
             self.logs_dir_path.as_deref(),
             &self.buck_out_path,
             self.is_paranoid_mode,
+            self.digest_config,
         )
         .await
     }
@@ -205,6 +208,7 @@ impl ReConnectionManager {
         logs_dir_path: Option<AbsNormPathBuf>,
         buck_out_path: AbsNormPathBuf,
         is_paranoid_mode: bool,
+        digest_config: DigestConfig,
     ) -> Self {
         Self {
             data: RwLock::new(Weak::new()),
@@ -216,6 +220,7 @@ impl ReConnectionManager {
                 logs_dir_path,
                 buck_out_path,
                 is_paranoid_mode,
+                digest_config,
             },
         }
     }
@@ -389,6 +394,7 @@ impl ManagedRemoteExecutionClient {
         skip_cache_read: bool,
         skip_cache_write: bool,
         re_max_queue_time: Option<Duration>,
+        re_priority: Option<i32>,
         knobs: &ExecutorGlobalKnobs,
     ) -> anyhow::Result<ExecuteResponseOrCancelled> {
         self.lock()?
@@ -403,6 +409,7 @@ impl ManagedRemoteExecutionClient {
                 skip_cache_read,
                 skip_cache_write,
                 re_max_queue_time,
+                re_priority,
                 knobs,
             )
             .await
@@ -23,6 +23,7 @@ use buck2_common::file_ops::FileDigestConfig;
 use buck2_common::liveliness_observer::LivelinessObserver;
 use buck2_common::liveliness_observer::LivelinessObserverExt;
 use buck2_common::local_resource_state::LocalResourceHolder;
+use buck2_core::execution_types::executor_config::LocalResourceLimits;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
@@ -84,6 +85,9 @@ use tracing::info;
 
 use crate::executors::worker::WorkerHandle;
 use crate::executors::worker::WorkerPool;
+use crate::sandbox::undeclared_paths_message;
+use crate::sandbox::warn_sandbox_unavailable;
+use crate::sandbox::SandboxLayout;
 
 #[derive(Debug, buck2_error::Error)]
 enum LocalExecutionError {
@@ -107,6 +111,8 @@ pub struct LocalExecutor {
     knobs: ExecutorGlobalKnobs,
     #[allow(unused)]
     worker_pool: Option<Arc<WorkerPool>>,
+    /// Limits for commands that don't set their own.
+    resource_limits: LocalResourceLimits,
 }
 
 impl LocalExecutor {
@@ -119,6 +125,7 @@ impl LocalExecutor {
         forkserver: Option<ForkserverClient>,
         knobs: ExecutorGlobalKnobs,
         worker_pool: Option<Arc<WorkerPool>>,
+        resource_limits: LocalResourceLimits,
     ) -> Self {
         Self {
             artifact_fs,
@@ -129,6 +136,7 @@ impl LocalExecutor {
             forkserver,
             knobs,
             worker_pool,
+            resource_limits,
         }
     }
 
@@ -144,6 +152,8 @@ impl LocalExecutor {
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         disable_miniperf: bool,
+        sandbox: Option<buck2_forkserver_proto::SandboxConfig>,
+        resource_limits: Option<buck2_forkserver_proto::ResourceLimits>,
     ) -> impl futures::future::Future<
         Output = anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>,
     > + Send
@@ -168,18 +178,22 @@ impl LocalExecutor {
                             env_inheritance,
                             liveliness_observer,
                             self.knobs.enable_miniperf && !disable_miniperf,
+                            sandbox,
+                            resource_limits,
                         )
                         .await
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = (forkserver, disable_miniperf);
+                        let _unused = (forkserver, disable_miniperf, sandbox, resource_limits);
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
 
                 None => {
+                    // We only sandbox (or limit) commands that go through the forkserver.
+                    let _unused = (sandbox, resource_limits);
                     let exe = maybe_absolutize_exe(exe, &working_directory)?;
                     let mut cmd = background_command(exe.as_ref());
                     cmd.current_dir(working_directory.as_path());
@@ -371,6 +385,35 @@ impl LocalExecutor {
 
         let (worker, manager) = self.initialize_worker(request, manager, dispatcher).await?;
 
+        let sandbox = if self.knobs.sandbox_local_actions
+            && !request.disable_sandbox()
+            && worker.is_none()
+            && self.forkserver.is_some()
+            && cfg!(target_os = "linux")
+        {
+            match self
+                .blocking_executor
+                .execute_io_inline(|| SandboxLayout::new(request, &self.artifact_fs))
+                .await
+            {
+                Ok(sandbox) => Some(sandbox),
+                Err(e) => return manager.error("sandbox_layout_failed", e),
+            }
+        } else {
+            None
+        };
+
+        let resource_limits = self.resource_limits(request);
+        let cgroup =
+            if self.knobs.local_action_cgroups && worker.is_none() && self.forkserver.is_some() {
+                Some(buck2_forkserver_proto::ResourceLimits {
+                    memory_max_bytes: resource_limits.memory_bytes,
+                    cpu_max_percent: resource_limits.cpu_percent,
+                })
+            } else {
+                None
+            };
+
         let execution_kind = match worker {
             None => CommandExecutionKind::Local {
                 digest: action_digest.dupe(),
@@ -435,6 +478,10 @@ impl LocalExecutor {
                         request.local_environment_inheritance(),
                         liveliness_observer,
                         request.disable_miniperf(),
+                        sandbox
+                            .as_ref()
+                            .map(|sandbox| sandbox.to_proto(self.artifact_fs.fs())),
+                        cgroup,
                     )
                     .await
                 };
@@ -455,17 +502,85 @@ impl LocalExecutor {
         )
         .await;
 
-        let (status, stdout, stderr) = match res {
+        let (status, stdout, mut stderr) = match res {
             Ok(res) => res,
             Err(e) => return manager.error("exec_failed", e), // TODO (torozco): Can this take CommandExecutionKind? Should this be a failure?
         };
 
+        let failed = match &status {
+            GatherOutputStatus::Finished { exit_code, .. } => *exit_code != 0,
+            GatherOutputStatus::SpawnFailed(..) => true,
+            GatherOutputStatus::TimedOut(..) | GatherOutputStatus::Cancelled => false,
+        };
+        if let GatherOutputStatus::Finished {
+            sandbox: Some(report),
+            ..
+        } = &status
+        {
+            if let Some(reason) = &report.unavailable {
+                warn_sandbox_unavailable(reason);
+            }
+            if let Some(error) = &report.error {
+                stderr.extend_from_slice(
+                    format!(
+                        "\nThe sandbox failed, so this action was stopped: {}\n",
+                        error
+                    )
+                    .as_bytes(),
+                );
+            }
+        }
+
+        let undeclared_paths = match &sandbox {
+            Some(sandbox) if failed => {
+                // The paths the command failed to access: those the forkserver saw it look up in
+                // vain, or its executable if it could not be spawned.
+                let accessed = match &status {
+                    GatherOutputStatus::Finished {
+                        sandbox: Some(report),
+                        ..
+                    } => report.hidden_paths.clone(),
+                    GatherOutputStatus::SpawnFailed(..) => vec![args[0].clone()],
+                    _ => Vec::new(),
+                };
+                self.undeclared_paths_message(sandbox, request, accessed)
+                    .await
+            }
+            _ => None,
+        };
+        if let (GatherOutputStatus::Finished { .. }, Some(message)) = (&status, &undeclared_paths) {
+            stderr.extend_from_slice(message.as_bytes());
+        }
+
+        if let GatherOutputStatus::Finished {
+            exit_code,
+            execution_stats: Some(stats),
+            ..
+        } = &status
+        {
+            if let (Some(memory_peak), Some(memory_max)) =
+                (stats.memory_peak, resource_limits.memory_bytes)
+            {
+                if *exit_code != 0 && memory_peak >= memory_max {
+                    stderr.extend_from_slice(
+                        format!(
+                            "\nThis action reached its memory limit ({} MiB) and was likely killed \
+                            because of it.\n",
+                            memory_max / (1024 * 1024)
+                        )
+                        .as_bytes(),
+                    );
+                }
+            }
+        }
+
         let std_streams = CommandStdStreams::Local { stdout, stderr };
 
         match status {
             GatherOutputStatus::Finished {
                 exit_code,
                 execution_stats,
+                ..
             } => {
                 let (outputs, hashing_time) = match self
                     .calculate_and_declare_output_values(request, digest_config)
@@ -515,8 +630,13 @@ impl LocalExecutor {
                     Default::default(),
                     CommandStdStreams::Local {
                         stdout: Default::default(),
-                        stderr: format!("Spawning executable `{}` failed: {}", args[0], reason)
-                            .into_bytes(),
+                        stderr: format!(
+                            "Spawning executable `{}` failed: {}{}",
+                            args[0],
+                            reason,
+                            undeclared_paths.as_deref().unwrap_or_default()
+                        )
+                        .into_bytes(),
                     },
                     None,
                     timing,
@@ -529,6 +649,42 @@ impl LocalExecutor {
         }
     }
 
+    /// The limits that apply to this command: its own, or the executor's.
+    fn resource_limits(&self, request: &CommandExecutionRequest) -> LocalResourceLimits {
+        request.local_resource_limits().or(self.resource_limits)
+    }
+
+    /// When a sandboxed command fails, explain which of the paths it failed to access were hidden
+    /// because they were not declared.
+    async fn undeclared_paths_message(
+        &self,
+        sandbox: &SandboxLayout,
+        request: &CommandExecutionRequest,
+        accessed: Vec<String>,
+    ) -> Option<String> {
+        let working_directory = request
+            .working_directory()
+            .unwrap_or_else(ProjectRelativePath::empty);
+
+        let paths = self
+            .blocking_executor
+            .execute_io_inline(|| {
+                Ok(sandbox.find_undeclared_paths(
+                    accessed.iter().map(|p| p.as_str()),
+                    working_directory,
+                    self.artifact_fs.fs(),
+                ))
+            })
+            .await
+            .ok()?;
+
+        if paths.is_empty() {
+            None
+        } else {
+            Some(undeclared_paths_message(&paths))
+        }
+    }
+
     async fn calculate_and_declare_output_values(
         &self,
         request: &CommandExecutionRequest,
@@ -733,8 +889,10 @@ impl PreparedCommandExecutor for LocalExecutor {
             buck2_data::LocalStage {
                 stage: Some(buck2_data::LocalQueued {}.into()),
             },
-            self.host_sharing_broker
-                .acquire(request.host_sharing_requirements()),
+            self.host_sharing_broker.acquire_with_memory(
+                request.host_sharing_requirements(),
+                self.resource_limits(request).memory_bytes,
+            ),
         )
         .await;
 
@@ -1065,6 +1223,8 @@ mod unix {
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         enable_miniperf: bool,
+        sandbox: Option<buck2_forkserver_proto::SandboxConfig>,
+        resource_limits: Option<buck2_forkserver_proto::ResourceLimits>,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let exe = exe.as_ref();
 
@@ -1082,6 +1242,8 @@ mod unix {
             enable_miniperf,
             std_redirects: None,
             graceful_shutdown_timeout_s: None,
+            sandbox,
+            resource_limits,
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -1266,6 +1428,7 @@ mod tests {
             None,
             ExecutorGlobalKnobs::default(),
             None,
+            LocalResourceLimits::default(),
         );
 
         Ok((executor, temp.path().root().to_buf(), temp))
@@ -1286,6 +1449,8 @@ mod tests {
                 None,
                 NoopLivelinessObserver::create(),
                 false,
+                None,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1322,6 +1487,8 @@ mod tests {
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
                 false,
+                None,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
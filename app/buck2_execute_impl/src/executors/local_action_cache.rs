@@ -0,0 +1,1470 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A content-addressed action cache stored on local disk.
+//!
+//! This lets builds that run without a remote execution backend reuse the results of actions
+//! that were executed locally by a previous daemon. Results are keyed by the same
+//! [`ActionDigest`] that we would use to query a remote action cache.
+//!
+//! The cache lives in a directory with the following layout:
+//!
+//! - `ac/<digest>`: a [`LocalActionCacheEntry`] describing the outputs of an action.
+//! - `cas/<prefix>/<digest>`: the contents of the files referenced by those entries.
+//! - `tmp/`: scratch space used to write files atomically.
+//!
+//! The cache is bounded in size. When it grows past its limit, we evict the least recently used
+//! entries, as well as the blobs that are no longer referenced by any entry. Access times are
+//! tracked in memory; when the cache is reopened, entries are ordered by the time they were
+//! written.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_action_metadata_proto::local_action_cache_output::Entry as CachedEntry;
+use buck2_action_metadata_proto::LocalActionCacheDirectory;
+use buck2_action_metadata_proto::LocalActionCacheEntry;
+use buck2_action_metadata_proto::LocalActionCacheFile;
+use buck2_action_metadata_proto::LocalActionCacheOutput;
+use buck2_common::cas_digest::CasDigest;
+use buck2_common::cas_digest::CasDigestConfig;
+use buck2_common::cas_digest::CasDigestKind;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::file_name::FileNameBuf;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::directory::extract_artifact_value;
+use buck2_execute::directory::insert_entry;
+use buck2_execute::directory::new_symlink;
+use buck2_execute::directory::ActionDirectoryBuilder;
+use buck2_execute::directory::ActionDirectoryEntry;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::directory::ActionSharedDirectory;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::blocking::BlockingExecutor;
+use buck2_execute::execute::clean_output_paths::CleanOutputPaths;
+use buck2_execute::execute::executor_stage_async;
+use buck2_execute::execute::kind::CommandExecutionKind;
+use buck2_execute::execute::manager::CommandExecutionManager;
+use buck2_execute::execute::manager::CommandExecutionManagerExt;
+use buck2_execute::execute::output::CommandStdStreams;
+use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::prepared::PreparedCommandExecutor;
+use buck2_execute::execute::prepared::PreparedCommandOptionalExecutor;
+use buck2_execute::execute::request::CommandExecutionOutput;
+use buck2_execute::execute::request::CommandExecutionOutputRef;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::result::CommandExecutionMetadata;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::materialize::materializer::Materializer;
+use dupe::Dupe;
+use indexmap::IndexMap;
+use more_futures::cancellation::CancellationContext;
+use parking_lot::Mutex;
+use prost::Message;
+
+/// Default upper bound on the size of the local action cache.
+pub const DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// When the cache exceeds its maximum size, we evict entries until it is back under this
+/// percentage of the maximum, so that we don't need to evict again on the very next insertion.
+const EVICTION_TARGET_PERCENT: u64 = 90;
+
+#[derive(Debug, buck2_error::Error)]
+enum LocalActionCacheError {
+    #[error("Invalid file name in local action cache: `{0}`")]
+    InvalidFileName(String),
+    #[error("Local action cache entry for `{0}` has an output with no contents")]
+    MissingEntry(String),
+    #[error("Local action cache entry references missing blob `{0}`")]
+    MissingBlob(FileDigest),
+}
+
+/// The on-disk local action cache. This is shared by all commands for the lifetime of the daemon.
+pub struct LocalActionCache {
+    root: AbsNormPathBuf,
+    max_bytes: u64,
+    index: Mutex<LocalActionCacheIndex>,
+    next_temp_file: AtomicU64,
+}
+
+impl LocalActionCache {
+    /// Open (or create) the cache at `root`, scanning existing entries to rebuild the index.
+    /// Entries that cannot be read are discarded, as are blobs that no entry references. Only
+    /// failing to create or list the cache directories is an error: anything unreadable within
+    /// them is skipped.
+    pub fn open(
+        root: AbsNormPathBuf,
+        max_bytes: u64,
+        cas_digest_config: CasDigestConfig,
+    ) -> anyhow::Result<Self> {
+        let cache = Self {
+            root,
+            max_bytes,
+            index: Mutex::new(LocalActionCacheIndex::default()),
+            next_temp_file: AtomicU64::new(0),
+        };
+
+        let temp_dir = cache.temp_dir();
+        if fs_util::try_exists(&temp_dir)? {
+            fs_util::remove_dir_all(&temp_dir)?;
+        }
+        fs_util::create_dir_all(&temp_dir)?;
+        fs_util::create_dir_all(cache.entries_dir())?;
+        fs_util::create_dir_all(cache.blobs_dir())?;
+
+        let mut entries = Vec::new();
+        for dir_entry in fs_util::read_dir(cache.entries_dir())? {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(e) => {
+                    tracing::warn!("Error listing local action cache entries: {:#}", e);
+                    continue;
+                }
+            };
+            let path = dir_entry.path();
+            match read_index_entry(&dir_entry, cas_digest_config) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => {
+                    tracing::warn!("Discarding local action cache entry `{}`: {:#}", path, e);
+                    remove_quietly(&path);
+                }
+            }
+        }
+        entries.sort_by_key(|(_, _, _, mtime)| *mtime);
+
+        let mut index = cache.index.lock();
+        for (digest, size, blobs, _) in entries {
+            index.acquire_blobs(&blobs);
+            index.insert(digest, size, blobs);
+        }
+
+        for shard in fs_util::read_dir(cache.blobs_dir())? {
+            let blobs = match shard.map_err(anyhow::Error::from).and_then(|shard| {
+                let path = shard.path();
+                fs_util::read_dir(&path).with_context(|| format!("Error listing `{}`", path))
+            }) {
+                Ok(blobs) => blobs,
+                Err(e) => {
+                    tracing::warn!("Error listing local action cache blobs: {:#}", e);
+                    continue;
+                }
+            };
+            // Blobs we can't list are left in place: they don't take part in the size accounting,
+            // and are overwritten if an entry referencing them is stored again.
+            for blob in blobs.flatten() {
+                let referenced = blob
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| parse_digest_file_name(name, cas_digest_config).ok())
+                    .map_or(false, |digest| index.blobs.contains_key(&digest));
+                if !referenced {
+                    remove_quietly(&blob.path());
+                }
+            }
+        }
+
+        cache.evict(&mut index);
+        drop(index);
+
+        Ok(cache)
+    }
+
+    fn entries_dir(&self) -> AbsNormPathBuf {
+        self.root.join(FileName::unchecked_new("ac"))
+    }
+
+    fn blobs_dir(&self) -> AbsNormPathBuf {
+        self.root.join(FileName::unchecked_new("cas"))
+    }
+
+    fn temp_dir(&self) -> AbsNormPathBuf {
+        self.root.join(FileName::unchecked_new("tmp"))
+    }
+
+    fn entry_path(&self, digest: &ActionDigest) -> AbsNormPathBuf {
+        self.entries_dir()
+            .join(FileName::unchecked_new(&digest_file_name(digest)))
+    }
+
+    fn blob_path(&self, digest: &FileDigest) -> AbsNormPathBuf {
+        let name = digest_file_name(digest);
+        self.blobs_dir()
+            .join(FileName::unchecked_new(&name[..2]))
+            .join(FileName::unchecked_new(&name))
+    }
+
+    fn temp_path(&self) -> AbsNormPathBuf {
+        let id = self.next_temp_file.fetch_add(1, Ordering::Relaxed);
+        self.temp_dir()
+            .join(FileName::unchecked_new(&id.to_string()))
+    }
+
+    /// Total size of the entries and blobs currently in the cache.
+    pub fn size_bytes(&self) -> u64 {
+        self.index.lock().total_bytes
+    }
+
+    /// Look up an entry, preventing it from being evicted until the returned lease is dropped.
+    fn acquire(&self, digest: &ActionDigest) -> Option<LocalActionCacheLease<'_>> {
+        if self.index.lock().pin(digest) {
+            Some(LocalActionCacheLease {
+                cache: self,
+                digest: digest.dupe(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Drop an entry, e.g. because it turned out to be corrupt.
+    fn remove(&self, digest: &ActionDigest) {
+        let mut index = self.index.lock();
+        if let Some(unused) = index.remove(digest) {
+            remove_quietly(&self.entry_path(digest));
+            for blob in unused {
+                remove_quietly(&self.blob_path(&blob));
+            }
+        }
+    }
+
+    /// Store the outputs of an action. `blobs` maps the digest of every file referenced by
+    /// `entry` to a path where its contents can currently be found.
+    fn store(
+        &self,
+        digest: &ActionDigest,
+        entry: &LocalActionCacheEntry,
+        blobs: &[(FileDigest, AbsNormPathBuf)],
+    ) -> anyhow::Result<()> {
+        let digests = blobs.iter().map(|(d, _)| d.dupe()).collect::<Vec<_>>();
+
+        let blobs_size: u64 = digests.iter().map(|d| d.size()).sum();
+        if blobs_size > self.max_bytes {
+            tracing::debug!(
+                "Not storing `{}` in the local action cache: outputs are too large",
+                digest
+            );
+            return Ok(());
+        }
+
+        let missing = self.index.lock().acquire_blobs(&digests);
+        let written = self.write_entry(entry, blobs, missing);
+
+        // The entry is moved into place under the lock, so that it can't race with the eviction
+        // or removal of a previous entry for the same action, which delete the same path.
+        let mut index = self.index.lock();
+        let written = written.and_then(|(temp, entry_size)| {
+            let dest = self.entry_path(digest);
+            match fs_util::rename(&temp, &dest) {
+                Ok(()) => Ok(entry_size),
+                Err(e) => {
+                    remove_quietly(&temp);
+                    Err(e)
+                }
+            }
+        });
+        match written {
+            Ok(entry_size) => {
+                for blob in index.insert(digest.dupe(), entry_size, digests) {
+                    remove_quietly(&self.blob_path(&blob));
+                }
+                self.evict(&mut index);
+                Ok(())
+            }
+            Err(e) => {
+                for blob in index.release_blobs(&digests) {
+                    remove_quietly(&self.blob_path(&blob));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Write the blobs that are `missing` from the cache, and the entry to a temporary file.
+    /// Returns that file and the size of the entry.
+    fn write_entry(
+        &self,
+        entry: &LocalActionCacheEntry,
+        blobs: &[(FileDigest, AbsNormPathBuf)],
+        missing: Vec<FileDigest>,
+    ) -> anyhow::Result<(AbsNormPathBuf, u64)> {
+        let mut missing = missing.into_iter().collect::<HashSet<_>>();
+
+        for (blob, path) in blobs {
+            if !missing.remove(blob) {
+                continue;
+            }
+
+            let dest = self.blob_path(blob);
+            self.write_atomically(&dest, |temp| {
+                fs_util::copy(path, temp)?;
+                make_non_executable(temp)
+            })
+            .with_context(|| format!("Error storing `{}` from `{}`", blob, path))?;
+        }
+
+        let data = entry.encode_to_vec();
+        let temp = self.temp_path();
+        if let Err(e) = fs_util::write(&temp, &data) {
+            remove_quietly(&temp);
+            return Err(e);
+        }
+
+        Ok((temp, data.len() as u64))
+    }
+
+    fn write_atomically(
+        &self,
+        dest: &AbsNormPath,
+        write: impl FnOnce(&AbsNormPath) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let temp = self.temp_path();
+        let res: anyhow::Result<()> = try {
+            write(&temp)?;
+            if let Some(parent) = dest.parent() {
+                fs_util::create_dir_all(parent)?;
+            }
+            fs_util::rename(&temp, dest)?;
+        };
+        if res.is_err() {
+            remove_quietly(&temp);
+        }
+        res
+    }
+
+    fn evict(&self, index: &mut LocalActionCacheIndex) {
+        if index.total_bytes <= self.max_bytes {
+            return;
+        }
+
+        let target = self.max_bytes / 100 * EVICTION_TARGET_PERCENT;
+        let (entries, blobs) = index.evict(target);
+        tracing::debug!(
+            "Evicted {} entries from the local action cache, which is now {} bytes",
+            entries.len(),
+            index.total_bytes,
+        );
+
+        for entry in entries {
+            remove_quietly(&self.entry_path(&entry));
+        }
+        for blob in blobs {
+            remove_quietly(&self.blob_path(&blob));
+        }
+    }
+}
+
+/// Reads the file name and metadata of an entry in the `ac` directory, along with the blobs it
+/// references.
+fn read_index_entry(
+    dir_entry: &fs_util::DirEntry,
+    cas_digest_config: CasDigestConfig,
+) -> anyhow::Result<(ActionDigest, u64, Vec<FileDigest>, SystemTime)> {
+    let name = dir_entry.file_name();
+    let name = name
+        .to_str()
+        .ok_or_else(|| LocalActionCacheError::InvalidFileName(name.to_string_lossy().into()))?;
+    let digest = parse_digest_file_name(name, cas_digest_config)?;
+
+    let metadata = dir_entry.metadata()?;
+    let entry = LocalActionCacheEntry::decode(fs_util::read(dir_entry.path())?.as_slice())?;
+
+    let mut blobs = Vec::new();
+    for output in &entry.outputs {
+        collect_blobs(output, cas_digest_config, &mut blobs)?;
+    }
+
+    Ok((digest, metadata.len(), blobs, metadata.modified()?))
+}
+
+fn collect_blobs(
+    output: &LocalActionCacheOutput,
+    cas_digest_config: CasDigestConfig,
+    blobs: &mut Vec<FileDigest>,
+) -> anyhow::Result<()> {
+    match &output.entry {
+        Some(CachedEntry::File(file)) => {
+            blobs.push(FileDigest::parse_digest(&file.digest, cas_digest_config)?.0);
+        }
+        Some(CachedEntry::Symlink(_)) => {}
+        Some(CachedEntry::Directory(dir)) => {
+            for entry in &dir.entries {
+                collect_blobs(entry, cas_digest_config, blobs)?;
+            }
+        }
+        None => return Err(LocalActionCacheError::MissingEntry(output.path.clone()).into()),
+    }
+    Ok(())
+}
+
+/// File names can't contain `:` on all platforms, so we don't use the `Display` format here.
+fn digest_file_name<Kind: CasDigestKind>(digest: &CasDigest<Kind>) -> String {
+    format!("{}_{}", digest.raw_digest(), digest.size())
+}
+
+fn parse_digest_file_name<Kind: CasDigestKind>(
+    name: &str,
+    cas_digest_config: CasDigestConfig,
+) -> anyhow::Result<CasDigest<Kind>> {
+    let (hash, size) = name
+        .split_once('_')
+        .ok_or_else(|| LocalActionCacheError::InvalidFileName(name.to_owned()))?;
+    Ok(CasDigest::parse_digest(&format!("{}:{}", hash, size), cas_digest_config)?.0)
+}
+
+fn remove_quietly(path: &AbsNormPath) {
+    if let Err(e) = fs_util::remove_file(path) {
+        tracing::debug!("Error removing `{}` from local action cache: {:#}", path, e);
+    }
+}
+
+/// Blobs may be shared by outputs that differ only in whether they are executable, so we store
+/// them without the executable bit and set it when restoring.
+fn make_non_executable(path: &AbsNormPath) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut perms = fs_util::metadata(path)?.permissions();
+        perms.set_mode(perms.mode() & !0o111);
+        fs_util::set_permissions(path, perms)?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ignore = path;
+    }
+
+    Ok(())
+}
+
+/// Keeps an entry from being evicted while we restore its outputs.
+struct LocalActionCacheLease<'a> {
+    cache: &'a LocalActionCache,
+    digest: ActionDigest,
+}
+
+impl LocalActionCacheLease<'_> {
+    fn read(&self) -> anyhow::Result<LocalActionCacheEntry> {
+        let data = fs_util::read(self.cache.entry_path(&self.digest))?;
+        Ok(LocalActionCacheEntry::decode(data.as_slice())?)
+    }
+}
+
+impl Drop for LocalActionCacheLease<'_> {
+    fn drop(&mut self) {
+        self.cache.index.lock().unpin(&self.digest);
+    }
+}
+
+/// In-memory bookkeeping for the cache: which entries exist, how recently they were used, and
+/// how many entries reference each blob.
+#[derive(Default)]
+struct LocalActionCacheIndex {
+    entries: HashMap<ActionDigest, IndexEntry>,
+    blobs: HashMap<FileDigest, BlobRefs>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+struct IndexEntry {
+    /// Size of the serialized entry (not including its blobs).
+    size: u64,
+    blobs: Vec<FileDigest>,
+    last_access: u64,
+    /// Number of in-flight restores of this entry. Pinned entries are never evicted.
+    pins: usize,
+}
+
+#[derive(Default)]
+struct BlobRefs {
+    /// Number of entries referencing the blob, including entries that are still being stored.
+    refs: usize,
+    /// Whether the blob was moved into place. Until then, every store referencing it writes it,
+    /// so that no entry is recorded before its blobs can be read.
+    written: bool,
+}
+
+impl LocalActionCacheIndex {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Take a reference to each of `blobs`. Returns the blobs that were not already written to
+    /// the cache, which the caller must write. Blobs that another store is still writing are
+    /// returned too: writes are atomic, so writing the same blob twice is harmless.
+    fn acquire_blobs(&mut self, blobs: &[FileDigest]) -> Vec<FileDigest> {
+        let mut missing = Vec::new();
+        for blob in blobs {
+            let entry = self.blobs.entry(blob.dupe()).or_default();
+            if entry.refs == 0 {
+                self.total_bytes += blob.size();
+            }
+            if !entry.written {
+                missing.push(blob.dupe());
+            }
+            entry.refs += 1;
+        }
+        missing
+    }
+
+    /// Drop a reference to each of `blobs`. Returns the blobs that are no longer referenced, which
+    /// the caller must delete.
+    fn release_blobs(&mut self, blobs: &[FileDigest]) -> Vec<FileDigest> {
+        let mut unused = Vec::new();
+        for blob in blobs {
+            if let Some(entry) = self.blobs.get_mut(blob) {
+                entry.refs -= 1;
+                if entry.refs == 0 {
+                    self.blobs.remove(blob);
+                    self.total_bytes -= blob.size();
+                    unused.push(blob.dupe());
+                }
+            }
+        }
+        unused
+    }
+
+    /// Record an entry whose blobs were already acquired and written. If this replaces an
+    /// existing entry, returns the blobs that are no longer referenced as a result.
+    fn insert(
+        &mut self,
+        digest: ActionDigest,
+        size: u64,
+        blobs: Vec<FileDigest>,
+    ) -> Vec<FileDigest> {
+        for blob in &blobs {
+            if let Some(entry) = self.blobs.get_mut(blob) {
+                entry.written = true;
+            }
+        }
+        let last_access = self.tick();
+        self.total_bytes += size;
+        // Restores of a previous entry may still be in flight. Their leases are released against
+        // this entry, so it must stay pinned until then.
+        let pins = self
+            .entries
+            .get(&digest)
+            .map_or(0, |previous| previous.pins);
+        let previous = self.entries.insert(
+            digest,
+            IndexEntry {
+                size,
+                blobs,
+                last_access,
+                pins,
+            },
+        );
+        match previous {
+            Some(previous) => {
+                self.total_bytes -= previous.size;
+                self.release_blobs(&previous.blobs)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Remove an entry. Returns `None` if it did not exist, and otherwise the blobs that are no
+    /// longer referenced.
+    fn remove(&mut self, digest: &ActionDigest) -> Option<Vec<FileDigest>> {
+        let entry = self.entries.remove(digest)?;
+        self.total_bytes -= entry.size;
+        Some(self.release_blobs(&entry.blobs))
+    }
+
+    fn pin(&mut self, digest: &ActionDigest) -> bool {
+        let last_access = self.tick();
+        match self.entries.get_mut(digest) {
+            Some(entry) => {
+                entry.pins += 1;
+                entry.last_access = last_access;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn unpin(&mut self, digest: &ActionDigest) {
+        if let Some(entry) = self.entries.get_mut(digest) {
+            entry.pins = entry.pins.saturating_sub(1);
+        }
+    }
+
+    /// Evict least recently used entries until the cache is no larger than `target` bytes.
+    /// Returns the evicted entries and the blobs that are no longer referenced.
+    fn evict(&mut self, target: u64) -> (Vec<ActionDigest>, Vec<FileDigest>) {
+        let mut candidates = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.pins == 0)
+            .map(|(digest, entry)| (entry.last_access, digest.dupe()))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(last_access, _)| *last_access);
+
+        let mut evicted = Vec::new();
+        let mut unused = Vec::new();
+        for (_, digest) in candidates {
+            if self.total_bytes <= target {
+                break;
+            }
+            if let Some(blobs) = self.remove(&digest) {
+                unused.extend(blobs);
+                evicted.push(digest);
+            }
+        }
+
+        (evicted, unused)
+    }
+}
+
+/// The key under which the results of `request` are stored, or `None` if they can't be cached.
+///
+/// Only actions whose outputs are a pure function of their inputs are cached: build actions that
+/// start from a clean output directory, and tests that opted into caching. Actions that run in a
+/// persistent worker depend on state the action digest doesn't cover, so they are never cached.
+/// Like the remote action cache, we key actions on their action digest, which doesn't cover the
+/// daemon environment that local commands inherit (minus a few exclusions), but commands that
+/// inherit the daemon's entire environment are not cached. Variables that a command picks from
+/// the daemon's environment (i.e. the allowlist of tests) are hashed into the key along with the
+/// action digest.
+fn cache_key(
+    request: &CommandExecutionRequest,
+    action: &ActionDigest,
+    cas_digest_config: CasDigestConfig,
+) -> Option<ActionDigest> {
+    if !request.outputs_cleanup || request.worker().is_some() {
+        return None;
+    }
+    let inheritance = request.local_environment_inheritance()?;
+
+    let cacheable_outputs = if request.cacheable_test() {
+        request
+            .outputs()
+            .all(|o| matches!(o, CommandExecutionOutputRef::TestPath { .. }))
+    } else {
+        let mut outputs = request.outputs().peekable();
+        outputs.peek().is_some()
+            && outputs.all(|o| matches!(o, CommandExecutionOutputRef::BuildArtifact { .. }))
+    };
+    if !cacheable_outputs {
+        return None;
+    }
+
+    let mut values = inheritance.values().peekable();
+    if values.peek().is_none() {
+        return Some(action.dupe());
+    }
+
+    let mut digester = ActionDigest::digester(cas_digest_config);
+    digester.update(action.to_string().as_bytes());
+    for (name, value) in values {
+        let value = value.to_string_lossy();
+        // Prefix every string with its length, so that different variables can't hash the same.
+        for part in [name, &*value] {
+            digester.update(&(part.len() as u64).to_le_bytes());
+            digester.update(part.as_bytes());
+        }
+    }
+    Some(digester.finalize())
+}
+
+/// Serves actions from the local action cache, and otherwise from the `fallback` cache.
+pub struct LocalActionCacheChecker {
+    pub cache: Arc<LocalActionCache>,
+    pub artifact_fs: ArtifactFs,
+    pub materializer: Arc<dyn Materializer>,
+    pub blocking_executor: Arc<dyn BlockingExecutor>,
+    pub fallback: Arc<dyn PreparedCommandOptionalExecutor>,
+}
+
+/// The outputs of a cache hit, resolved against the action's inputs.
+struct CachedOutputs<'a> {
+    outputs: IndexMap<CommandExecutionOutput, ArtifactValue>,
+    to_declare: Vec<(ProjectRelativePathBuf, ArtifactValue)>,
+    to_restore: Vec<(ProjectRelativePathBuf, &'a LocalActionCacheOutput)>,
+}
+
+impl LocalActionCacheChecker {
+    async fn lookup<'a>(
+        &'a self,
+        digest: &ActionDigest,
+    ) -> anyhow::Result<Option<(LocalActionCacheLease<'a>, LocalActionCacheEntry)>> {
+        let lease = match self.cache.acquire(digest) {
+            Some(lease) => lease,
+            None => return Ok(None),
+        };
+
+        let entry = self
+            .blocking_executor
+            .execute_io_inline(|| lease.read())
+            .await;
+        match entry {
+            Ok(entry) => Ok(Some((lease, entry))),
+            Err(e) => {
+                drop(lease);
+                self.cache.remove(digest);
+                Err(e.context(format!(
+                    "Error reading local action cache entry `{}`",
+                    digest
+                )))
+            }
+        }
+    }
+
+    fn resolve_outputs<'a>(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        entry: &'a LocalActionCacheEntry,
+    ) -> anyhow::Result<CachedOutputs<'a>> {
+        let cas_digest_config = command.digest_config.cas_digest_config();
+        let cached = entry
+            .outputs
+            .iter()
+            .map(|o| (o.path.as_str(), o))
+            .collect::<HashMap<_, _>>();
+
+        let mut builder = command
+            .request
+            .paths()
+            .input_directory()
+            .clone()
+            .into_builder();
+        let mut found = Vec::new();
+
+        for output in command.request.outputs() {
+            let path = output.resolve(&self.artifact_fs).into_path();
+            if let Some(cached_output) = cached.get(path.as_str()) {
+                let value = output_from_proto(cached_output, cas_digest_config)?;
+                insert_entry(&mut builder, &path, value)?;
+                found.push((output.cloned(), path, *cached_output));
+            }
+        }
+
+        let mut outputs = IndexMap::with_capacity(found.len());
+        let mut to_declare = Vec::with_capacity(found.len());
+        let mut to_restore = Vec::with_capacity(found.len());
+
+        for (output, path, cached_output) in found {
+            if let Some(value) = extract_artifact_value(&builder, &path, command.digest_config)? {
+                match output {
+                    CommandExecutionOutput::BuildArtifact { .. } => {
+                        to_declare.push((path.clone(), value.dupe()));
+                    }
+                    CommandExecutionOutput::TestPath { .. } => {}
+                }
+                outputs.insert(output, value);
+                to_restore.push((path, cached_output));
+            }
+        }
+
+        Ok(CachedOutputs {
+            outputs,
+            to_declare,
+            to_restore,
+        })
+    }
+
+    async fn restore(
+        &self,
+        cached: &CachedOutputs<'_>,
+        cas_digest_config: CasDigestConfig,
+        cancellations: &CancellationContext<'_>,
+    ) -> anyhow::Result<()> {
+        let paths = cached
+            .to_restore
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        self.materializer.invalidate_many(paths.clone()).await?;
+        self.blocking_executor
+            .execute_io(Box::new(CleanOutputPaths { paths }), cancellations)
+            .await
+            .context("Failed to cleanup output directory")?;
+
+        self.blocking_executor
+            .execute_io_inline(|| {
+                for (path, output) in &cached.to_restore {
+                    let dest = self.artifact_fs.fs().resolve(path);
+                    if let Some(parent) = dest.parent() {
+                        fs_util::create_dir_all(parent)?;
+                    }
+                    restore_output(&self.cache, output, &dest, cas_digest_config)
+                        .with_context(|| format!("Error restoring `{}`", path))?;
+                }
+                Ok(())
+            })
+            .await?;
+
+        self.materializer
+            .declare_existing(cached.to_declare.clone())
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl LocalActionCacheChecker {
+    async fn check(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+        cancellations: &CancellationContext<'_>,
+    ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+        let digest = match cache_key(
+            command.request,
+            &command.prepared_action.action_and_blobs.action,
+            command.digest_config.cas_digest_config(),
+        ) {
+            Some(digest) => digest,
+            None => return ControlFlow::Continue(manager),
+        };
+        let digest = &digest;
+
+        let start = Instant::now();
+        let start_time = SystemTime::now();
+
+        let lookup = executor_stage_async(
+            buck2_data::CacheQuery {
+                action_digest: digest.to_string(),
+                cache_type: buck2_data::CacheType::LocalActionCache.into(),
+            },
+            async {
+                let (lease, entry) = match self.lookup(digest).await? {
+                    Some(hit) => hit,
+                    None => return Ok(None),
+                };
+
+                // Make sure all the contents are still there before we commit to using this entry.
+                let all_present = self
+                    .blocking_executor
+                    .execute_io_inline(|| {
+                        let mut blobs = Vec::new();
+                        for output in &entry.outputs {
+                            collect_blobs(
+                                output,
+                                command.digest_config.cas_digest_config(),
+                                &mut blobs,
+                            )?;
+                        }
+                        for blob in &blobs {
+                            if !fs_util::try_exists(self.cache.blob_path(blob))? {
+                                return Err(LocalActionCacheError::MissingBlob(blob.dupe()).into());
+                            }
+                        }
+                        Ok(())
+                    })
+                    .await;
+                if let Err(e) = all_present {
+                    drop(lease);
+                    self.cache.remove(digest);
+                    return Err(e);
+                }
+
+                anyhow::Ok(Some((lease, entry)))
+            },
+        )
+        .await;
+
+        let (_lease, entry) = match lookup {
+            Ok(Some(hit)) => hit,
+            Ok(None) => return ControlFlow::Continue(manager),
+            Err(e) => {
+                tracing::warn!("Local action cache lookup failed: {:#}", e);
+                return ControlFlow::Continue(manager);
+            }
+        };
+
+        let cached = match self.resolve_outputs(command, &entry) {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("Discarding local action cache entry `{}`: {:#}", digest, e);
+                self.cache.remove(digest);
+                return ControlFlow::Continue(manager);
+            }
+        };
+
+        let manager = manager.claim().await;
+
+        if let Err(e) = self
+            .restore(
+                &cached,
+                command.digest_config.cas_digest_config(),
+                cancellations,
+            )
+            .await
+        {
+            return ControlFlow::Break(manager.error("local_action_cache_restore", e));
+        }
+
+        let timing = CommandExecutionMetadata {
+            wall_time: start.elapsed(),
+            execution_time: Duration::from_micros(entry.execution_time_us),
+            start_time,
+            execution_stats: None,
+            input_materialization_duration: Duration::ZERO,
+            hashing_duration: Duration::ZERO,
+        };
+
+        ControlFlow::Break(manager.success(
+            CommandExecutionKind::LocalActionCache {
+                digest: digest.dupe(),
+            },
+            cached.outputs,
+            CommandStdStreams::Local {
+                stdout: entry.stdout,
+                stderr: entry.stderr,
+            },
+            timing,
+        ))
+    }
+}
+
+#[async_trait]
+impl PreparedCommandOptionalExecutor for LocalActionCacheChecker {
+    async fn maybe_execute(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+        cancellations: &CancellationContext,
+    ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+        let manager = self.check(command, manager, cancellations).await?;
+        self.fallback
+            .maybe_execute(command, manager, cancellations)
+            .await
+    }
+}
+
+fn output_from_proto(
+    output: &LocalActionCacheOutput,
+    cas_digest_config: CasDigestConfig,
+) -> anyhow::Result<ActionDirectoryEntry<ActionDirectoryBuilder>> {
+    Ok(
+        match output
+            .entry
+            .as_ref()
+            .ok_or_else(|| LocalActionCacheError::MissingEntry(output.path.clone()))?
+        {
+            CachedEntry::File(file) => {
+                let (digest, _) = FileDigest::parse_digest(&file.digest, cas_digest_config)?;
+                DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+                    digest: TrackedFileDigest::new(digest, cas_digest_config),
+                    is_executable: file.is_executable,
+                }))
+            }
+            CachedEntry::Symlink(target) => DirectoryEntry::Leaf(new_symlink(target)?),
+            CachedEntry::Directory(dir) => {
+                let mut builder = ActionDirectoryBuilder::empty();
+                for entry in &dir.entries {
+                    let name = FileNameBuf::try_from(entry.path.clone())?;
+                    builder.insert(name, output_from_proto(entry, cas_digest_config)?)?;
+                }
+                DirectoryEntry::Dir(builder)
+            }
+        },
+    )
+}
+
+fn restore_output(
+    cache: &LocalActionCache,
+    output: &LocalActionCacheOutput,
+    dest: &AbsNormPath,
+    cas_digest_config: CasDigestConfig,
+) -> anyhow::Result<()> {
+    match output
+        .entry
+        .as_ref()
+        .ok_or_else(|| LocalActionCacheError::MissingEntry(output.path.clone()))?
+    {
+        CachedEntry::File(file) => {
+            let (digest, _) = FileDigest::parse_digest(&file.digest, cas_digest_config)?;
+            fs_util::copy(cache.blob_path(&digest), dest)?;
+            if file.is_executable {
+                fs_util::set_executable(dest)?;
+            }
+        }
+        CachedEntry::Symlink(target) => fs_util::symlink(target, dest)?,
+        CachedEntry::Directory(dir) => {
+            fs_util::create_dir_all(dest)?;
+            for entry in &dir.entries {
+                let dest = dest.join(FileName::new(&entry.path)?);
+                restore_output(cache, entry, &dest, cas_digest_config)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serialize an output for storage in the cache. Returns `None` if the output contains something
+/// we can't store (i.e. a symlink to an absolute path).
+fn output_to_proto(
+    path: String,
+    entry: DirectoryEntry<&ActionSharedDirectory, &ActionDirectoryMember>,
+    abspath: AbsNormPathBuf,
+    blobs: &mut Vec<(FileDigest, AbsNormPathBuf)>,
+) -> Option<LocalActionCacheOutput> {
+    let entry = match entry {
+        DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+            blobs.push((f.digest.data().dupe(), abspath));
+            CachedEntry::File(LocalActionCacheFile {
+                digest: f.digest.data().to_string(),
+                is_executable: f.is_executable,
+            })
+        }
+        DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => {
+            CachedEntry::Symlink(s.target().as_str().to_owned())
+        }
+        DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(..)) => return None,
+        DirectoryEntry::Dir(d) => {
+            let mut entries = Vec::new();
+            for (name, entry) in d.entries() {
+                entries.push(output_to_proto(
+                    name.as_str().to_owned(),
+                    entry.as_ref(),
+                    abspath.join(name),
+                    blobs,
+                )?);
+            }
+            CachedEntry::Directory(LocalActionCacheDirectory { entries })
+        }
+    };
+
+    Some(LocalActionCacheOutput {
+        path,
+        entry: Some(entry),
+    })
+}
+
+/// Wraps a local executor to store the results of successful executions in the local action
+/// cache.
+pub struct LocalActionCacheExecutor {
+    pub inner: Arc<dyn PreparedCommandExecutor>,
+    pub cache: Arc<LocalActionCache>,
+    pub artifact_fs: ArtifactFs,
+    pub blocking_executor: Arc<dyn BlockingExecutor>,
+}
+
+impl LocalActionCacheExecutor {
+    async fn store(
+        &self,
+        digest: &ActionDigest,
+        result: &CommandExecutionResult,
+    ) -> anyhow::Result<()> {
+        let (stdout, stderr) = match &result.report.std_streams {
+            CommandStdStreams::Local { stdout, stderr } => (stdout.clone(), stderr.clone()),
+            _ => return Ok(()),
+        };
+
+        let mut outputs = Vec::with_capacity(result.outputs.len());
+        let mut blobs = Vec::new();
+        for (output, value) in result.resolve_outputs(&self.artifact_fs) {
+            let abspath = self.artifact_fs.fs().resolve(output.path());
+            match output_to_proto(
+                output.path().as_str().to_owned(),
+                value.entry().as_ref(),
+                abspath,
+                &mut blobs,
+            ) {
+                Some(output) => outputs.push(output),
+                None => return Ok(()),
+            }
+        }
+
+        let entry = LocalActionCacheEntry {
+            outputs,
+            stdout,
+            stderr,
+            execution_time_us: result
+                .report
+                .timing
+                .execution_time
+                .as_micros()
+                .try_into()
+                .unwrap_or(u64::MAX),
+        };
+
+        self.blocking_executor
+            .execute_io_inline(|| self.cache.store(digest, &entry, &blobs))
+            .await
+    }
+}
+
+#[async_trait]
+impl PreparedCommandExecutor for LocalActionCacheExecutor {
+    async fn exec_cmd(
+        &self,
+        command: &PreparedCommand<'_, '_>,
+        manager: CommandExecutionManager,
+        cancellations: &CancellationContext,
+    ) -> CommandExecutionResult {
+        let result = self.inner.exec_cmd(command, manager, cancellations).await;
+
+        let digest = match result.was_locally_executed() && command.request.allow_cache_upload() {
+            true => cache_key(
+                command.request,
+                &command.prepared_action.action_and_blobs.action,
+                command.digest_config.cas_digest_config(),
+            ),
+            false => None,
+        };
+        if let Some(digest) = &digest {
+            if let Err(e) = self.store(digest, &result).await {
+                tracing::warn!(
+                    "Error storing `{}` in the local action cache: {:#}",
+                    digest,
+                    e
+                );
+            }
+        }
+
+        result
+    }
+
+    fn is_local_execution_possible(&self, executor_preference: ExecutorPreference) -> bool {
+        self.inner.is_local_execution_possible(executor_preference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use buck2_common::cas_digest::testing;
+    use buck2_core::base_deferred_key::BaseDeferredKey;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::name::CellName;
+    use buck2_core::cells::CellResolver;
+    use buck2_core::configuration::data::ConfigurationData;
+    use buck2_core::fs::buck_out_path::BuckOutPath;
+    use buck2_core::fs::buck_out_path::BuckOutPathResolver;
+    use buck2_core::fs::buck_out_path::BuckOutTestPath;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+    use buck2_core::fs::project::ProjectRoot;
+    use buck2_core::fs::project::ProjectRootTemp;
+    use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+    use buck2_execute::digest_config::DigestConfig;
+    use buck2_execute::execute::environment_inheritance::EnvironmentInheritance;
+    use buck2_execute::execute::request::CommandExecutionPaths;
+    use buck2_execute::execute::request::OutputCreationBehavior;
+    use buck2_execute::execute::request::OutputType;
+    use indexmap::indexset;
+
+    use super::*;
+
+    fn action(n: u8) -> ActionDigest {
+        ActionDigest::new_sha1([n; 20], 10)
+    }
+
+    fn blob(n: u8, size: u64) -> FileDigest {
+        FileDigest::new_sha1([n; 20], size)
+    }
+
+    #[test]
+    fn test_digest_file_name_roundtrip() -> anyhow::Result<()> {
+        let digest = blob(7, 123);
+        let name = digest_file_name(&digest);
+        assert!(!name.contains(':'));
+        assert_eq!(
+            digest,
+            parse_digest_file_name(&name, testing::sha1_sha256())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_and_restore() -> anyhow::Result<()> {
+        let cas_digest_config = testing::sha1_sha256();
+        let temp = ProjectRootTemp::new()?;
+        let root = temp.path().root().to_buf();
+        let cache_dir = root.join(FileName::unchecked_new("cache"));
+
+        let contents = b"cached output";
+        let digest = FileDigest::from_content(contents, cas_digest_config);
+        let src = root.join(FileName::unchecked_new("src"));
+        fs_util::write(&src, contents)?;
+
+        let entry = LocalActionCacheEntry {
+            outputs: vec![LocalActionCacheOutput {
+                path: "buck-out/out".to_owned(),
+                entry: Some(CachedEntry::Directory(LocalActionCacheDirectory {
+                    entries: vec![
+                        LocalActionCacheOutput {
+                            path: "bin".to_owned(),
+                            entry: Some(CachedEntry::File(LocalActionCacheFile {
+                                digest: digest.to_string(),
+                                is_executable: true,
+                            })),
+                        },
+                        LocalActionCacheOutput {
+                            path: "link".to_owned(),
+                            entry: Some(CachedEntry::Symlink("bin".to_owned())),
+                        },
+                    ],
+                })),
+            }],
+            stdout: b"stdout".to_vec(),
+            stderr: Vec::new(),
+            execution_time_us: 1000,
+        };
+
+        let cache = LocalActionCache::open(
+            cache_dir.clone(),
+            DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES,
+            cas_digest_config,
+        )?;
+        cache.store(&action(1), &entry, &[(digest.dupe(), src)])?;
+        drop(cache);
+
+        // The entry survives reopening the cache, like it would a daemon restart.
+        let cache = LocalActionCache::open(
+            cache_dir,
+            DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES,
+            cas_digest_config,
+        )?;
+        assert!(cache.size_bytes() > digest.size());
+        let lease = cache.acquire(&action(1)).context("Missing entry")?;
+        let restored = lease.read()?;
+        assert_eq!(entry, restored);
+
+        let dest = root.join(FileName::unchecked_new("dest"));
+        restore_output(&cache, &restored.outputs[0], &dest, cas_digest_config)?;
+        let bin = dest.join(FileName::unchecked_new("bin"));
+        assert_eq!(contents.as_slice(), fs_util::read(&bin)?.as_slice());
+        assert_eq!(
+            PathBuf::from("bin"),
+            fs_util::read_link(dest.join(FileName::unchecked_new("link")))?
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            assert_ne!(0, fs_util::metadata(&bin)?.permissions().mode() & 0o111);
+        }
+
+        Ok(())
+    }
+
+    fn artifact_fs(project_fs: ProjectRoot) -> ArtifactFs {
+        ArtifactFs::new(
+            CellResolver::testing_with_name_and_path(
+                CellName::testing_new("cell"),
+                CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("cell_path".into())),
+            ),
+            BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new("buck_out/v2".into())),
+            project_fs,
+        )
+    }
+
+    /// A request with the outputs and environment inheritance that `run` actions use.
+    fn run_request(fs: &ArtifactFs) -> anyhow::Result<CommandExecutionRequest> {
+        let path = BuckOutPath::new(
+            BaseDeferredKey::TargetLabel(ConfiguredTargetLabel::testing_parse(
+                "cell//pkg:foo",
+                ConfigurationData::testing_new(),
+            )),
+            ForwardRelativePathBuf::unchecked_new("foo.out".into()),
+        );
+        let outputs = indexset![CommandExecutionOutput::BuildArtifact {
+            path,
+            output_type: OutputType::File,
+        }];
+        Ok(CommandExecutionRequest::new(
+            vec![],
+            vec!["true".to_owned()],
+            CommandExecutionPaths::new(vec![], outputs, fs, DigestConfig::testing_default())?,
+            Default::default(),
+        )
+        .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions()))
+    }
+
+    #[test]
+    fn test_run_action_hits_after_store() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = artifact_fs(temp.path().dupe());
+        let request = run_request(&fs)?;
+
+        let key = cache_key(&request, &action(1), testing::sha1_sha256())
+            .context("Run action is not cacheable")?;
+        assert_eq!(action(1), key);
+
+        let cache = LocalActionCache::open(
+            temp.path().root().join(FileName::unchecked_new("cache")),
+            DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES,
+            testing::sha1_sha256(),
+        )?;
+        assert!(cache.acquire(&key).is_none());
+        cache.store(&key, &LocalActionCacheEntry::default(), &[])?;
+        assert!(cache.acquire(&key).is_some());
+
+        // Actions that don't start from a clean output directory are not cached.
+        let mut request = run_request(&fs)?;
+        request.outputs_cleanup = false;
+        assert_eq!(
+            None,
+            cache_key(&request, &action(1), testing::sha1_sha256())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cacheable_test_hits_after_store() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = artifact_fs(temp.path().dupe());
+        let outputs = indexset![CommandExecutionOutput::TestPath {
+            path: BuckOutTestPath::new(
+                ForwardRelativePathBuf::unchecked_new("test".into()),
+                ForwardRelativePathBuf::unchecked_new("out".into()),
+            ),
+            create: OutputCreationBehavior::Create,
+        }];
+        let request = CommandExecutionRequest::new(
+            vec![],
+            vec!["true".to_owned()],
+            CommandExecutionPaths::new(vec![], outputs, &fs, DigestConfig::testing_default())?,
+            Default::default(),
+        )
+        .with_local_environment_inheritance(EnvironmentInheritance::test_allowlist());
+
+        // Tests are only cached if they opt in.
+        assert_eq!(
+            None,
+            cache_key(&request, &action(1), testing::sha1_sha256())
+        );
+        let request = request.with_cacheable_test(true);
+
+        let key = cache_key(&request, &action(1), testing::sha1_sha256())
+            .context("Cacheable test is not cacheable")?;
+        assert_eq!(
+            Some(&key),
+            cache_key(&request, &action(1), testing::sha1_sha256()).as_ref()
+        );
+        if EnvironmentInheritance::test_allowlist()
+            .values()
+            .next()
+            .is_some()
+        {
+            // The allowlisted variables are part of the key.
+            assert_ne!(action(1), key);
+        }
+
+        let cache = LocalActionCache::open(
+            temp.path().root().join(FileName::unchecked_new("cache")),
+            DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES,
+            testing::sha1_sha256(),
+        )?;
+        assert!(cache.acquire(&key).is_none());
+        cache.store(&key, &LocalActionCacheEntry::default(), &[])?;
+        assert!(cache.acquire(&key).is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_shares_blobs() {
+        let mut index = LocalActionCacheIndex::default();
+
+        assert_eq!(vec![blob(1, 100)], index.acquire_blobs(&[blob(1, 100)]));
+        index.insert(action(1), 1, vec![blob(1, 100)]);
+
+        // The blob is already there, so it doesn't need to be written again.
+        assert!(index.acquire_blobs(&[blob(1, 100)]).is_empty());
+        index.insert(action(2), 1, vec![blob(1, 100)]);
+        assert_eq!(102, index.total_bytes);
+
+        assert_eq!(Some(vec![]), index.remove(&action(1)));
+        assert_eq!(Some(vec![blob(1, 100)]), index.remove(&action(2)));
+        assert_eq!(None, index.remove(&action(2)));
+        assert_eq!(0, index.total_bytes);
+    }
+
+    #[test]
+    fn test_index_writes_blobs_until_stored() {
+        let mut index = LocalActionCacheIndex::default();
+
+        // Until an entry referencing the blob is stored, concurrent stores all write it.
+        assert_eq!(vec![blob(1, 100)], index.acquire_blobs(&[blob(1, 100)]));
+        assert_eq!(vec![blob(1, 100)], index.acquire_blobs(&[blob(1, 100)]));
+        assert_eq!(100, index.total_bytes);
+
+        index.insert(action(1), 1, vec![blob(1, 100)]);
+        assert!(index.acquire_blobs(&[blob(1, 100)]).is_empty());
+
+        // A failed store leaves the blob in place for the stores still referencing it.
+        assert!(index.release_blobs(&[blob(1, 100)]).is_empty());
+        assert!(index.release_blobs(&[blob(1, 100)]).is_empty());
+        assert_eq!(Some(vec![blob(1, 100)]), index.remove(&action(1)));
+    }
+
+    #[test]
+    fn test_index_evicts_least_recently_used() {
+        let mut index = LocalActionCacheIndex::default();
+        for i in 1..=3 {
+            index.acquire_blobs(&[blob(i, 100)]);
+            index.insert(action(i), 0, vec![blob(i, 100)]);
+        }
+
+        // Using an entry makes it the most recently used one.
+        assert!(index.pin(&action(1)));
+        index.unpin(&action(1));
+
+        let (entries, blobs) = index.evict(200);
+        assert_eq!(vec![action(2)], entries);
+        assert_eq!(vec![blob(2, 100)], blobs);
+        assert_eq!(200, index.total_bytes);
+    }
+
+    #[test]
+    fn test_index_does_not_evict_pinned() {
+        let mut index = LocalActionCacheIndex::default();
+        for i in 1..=2 {
+            index.acquire_blobs(&[blob(i, 100)]);
+            index.insert(action(i), 0, vec![blob(i, 100)]);
+        }
+
+        assert!(index.pin(&action(1)));
+        let (entries, _) = index.evict(0);
+        assert_eq!(vec![action(2)], entries);
+        assert_eq!(100, index.total_bytes);
+    }
+
+    #[test]
+    fn test_index_insert_keeps_pins() {
+        let mut index = LocalActionCacheIndex::default();
+        index.acquire_blobs(&[blob(1, 100)]);
+        index.insert(action(1), 0, vec![blob(1, 100)]);
+        assert!(index.pin(&action(1)));
+
+        // Replacing an entry that is being restored keeps it from being evicted.
+        index.acquire_blobs(&[blob(2, 100)]);
+        index.insert(action(1), 0, vec![blob(2, 100)]);
+        assert_eq!((vec![], vec![]), index.evict(0));
+
+        index.unpin(&action(1));
+        assert_eq!((vec![action(1)], vec![blob(2, 100)]), index.evict(0));
+    }
+}
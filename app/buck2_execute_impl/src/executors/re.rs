@@ -69,6 +69,7 @@ pub struct ReExecutor {
     pub skip_cache_read: bool,
     pub skip_cache_write: bool,
     pub re_max_queue_time_ms: Option<u64>,
+    pub re_priority: Option<i32>,
     pub paranoid: Option<ParanoidDownloader>,
     pub materialize_failed_inputs: bool,
 }
@@ -145,6 +146,7 @@ impl ReExecutor {
                 self.skip_cache_read,
                 self.skip_cache_write,
                 self.re_max_queue_time_ms.map(Duration::from_millis),
+                self.re_priority,
                 &self.knobs,
             )
             .await;
@@ -148,6 +148,8 @@ fn spawn_via_forkserver(
                 stderr: stderr_path.as_os_str().as_bytes().into(),
             }),
             graceful_shutdown_timeout_s,
+            sandbox: None,
+            resource_limits: None,
         };
         apply_local_execution_environment(&mut req, &working_directory, env, None);
         let res = forkserver
@@ -433,6 +435,7 @@ impl WorkerHandle {
                     GatherOutputStatus::Finished {
                         exit_code: exec_response.exit_code,
                         execution_stats: None,
+                        sandbox: None,
                     },
                     vec![],
                     exec_response.stderr.into(),
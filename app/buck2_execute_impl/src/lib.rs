@@ -17,3 +17,4 @@ pub mod executors;
 pub mod low_pass_filter;
 pub mod materializers;
 pub mod re;
+pub mod sandbox;
@@ -0,0 +1,406 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Decides what a local action gets to see of the project when it runs in a sandbox. The sandbox
+//! itself is set up by the forkserver.
+//!
+//! Each entry is a mount, and large actions would run out of them if every input was mounted on
+//! its own. So a directory whose contents on disk are all declared inputs is mounted as a whole,
+//! and only directories that also contain undeclared paths have their inputs mounted one by one.
+
+use std::path::Path;
+use std::sync::Once;
+
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_core::directory::Directory;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::directory::ActionDirectoryBuilder;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::inputs_directory::inputs_directory;
+use buck2_execute::execute::request::CommandExecutionInput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+
+/// Don't list more than this many undeclared paths in errors.
+const MAX_UNDECLARED_PATHS: usize = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SandboxEntryKind {
+    Input,
+    Output,
+    Directory,
+}
+
+/// The paths that are visible to an action running in a sandbox.
+#[derive(Debug)]
+pub struct SandboxLayout {
+    entries: Vec<(ProjectRelativePathBuf, SandboxEntryKind)>,
+}
+
+impl SandboxLayout {
+    /// Expose the action's inputs read-only, and its output directories and scratch path
+    /// read-write. This lists the directories of inputs on disk, so it should run on the blocking
+    /// executor.
+    pub fn new(
+        request: &CommandExecutionRequest,
+        artifact_fs: &ArtifactFs,
+    ) -> anyhow::Result<Self> {
+        let inputs = inputs_directory(request.inputs(), artifact_fs)?;
+
+        let mut writable = Vec::new();
+        for output in request.outputs() {
+            if let Some(path) = output.resolve(artifact_fs).path_to_create() {
+                writable.push(path.to_buf());
+            }
+        }
+        for input in request.inputs() {
+            if let CommandExecutionInput::ScratchPath(path) = input {
+                writable.push(artifact_fs.buck_out_path_resolver().resolve_scratch(path));
+            }
+        }
+
+        Self::from_inputs_and_outputs(&inputs, writable, artifact_fs.fs())
+    }
+
+    fn from_inputs_and_outputs(
+        inputs: &ActionDirectoryBuilder,
+        mut writable: Vec<ProjectRelativePathBuf>,
+        fs: &ProjectRoot,
+    ) -> anyhow::Result<Self> {
+        // Outputs nested in other outputs are already visible.
+        writable.sort();
+        writable.dedup_by(|path, parent| path.starts_with(&**parent));
+
+        let mut entries = Vec::new();
+        visit_inputs(
+            inputs,
+            ProjectRelativePath::empty(),
+            &writable,
+            fs,
+            &mut entries,
+        )?;
+
+        // Those come last so that outputs nested in (read-only) inputs get mounted over them.
+        entries.extend(
+            writable
+                .into_iter()
+                .map(|path| (path, SandboxEntryKind::Output)),
+        );
+
+        Ok(Self { entries })
+    }
+
+    pub fn to_proto(&self, fs: &ProjectRoot) -> buck2_forkserver_proto::SandboxConfig {
+        buck2_forkserver_proto::SandboxConfig {
+            project_root: path_bytes(fs.root().as_path()),
+            entries: self
+                .entries
+                .iter()
+                .map(|(path, kind)| buck2_forkserver_proto::SandboxEntry {
+                    path: path.as_str().as_bytes().to_vec(),
+                    data: Some(match kind {
+                        SandboxEntryKind::Input => buck2_forkserver_proto::SandboxInput {}.into(),
+                        SandboxEntryKind::Output => buck2_forkserver_proto::SandboxOutput {}.into(),
+                        SandboxEntryKind::Directory => {
+                            buck2_forkserver_proto::SandboxDirectory {}.into()
+                        }
+                    }),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether this path exists in the sandbox (assuming it exists in the project).
+    fn is_visible(&self, path: &ProjectRelativePath) -> bool {
+        self.entries.iter().any(|(entry, kind)| {
+            // Parents of entries get created in the sandbox.
+            entry.starts_with(path)
+                || (*kind != SandboxEntryKind::Directory && path.starts_with(entry))
+        })
+    }
+
+    /// Of the paths a command failed to access, return those that exist in the project but were
+    /// hidden from it by the sandbox. Relative paths are resolved against `working_directory`.
+    pub fn find_undeclared_paths<'a>(
+        &self,
+        accessed: impl IntoIterator<Item = &'a str>,
+        working_directory: &ProjectRelativePath,
+        fs: &ProjectRoot,
+    ) -> Vec<ProjectRelativePathBuf> {
+        let mut found = Vec::new();
+
+        for path in accessed {
+            if found.len() >= MAX_UNDECLARED_PATHS {
+                break;
+            }
+
+            let path = match self.resolve(path, working_directory, fs) {
+                Some(path) => path,
+                None => continue,
+            };
+
+            if path.is_empty()
+                || working_directory.starts_with(&path)
+                || self.is_visible(&path)
+                || found.contains(&path)
+            {
+                continue;
+            }
+
+            if let Ok(Some(_)) = fs_util::symlink_metadata_if_exists(fs.resolve(&path)) {
+                found.push(path);
+            }
+        }
+
+        found
+    }
+
+    fn resolve(
+        &self,
+        path: &str,
+        working_directory: &ProjectRelativePath,
+        fs: &ProjectRoot,
+    ) -> Option<ProjectRelativePathBuf> {
+        let as_path = Path::new(path);
+        if as_path.is_absolute() {
+            let path = AbsPath::new(as_path).ok()?;
+            if !path.starts_with(fs.root().as_path()) {
+                return None;
+            }
+            fs.relativize_any(path).ok()
+        } else {
+            working_directory.join_normalized(path).ok()
+        }
+    }
+}
+
+/// Format a message for actions that failed in the sandbox after referencing undeclared paths.
+pub fn undeclared_paths_message(paths: &[ProjectRelativePathBuf]) -> String {
+    let mut message = String::from(
+        "\nThis action ran in a sandbox, which hid the following paths because they are not \
+        declared as inputs of the action:\n",
+    );
+    for path in paths {
+        message.push_str(&format!("  {}\n", path));
+    }
+    message.push_str(
+        "Declare them as inputs, or pass `no_sandbox = True` to `ctx.actions.run` to run this \
+        action without a sandbox.\n",
+    );
+    message
+}
+
+/// Warn (once) that sandboxing is enabled, but not available on this host.
+pub fn warn_sandbox_unavailable(reason: &str) {
+    static WARNED: Once = Once::new();
+
+    WARNED.call_once(|| {
+        tracing::warn!(
+            "`buck2.sandbox_local_actions` is enabled, but the sandbox could not be set up on this \
+            host ({}). Local actions will run without it.",
+            reason
+        );
+    });
+}
+
+fn path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+}
+
+/// Add entries for the contents of `dir`, which is at `path`. Returns whether `dir` contains
+/// everything there is on disk at `path`, in which case the caller can expose `path` as a whole
+/// instead of the entries that were added.
+fn visit_inputs(
+    dir: &dyn Directory<ActionDirectoryMember, TrackedFileDigest>,
+    path: &ProjectRelativePath,
+    writable: &[ProjectRelativePathBuf],
+    fs: &ProjectRoot,
+    entries: &mut Vec<(ProjectRelativePathBuf, SandboxEntryKind)>,
+) -> anyhow::Result<bool> {
+    let mut complete = match fs_util::read_dir_if_exists(fs.resolve(path)) {
+        Ok(Some(on_disk)) => {
+            let mut complete = true;
+            let mut count = 0;
+            for entry in on_disk {
+                let name = entry?.file_name();
+                let declared = name
+                    .to_str()
+                    .and_then(|name| FileName::new(name).ok())
+                    .map_or(false, |name| dir.get(name).is_some());
+                complete &= declared;
+                count += 1;
+            }
+            complete && count == dir.entries().count()
+        }
+        _ => false,
+    };
+
+    for (name, entry) in dir.entries() {
+        let path = path.join(name);
+
+        // Outputs are exposed separately.
+        if writable.iter().any(|w| path.starts_with(w)) {
+            complete = false;
+            continue;
+        }
+
+        match entry {
+            DirectoryEntry::Dir(d) => {
+                let mut children = Vec::new();
+                if visit_inputs(d, &path, writable, fs, &mut children)? {
+                    entries.push((path, SandboxEntryKind::Input));
+                } else {
+                    complete = false;
+                    if d.entries().next().is_none() {
+                        entries.push((path, SandboxEntryKind::Directory));
+                    } else {
+                        entries.extend(children);
+                    }
+                }
+            }
+            DirectoryEntry::Leaf(..) => {
+                entries.push((path, SandboxEntryKind::Input));
+            }
+        }
+    }
+
+    Ok(complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::file_ops::FileMetadata;
+    use buck2_core::fs::project::ProjectRootTemp;
+    use buck2_execute::digest_config::DigestConfig;
+    use buck2_execute::directory::insert_file;
+
+    use super::*;
+
+    fn layout(
+        inputs: &[&str],
+        outputs: &[&str],
+        fs: &ProjectRoot,
+    ) -> anyhow::Result<SandboxLayout> {
+        let digest_config = DigestConfig::testing_default();
+        let mut builder = ActionDirectoryBuilder::empty();
+        for input in inputs {
+            insert_file(
+                &mut builder,
+                ProjectRelativePath::new(input)?,
+                FileMetadata {
+                    digest: TrackedFileDigest::from_content(
+                        input.as_bytes(),
+                        digest_config.cas_digest_config(),
+                    ),
+                    is_executable: false,
+                },
+            )?;
+        }
+        let outputs = outputs
+            .iter()
+            .map(|o| ProjectRelativePathBuf::unchecked_new((*o).to_owned()))
+            .collect();
+        SandboxLayout::from_inputs_and_outputs(&builder, outputs, fs)
+    }
+
+    fn describe(layout: &SandboxLayout) -> Vec<String> {
+        layout
+            .entries
+            .iter()
+            .map(|(path, kind)| format!("{:?} {}", kind, path))
+            .collect()
+    }
+
+    #[test]
+    fn test_layout() -> anyhow::Result<()> {
+        let t = ProjectRootTemp::new()?;
+        let fs = t.path();
+
+        t.write_file("complete/a", "");
+        t.write_file("complete/sub/b", "");
+        t.write_file("partial/c", "");
+        t.write_file("partial/undeclared", "");
+        t.write_file("partial/sub/d", "");
+        fs_util::create_dir_all(fs.resolve(ProjectRelativePath::new("out/gen")?))?;
+
+        let layout = layout(
+            &[
+                "complete/a",
+                "complete/sub/b",
+                "partial/c",
+                "partial/sub/d",
+                "out/gen/meta",
+            ],
+            &["out/gen", "out/gen/nested"],
+            fs,
+        )?;
+
+        assert_eq!(
+            describe(&layout),
+            vec![
+                "Input complete",
+                "Input partial/c",
+                "Input partial/sub",
+                "Output out/gen"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_undeclared_paths() -> anyhow::Result<()> {
+        let t = ProjectRootTemp::new()?;
+        let fs = t.path();
+
+        t.write_file("src/main.c", "");
+        t.write_file("src/sibling.h", "");
+        t.write_file("include/undeclared.h", "");
+        t.write_file("lib/x.h", "");
+
+        let layout = layout(&["src/main.c", "lib/x.h"], &["out"], fs)?;
+        let undeclared = fs.root().as_path().join("include/undeclared.h");
+        let declared = fs.root().as_path().join("lib/x.h");
+        let accessed = [
+            undeclared.to_str().unwrap(),
+            declared.to_str().unwrap(),
+            "main.c",
+            "/usr/include/stdio.h",
+            "missing.h",
+            "../out/foo",
+            // Hidden, even though a declared input is in the same directory.
+            "sibling.h",
+        ];
+
+        assert_eq!(
+            layout.find_undeclared_paths(accessed, ProjectRelativePath::new("src")?, fs),
+            vec![
+                ProjectRelativePathBuf::unchecked_new("include/undeclared.h".to_owned()),
+                ProjectRelativePathBuf::unchecked_new("src/sibling.h".to_owned()),
+            ]
+        );
+
+        Ok(())
+    }
+}
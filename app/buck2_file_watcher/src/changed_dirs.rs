@@ -0,0 +1,29 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashSet;
+
+use buck2_core::cells::cell_path::CellPath;
+
+/// The directories whose listings a file watcher sync invalidated.
+pub enum ChangedDirs {
+    /// The file watcher lost track of changes (e.g. on a Watchman fresh instance), so any
+    /// directory may have changed.
+    All,
+    Dirs(HashSet<CellPath>),
+}
+
+impl ChangedDirs {
+    pub fn contains(&self, dir: &CellPath) -> bool {
+        match self {
+            ChangedDirs::All => true,
+            ChangedDirs::Dirs(dirs) => dirs.contains(dir),
+        }
+    }
+}
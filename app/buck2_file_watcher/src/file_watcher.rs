@@ -21,6 +21,7 @@ use buck2_core::fs::project::ProjectRoot;
 use buck2_core::is_open_source;
 use dice::DiceTransactionUpdater;
 
+use crate::changed_dirs::ChangedDirs;
 use crate::mergebase::Mergebase;
 use crate::notify::NotifyFileWatcher;
 use crate::watchman::interface::WatchmanFileWatcher;
@@ -30,7 +31,7 @@ pub trait FileWatcher: Allocative + Send + Sync + 'static {
     async fn sync(
         &self,
         dice: DiceTransactionUpdater,
-    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase)>;
+    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase, ChangedDirs)>;
 }
 
 impl dyn FileWatcher {
@@ -9,6 +9,7 @@
 
 #![feature(error_generic_member_access)]
 
+pub mod changed_dirs;
 pub mod dep_files;
 pub mod file_watcher;
 pub mod mergebase;
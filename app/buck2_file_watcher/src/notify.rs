@@ -35,6 +35,7 @@ use notify::Watcher;
 use starlark_map::ordered_set::OrderedSet;
 use tracing::info;
 
+use crate::changed_dirs::ChangedDirs;
 use crate::file_watcher::FileWatcher;
 use crate::mergebase::Mergebase;
 use crate::stats::FileWatcherStats;
@@ -207,12 +208,17 @@ impl NotifyFileWatcher {
     fn sync2(
         &self,
         mut dice: DiceTransactionUpdater,
-    ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransactionUpdater)> {
+    ) -> anyhow::Result<(
+        buck2_data::FileWatcherStats,
+        ChangedDirs,
+        DiceTransactionUpdater,
+    )> {
         let mut guard = self.data.lock().unwrap();
         let old = mem::replace(&mut *guard, Ok(NotifyFileData::new()));
         let (stats, changes) = old?.sync();
+        let changed_dirs = ChangedDirs::Dirs(changes.changed_dirs());
         changes.write_to_dice(&mut dice)?;
-        Ok((stats, dice))
+        Ok((stats, changed_dirs, dice))
     }
 }
 
@@ -221,16 +227,16 @@ impl FileWatcher for NotifyFileWatcher {
     async fn sync(
         &self,
         dice: DiceTransactionUpdater,
-    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase)> {
+    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase, ChangedDirs)> {
         span_async(
             buck2_data::FileWatcherStart {
                 provider: buck2_data::FileWatcherProvider::RustNotify as i32,
             },
             async {
                 let (stats, res) = match self.sync2(dice) {
-                    Ok((stats, dice)) => {
+                    Ok((stats, changed_dirs, dice)) => {
                         let mergebase = Mergebase(Arc::new(stats.branched_from_revision.clone()));
-                        ((Some(stats)), Ok((dice, mergebase)))
+                        ((Some(stats)), Ok((dice, mergebase, changed_dirs)))
                     }
                     Err(e) => (None, Err(e)),
                 };
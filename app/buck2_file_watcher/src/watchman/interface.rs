@@ -30,6 +30,7 @@ use watchman_client::expr::Expr;
 use watchman_client::prelude::Connector;
 use watchman_client::prelude::FileType;
 
+use crate::changed_dirs::ChangedDirs;
 use crate::file_watcher::FileWatcher;
 use crate::mergebase::Mergebase;
 use crate::stats::FileWatcherStats;
@@ -61,7 +62,10 @@ impl WatchmanQueryProcessor {
         mut ctx: DiceTransactionUpdater,
         events: Vec<WatchmanEvent>,
         watchman_version: Option<String>,
-    ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransactionUpdater)> {
+    ) -> anyhow::Result<(
+        (buck2_data::FileWatcherStats, ChangedDirs),
+        DiceTransactionUpdater,
+    )> {
         let mut handler = FileChangeTracker::new();
         let mut stats = FileWatcherStats::new(
             events.len(),
@@ -94,9 +98,10 @@ impl WatchmanQueryProcessor {
         }
 
         let stats = stats.finish();
+        let changed_dirs = ChangedDirs::Dirs(handler.changed_dirs());
         handler.write_to_dice(&mut ctx)?;
 
-        Ok((stats, ctx))
+        Ok(((stats, changed_dirs), ctx))
     }
 
     fn process_one_change(
@@ -231,7 +236,7 @@ async fn try_fetch_global_rev(hash: &str) -> Option<u64> {
 
 #[async_trait]
 impl SyncableQueryProcessor for WatchmanQueryProcessor {
-    type Output = buck2_data::FileWatcherStats;
+    type Output = (buck2_data::FileWatcherStats, ChangedDirs);
     type Payload = DiceTransactionUpdater;
 
     async fn process_events(
@@ -286,19 +291,22 @@ impl SyncableQueryProcessor for WatchmanQueryProcessor {
         let ctx = ctx.unstable_take();
 
         Ok((
-            buck2_data::FileWatcherStats {
-                fresh_instance: true,
-                branched_from_revision: mergebase.clone(),
-                branched_from_global_rev: self.last_mergebase_global_rev,
-                incomplete_events_reason: Some("Fresh instance".to_owned()),
-                watchman_version,
-                fresh_instance_data: Some(buck2_data::FreshInstance {
-                    new_mergebase: has_new_mergebase,
-                    cleared_dice: true,
-                    cleared_dep_files: clear_dep_files,
-                }),
-                ..Default::default()
-            },
+            (
+                buck2_data::FileWatcherStats {
+                    fresh_instance: true,
+                    branched_from_revision: mergebase.clone(),
+                    branched_from_global_rev: self.last_mergebase_global_rev,
+                    incomplete_events_reason: Some("Fresh instance".to_owned()),
+                    watchman_version,
+                    fresh_instance_data: Some(buck2_data::FreshInstance {
+                        new_mergebase: has_new_mergebase,
+                        cleared_dice: true,
+                        cleared_dep_files: clear_dep_files,
+                    }),
+                    ..Default::default()
+                },
+                ChangedDirs::All,
+            ),
             ctx,
         ))
     }
@@ -307,7 +315,7 @@ impl SyncableQueryProcessor for WatchmanQueryProcessor {
 #[derive(Allocative)]
 pub(crate) struct WatchmanFileWatcher {
     #[allocative(skip)]
-    query: SyncableQuery<buck2_data::FileWatcherStats, DiceTransactionUpdater>,
+    query: SyncableQuery<(buck2_data::FileWatcherStats, ChangedDirs), DiceTransactionUpdater>,
 }
 
 /// The watchman query is constructed once on daemon startup. It is an unfiltered watchman query
@@ -362,16 +370,16 @@ impl FileWatcher for WatchmanFileWatcher {
     async fn sync(
         &self,
         dice: DiceTransactionUpdater,
-    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase)> {
+    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase, ChangedDirs)> {
         span_async(
             buck2_data::FileWatcherStart {
                 provider: buck2_data::FileWatcherProvider::Watchman as i32,
             },
             async {
                 let (stats, res) = match self.query.sync(dice).await {
-                    Ok((stats, dice)) => {
+                    Ok(((stats, changed_dirs), dice)) => {
                         let mergebase = Mergebase(Arc::new(stats.branched_from_revision.clone()));
-                        ((Some(stats)), Ok((dice, mergebase)))
+                        ((Some(stats)), Ok((dice, mergebase, changed_dirs)))
                     }
                     Err(e) => (None, Err(e)),
                 };
@@ -34,9 +34,11 @@ where
             CommandEvent::Exit(GatherOutputStatus::Finished {
                 exit_code,
                 execution_stats,
+                sandbox,
             }) => Data::Exit(buck2_forkserver_proto::ExitEvent {
                 exit_code,
                 execution_stats,
+                sandbox,
             }),
             CommandEvent::Exit(GatherOutputStatus::TimedOut(duration)) => {
                 Data::Timeout(buck2_forkserver_proto::TimeoutEvent {
@@ -78,9 +80,11 @@ where
             Data::Exit(buck2_forkserver_proto::ExitEvent {
                 exit_code,
                 execution_stats,
+                sandbox,
             }) => CommandEvent::Exit(GatherOutputStatus::Finished {
                 exit_code,
                 execution_stats,
+                sandbox,
             }),
             Data::Timeout(buck2_forkserver_proto::TimeoutEvent { duration }) => {
                 CommandEvent::Exit(GatherOutputStatus::TimedOut(
@@ -48,6 +48,7 @@ pub enum GatherOutputStatus {
     Finished {
         exit_code: i32,
         execution_stats: Option<buck2_data::CommandExecutionStats>,
+        sandbox: Option<buck2_forkserver_proto::SandboxReport>,
     },
     TimedOut(Duration),
     Cancelled,
@@ -60,9 +61,11 @@ impl From<DecodedStatus> for GatherOutputStatus {
             DecodedStatus::Status {
                 exit_code,
                 execution_stats,
+                sandbox,
             } => Self::Finished {
                 exit_code,
                 execution_stats,
+                sandbox,
             },
             DecodedStatus::SpawnFailed(v) => Self::SpawnFailed(v),
         }
@@ -8,6 +8,8 @@
  */
 
 use std::process::ExitStatus;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
@@ -19,6 +21,7 @@ pub enum DecodedStatus {
     Status {
         exit_code: i32,
         execution_stats: Option<buck2_data::CommandExecutionStats>,
+        sandbox: Option<buck2_forkserver_proto::SandboxReport>,
     },
 
     /// Spawn failed, provide the error.
@@ -43,6 +46,7 @@ impl StatusDecoder for DefaultStatusDecoder {
         Ok(DecodedStatus::Status {
             exit_code: default_decode_exit_code(status),
             execution_stats: None,
+            sandbox: None,
         })
     }
 
@@ -51,6 +55,40 @@ impl StatusDecoder for DefaultStatusDecoder {
     }
 }
 
+/// Reports what happened in the sandbox the command ran in, if it was asked to run in one.
+pub struct SandboxStatusDecoder<D> {
+    inner: D,
+    report: Option<Arc<Mutex<buck2_forkserver_proto::SandboxReport>>>,
+}
+
+impl<D> SandboxStatusDecoder<D> {
+    pub fn new(
+        inner: D,
+        report: Option<Arc<Mutex<buck2_forkserver_proto::SandboxReport>>>,
+    ) -> Self {
+        Self { inner, report }
+    }
+}
+
+#[async_trait]
+impl<D: StatusDecoder + Send> StatusDecoder for SandboxStatusDecoder<D> {
+    async fn decode_status(self, status: ExitStatus) -> anyhow::Result<DecodedStatus> {
+        let mut status = self.inner.decode_status(status).await?;
+
+        if let (Some(report), DecodedStatus::Status { sandbox, .. }) = (&self.report, &mut status) {
+            // Processes the command left behind might still be traced, but what matters is what
+            // happened up to now.
+            *sandbox = Some(report.lock().unwrap().clone());
+        }
+
+        Ok(status)
+    }
+
+    async fn cancel(self) -> anyhow::Result<()> {
+        self.inner.cancel().await
+    }
+}
+
 pub fn default_decode_exit_code(status: ExitStatus) -> i32 {
     let exit_code;
 
@@ -87,6 +125,7 @@ impl StatusDecoder for MiniperfStatusDecoder {
             return Ok(DecodedStatus::Status {
                 exit_code: default_decode_exit_code(status),
                 execution_stats: None,
+                sandbox: None,
             });
         }
 
@@ -123,6 +162,7 @@ impl StatusDecoder for MiniperfStatusDecoder {
                                 ),
                                 userspace_events: Some(counters.user_instructions.to_proto()),
                                 kernel_events: Some(counters.kernel_instructions.to_proto()),
+                                memory_peak: None,
                             });
 
                     if let Err(e) = execution_stats.as_ref() {
@@ -134,6 +174,7 @@ impl StatusDecoder for MiniperfStatusDecoder {
                     Ok(DecodedStatus::Status {
                         exit_code,
                         execution_stats: execution_stats.ok(),
+                        sandbox: None,
                     })
                 }
 
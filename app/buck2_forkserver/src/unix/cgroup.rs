@@ -0,0 +1,343 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Resource control for local commands, using cgroup v2 (Linux only).
+//!
+//! Commands that ask for it run in a cgroup of their own, where their limits are applied, and from
+//! which we read their peak memory usage once they exit.
+//!
+//! A cgroup cannot both contain processes and enable controllers for its children, and the
+//! forkserver shares its cgroup with the daemon. So, we create the cgroups for commands under a
+//! cgroup of ours (`buck2-forkserver-<pid>`) that we create next to our own cgroup. This requires
+//! the parent of our cgroup to be delegated to the current user, and to have the `memory` (and
+//! optionally `cpu`) controllers enabled, which is the case for e.g. systemd user sessions.
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_forkserver_proto::ResourceLimits;
+use dupe::Dupe;
+
+use crate::run::status_decoder::DecodedStatus;
+use crate::run::status_decoder::StatusDecoder;
+
+/// Where the cgroup v2 hierarchy is mounted.
+const CGROUP_MOUNT: &str = "/sys/fs/cgroup";
+
+/// The prefix for the cgroups we create to hold commands' cgroups.
+const ROOT_PREFIX: &str = "buck2-forkserver-";
+
+/// The period we express CPU limits over, in microseconds (this is the kernel's default).
+const CPU_PERIOD_US: u64 = 100_000;
+
+#[derive(Debug, buck2_error::Error)]
+enum CgroupError {
+    #[error("Resource limits for local commands are only supported on Linux")]
+    Unsupported,
+    #[error("cgroup v2 is not mounted at `{0}`")]
+    NotMounted(&'static str),
+    #[error("The forkserver is not in a cgroup v2 hierarchy")]
+    NoCgroup,
+    #[error(
+        "The `memory` controller is not available in `{0}`. Enable it in `cgroup.subtree_control` of its parent"
+    )]
+    NoMemoryController(AbsNormPathBuf),
+    #[error("The `cpu` controller is not available in `{0}`, so CPU limits cannot be applied")]
+    NoCpuController(AbsNormPathBuf),
+}
+
+/// The cgroup under which we create a cgroup for each command.
+pub(crate) struct ActionCgroups {
+    root: AbsNormPathBuf,
+    cpu_controller: bool,
+    next_id: AtomicU64,
+    /// Cgroups we could not remove yet because processes spawned by their command were still
+    /// running in them.
+    leaked: Mutex<Vec<AbsNormPathBuf>>,
+}
+
+impl ActionCgroups {
+    pub(crate) fn new() -> anyhow::Result<Arc<Self>> {
+        if !cfg!(target_os = "linux") {
+            return Err(CgroupError::Unsupported.into());
+        }
+
+        let mount = AbsNormPath::new(CGROUP_MOUNT)?;
+        if !fs_util::try_exists(
+            mount.join(ForwardRelativePath::unchecked_new("cgroup.controllers")),
+        )? {
+            return Err(CgroupError::NotMounted(CGROUP_MOUNT).into());
+        }
+
+        let own = fs_util::read_to_string(AbsNormPath::new("/proc/self/cgroup")?)?;
+        let own = parse_own_cgroup(&own).ok_or(CgroupError::NoCgroup)?;
+        let own = AbsNormPathBuf::new(mount.as_path().join(own.trim_start_matches('/')))?;
+        let parent = own
+            .parent()
+            .filter(|p| p.as_path().starts_with(mount.as_path()))
+            .unwrap_or(mount);
+
+        remove_stale_roots(parent);
+
+        let root = parent.join(ForwardRelativePath::unchecked_new(&format!(
+            "{}{}",
+            ROOT_PREFIX,
+            std::process::id()
+        )));
+        fs_util::create_dir_all(&root)?;
+
+        let available = fs_util::read_to_string(cgroup_file(&root, "cgroup.controllers"))?;
+        let available = available.split_whitespace().collect::<Vec<_>>();
+        if !available.contains(&"memory") {
+            return Err(CgroupError::NoMemoryController(root).into());
+        }
+        let cpu_controller = available.contains(&"cpu");
+
+        let controllers = if cpu_controller {
+            "+memory +cpu"
+        } else {
+            "+memory"
+        };
+        write_file(&cgroup_file(&root, "cgroup.subtree_control"), controllers)?;
+
+        Ok(Arc::new(Self {
+            root,
+            cpu_controller,
+            next_id: AtomicU64::new(0),
+            leaked: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// Create a cgroup for a command.
+    pub(crate) fn create(self: &Arc<Self>, limits: ResourceLimits) -> anyhow::Result<ActionCgroup> {
+        self.retry_leaked();
+
+        if limits.cpu_max_percent.is_some() && !self.cpu_controller {
+            return Err(CgroupError::NoCpuController(self.root.clone()).into());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let path = self.root.join(ForwardRelativePath::unchecked_new(&format!(
+            "action-{}",
+            id
+        )));
+        fs_util::create_dir(&path)?;
+
+        let procs = cgroup_file(&path, "cgroup.procs");
+        let cgroup = ActionCgroup {
+            procs: File::options()
+                .write(true)
+                .open(procs.as_path())
+                .with_context(|| format!("Error opening `{}`", procs))?,
+            path,
+            cgroups: self.dupe(),
+        };
+
+        if let Some(memory_max) = limits.memory_max_bytes {
+            write_file(
+                &cgroup_file(&cgroup.path, "memory.max"),
+                &memory_max.to_string(),
+            )?;
+        }
+
+        if let Some(cpu_max_percent) = limits.cpu_max_percent {
+            let quota = u64::from(cpu_max_percent.max(1)) * CPU_PERIOD_US / 100;
+            write_file(
+                &cgroup_file(&cgroup.path, "cpu.max"),
+                &format!("{} {}", quota, CPU_PERIOD_US),
+            )?;
+        }
+
+        Ok(cgroup)
+    }
+
+    fn retry_leaked(&self) {
+        self.leaked
+            .lock()
+            .unwrap()
+            .retain(|path| fs_util::remove_dir(path).is_err());
+    }
+}
+
+/// The cgroup a single command runs in. It is removed when this is dropped.
+pub(crate) struct ActionCgroup {
+    path: AbsNormPathBuf,
+    /// `cgroup.procs`, opened before forking so the child doesn't have to.
+    procs: File,
+    cgroups: Arc<ActionCgroups>,
+}
+
+impl ActionCgroup {
+    /// Configure `cmd` to move itself to this cgroup before it executes. This must be called
+    /// before any other `pre_exec` hook that might drop privileges (like the sandbox).
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        let fd = self.procs.as_raw_fd();
+        unsafe {
+            cmd.pre_exec(move || {
+                // Writing 0 moves the writing process.
+                if libc::write(fd, b"0".as_ptr().cast(), 1) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// The peak memory usage of the processes in this cgroup. This requires Linux 5.19 or later.
+    fn memory_peak(&self) -> Option<u64> {
+        fs_util::read_to_string(cgroup_file(&self.path, "memory.peak"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+}
+
+impl Drop for ActionCgroup {
+    fn drop(&mut self) {
+        // Processes the command spawned might still be running, in which case we'll retry later.
+        if let Err(e) = fs_util::remove_dir(&self.path) {
+            tracing::debug!("Error removing cgroup: {:#}", e);
+            self.cgroups.leaked.lock().unwrap().push(self.path.clone());
+        }
+    }
+}
+
+/// Reports the peak memory usage of the command from its cgroup, and cleans up the cgroup.
+pub(crate) struct CgroupStatusDecoder<D> {
+    inner: D,
+    cgroup: Option<ActionCgroup>,
+}
+
+impl<D> CgroupStatusDecoder<D> {
+    pub(crate) fn new(inner: D, cgroup: Option<ActionCgroup>) -> Self {
+        Self { inner, cgroup }
+    }
+}
+
+#[async_trait]
+impl<D: StatusDecoder + Send> StatusDecoder for CgroupStatusDecoder<D> {
+    async fn decode_status(self, status: ExitStatus) -> anyhow::Result<DecodedStatus> {
+        let mut status = self.inner.decode_status(status).await?;
+
+        if let (
+            Some(cgroup),
+            DecodedStatus::Status {
+                execution_stats, ..
+            },
+        ) = (&self.cgroup, &mut status)
+        {
+            execution_stats
+                .get_or_insert_with(Default::default)
+                .memory_peak = cgroup.memory_peak();
+        }
+
+        Ok(status)
+    }
+
+    async fn cancel(self) -> anyhow::Result<()> {
+        self.inner.cancel().await
+    }
+}
+
+/// Find the path of our cgroup in the cgroup v2 hierarchy, given the contents of
+/// `/proc/self/cgroup`.
+fn parse_own_cgroup(proc_cgroup: &str) -> Option<&str> {
+    proc_cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+}
+
+/// Remove the cgroups left behind by forkservers that are no longer running.
+fn remove_stale_roots(parent: &AbsNormPath) {
+    let entries = match fs_util::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let pid = match name
+            .to_str()
+            .and_then(|name| name.strip_prefix(ROOT_PREFIX))
+            .and_then(|pid| pid.parse::<libc::pid_t>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        let alive = unsafe { libc::kill(pid, 0) } == 0
+            || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH);
+        if alive {
+            continue;
+        }
+
+        let root = parent.join(ForwardRelativePath::unchecked_new(&name.to_string_lossy()));
+        if let Ok(children) = fs_util::read_dir(&root) {
+            for child in children.flatten() {
+                if child.file_type().map_or(false, |t| t.is_dir()) {
+                    if let Ok(child) = AbsNormPathBuf::new(child.path()) {
+                        let _ignored = fs_util::remove_dir(child);
+                    }
+                }
+            }
+        }
+        let _ignored = fs_util::remove_dir(&root);
+    }
+}
+
+fn cgroup_file(cgroup: &AbsNormPath, name: &str) -> AbsNormPathBuf {
+    cgroup.join(ForwardRelativePath::unchecked_new(name))
+}
+
+fn write_file(path: &AbsNormPath, data: &str) -> anyhow::Result<()> {
+    // Those are not regular files: each write is interpreted as a command, so we must not write
+    // in multiple chunks.
+    let mut file = File::options()
+        .write(true)
+        .open(path.as_path())
+        .with_context(|| format!("Error opening `{}`", path))?;
+    file.write_all(data.as_bytes())
+        .with_context(|| format!("Error writing `{}` to `{}`", data, path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_own_cgroup() {
+        assert_eq!(
+            parse_own_cgroup("0::/user.slice/user-1000.slice/session-1.scope\n"),
+            Some("/user.slice/user-1000.slice/session-1.scope")
+        );
+        assert_eq!(
+            parse_own_cgroup("12:memory:/foo\n1:name=systemd:/foo\n0::/bar\n"),
+            Some("/bar")
+        );
+        // cgroup v1 only.
+        assert_eq!(parse_own_cgroup("12:memory:/foo\n"), None);
+    }
+}
@@ -0,0 +1,1214 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Hermetic sandboxing for local commands (Linux only).
+//!
+//! Sandboxed commands run in new user & mount namespaces. In the new mount namespace, the project
+//! root is replaced with an empty tmpfs, which is then populated with the entries the daemon asked
+//! for: inputs are bind-mounted read-only from the real project root, outputs are bind-mounted
+//! read-write. The rest of the filesystem is left untouched, so tools installed on the host keep
+//! working, but anything in the project that the command did not declare is simply not there.
+//!
+//! If the host doesn't let us create those namespaces, the command runs without the sandbox, and
+//! we report why so the daemon can warn about it. Once the namespaces exist though, any failure
+//! to set up the sandbox stops the command: it must not run in a sandbox that is half set up.
+//!
+//! To tell the user what the command was missing when it fails, we trace the files it opens and
+//! executes using a seccomp filter that notifies us of those syscalls. The paths that exist in the
+//! project but not in the sandbox are the accesses that failed because of it. If tracing fails
+//! while the command runs, we kill it, since its traced syscalls would fail from then on.
+//!
+//! All the work that requires allocating (resolving paths, inspecting the real project root) is
+//! done before forking. In the child, we only make syscalls.
+
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileExt;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use anyhow::Context as _;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_forkserver_proto::sandbox_entry::Data;
+use buck2_forkserver_proto::SandboxConfig;
+use buck2_forkserver_proto::SandboxEntry;
+use buck2_forkserver_proto::SandboxReport;
+
+/// Don't report more than this many hidden paths for a single command.
+const MAX_HIDDEN_PATHS: usize = 100;
+
+// From `linux/seccomp.h`, which the libc crate doesn't expose (fully) yet.
+const SECCOMP_SET_MODE_FILTER: libc::c_ulong = 1;
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: libc::c_ulong = 1 << 3;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+
+/// What a sandboxed command exits with if the sandbox could not be set up.
+const SANDBOX_SETUP_FAILED_EXIT_CODE: i32 = 125;
+
+// From `linux/filter.h`.
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+
+// Some of those fields are only used by the kernel.
+#[allow(dead_code)]
+#[repr(C)]
+struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32,
+}
+
+/// Where the path looked up by a traced syscall is.
+#[derive(Copy, Clone, Debug)]
+enum PathArg {
+    /// In the first argument, relative to the working directory.
+    Cwd,
+    /// In the second argument, relative to the directory FD in the first argument.
+    At,
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+
+#[cfg(target_arch = "x86_64")]
+const TRACED_SYSCALLS: &[(libc::c_long, PathArg)] = &[
+    (libc::SYS_open, PathArg::Cwd),
+    (libc::SYS_execve, PathArg::Cwd),
+    (libc::SYS_openat, PathArg::At),
+    (libc::SYS_openat2, PathArg::At),
+    (libc::SYS_execveat, PathArg::At),
+];
+
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+#[cfg(target_arch = "aarch64")]
+const TRACED_SYSCALLS: &[(libc::c_long, PathArg)] = &[
+    (libc::SYS_execve, PathArg::Cwd),
+    (libc::SYS_openat, PathArg::At),
+    (libc::SYS_openat2, PathArg::At),
+    (libc::SYS_execveat, PathArg::At),
+];
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: u32 = 0;
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const TRACED_SYSCALLS: &[(libc::c_long, PathArg)] = &[];
+
+#[derive(Debug, buck2_error::Error)]
+enum SandboxError {
+    #[error("Sandbox entry `{}` is missing data", .0.display())]
+    MissingData(PathBuf),
+    #[error("Sandbox entry `{}` is not a relative path", .0.display())]
+    InvalidPath(PathBuf),
+}
+
+/// What the child tells us once it has set up the sandbox (or failed to).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(i32)]
+enum ChildStatus {
+    /// The command runs in the sandbox, and the seccomp listener to trace it is attached.
+    Traced = 0,
+    /// The command runs in the sandbox, but it can't be traced.
+    Untraced = 1,
+    /// The namespaces could not be created, so the command runs without the sandbox.
+    Unsandboxed = 2,
+    /// The sandbox could only be set up partially, so the command does not run.
+    Failed = 3,
+}
+
+impl ChildStatus {
+    fn from_i32(status: i32) -> Option<Self> {
+        match status {
+            0 => Some(Self::Traced),
+            1 => Some(Self::Untraced),
+            2 => Some(Self::Unsandboxed),
+            3 => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// The step of the sandbox setup that failed, for `ChildStatus::Failed`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(i32)]
+enum SetupStep {
+    IdMaps = 0,
+    Mounts = 1,
+    WorkingDirectory = 2,
+}
+
+impl SetupStep {
+    fn describe(step: i32) -> &'static str {
+        match step {
+            0 => "mapping user and group IDs",
+            1 => "mounting the project",
+            2 => "changing to the working directory",
+            _ => "setting it up",
+        }
+    }
+}
+
+/// The status the child reports, along with the details the monitor needs.
+struct ReceivedStatus {
+    status: ChildStatus,
+    errno: i32,
+    step: i32,
+    /// The command's PID, which is also its process group.
+    pid: libc::pid_t,
+    listener: Option<OwnedFd>,
+}
+
+/// Keeps the resources the sandbox setup refers to alive until the command has been spawned.
+pub(crate) struct SandboxGuard {
+    _project_root: File,
+    /// The child's end of the socket it reports its status on. We must close our copy once the
+    /// command is spawned, so that the monitor notices if the child never reports anything.
+    _child_socket: UnixStream,
+}
+
+/// Configure `cmd` to run in the sandbox described by `config`. The returned guard must be kept
+/// alive until the command is spawned. The returned report is filled in as the command runs.
+pub(crate) fn apply_sandbox(
+    cmd: &mut Command,
+    config: SandboxConfig,
+    cwd: &AbsPath,
+) -> anyhow::Result<(SandboxGuard, Arc<Mutex<SandboxReport>>)> {
+    let SandboxConfig {
+        project_root,
+        entries,
+    } = config;
+    let project_root = Path::new(OsStr::from_bytes(&project_root));
+    let project_root = AbsPath::new(project_root).context("Invalid sandbox project root")?;
+
+    // We keep a handle to the real project root so that we can still reach its contents once the
+    // tmpfs is mounted over it. The child inherits this FD (it's CLOEXEC so the command won't).
+    let root = File::open(project_root)
+        .with_context(|| format!("Error opening project root `{}`", project_root.display()))?;
+
+    let plan = SandboxPlan::new(project_root, &root, entries, cwd)?;
+
+    let (socket, child_socket) =
+        UnixStream::pair().context("Error creating sandbox status socket")?;
+    let child_fd = child_socket.as_raw_fd();
+
+    // The monitor has to be running before we spawn: the command's `execve` is traced too.
+    let report = Arc::new(Mutex::new(SandboxReport::default()));
+    let monitor_root = project_root.as_path().to_owned();
+    let monitor_report = report.clone();
+    std::thread::Builder::new()
+        .name("sandbox-monitor".to_owned())
+        .spawn(move || {
+            if let Err(e) = monitor(socket, &monitor_root, &monitor_report) {
+                tracing::debug!("Error tracing sandboxed command: {:#}", e);
+            }
+        })
+        .context("Error spawning sandbox monitor")?;
+
+    unsafe {
+        cmd.pre_exec(move || plan.enter(child_fd));
+    }
+
+    Ok((
+        SandboxGuard {
+            _project_root: root,
+            _child_socket: child_socket,
+        },
+        report,
+    ))
+}
+
+enum SandboxOp {
+    /// Create a directory, e.g. to mount over.
+    Mkdir(CString),
+    /// Create an empty file to mount over.
+    Touch(CString),
+    Bind {
+        source: CString,
+        target: CString,
+        /// If set, the flags to remount the target with to make it read-only.
+        read_only: Option<libc::c_ulong>,
+    },
+    Symlink {
+        target: CString,
+        link: CString,
+    },
+}
+
+struct SandboxPlan {
+    project_root: CString,
+    /// The FD of our handle to the real project root, which the bind mount sources refer to.
+    root_fd: RawFd,
+    cwd: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    ops: Vec<SandboxOp>,
+    /// The seccomp filter to trace the command with, if we can.
+    filter: Option<Vec<libc::sock_filter>>,
+}
+
+impl SandboxPlan {
+    fn new(
+        project_root: &AbsPath,
+        root: &File,
+        entries: Vec<SandboxEntry>,
+        cwd: &AbsPath,
+    ) -> anyhow::Result<Self> {
+        let source_root = PathBuf::from(format!("/proc/self/fd/{}", root.as_raw_fd()));
+
+        let mut plan = Self {
+            project_root: cstring(project_root.as_path())?,
+            root_fd: root.as_raw_fd(),
+            cwd: cstring(cwd.as_path())?,
+            uid_map: id_map(unsafe { libc::getuid() }),
+            gid_map: id_map(unsafe { libc::getgid() }),
+            ops: Vec::new(),
+            filter: seccomp_filter(),
+        };
+
+        let mut dirs = HashSet::new();
+        for entry in entries {
+            let path = PathBuf::from(OsStr::from_bytes(&entry.path));
+            let data = entry
+                .data
+                .ok_or_else(|| SandboxError::MissingData(path.clone()))?;
+            if !path.is_relative() || path.as_os_str().is_empty() {
+                return Err(SandboxError::InvalidPath(path).into());
+            }
+
+            if let Some(parent) = path.parent() {
+                plan.mkdir_all(project_root, parent, &mut dirs)?;
+            }
+
+            let source = source_root.join(&path);
+            let target = project_root.as_path().join(&path);
+
+            let read_only = match data {
+                Data::Directory(..) => {
+                    plan.mkdir_all(project_root, &path, &mut dirs)?;
+                    continue;
+                }
+                Data::Input(..) => true,
+                Data::Output(..) => false,
+            };
+
+            // The daemon's view of the inputs might not match what's on disk. If something is
+            // missing, the command won't find it in the sandbox either, which is what we want.
+            let metadata = match fs_util::symlink_metadata_if_exists(project_root.join(&path))? {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+            let file_type = metadata.file_type();
+
+            if file_type.is_symlink() {
+                // Bind mounts follow symlinks, so recreate the symlink instead.
+                let link_target = fs_util::read_link(project_root.join(&path))?;
+                plan.ops.push(SandboxOp::Symlink {
+                    target: cstring(&link_target)?,
+                    link: cstring(&target)?,
+                });
+                continue;
+            }
+
+            if file_type.is_dir() {
+                plan.ops.push(SandboxOp::Mkdir(cstring(&target)?));
+                dirs.insert(path.clone());
+            } else if file_type.is_file() || file_type.is_fifo() || file_type.is_socket() {
+                plan.ops.push(SandboxOp::Touch(cstring(&target)?));
+            } else {
+                continue;
+            }
+
+            let read_only = if read_only {
+                Some(remount_flags(&source)?)
+            } else {
+                None
+            };
+
+            plan.ops.push(SandboxOp::Bind {
+                source: cstring(&source)?,
+                target: cstring(&target)?,
+                read_only,
+            });
+        }
+
+        // The working directory has to exist in the sandbox, even if nothing was declared in it.
+        if let Ok(cwd) = cwd.strip_prefix(project_root) {
+            plan.mkdir_all(project_root, cwd, &mut dirs)?;
+        }
+
+        Ok(plan)
+    }
+
+    fn mkdir_all(
+        &mut self,
+        project_root: &AbsPath,
+        path: &Path,
+        dirs: &mut HashSet<PathBuf>,
+    ) -> anyhow::Result<()> {
+        if path.as_os_str().is_empty() || dirs.contains(path) {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            self.mkdir_all(project_root, parent, dirs)?;
+        }
+        self.ops.push(SandboxOp::Mkdir(cstring(
+            &project_root.as_path().join(path),
+        )?));
+        dirs.insert(path.to_owned());
+        Ok(())
+    }
+
+    /// Runs in the child, between fork and exec. This must not allocate. Reports how it went on
+    /// `socket`, and waits for the monitor to acknowledge it.
+    fn enter(&self, socket: RawFd) -> io::Result<()> {
+        unsafe {
+            if let Err(e) = check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS)) {
+                // Nothing is different for the command yet, so it can still run without the
+                // sandbox.
+                return send_status(socket, ChildStatus::Unsandboxed, errno(&e), 0, None);
+            }
+
+            // From here on, the command would run in a half set up sandbox, where it could
+            // behave in all sorts of confusing ways. Stop it instead.
+            if let Err((step, e)) = self.set_up() {
+                let _ignored =
+                    send_status(socket, ChildStatus::Failed, errno(&e), step as i32, None);
+                libc::_exit(SANDBOX_SETUP_FAILED_EXIT_CODE);
+            }
+
+            match self.trace() {
+                Ok(listener) => {
+                    let res = send_status(socket, ChildStatus::Traced, 0, 0, Some(listener));
+                    libc::close(listener);
+                    res
+                }
+                Err(e) => send_status(socket, ChildStatus::Untraced, errno(&e), 0, None),
+            }
+        }
+    }
+
+    /// Set up the sandbox, once we are in new namespaces.
+    unsafe fn set_up(&self) -> Result<(), (SetupStep, io::Error)> {
+        self.map_ids().map_err(|e| (SetupStep::IdMaps, e))?;
+        self.mount().map_err(|e| (SetupStep::Mounts, e))?;
+        // We were moved to the working directory before the tmpfs was mounted over it, so move
+        // there again to see the sandbox.
+        check(libc::chdir(self.cwd.as_ptr())).map_err(|e| (SetupStep::WorkingDirectory, e))?;
+        Ok(())
+    }
+
+    /// Map our own user and group into the new user namespace, so that files we create keep the
+    /// right owner.
+    unsafe fn map_ids(&self) -> io::Result<()> {
+        write_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_file(b"/proc/self/uid_map\0", &self.uid_map)?;
+        write_file(b"/proc/self/gid_map\0", &self.gid_map)?;
+        Ok(())
+    }
+
+    /// Mount an empty tmpfs over the project root, and populate it.
+    unsafe fn mount(&self) -> io::Result<()> {
+        // Make sure none of what we do below propagates back to the host.
+        check(libc::mount(
+            std::ptr::null(),
+            b"/\0".as_ptr().cast(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            std::ptr::null(),
+        ))?;
+
+        // Our handle to the project root belongs to the host's mount namespace, and the kernel
+        // won't bind mount from another namespace. Reopen it in ours, under the same FD so that
+        // the sources we computed still refer to it.
+        let fd = check(libc::open(
+            self.project_root.as_ptr(),
+            libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+        ))?;
+        let res = check(libc::dup3(fd, self.root_fd, libc::O_CLOEXEC));
+        libc::close(fd);
+        res?;
+
+        check(libc::mount(
+            b"tmpfs\0".as_ptr().cast(),
+            self.project_root.as_ptr(),
+            b"tmpfs\0".as_ptr().cast(),
+            libc::MS_NOSUID | libc::MS_NODEV,
+            b"mode=0755\0".as_ptr().cast(),
+        ))?;
+
+        for op in &self.ops {
+            op.apply()?;
+        }
+
+        Ok(())
+    }
+
+    /// Install our seccomp filter, and return the listener for its notifications.
+    unsafe fn trace(&self) -> io::Result<RawFd> {
+        let filter = self
+            .filter
+            .as_ref()
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOSYS))?;
+        let prog = libc::sock_fprog {
+            len: filter.len() as _,
+            filter: filter.as_ptr() as *mut _,
+        };
+
+        // This lets us install a filter regardless of our capabilities. It only prevents gaining
+        // privileges through setuid binaries, which don't work in a user namespace anyway.
+        check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+
+        let listener = libc::syscall(
+            libc::SYS_seccomp,
+            SECCOMP_SET_MODE_FILTER,
+            SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            &prog as *const libc::sock_fprog,
+        );
+        if listener < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(listener as RawFd)
+    }
+}
+
+impl SandboxOp {
+    unsafe fn apply(&self) -> io::Result<()> {
+        match self {
+            Self::Mkdir(path) => {
+                if libc::mkdir(path.as_ptr(), 0o755) != 0 {
+                    let err = io::Error::last_os_error();
+                    if err.raw_os_error() != Some(libc::EEXIST) {
+                        return Err(err);
+                    }
+                }
+            }
+            Self::Touch(path) => {
+                let fd = check(libc::open(
+                    path.as_ptr(),
+                    libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                    0o644,
+                ))?;
+                libc::close(fd);
+            }
+            Self::Bind {
+                source,
+                target,
+                read_only,
+            } => {
+                check(libc::mount(
+                    source.as_ptr(),
+                    target.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND | libc::MS_REC,
+                    std::ptr::null(),
+                ))?;
+
+                if let Some(flags) = read_only {
+                    check(libc::mount(
+                        std::ptr::null(),
+                        target.as_ptr(),
+                        std::ptr::null(),
+                        libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                        std::ptr::null(),
+                    ))?;
+                }
+            }
+            Self::Symlink { target, link } => {
+                check(libc::symlink(target.as_ptr(), link.as_ptr()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Flags on a mount that an unprivileged user is not allowed to clear, and that therefore must be
+/// passed again when remounting it read-only.
+fn remount_flags(path: &Path) -> anyhow::Result<libc::c_ulong> {
+    let path_c = cstring(path)?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(path_c.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("statvfs({})", path.display()));
+    }
+    let stat = unsafe { stat.assume_init() };
+
+    let mut flags = 0;
+    for (st, ms) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if stat.f_flag & st != 0 {
+            flags |= ms;
+        }
+    }
+    Ok(flags)
+}
+
+fn id_map(id: u32) -> Vec<u8> {
+    format!("{} {} 1", id, id).into_bytes()
+}
+
+fn cstring(path: &Path) -> anyhow::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("Invalid path: `{}`", path.display()))
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Write `data` to a file. `path` must be nul-terminated.
+unsafe fn write_file(path: &[u8], data: &[u8]) -> io::Result<()> {
+    let fd = check(libc::open(
+        path.as_ptr().cast(),
+        libc::O_WRONLY | libc::O_CLOEXEC,
+    ))?;
+    let written = libc::write(fd, data.as_ptr().cast(), data.len());
+    let err = io::Error::last_os_error();
+    libc::close(fd);
+    if written < 0 {
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn errno(e: &io::Error) -> i32 {
+    e.raw_os_error().unwrap_or_default()
+}
+
+/// Send our status (and the seccomp listener, if any) to the forkserver, and wait for it to be
+/// acknowledged. That way, the report is complete before the command does anything, and if the
+/// monitor isn't there to trace the command, it doesn't run. This runs in the child, so it must
+/// not allocate.
+unsafe fn send_status(
+    socket: RawFd,
+    status: ChildStatus,
+    errno: i32,
+    step: i32,
+    listener: Option<RawFd>,
+) -> io::Result<()> {
+    let mut data = [status as i32, errno, step, libc::getpid()];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: std::mem::size_of_val(&data),
+    };
+    // Large enough, and aligned, for one FD.
+    let mut control = [0u64; 4];
+
+    let mut msg: libc::msghdr = std::mem::zeroed();
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    if let Some(listener) = listener {
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as _) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as _) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>(), listener);
+    }
+
+    if libc::sendmsg(socket, &msg, libc::MSG_NOSIGNAL) < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut ack = 0u8;
+    loop {
+        match libc::recv(socket, (&mut ack as *mut u8).cast(), 1, 0) {
+            1 => return Ok(()),
+            0 => return Err(io::Error::from_raw_os_error(libc::ECONNRESET)),
+            _ => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+/// Receive the child's status. Returns `None` if the child exited (or exec'd) without reporting
+/// anything, which happens if it failed before it set up the sandbox.
+fn receive_status(socket: &UnixStream) -> io::Result<Option<ReceivedStatus>> {
+    let mut data = [0i32; 4];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: std::mem::size_of_val(&data),
+    };
+    let mut control = [0u64; 4];
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    let received = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if received == 0 {
+        return Ok(None);
+    }
+
+    let mut listener = None;
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if !cmsg.is_null()
+            && (*cmsg).cmsg_level == libc::SOL_SOCKET
+            && (*cmsg).cmsg_type == libc::SCM_RIGHTS
+        {
+            let fd = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast::<RawFd>());
+            listener = Some(OwnedFd::from_raw_fd(fd));
+        }
+    }
+
+    if received as usize != std::mem::size_of_val(&data) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated sandbox status",
+        ));
+    }
+    let status = ChildStatus::from_i32(data[0])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid sandbox status"))?;
+
+    Ok(Some(ReceivedStatus {
+        status,
+        errno: data[1],
+        step: data[2],
+        pid: data[3],
+        listener,
+    }))
+}
+
+/// Wait for the child to report its status, then trace the paths the command (and the processes
+/// it spawns) open until they have all exited, recording those that were hidden by the sandbox.
+fn monitor(
+    mut socket: UnixStream,
+    project_root: &Path,
+    report: &Mutex<SandboxReport>,
+) -> anyhow::Result<()> {
+    let received = match receive_status(&socket).context("Error receiving sandbox status")? {
+        Some(received) => received,
+        None => return Ok(()),
+    };
+
+    let listener = match received.status {
+        ChildStatus::Traced => Some(
+            received
+                .listener
+                .context("Child did not send a seccomp listener")?,
+        ),
+        ChildStatus::Untraced => {
+            tracing::debug!(
+                "Sandboxed command cannot be traced: {}",
+                io::Error::from_raw_os_error(received.errno)
+            );
+            None
+        }
+        ChildStatus::Unsandboxed => {
+            report.lock().unwrap().unavailable = Some(format!(
+                "Error setting up namespaces: {}",
+                io::Error::from_raw_os_error(received.errno)
+            ));
+            None
+        }
+        ChildStatus::Failed => {
+            report.lock().unwrap().error = Some(format!(
+                "Error {} in the sandbox: {}",
+                SetupStep::describe(received.step),
+                io::Error::from_raw_os_error(received.errno)
+            ));
+            None
+        }
+    };
+
+    socket
+        .write_all(&[0])
+        .context("Error acknowledging sandbox status")?;
+
+    if let Some(listener) = listener {
+        if let Err(e) = trace_accesses(&listener, project_root, report) {
+            // Once we stop responding, the traced syscalls fail with `ENOSYS`, which would make
+            // the command fail in confusing ways, or worse, succeed with different outputs.
+            report.lock().unwrap().error = Some(format!("Error tracing the command: {:#}", e));
+            unsafe { libc::kill(-received.pid, libc::SIGKILL) };
+        }
+    }
+
+    Ok(())
+}
+
+/// Respond to the notifications of the seccomp filter until all the processes it applies to have
+/// exited, recording the paths they looked up that were hidden by the sandbox.
+fn trace_accesses(
+    listener: &OwnedFd,
+    project_root: &Path,
+    report: &Mutex<SandboxReport>,
+) -> anyhow::Result<()> {
+    loop {
+        let mut pollfd = libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pollfd, 1, -1) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err).context("Error polling seccomp listener");
+        }
+        // Without `POLLIN`, this is a `POLLHUP`: all the processes we traced have exited.
+        if pollfd.revents & libc::POLLIN == 0 {
+            return Ok(());
+        }
+
+        let mut notif: SeccompNotif = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_RECV, &mut notif) } < 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                // The process was killed since we polled.
+                Some(libc::ENOENT) | Some(libc::EINTR) => continue,
+                _ => return Err(err).context("Error receiving seccomp notification"),
+            }
+        }
+
+        // The process is blocked until we respond, so record the path first: that way, it's
+        // there by the time the command exits.
+        if let Some(path) = hidden_path(&notif, project_root) {
+            let mut report = report.lock().unwrap();
+            if report.hidden_paths.len() < MAX_HIDDEN_PATHS && !report.hidden_paths.contains(&path)
+            {
+                report.hidden_paths.push(path);
+            }
+        }
+
+        // Let the syscall proceed as usual. This fails if the process was killed, which is fine.
+        let resp = SeccompNotifResp {
+            id: notif.id,
+            val: 0,
+            error: 0,
+            flags: SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+        };
+        unsafe { libc::ioctl(listener.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND, &resp) };
+    }
+}
+
+/// If the syscall we were notified of looks up a path that exists in the project, but not in the
+/// sandbox, return it: that access fails because of the sandbox.
+fn hidden_path(notif: &SeccompNotif, project_root: &Path) -> Option<String> {
+    let nr = libc::c_long::from(notif.data.nr);
+    let (_, arg) = TRACED_SYSCALLS.iter().find(|(traced, _)| *traced == nr)?;
+    let (dirfd, path) = match arg {
+        PathArg::Cwd => (None, notif.data.args[0]),
+        PathArg::At => (Some(notif.data.args[0] as i32), notif.data.args[1]),
+    };
+
+    let path = read_path(notif.pid, path).ok()?;
+    // With `AT_EMPTY_PATH`, this refers to the directory FD itself.
+    if path.is_empty() {
+        return None;
+    }
+    let path = resolve_path(notif.pid, dirfd, Path::new(OsStr::from_bytes(&path))).ok()?;
+
+    let relative = path.strip_prefix(project_root).ok()?;
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    if std::fs::symlink_metadata(&path).is_err() {
+        return None;
+    }
+    // The process' root gives us its view of the filesystem, i.e. the sandbox.
+    let in_sandbox =
+        Path::new(&format!("/proc/{}/root", notif.pid)).join(path.strip_prefix("/").ok()?);
+    match std::fs::symlink_metadata(in_sandbox) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.into_os_string().into_string().ok(),
+        _ => None,
+    }
+}
+
+/// Read the nul-terminated path at `addr` in the memory of process `pid`.
+fn read_path(pid: u32, mut addr: u64) -> io::Result<Vec<u8>> {
+    const PAGE_SIZE: u64 = 4096;
+
+    let mem = File::open(format!("/proc/{}/mem", pid))?;
+    let mut path = Vec::new();
+    let mut buf = [0; PAGE_SIZE as usize];
+
+    while path.len() <= libc::PATH_MAX as usize {
+        // Don't read across pages, the next one might not be mapped.
+        let len = (PAGE_SIZE - addr % PAGE_SIZE) as usize;
+        let read = mem.read_at(&mut buf[..len], addr)?;
+        if read == 0 {
+            break;
+        }
+        if let Some(end) = buf[..read].iter().position(|b| *b == 0) {
+            path.extend_from_slice(&buf[..end]);
+            return Ok(path);
+        }
+        path.extend_from_slice(&buf[..read]);
+        addr += read as u64;
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Path is not nul-terminated",
+    ))
+}
+
+/// Make `path`, as looked up by process `pid` relative to `dirfd` (or its working directory),
+/// absolute and normalized. This is only lexical, so it might be wrong for paths that go through
+/// `..` after a symlink, which is fine for our purposes.
+fn resolve_path(pid: u32, dirfd: Option<i32>, path: &Path) -> io::Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_owned()
+    } else {
+        let base = match dirfd {
+            Some(dirfd) if dirfd != libc::AT_FDCWD => format!("/proc/{}/fd/{}", pid, dirfd),
+            _ => format!("/proc/{}/cwd", pid),
+        };
+        std::fs::read_link(base)?.join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    Ok(normalized)
+}
+
+/// A seccomp filter that notifies us of the syscalls in `TRACED_SYSCALLS`, and allows everything
+/// else. Returns `None` if we can't trace commands on this platform.
+fn seccomp_filter() -> Option<Vec<libc::sock_filter>> {
+    if TRACED_SYSCALLS.is_empty() || !supports_notif_continue() {
+        return None;
+    }
+
+    let stmt = |code, k| libc::sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |k, jt, jf| libc::sock_filter {
+        code: BPF_JMP_JEQ_K,
+        jt,
+        jf,
+        k,
+    };
+
+    // Offsets in `struct seccomp_data`.
+    let nr_offset = 0;
+    let arch_offset = 4;
+
+    let count = TRACED_SYSCALLS.len() as u8;
+    let mut filter = vec![
+        stmt(BPF_LD_W_ABS, arch_offset),
+        // Skip to the final "allow" for other architectures.
+        jump(AUDIT_ARCH, 0, count + 1),
+        stmt(BPF_LD_W_ABS, nr_offset),
+    ];
+    for (i, (nr, _)) in TRACED_SYSCALLS.iter().enumerate() {
+        // Skip to the final "notify" if it matches.
+        filter.push(jump(*nr as u32, count - i as u8, 0));
+    }
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    filter.push(stmt(BPF_RET_K, SECCOMP_RET_USER_NOTIF));
+    Some(filter)
+}
+
+/// Letting traced syscalls proceed (`SECCOMP_USER_NOTIF_FLAG_CONTINUE`) requires Linux 5.5. On
+/// older kernels, the command would block on the first traced syscall, so don't trace it at all.
+fn supports_notif_continue() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+    *SUPPORTED.get_or_init(|| {
+        let mut uname = unsafe { std::mem::zeroed::<libc::utsname>() };
+        if unsafe { libc::uname(&mut uname) } != 0 {
+            return false;
+        }
+        let release = unsafe { std::ffi::CStr::from_ptr(uname.release.as_ptr()) };
+        kernel_at_least(&release.to_string_lossy(), (5, 5))
+    })
+}
+
+/// Whether this kernel release (e.g. `6.1.0-13-amd64`) is at least `version`.
+fn kernel_at_least(release: &str, version: (u32, u32)) -> bool {
+    let mut parts = release
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse::<u32>());
+    match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => (major, minor) >= version,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_forkserver_proto::SandboxDirectory;
+    use buck2_forkserver_proto::SandboxEntry;
+    use buck2_forkserver_proto::SandboxInput;
+    use buck2_forkserver_proto::SandboxOutput;
+
+    use super::*;
+
+    fn entry(path: &str, data: impl Into<Data>) -> SandboxEntry {
+        SandboxEntry {
+            path: path.as_bytes().to_vec(),
+            data: Some(data.into()),
+        }
+    }
+
+    fn describe(plan: &SandboxPlan, root: &Path) -> Vec<String> {
+        let rel = |p: &CString| {
+            let p = Path::new(OsStr::from_bytes(p.as_bytes()));
+            match p.strip_prefix(root) {
+                Ok(p) => p.display().to_string(),
+                Err(_) => p.display().to_string(),
+            }
+        };
+        plan.ops
+            .iter()
+            .map(|op| match op {
+                SandboxOp::Mkdir(p) => format!("mkdir {}", rel(p)),
+                SandboxOp::Touch(p) => format!("touch {}", rel(p)),
+                SandboxOp::Bind {
+                    target, read_only, ..
+                } => format!(
+                    "bind {} {}",
+                    rel(target),
+                    if read_only.is_some() { "ro" } else { "rw" }
+                ),
+                SandboxOp::Symlink { target, link } => {
+                    format!("symlink {} -> {}", rel(link), rel(target))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsPath::new(tempdir.path())?;
+
+        fs_util::create_dir_all(root.join("src/lib"))?;
+        fs_util::write(root.join("src/lib/a.h"), "")?;
+        fs_util::write(root.join("src/lib/undeclared.h"), "")?;
+        fs_util::symlink("a.h", root.join("src/lib/link.h"))?;
+        fs_util::create_dir_all(root.join("out/dir"))?;
+
+        let config = SandboxConfig {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            entries: vec![
+                entry("src/lib/a.h", SandboxInput {}),
+                entry("src/lib/link.h", SandboxInput {}),
+                entry("src/lib/missing.h", SandboxInput {}),
+                entry("empty", SandboxDirectory {}),
+                entry("out/dir", SandboxOutput {}),
+            ],
+        };
+
+        let file = File::open(root)?;
+        let plan = SandboxPlan::new(root, &file, config.entries, &root.join("src"))?;
+
+        assert_eq!(
+            describe(&plan, root.as_path()),
+            vec![
+                "mkdir src",
+                "mkdir src/lib",
+                "touch src/lib/a.h",
+                "bind src/lib/a.h ro",
+                "symlink src/lib/link.h -> a.h",
+                "mkdir empty",
+                "mkdir out",
+                "mkdir out/dir",
+                "bind out/dir rw",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_rejects_absolute_paths() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsPath::new(tempdir.path())?;
+
+        let config = SandboxConfig {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            entries: vec![entry("/etc/passwd", SandboxInput {})],
+        };
+
+        let file = File::open(root)?;
+        assert!(SandboxPlan::new(root, &file, config.entries, root).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sandbox_reports_hidden_paths() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsPath::new(tempdir.path())?;
+
+        fs_util::write(root.join("declared"), "")?;
+        fs_util::write(root.join("undeclared"), "")?;
+
+        let config = SandboxConfig {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            entries: vec![entry("declared", SandboxInput {})],
+        };
+
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", "cat declared undeclared"]);
+        cmd.current_dir(root);
+        let (guard, report) = apply_sandbox(&mut cmd, config, root)?;
+        let status = cmd.status()?;
+        drop(guard);
+
+        let report = report.lock().unwrap().clone();
+        if report.unavailable.is_some() {
+            // This host doesn't let us create namespaces, so the command ran without the sandbox.
+            assert!(status.success());
+        } else {
+            assert_eq!(status.code(), Some(1));
+            assert_eq!(report.error, None);
+            if seccomp_filter().is_some() {
+                let undeclared = root.join("undeclared").to_str().unwrap().to_owned();
+                assert_eq!(report.hidden_paths, vec![undeclared]);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sandbox_setup_failure_stops_command() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsPath::new(tempdir.path())?;
+
+        // The symlink is recreated in the sandbox, but not what it points to, so the working
+        // directory can't be entered there.
+        fs_util::create_dir_all(root.join("real"))?;
+        fs_util::symlink("real", root.join("link"))?;
+        let cwd = root.join("link");
+
+        let config = SandboxConfig {
+            project_root: root.as_os_str().as_bytes().to_vec(),
+            entries: vec![entry("link", SandboxInput {})],
+        };
+
+        let mut cmd = Command::new("/bin/true");
+        cmd.current_dir(&cwd);
+        let (guard, report) = apply_sandbox(&mut cmd, config, &cwd)?;
+        let status = cmd.status()?;
+        drop(guard);
+
+        let report = report.lock().unwrap().clone();
+        if report.unavailable.is_some() {
+            assert!(status.success());
+        } else {
+            assert_eq!(status.code(), Some(SANDBOX_SETUP_FAILED_EXIT_CODE));
+            assert!(
+                report
+                    .error
+                    .as_deref()
+                    .unwrap_or_default()
+                    .contains("changing to the working directory"),
+                "{:?}",
+                report
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_path() -> anyhow::Result<()> {
+        assert_eq!(
+            resolve_path(0, None, Path::new("/a/./b/../c/d"))?,
+            Path::new("/a/c/d")
+        );
+        assert_eq!(
+            resolve_path(0, Some(libc::AT_FDCWD), Path::new("/../a"))?,
+            Path::new("/a")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_at_least() {
+        assert!(kernel_at_least("6.1.0-13-amd64", (5, 5)));
+        assert!(kernel_at_least("5.5.0", (5, 5)));
+        assert!(kernel_at_least("5.15.90.1-microsoft-standard-WSL2", (5, 5)));
+        assert!(!kernel_at_least("5.4.0-150-generic", (5, 5)));
+        assert!(!kernel_at_least("4.18.0-477.el8.x86_64", (5, 5)));
+        assert!(!kernel_at_least("garbage", (5, 5)));
+    }
+}
@@ -15,6 +15,7 @@ use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::OnceLock;
 
 use anyhow::Context as _;
 use buck2_common::convert::ProstDurationExt;
@@ -48,10 +49,16 @@ use crate::run::prepare_command;
 use crate::run::process_group::ProcessGroup;
 use crate::run::status_decoder::DefaultStatusDecoder;
 use crate::run::status_decoder::MiniperfStatusDecoder;
+use crate::run::status_decoder::SandboxStatusDecoder;
 use crate::run::stream_command_events;
 use crate::run::timeout_into_cancellation;
 use crate::run::DefaultKillProcess;
 use crate::run::GatherOutputStatus;
+use crate::unix::cgroup::ActionCgroup;
+use crate::unix::cgroup::ActionCgroups;
+use crate::unix::cgroup::CgroupStatusDecoder;
+#[cfg(target_os = "linux")]
+use crate::unix::sandbox::apply_sandbox;
 
 // Not quite BoxStream: it has to be Sync (...)
 type RunStream =
@@ -62,6 +69,10 @@ pub struct UnixForkserverService {
 
     /// State for Miniperf.
     miniperf: Option<MiniperfContainer>,
+
+    /// The cgroups commands run in when they request resource limits. Those are set up the first
+    /// time they're needed. If that fails, we warn once and run commands without resource limits.
+    cgroups: OnceLock<Option<Arc<ActionCgroups>>>,
 }
 
 impl UnixForkserverService {
@@ -74,8 +85,37 @@ impl UnixForkserverService {
         Ok(Self {
             log_reload_handle,
             miniperf,
+            cgroups: OnceLock::new(),
         })
     }
+
+    /// The cgroup to run a command with these limits in, or `None` if we can't create one, in
+    /// which case the command runs without resource limits rather than failing.
+    fn action_cgroup(
+        &self,
+        limits: buck2_forkserver_proto::ResourceLimits,
+    ) -> Option<ActionCgroup> {
+        let cgroups = self.cgroups.get_or_init(|| match ActionCgroups::new() {
+            Ok(cgroups) => Some(cgroups),
+            Err(e) => {
+                tracing::warn!(
+                    "Error setting up cgroups, commands will run without resource limits: {:#}",
+                    e
+                );
+                None
+            }
+        });
+        match cgroups.as_ref()?.create(limits) {
+            Ok(cgroup) => Some(cgroup),
+            Err(e) => {
+                tracing::warn!(
+                    "Error creating cgroup, command will run without resource limits: {:#}",
+                    e
+                );
+                None
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -116,6 +156,8 @@ impl Forkserver for UnixForkserverService {
                 enable_miniperf,
                 std_redirects,
                 graceful_shutdown_timeout_s,
+                sandbox,
+                resource_limits,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -130,7 +172,8 @@ impl Forkserver for UnixForkserverService {
             let exe = maybe_absolutize_exe(exe, cwd)?;
 
             let (mut cmd, miniperf_output) = match (enable_miniperf, &self.miniperf) {
-                (true, Some(miniperf)) => {
+                // Miniperf lives in buck-out, which the sandbox hides.
+                (true, Some(miniperf)) if sandbox.is_none() => {
                     let mut cmd = background_command(miniperf.miniperf.as_path());
                     let output_path = miniperf.allocate_output_path();
                     cmd.arg(output_path.as_path());
@@ -161,6 +204,19 @@ impl Forkserver for UnixForkserverService {
                 }
             }
 
+            let cgroup = resource_limits.and_then(|limits| self.action_cgroup(limits));
+            if let Some(cgroup) = &cgroup {
+                cgroup.apply(&mut cmd);
+            }
+
+            let (_sandbox_guard, sandbox_report) = match sandbox {
+                Some(sandbox) => {
+                    let (guard, report) = apply_sandbox(&mut cmd, sandbox, cwd)?;
+                    (Some(guard), Some(report))
+                }
+                None => (None, None),
+            };
+
             let mut cmd = prepare_command(cmd);
             let stream_stdio = std_redirects.is_none();
             if let Some(std_redirects) = std_redirects {
@@ -180,7 +236,10 @@ impl Forkserver for UnixForkserverService {
                 Some(out) => stream_command_events(
                     process_group,
                     cancellation,
-                    MiniperfStatusDecoder::new(out),
+                    SandboxStatusDecoder::new(
+                        CgroupStatusDecoder::new(MiniperfStatusDecoder::new(out), cgroup),
+                        sandbox_report,
+                    ),
                     DefaultKillProcess {
                         graceful_shutdown_timeout_s,
                     },
@@ -190,7 +249,10 @@ impl Forkserver for UnixForkserverService {
                 None => stream_command_events(
                     process_group,
                     cancellation,
-                    DefaultStatusDecoder,
+                    SandboxStatusDecoder::new(
+                        CgroupStatusDecoder::new(DefaultStatusDecoder, cgroup),
+                        sandbox_report,
+                    ),
                     DefaultKillProcess {
                         graceful_shutdown_timeout_s,
                     },
@@ -283,3 +345,17 @@ impl MiniperfContainer {
             .join(ForwardRelativePath::unchecked_new(&name))
     }
 }
+
+/// Sandboxing relies on Linux namespaces. The daemon only asks for it on Linux, but be explicit
+/// about it if it does anyway.
+#[cfg(not(target_os = "linux"))]
+fn apply_sandbox(
+    _cmd: &mut std::process::Command,
+    _config: buck2_forkserver_proto::SandboxConfig,
+    _cwd: &AbsPath,
+) -> anyhow::Result<(
+    (),
+    Arc<std::sync::Mutex<buck2_forkserver_proto::SandboxReport>>,
+)> {
+    Err(anyhow::anyhow!("Sandboxing is only supported on Linux"))
+}
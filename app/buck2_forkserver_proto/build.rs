@@ -31,6 +31,10 @@ fn main() -> io::Result<()> {
             "buck.forkserver.EnvDirective.data",
             "#[derive(::derive_more::From, ::gazebo::variants::VariantName, ::gazebo::variants::UnpackVariants)]",
         )
+        .type_attribute(
+            "buck.forkserver.SandboxEntry.data",
+            "#[derive(::derive_more::From, ::gazebo::variants::VariantName, ::gazebo::variants::UnpackVariants)]",
+        )
         .extern_path(".buck.data", "::buck2_data")
         .compile(proto_files, &[".", &data_include])
 }
@@ -137,6 +137,7 @@ pub fn starlark_library_extensions_for_buck2() -> &'static [LibraryExtension] {
         LibraryExtension::Print,
         LibraryExtension::RecordType,
         LibraryExtension::StructType,
+        LibraryExtension::SetType,
         LibraryExtension::Typing,
         LibraryExtension::Internal,
         LibraryExtension::CallStack,
@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Finding cycles in the graph formed by a set of targets and the dependencies between them.
+//! Dependencies on targets outside the set are ignored.
+
+use std::collections::VecDeque;
+
+use dupe::Dupe;
+
+use crate::query::environment::LabeledNode;
+use crate::query::environment::QueryTarget;
+use crate::query::syntax::simple::eval::set::TargetSet;
+
+const UNVISITED: usize = usize::MAX;
+
+fn edges<T: QueryTarget>(targets: &TargetSet<T>) -> Vec<Vec<usize>> {
+    targets
+        .iter()
+        .map(|t| t.deps().filter_map(|d| targets.get_index_of(d)).collect())
+        .collect()
+}
+
+fn to_target_set<T: QueryTarget>(targets: &TargetSet<T>, indices: &[usize]) -> TargetSet<T> {
+    // The indices all came from `targets`.
+    indices
+        .iter()
+        .map(|i| targets.get_index(*i).unwrap().dupe())
+        .collect()
+}
+
+/// State for Tarjan's algorithm, using an explicit stack so deep graphs don't overflow.
+struct Tarjan {
+    edges: Vec<Vec<usize>>,
+    index: Vec<usize>,
+    lowlink: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    /// Each frame is a node and the position of the next edge to follow.
+    work: Vec<(usize, usize)>,
+    next: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan {
+    fn enter(&mut self, v: usize) {
+        self.index[v] = self.next;
+        self.lowlink[v] = self.next;
+        self.next += 1;
+        self.stack.push(v);
+        self.on_stack[v] = true;
+        self.work.push((v, 0));
+    }
+
+    fn run(&mut self, root: usize) {
+        self.enter(root);
+        while let Some((v, i)) = self.work.last().copied() {
+            if let Some(&w) = self.edges[v].get(i) {
+                self.work.last_mut().unwrap().1 += 1;
+                if self.index[w] == UNVISITED {
+                    self.enter(w);
+                } else if self.on_stack[w] {
+                    self.lowlink[v] = self.lowlink[v].min(self.index[w]);
+                }
+                continue;
+            }
+            self.work.pop();
+            if let Some(&(parent, _)) = self.work.last() {
+                self.lowlink[parent] = self.lowlink[parent].min(self.lowlink[v]);
+            }
+            if self.lowlink[v] == self.index[v] {
+                let mut component = Vec::new();
+                loop {
+                    let w = self.stack.pop().unwrap();
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                if component.len() > 1 || self.edges[v].contains(&v) {
+                    component.sort_unstable();
+                    self.components.push(component);
+                }
+            }
+        }
+    }
+}
+
+/// The strongly connected components of the targets which contain a cycle, i.e. those
+/// with more than one target, or a single target depending on itself.
+///
+/// Targets within a component keep their order from `targets`, and components are
+/// ordered by their first target.
+pub fn strongly_connected_components<T: QueryTarget>(targets: &TargetSet<T>) -> Vec<TargetSet<T>> {
+    let edges = edges(targets);
+    let n = edges.len();
+    let mut tarjan = Tarjan {
+        edges,
+        index: vec![UNVISITED; n],
+        lowlink: vec![0; n],
+        on_stack: vec![false; n],
+        stack: Vec::new(),
+        work: Vec::new(),
+        next: 0,
+        components: Vec::new(),
+    };
+    for root in 0..n {
+        if tarjan.index[root] == UNVISITED {
+            tarjan.run(root);
+        }
+    }
+
+    let mut components = tarjan.components;
+    components.sort_unstable_by_key(|c| c[0]);
+    components
+        .iter()
+        .map(|c| to_target_set(targets, c))
+        .collect()
+}
+
+/// A shortest cycle through the first target of a strongly connected component, in
+/// dependency order: each target depends on the next, and the last on the first.
+///
+/// Returns an empty set if there is no such cycle.
+pub fn representative_cycle<T: QueryTarget>(component: &TargetSet<T>) -> TargetSet<T> {
+    let edges = edges(component);
+    let mut parent: Vec<Option<usize>> = vec![None; edges.len()];
+    let mut visited = vec![false; edges.len()];
+    let mut queue = VecDeque::new();
+    if !edges.is_empty() {
+        visited[0] = true;
+        queue.push_back(0);
+    }
+    while let Some(v) = queue.pop_front() {
+        for &w in &edges[v] {
+            if w == 0 {
+                let mut path = vec![v];
+                let mut x = v;
+                while let Some(p) = parent[x] {
+                    path.push(p);
+                    x = p;
+                }
+                path.reverse();
+                return to_target_set(component, &path);
+            }
+            if !visited[w] {
+                visited[w] = true;
+                parent[w] = Some(v);
+                queue.push_back(w);
+            }
+        }
+    }
+    TargetSet::new()
+}
+
+/// A human readable report with one representative cycle per strongly connected
+/// component, one per line, e.g. `//a:a -> //b:b -> //a:a`.
+pub fn cycles_report<T: QueryTarget>(targets: &TargetSet<T>) -> String {
+    let mut res = String::new();
+    for component in strongly_connected_components(targets) {
+        let cycle = representative_cycle(&component);
+        for t in &cycle {
+            res.push_str(&t.node_ref().to_string());
+            res.push_str(" -> ");
+        }
+        if let Some(first) = cycle.iter().next() {
+            res.push_str(&first.node_ref().to_string());
+        }
+        res.push('\n');
+    }
+    res
+}
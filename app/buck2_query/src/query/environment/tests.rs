@@ -23,6 +23,11 @@ use indexmap::IndexSet;
 use serde::Serializer;
 
 use super::*;
+use crate::query::cycles::cycles_report;
+use crate::query::cycles::representative_cycle;
+use crate::query::cycles::strongly_connected_components;
+use crate::query::syntax::simple::eval::values::QueryValue;
+use crate::query::syntax::simple::functions::DefaultQueryFunctions;
 use crate::query::traversal::AsyncNodeLookup;
 
 #[derive(Debug, Copy, Clone, Dupe, Eq, PartialEq, Hash, Display, From)]
@@ -366,3 +371,77 @@ async fn test_paths_with_cycles_present() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_strongly_connected_components() -> anyhow::Result<()> {
+    let mut env = TestEnvBuilder::default();
+    env.edge(1, 2);
+    env.edge(2, 3);
+    env.edge(3, 1);
+    env.edge(3, 4);
+    env.edge(4, 5);
+    env.edge(5, 5);
+    env.edge(5, 6);
+    // A cycle through a target outside the set is ignored.
+    env.edge(6, 7);
+    env.edge(7, 6);
+    let env = env.build();
+
+    let targets = env.set("4,3,2,1,5,6")?;
+    let components = strongly_connected_components(&targets);
+    assert_eq!(components, vec![env.set("3,2,1")?, env.set("5")?]);
+
+    assert_eq!(representative_cycle(&components[0]), env.set("3,1,2")?);
+    assert_eq!(representative_cycle(&components[1]), env.set("5")?);
+
+    assert_eq!(cycles_report(&targets), "3 -> 1 -> 2 -> 3\n5 -> 5\n");
+    assert_eq!(cycles_report(&env.set("1,2,4")?), "");
+
+    Ok(())
+}
+
+#[test]
+fn test_representative_cycle_is_shortest() -> anyhow::Result<()> {
+    let mut env = TestEnvBuilder::default();
+    env.edge(1, 2);
+    env.edge(2, 3);
+    env.edge(3, 4);
+    env.edge(4, 1);
+    env.edge(2, 4);
+    let env = env.build();
+
+    let components = strongly_connected_components(&env.set("1,2,3,4")?);
+    assert_eq!(components, vec![env.set("1,2,3,4")?]);
+    assert_eq!(representative_cycle(&components[0]), env.set("1,2,4")?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_scc_keeps_components_apart() -> anyhow::Result<()> {
+    let mut env = TestEnvBuilder::default();
+    env.edge(1, 2);
+    env.edge(2, 1);
+    env.edge(2, 3);
+    env.edge(3, 4);
+    env.edge(4, 3);
+    let env = env.build();
+
+    let functions = DefaultQueryFunctions::<TestEnv>::new();
+    let targets = env.set("1,2,3,4")?;
+    let components = functions.scc(&targets);
+    assert_eq!(components, vec![env.set("1,2")?, env.set("3,4")?]);
+    assert_eq!(functions.cycles(&targets), components);
+
+    // Where a single set is expected, the components are merged.
+    let union = functions
+        .union(
+            &env,
+            QueryValue::TargetSetList(components),
+            QueryValue::TargetSet(TargetSet::new()),
+        )
+        .await?;
+    assert_eq!(union, QueryValue::TargetSet(targets));
+
+    Ok(())
+}
@@ -143,6 +143,9 @@ impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
                     )),
                     QueryValue::TargetSet(targets) => Ok(QueryEvaluationValue::TargetSet(targets)),
                     QueryValue::FileSet(files) => Ok(QueryEvaluationValue::FileSet(files)),
+                    QueryValue::TargetSetList(sets) => {
+                        Ok(QueryEvaluationValue::TargetSetList(sets))
+                    }
                     _ => Err(QueryError::InvalidType {
                         expected: "targets",
                         actual: value.variant_name(),
@@ -42,6 +42,10 @@ impl<T: QueryTarget> MultiQueryResult<T> {
                 (QueryEvaluationValue::FileSet(value), QueryEvaluationValue::FileSet(results)) => {
                     results.insert_all(&value)
                 }
+                (
+                    QueryEvaluationValue::TargetSetList(value),
+                    QueryEvaluationValue::TargetSetList(results),
+                ) => results.extend(value),
                 _ => unreachable!(
                     "no queries should return different types for different literals, but somehow that happened for `{}` and `{}`",
                     first_literal, name
@@ -104,6 +104,15 @@ impl<T: QueryTarget> TargetSet<T> {
         Self { targets }
     }
 
+    /// All the targets in `sets`, in order.
+    pub fn union_all(sets: impl IntoIterator<Item = TargetSet<T>>) -> TargetSet<T> {
+        let mut targets = TargetSet::new();
+        for set in sets {
+            targets.extend(set.into_iter());
+        }
+        targets
+    }
+
     pub fn iter_names(&self) -> impl Iterator<Item = &T::NodeRef> + Clone {
         self.targets.iter().map(|e| e.node_ref())
     }
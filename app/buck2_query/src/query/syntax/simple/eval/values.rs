@@ -30,6 +30,9 @@ pub enum QueryValue<T: QueryTarget> {
     Integer(u64),
     TargetSet(TargetSet<T>),
     FileSet(FileSet),
+    /// Target sets that are output separately, like the components found by `scc()`. Anywhere a
+    /// single target set is expected, they are merged into one.
+    TargetSetList(Vec<TargetSet<T>>),
 }
 
 /// Used as a value in query evaluation where sets are valid, may appear in arguments to functions, results of functions etc.
@@ -46,12 +49,14 @@ pub enum QueryValueSet<T: QueryTarget> {
 pub enum QueryEvaluationValue<T: QueryTarget> {
     TargetSet(TargetSet<T>),
     FileSet(FileSet),
+    TargetSetList(Vec<TargetSet<T>>),
 }
 
 impl<T: QueryTarget> QueryEvaluationValue<T> {
     pub fn try_into_targets(self) -> anyhow::Result<TargetSet<T>> {
         match self {
             QueryEvaluationValue::TargetSet(targets) => Ok(targets),
+            QueryEvaluationValue::TargetSetList(sets) => Ok(TargetSet::union_all(sets)),
             v => {
                 return Err(QueryError::InvalidType {
                     expected: "targets",
@@ -97,6 +97,9 @@ impl<Env: QueryEnvironment> DepsFunction<Env> {
                         match evaluator.eval_parsed_query(self.expr.expr).await {
                             Ok(v) => match v.value {
                                 QueryEvaluationValue::TargetSet(v) => Ok(v),
+                                QueryEvaluationValue::TargetSetList(v) => {
+                                    Ok(TargetSet::union_all(v))
+                                }
                                 v => Err(QueryError::InvalidType {
                                     expected: "targets",
                                     actual: v.variant_name(),
@@ -200,6 +200,7 @@ impl<'a, Env: QueryEnvironment> QueryFunctionArg<'a, Env> for QueryValueSet<Env:
             QueryValue::String(s) => Ok(QueryValueSet::TargetSet(env.eval_literals(&[&s]).await?)),
             QueryValue::TargetSet(x) => Ok(QueryValueSet::TargetSet(x)),
             QueryValue::FileSet(x) => Ok(QueryValueSet::FileSet(x)),
+            QueryValue::TargetSetList(x) => Ok(QueryValueSet::TargetSet(TargetSet::union_all(x))),
             _ => Err(QueryError::InvalidType {
                 expected: "file or target set",
                 actual: val.variant_name(),
@@ -233,6 +234,7 @@ impl<'a, Env: QueryEnvironment> QueryFunctionArg<'a, Env> for TargetSet<Env::Tar
         match val {
             QueryValue::String(s) => Ok(env.eval_literals(&[&s]).await?),
             QueryValue::TargetSet(t) => Ok(t),
+            QueryValue::TargetSetList(t) => Ok(TargetSet::union_all(t)),
             _ => Err(QueryError::InvalidType {
                 expected: "target_set",
                 actual: val.variant_name(),
@@ -20,6 +20,8 @@ use buck2_query_parser::BinaryOp;
 use buck2_query_parser::Expr;
 use gazebo::variants::VariantName;
 
+use crate::query::cycles::representative_cycle;
+use crate::query::cycles::strongly_connected_components;
 use crate::query::environment::QueryEnvironment;
 use crate::query::syntax::simple::eval::error::QueryError;
 use crate::query::syntax::simple::eval::evaluator::QueryEvaluator;
@@ -181,6 +183,7 @@ async fn accept_target_set<Env: QueryEnvironment>(
 ) -> Result<TargetSet<Env::Target>, QueryError> {
     match val {
         QueryValue::TargetSet(x) => Ok(x),
+        QueryValue::TargetSetList(x) => Ok(TargetSet::union_all(x)),
         QueryValue::String(literal) => Ok(env.eval_literals(&[&literal]).await?),
         _ => Err(QueryError::InvalidType {
             expected: "target_set",
@@ -228,6 +231,28 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
         Ok(self.implementation.somepath(env, &from, &to).await?.into())
     }
 
+    /// Computes the targets in dependency cycles.
+    ///
+    /// The `scc(targets)` function evaluates to the targets which are part of a cycle in the graph formed by `targets` and the dependencies between them, i.e. the strongly connected components of that graph with more than one target, or a target depending on itself. Dependencies on targets outside `targets` are ignored. In cquery the configured dependencies are used.
+    ///
+    /// Each component is output separately: as a block of lines separated by an empty line, or as a list of lists with `--json`. Graph output formats show all of them in one graph. When passed to another function, the components are merged into a single set of targets.
+    ///
+    /// The argument is usually an expression producing a closed set of targets, for example:
+    /// `buck2 cquery "scc(deps('//foo:bar'))"`
+    async fn scc(&self, targets: TargetSet<Env::Target>) -> QueryFuncResult<Env> {
+        Ok(QueryValue::TargetSetList(self.implementation.scc(&targets)))
+    }
+
+    /// Computes one representative dependency cycle for each strongly connected component.
+    ///
+    /// The `cycles(targets)` function finds the same strongly connected components as `scc(targets)`, and evaluates to a shortest cycle through the first target of each, in dependency order: each target depends on the next, and the last target of a cycle depends on its first. This makes it easier to see where to break a cycle than the whole component. Each cycle is output separately, like the components of `scc()`. For example:
+    /// `buck2 cquery "cycles(deps('//foo:bar'))" --output-format=dot`
+    async fn cycles(&self, targets: TargetSet<Env::Target>) -> QueryFuncResult<Env> {
+        Ok(QueryValue::TargetSetList(
+            self.implementation.cycles(&targets),
+        ))
+    }
+
     async fn attrfilter(
         &self,
         attr: String,
@@ -446,6 +471,19 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
         Ok(env.somepath(from, to).await?)
     }
 
+    /// The strongly connected components with a cycle.
+    pub fn scc(&self, targets: &TargetSet<Env::Target>) -> Vec<TargetSet<Env::Target>> {
+        strongly_connected_components(targets)
+    }
+
+    /// One representative cycle per strongly connected component, in dependency order.
+    pub fn cycles(&self, targets: &TargetSet<Env::Target>) -> Vec<TargetSet<Env::Target>> {
+        strongly_connected_components(targets)
+            .iter()
+            .map(representative_cycle)
+            .collect()
+    }
+
     pub fn attrfilter(
         &self,
         attr: &str,
@@ -611,7 +649,11 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
     ) -> Result<QueryValue<Env::Target>, QueryError> {
         // If the operations are of the same type, which + join them.
         // If one is a string, and the other a FileSet or TargetSet, we can promote the string
-        match (left, right) {
+        let flatten = |value: QueryValue<Env::Target>| match value {
+            QueryValue::TargetSetList(sets) => QueryValue::TargetSet(TargetSet::union_all(sets)),
+            value => value,
+        };
+        match (flatten(left), flatten(right)) {
             (QueryValue::TargetSet(l), QueryValue::TargetSet(r)) => {
                 Ok(QueryValue::TargetSet(l.union(&r)))
             }
@@ -202,6 +202,8 @@ pub struct Buck2OssReConfiguration {
     pub capabilities: Option<bool>,
     /// The instance name to use in requests.
     pub instance_name: Option<String>,
+    /// Whether to compress blobs with zstd when the RBE backend advertises support for it.
+    pub compression: Option<bool>,
 }
 
 #[derive(Clone, Debug, Default, Allocative)]
@@ -255,6 +257,7 @@ impl Buck2OssReConfiguration {
                 .unwrap_or_default(), // Empty list is as good None.
             capabilities: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "capabilities")?,
             instance_name: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "instance_name")?,
+            compression: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "compression")?,
         })
     }
 }
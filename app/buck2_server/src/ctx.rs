@@ -13,6 +13,7 @@ use std::io::BufWriter;
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use allocative::Allocative;
 use anyhow::Context;
@@ -73,6 +74,7 @@ use buck2_execute::materialize::materializer::SetMaterializer;
 use buck2_execute::re::client::RemoteExecutionClient;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionObserver;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCache;
 use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_execute_impl::re::paranoid_download::ParanoidDownloader;
@@ -120,6 +122,7 @@ use crate::configs::parse_legacy_cells;
 use crate::daemon::common::get_default_executor_config;
 use crate::daemon::common::parse_concurrency;
 use crate::daemon::common::CommandExecutorFactory;
+use crate::daemon::persisted_package_listings::PersistedPackageListings;
 use crate::daemon::state::DaemonStateData;
 use crate::dice_tracker::BuckDiceTracker;
 use crate::heartbeat_guard::HeartbeatGuard;
@@ -424,6 +427,7 @@ impl<'a> ServerCommandContext<'a> {
                 .build_options
                 .as_ref()
                 .map_or(false, |opts| opts.materialize_failed_inputs),
+            local_action_cache: self.base_context.daemon.local_action_cache.dupe(),
         }
     }
 
@@ -437,6 +441,7 @@ impl<'a> ServerCommandContext<'a> {
 
         Ok(DiceCommandUpdater {
             file_watcher: self.base_context.daemon.file_watcher.dupe(),
+            persisted_package_listings: self.base_context.daemon.persisted_package_listings.dupe(),
             cell_config_loader: self.cell_configs_loader.dupe(),
             buck_out_dir: self.buck_out_dir.clone(),
             interpreter_platform,
@@ -531,6 +536,7 @@ struct DiceCommandDataProvider {
     paranoid: Option<ParanoidDownloader>,
     spawner: Arc<BuckSpawner>,
     materialize_failed_inputs: bool,
+    local_action_cache: Option<Arc<LocalActionCache>>,
 }
 
 #[async_trait]
@@ -568,6 +574,19 @@ impl DiceDataProvider for DiceCommandDataProvider {
             .unwrap_or_else(RolloutPercentage::always)
             .roll();
 
+        let sandbox_local_actions = root_config
+            .parse::<bool>("buck2", "sandbox_local_actions")?
+            .unwrap_or(false);
+
+        let local_action_cgroups = root_config
+            .parse::<bool>("buck2", "local_action_cgroups")?
+            .unwrap_or(false);
+
+        let local_action_memory_budget_mebibytes =
+            root_config.parse::<u64>("buck2", "local_action_memory_budget_mebibytes")?;
+        let local_action_default_memory_mebibytes =
+            root_config.parse::<u64>("buck2", "local_action_default_memory_mebibytes")?;
+
         let log_configured_graph_size = root_config
             .parse::<bool>("buck2", "log_configured_graph_size")?
             .unwrap_or(false);
@@ -579,10 +598,20 @@ impl DiceDataProvider for DiceCommandDataProvider {
         let executor_global_knobs = ExecutorGlobalKnobs {
             enable_miniperf,
             log_action_keys,
+            sandbox_local_actions,
+            local_action_cgroups,
         };
 
-        let host_sharing_broker =
+        let mut host_sharing_broker =
             HostSharingBroker::new(HostSharingStrategy::SmallerTasksFirst, concurrency);
+        if let Some(mebibytes) = local_action_memory_budget_mebibytes {
+            // By default, actions that don't declare how much memory they need get an even share
+            // of the budget.
+            let default_mebibytes = local_action_default_memory_mebibytes
+                .unwrap_or_else(|| mebibytes / (concurrency.max(1) as u64));
+            host_sharing_broker = host_sharing_broker
+                .with_memory_budget(mebibytes * 1024 * 1024, default_mebibytes * 1024 * 1024);
+        }
 
         // We use the job count for the low pass filter too. The low pass filter prevents sending
         // RE-eligile tasks to local if their concurrency is higher than our threshold. While it
@@ -644,6 +673,7 @@ impl DiceDataProvider for DiceCommandDataProvider {
             worker_pool,
             self.paranoid.dupe(),
             self.materialize_failed_inputs,
+            self.local_action_cache.dupe(),
         )));
         data.set_blocking_executor(self.blocking_executor.dupe());
         data.set_http_client(self.http_client.dupe());
@@ -680,6 +710,7 @@ fn create_cycle_detector() -> Arc<dyn UserCycleDetector> {
 
 struct DiceCommandUpdater {
     file_watcher: Arc<dyn FileWatcher>,
+    persisted_package_listings: Option<Arc<PersistedPackageListings>>,
     cell_config_loader: Arc<CellConfigLoader>,
     buck_out_dir: ProjectRelativePathBuf,
     interpreter_platform: InterpreterHostPlatform,
@@ -720,9 +751,22 @@ impl DiceUpdater for DiceCommandUpdater {
             None,
         )?;
 
-        let (mut ctx, mergebase) = self.file_watcher.sync(ctx).await?;
+        let sync_time = SystemTime::now();
+        let (mut ctx, mergebase, changed_dirs) = self.file_watcher.sync(ctx).await?;
         user_data.set_mergebase(mergebase);
 
+        if let Some(persisted_package_listings) = &self.persisted_package_listings {
+            persisted_package_listings
+                .on_sync(
+                    &mut ctx,
+                    sync_time,
+                    &changed_dirs,
+                    &cell_resolver,
+                    &legacy_configs,
+                )
+                .await?;
+        }
+
         ctx.set_buck_out_path(Some(self.buck_out_dir.clone()))?;
 
         setup_interpreter(
@@ -42,6 +42,9 @@ use buck2_execute_impl::executors::action_cache::RemoteDepFileCacheChecker;
 use buck2_execute_impl::executors::caching::CacheUploader;
 use buck2_execute_impl::executors::hybrid::HybridExecutor;
 use buck2_execute_impl::executors::local::LocalExecutor;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCache;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCacheChecker;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCacheExecutor;
 use buck2_execute_impl::executors::re::ReExecutor;
 use buck2_execute_impl::executors::stacked::StackedExecutor;
 use buck2_execute_impl::executors::worker::WorkerPool;
@@ -85,6 +88,7 @@ pub struct CommandExecutorFactory {
     worker_pool: Arc<WorkerPool>,
     paranoid: Option<ParanoidDownloader>,
     materialize_failed_inputs: bool,
+    local_action_cache: Option<Arc<LocalActionCache>>,
 }
 
 impl CommandExecutorFactory {
@@ -104,6 +108,7 @@ impl CommandExecutorFactory {
         worker_pool: Arc<WorkerPool>,
         paranoid: Option<ParanoidDownloader>,
         materialize_failed_inputs: bool,
+        local_action_cache: Option<Arc<LocalActionCache>>,
     ) -> Self {
         Self {
             re_connection,
@@ -121,6 +126,44 @@ impl CommandExecutorFactory {
             worker_pool,
             paranoid,
             materialize_failed_inputs,
+            local_action_cache,
+        }
+    }
+
+    /// Consult the local action cache, when enabled, before `fallback`. This is done for
+    /// executors that may run actions locally.
+    fn with_local_cache_checker(
+        &self,
+        artifact_fs: &ArtifactFs,
+        fallback: Arc<dyn PreparedCommandOptionalExecutor>,
+    ) -> Arc<dyn PreparedCommandOptionalExecutor> {
+        match &self.local_action_cache {
+            Some(cache) if !self.skip_cache_read => Arc::new(LocalActionCacheChecker {
+                cache: cache.dupe(),
+                artifact_fs: artifact_fs.clone(),
+                materializer: self.materializer.dupe(),
+                blocking_executor: self.blocking_executor.dupe(),
+                fallback,
+            }),
+            _ => fallback,
+        }
+    }
+
+    /// Store the results of actions `inner` executes locally in the local action cache, when
+    /// enabled.
+    fn with_local_cache_executor(
+        &self,
+        artifact_fs: &ArtifactFs,
+        inner: Arc<dyn PreparedCommandExecutor>,
+    ) -> Arc<dyn PreparedCommandExecutor> {
+        match &self.local_action_cache {
+            Some(cache) if !self.skip_cache_write => Arc::new(LocalActionCacheExecutor {
+                inner,
+                cache: cache.dupe(),
+                artifact_fs: artifact_fs.clone(),
+                blocking_executor: self.blocking_executor.dupe(),
+            }),
+            _ => inner,
         }
     }
 }
@@ -149,9 +192,18 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 self.forkserver.dupe(),
                 self.executor_global_knobs.dupe(),
                 worker_pool,
+                options.resource_limits,
             )
         };
 
+        let local_only_response = |executor: LocalExecutor| CommandExecutorResponse {
+            executor: self.with_local_cache_executor(artifact_fs, Arc::new(executor)),
+            platform: Default::default(),
+            cache_checker: self
+                .with_local_cache_checker(artifact_fs, Arc::new(NoOpCommandOptionalExecutor {})),
+            cache_uploader: Arc::new(NoOpCacheUploader {}),
+        };
+
         if !buck2_core::is_open_source() && !cfg!(fbcode_build) {
             static WARN: OnceLock<()> = OnceLock::new();
             WARN.get_or_init(|| {
@@ -165,12 +217,9 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 ));
             }
 
-            return Ok(CommandExecutorResponse {
-                executor: Arc::new(local_executor_new(&LocalExecutorOptions::default())),
-                platform: Default::default(),
-                cache_checker: Arc::new(NoOpCommandOptionalExecutor {}),
-                cache_uploader: Arc::new(NoOpCacheUploader {}),
-            });
+            return Ok(local_only_response(local_executor_new(
+                &LocalExecutorOptions::default(),
+            )));
         }
 
         let remote_executor_new = |options: &RemoteExecutorOptions,
@@ -185,6 +234,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 re_use_case: *re_use_case,
                 re_action_key: re_action_key.clone(),
                 re_max_queue_time_ms: options.re_max_queue_time_ms,
+                re_priority: options.re_priority,
                 knobs: self.executor_global_knobs.dupe(),
                 skip_cache_read: self.skip_cache_read || !remote_cache_enabled,
                 skip_cache_write: self.skip_cache_write || !remote_cache_enabled,
@@ -198,12 +248,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 if self.strategy.ban_local() {
                     None
                 } else {
-                    Some(CommandExecutorResponse {
-                        executor: Arc::new(local_executor_new(local)),
-                        platform: Default::default(),
-                        cache_checker: Arc::new(NoOpCommandOptionalExecutor {}),
-                        cache_uploader: Arc::new(NoOpCacheUploader {}),
-                    })
+                    Some(local_only_response(local_executor_new(local)))
                 }
             }
             Executor::RemoteEnabled {
@@ -273,6 +318,11 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     }
                 };
 
+                let may_run_locally = matches!(
+                    executor,
+                    RemoteEnabledExecutor::Local(..) | RemoteEnabledExecutor::Hybrid { .. }
+                );
+
                 let executor: Option<Arc<dyn PreparedCommandExecutor>> = match &executor {
                     RemoteEnabledExecutor::Local(local) if !self.strategy.ban_local() => {
                         Some(Arc::new(local_executor_new(local)))
@@ -341,6 +391,15 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     cache_checker_new()
                 };
 
+                let (executor, cache_checker) = if may_run_locally {
+                    (
+                        executor.map(|e| self.with_local_cache_executor(artifact_fs, e)),
+                        self.with_local_cache_checker(artifact_fs, cache_checker),
+                    )
+                } else {
+                    (executor, cache_checker)
+                };
+
                 let platform = RE::Platform {
                     properties: re_properties
                         .iter()
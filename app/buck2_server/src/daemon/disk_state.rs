@@ -27,11 +27,14 @@ use buck2_execute_impl::materializers::sqlite::MaterializerState;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateSqliteDb;
 use buck2_execute_impl::materializers::sqlite::DB_SCHEMA_VERSION;
 
+use crate::daemon::persisted_package_listings::PersistedPackageListings;
 use crate::daemon::server::BuckdServerInitPreferences;
 
 #[derive(Allocative)]
 pub struct DiskStateOptions {
     pub sqlite_materializer_state: bool,
+    /// Whether to persist package listings across daemon restarts.
+    pub persist_package_listings: bool,
     // In future, this will include the config for dep files on disk
 }
 
@@ -48,8 +51,12 @@ impl DiskStateOptions {
             .parse::<RolloutPercentage>("buck2", "sqlite_materializer_state")?
             .unwrap_or_else(RolloutPercentage::never)
             .roll();
+        let persist_package_listings = root_config
+            .parse("buck2", "persist_package_listings")?
+            .unwrap_or(false);
         Ok(Self {
             sqlite_materializer_state,
+            persist_package_listings,
         })
     }
 }
@@ -116,6 +123,22 @@ pub(crate) async fn maybe_initialize_materializer_sqlite_db(
     Ok((Some(db), materializer_state))
 }
 
+pub(crate) fn maybe_load_persisted_package_listings(
+    options: &DiskStateOptions,
+    paths: &InvocationPaths,
+    fs: ProjectRoot,
+) -> anyhow::Result<Option<Arc<PersistedPackageListings>>> {
+    let dir = paths.dice_state_path();
+    if !options.persist_package_listings {
+        // As for the materializer state, delete the persisted listings when the feature is
+        // disabled, so that they don't go stale in case it gets enabled again.
+        fs.remove_path_recursive(&dir)?;
+        return Ok(None);
+    }
+
+    Ok(Some(Arc::new(PersistedPackageListings::load(dir, fs))))
+}
+
 // Once we start storing disk state in the cache directory, we need to make sure
 // buck2 always deletes the cache directory if the cache is disabled.
 // Otherwise, buck-out state can diverge from the state of on-disk cache when
@@ -16,6 +16,7 @@ pub mod forkserver;
 pub(crate) mod io_provider;
 mod multi_event_stream;
 pub mod panic;
+pub mod persisted_package_listings;
 pub mod server;
 pub(crate) mod server_allocative;
 pub mod state;
@@ -0,0 +1,564 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Persists package listings across daemon restarts, so that the first command after a
+//! `buck2 kill` or an upgrade doesn't have to walk the whole project again.
+//!
+//! Parsed build files and analysis results are not persisted, so they are still recomputed after
+//! a restart: their values hold frozen Starlark values, which need a serializable form first.
+//! Those keys also have deps, which would need to be persisted too so that the keys can be
+//! invalidated when their deps change.
+//!
+//! When the daemon shuts down, we write the package listings that are up to date in DICE, along
+//! with the modification times of the directories they were listed from. After the first file
+//! watcher sync of the next daemon, we inject the listings whose directories are unchanged into
+//! DICE. Injected values have no deps, so DICE wouldn't invalidate them when files change:
+//! instead, on every later sync, we invalidate the restored listings with a directory the file
+//! watcher reported as changed, and DICE recomputes those with their deps.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use allocative::Allocative;
+use anyhow::Context;
+use buck2_client_ctx::version::BuckVersion;
+use buck2_common::legacy_configs::LegacyBuckConfigs;
+use buck2_common::package_listing::dice::PackageListingKey;
+use buck2_common::package_listing::listing::PackageListing;
+use buck2_common::package_listing::listing::PersistedPackageListing;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::name::CellName;
+use buck2_core::cells::paths::CellRelativePath;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_core::package::PackageLabel;
+use buck2_file_watcher::changed_dirs::ChangedDirs;
+use dice::Dice;
+use dice::DiceTransactionUpdater;
+use dupe::Dupe;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Bump this when the format of the persisted state changes.
+const SCHEMA_VERSION: u32 = 3;
+
+/// The file the state is persisted to, in the DICE state directory.
+const STATE_FILE_NAME: &str = "state";
+
+/// The file watcher may report changes made shortly before a sync only on the next sync, so we
+/// don't persist listings of directories that were modified that close to the last sync.
+const FILE_WATCHER_LATENCY: Duration = Duration::from_secs(5);
+
+/// The config sections package listings depend on: the cells, the names of build files and the
+/// ignored paths.
+const PACKAGE_LISTING_CONFIG_SECTIONS: &[&str] = &["buildfile", "cells", "project", "repositories"];
+
+/// A BLAKE3 hash of the config package listings depend on. This is written to disk, so it must be
+/// stable across builds of buck2.
+type ConfigFingerprint = [u8; 32];
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PersistedState {
+    schema_version: u32,
+    /// The unique id of the buck2 binary that wrote this state. We only restore state written by
+    /// the same binary.
+    buck2_version: String,
+    /// A hash of the config the persisted values were computed with.
+    config_fingerprint: ConfigFingerprint,
+    package_listings: Vec<PersistedPackageListingEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PersistedPackageListingEntry {
+    cell: String,
+    path: String,
+    listing: PersistedPackageListing,
+    /// The modification times of the directories returned by `listed_dirs`, in that order.
+    dir_mtimes: Vec<Mtime>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct Mtime {
+    secs: u64,
+    nanos: u32,
+}
+
+impl Mtime {
+    fn of(path: &AbsNormPathBuf) -> anyhow::Result<Mtime> {
+        let mtime = fs_util::metadata(path)?
+            .modified()?
+            .duration_since(SystemTime::UNIX_EPOCH)?;
+        Ok(Mtime {
+            secs: mtime.as_secs(),
+            nanos: mtime.subsec_nanos(),
+        })
+    }
+
+    fn to_system_time(self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::new(self.secs, self.nanos)
+    }
+}
+
+/// The state of the file system and config DICE was last synced with.
+struct LastSync {
+    time: SystemTime,
+    cells: CellResolver,
+    config_fingerprint: ConfigFingerprint,
+}
+
+#[derive(Default)]
+struct State {
+    /// State loaded from disk that wasn't restored yet.
+    loaded: Option<PersistedState>,
+    /// Keys injected into DICE by the restore that weren't invalidated yet, with the directories
+    /// they were listed from.
+    restored: HashMap<PackageListingKey, Vec<CellPath>>,
+    last_sync: Option<LastSync>,
+}
+
+/// Package listings persisted across daemon restarts, enabled by `buck2.persist_package_listings`.
+#[derive(Allocative)]
+pub struct PersistedPackageListings {
+    dir: AbsNormPathBuf,
+    project_root: ProjectRoot,
+    #[allocative(skip)]
+    state: Mutex<State>,
+}
+
+impl PersistedPackageListings {
+    /// Load the state persisted by the previous daemon, if any. The state is deleted from disk
+    /// so that it can't be restored twice, and written again on shutdown. The state is only a
+    /// cache, so if it can't be loaded, it is discarded rather than preventing startup.
+    pub(crate) fn load(dir: AbsNormPathBuf, project_root: ProjectRoot) -> Self {
+        let path = dir.join(FileName::unchecked_new(STATE_FILE_NAME));
+        let loaded = match Self::read(&path) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!("Discarding persisted package listings: {:#}", e);
+                if let Err(e) = fs_util::remove_all(&path) {
+                    tracing::warn!("Error deleting persisted package listings: {:#}", e);
+                }
+                None
+            }
+        };
+
+        Self {
+            dir,
+            project_root,
+            state: Mutex::new(State {
+                loaded,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Read and delete the state at `path`. This returns `None` if there is none, or if it was
+    /// persisted by another version of buck2.
+    fn read(path: &AbsNormPathBuf) -> anyhow::Result<Option<PersistedState>> {
+        let bytes = match fs_util::read_if_exists(path)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        fs_util::remove_file(path)?;
+        let state = bincode::deserialize::<PersistedState>(&bytes)
+            .context("Invalid persisted package listings")?;
+        if state.schema_version != SCHEMA_VERSION
+            || state.buck2_version != BuckVersion::get_unique_id()
+        {
+            return Ok(None);
+        }
+        Ok(Some(state))
+    }
+
+    /// Called after every file watcher sync, with the directories it reported as changed and the
+    /// config of the command. On the first sync, this injects the persisted values that are still
+    /// valid into DICE. On later ones, this invalidates the injected values whose directories
+    /// changed, so that DICE recomputes them with their deps.
+    pub(crate) async fn on_sync(
+        self: &Arc<Self>,
+        ctx: &mut DiceTransactionUpdater,
+        sync_time: SystemTime,
+        changed_dirs: &ChangedDirs,
+        cells: &CellResolver,
+        configs: &LegacyBuckConfigs,
+    ) -> anyhow::Result<()> {
+        let config_fingerprint = config_fingerprint(configs);
+
+        let (loaded, invalidated) = {
+            let mut state = self.state.lock();
+            let config_changed = state
+                .last_sync
+                .as_ref()
+                .map_or(false, |s| s.config_fingerprint != config_fingerprint);
+            state.last_sync = Some(LastSync {
+                time: sync_time,
+                cells: cells.dupe(),
+                config_fingerprint,
+            });
+            let invalidated = invalidated_keys(&mut state.restored, changed_dirs, config_changed);
+            (state.loaded.take(), invalidated)
+        };
+
+        if !invalidated.is_empty() {
+            ctx.changed(invalidated)?;
+        }
+
+        let loaded = match loaded {
+            Some(loaded) if loaded.config_fingerprint == config_fingerprint => loaded,
+            _ => return Ok(()),
+        };
+
+        let this = self.dupe();
+        let cells = cells.dupe();
+        let listings = tokio::task::spawn_blocking(move || {
+            loaded
+                .package_listings
+                .into_iter()
+                .filter_map(|entry| this.validate(entry, &cells).ok().flatten())
+                .collect::<Vec<_>>()
+        })
+        .await?;
+
+        tracing::info!("Restored {} persisted package listings", listings.len());
+        let mut restored = HashMap::with_capacity(listings.len());
+        let mut values = Vec::with_capacity(listings.len());
+        for (key, dirs, listing) in listings {
+            restored.insert(key.dupe(), dirs);
+            values.push((key, listing));
+        }
+        self.state.lock().restored = restored;
+        ctx.changed_to(values)?;
+
+        Ok(())
+    }
+
+    /// The listing of this entry and the directories it was listed from, if they are unchanged
+    /// since it was persisted.
+    fn validate(
+        &self,
+        entry: PersistedPackageListingEntry,
+        cells: &CellResolver,
+    ) -> anyhow::Result<
+        Option<(
+            PackageListingKey,
+            Vec<CellPath>,
+            buck2_error::Result<PackageListing>,
+        )>,
+    > {
+        let package = PackageLabel::new(
+            CellName::unchecked_new(&entry.cell)?,
+            <&CellRelativePath>::try_from(entry.path.as_str())?,
+        );
+        let dirs = listed_dirs(cells, package.dupe(), &entry.listing)?;
+        if dirs.len() != entry.dir_mtimes.len() {
+            return Ok(None);
+        }
+        let mut cell_dirs = Vec::with_capacity(dirs.len());
+        for (dir, mtime) in dirs.iter().zip(entry.dir_mtimes) {
+            if Mtime::of(&self.project_root.resolve(dir))? != mtime {
+                return Ok(None);
+            }
+            cell_dirs.push(cells.get_cell_path(dir)?);
+        }
+        Ok(Some((
+            PackageListingKey(package),
+            cell_dirs,
+            Ok(entry.listing.into_listing()?),
+        )))
+    }
+
+    /// Persist the package listings that are up to date in DICE. If no command ran, the state
+    /// loaded on startup is persisted again.
+    pub(crate) async fn save(self: &Arc<Self>, dice: &Dice) -> anyhow::Result<()> {
+        let (last_sync, loaded) = {
+            let mut state = self.state.lock();
+            (state.last_sync.take(), state.loaded.take())
+        };
+
+        let this = self.dupe();
+        let state = match last_sync {
+            Some(last_sync) => {
+                let listings = dice.current_values::<PackageListingKey>();
+                tokio::task::spawn_blocking(move || {
+                    let package_listings = listings
+                        .into_iter()
+                        .filter_map(|(key, listing)| {
+                            let listing = PersistedPackageListing::new(&listing.ok()?);
+                            this.fingerprint(key.0, listing, &last_sync).ok().flatten()
+                        })
+                        .collect();
+                    PersistedState {
+                        schema_version: SCHEMA_VERSION,
+                        buck2_version: BuckVersion::get_unique_id().to_owned(),
+                        config_fingerprint: last_sync.config_fingerprint,
+                        package_listings,
+                    }
+                })
+                .await?
+            }
+            None => match loaded {
+                Some(loaded) => loaded,
+                None => return Ok(()),
+            },
+        };
+
+        tracing::info!(
+            "Persisting {} package listings",
+            state.package_listings.len()
+        );
+        fs_util::create_dir_all(&self.dir)?;
+        let tmp = self.dir.join(FileName::unchecked_new("state.tmp"));
+        fs_util::write(&tmp, bincode::serialize(&state)?)?;
+        fs_util::rename(
+            &tmp,
+            self.dir.join(FileName::unchecked_new(STATE_FILE_NAME)),
+        )?;
+
+        Ok(())
+    }
+
+    /// The entry to persist for this listing, or `None` if one of its directories may have
+    /// changed since DICE was last synced.
+    fn fingerprint(
+        &self,
+        package: PackageLabel,
+        listing: PersistedPackageListing,
+        last_sync: &LastSync,
+    ) -> anyhow::Result<Option<PersistedPackageListingEntry>> {
+        let dirs = listed_dirs(&last_sync.cells, package.dupe(), &listing)?;
+        let mut dir_mtimes = Vec::with_capacity(dirs.len());
+        for dir in dirs {
+            let mtime = Mtime::of(&self.project_root.resolve(&dir))?;
+            if mtime.to_system_time() + FILE_WATCHER_LATENCY >= last_sync.time {
+                return Ok(None);
+            }
+            dir_mtimes.push(mtime);
+        }
+        Ok(Some(PersistedPackageListingEntry {
+            cell: package.cell_name().as_str().to_owned(),
+            path: package.cell_relative_path().as_str().to_owned(),
+            listing,
+            dir_mtimes,
+        }))
+    }
+}
+
+/// The directories a package listing was computed from: the package, its subdirectories, and its
+/// subpackages, whose build files are what makes them subpackages.
+fn listed_dirs(
+    cells: &CellResolver,
+    package: PackageLabel,
+    listing: &PersistedPackageListing,
+) -> anyhow::Result<Vec<ProjectRelativePathBuf>> {
+    let root = cells.resolve_package(package)?;
+    let mut dirs = vec![root.clone()];
+    for dir in listing.directories.iter().chain(&listing.subpackages) {
+        dirs.push(root.join(ForwardRelativePath::new(dir)?));
+    }
+    Ok(dirs)
+}
+
+/// Remove and return the restored keys to invalidate: all of them if the config changed, and
+/// otherwise those with a changed directory.
+fn invalidated_keys(
+    restored: &mut HashMap<PackageListingKey, Vec<CellPath>>,
+    changed_dirs: &ChangedDirs,
+    config_changed: bool,
+) -> Vec<PackageListingKey> {
+    if config_changed {
+        return restored.drain().map(|(key, _)| key).collect();
+    }
+    let mut invalidated = Vec::new();
+    restored.retain(|key, dirs| {
+        if dirs.iter().any(|dir| changed_dirs.contains(dir)) {
+            invalidated.push(key.dupe());
+            false
+        } else {
+            true
+        }
+    });
+    invalidated
+}
+
+fn config_fingerprint(configs: &LegacyBuckConfigs) -> ConfigFingerprint {
+    let mut configs = configs.iter().collect::<Vec<_>>();
+    configs.sort_by_key(|(cell, _)| cell.as_str());
+
+    let mut hasher = blake3::Hasher::new();
+    // Prefix every string with its length, so that different configs can't hash the same.
+    let mut update = |s: &str| {
+        hasher.update(&(s.len() as u64).to_le_bytes());
+        hasher.update(s.as_bytes());
+    };
+    for (cell, config) in configs {
+        update(cell.as_str());
+        for section in PACKAGE_LISTING_CONFIG_SECTIONS {
+            update(*section);
+            for (key, value) in config
+                .get_section(section)
+                .into_iter()
+                .flat_map(|s| s.iter())
+            {
+                update(key);
+                update(value.as_str());
+            }
+        }
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::package_listing::listing::testing::PackageListingExt;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    #[test]
+    fn test_persist_and_validate() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        fs.write_file("foo/bar/a", "");
+
+        let cells = CellResolver::testing_with_name_and_path(
+            CellName::testing_new("root"),
+            CellRootPathBuf::testing_new(""),
+        );
+        let package = PackageLabel::testing_new("root", "foo");
+        let listing = PersistedPackageListing::new(&PackageListing::testing_files(&["bar/a"]));
+
+        let state = PersistedPackageListings::load(
+            fs.path().root().join(FileName::unchecked_new("dice_state")),
+            fs.path().dupe(),
+        );
+        let last_sync = |time| LastSync {
+            time,
+            cells: cells.dupe(),
+            config_fingerprint: [0; 32],
+        };
+
+        // Directories modified right before the last sync may have changed since.
+        assert_eq!(
+            state.fingerprint(
+                package.dupe(),
+                listing.clone(),
+                &last_sync(SystemTime::now())
+            )?,
+            None
+        );
+
+        let entry = state
+            .fingerprint(
+                package.dupe(),
+                listing.clone(),
+                &last_sync(SystemTime::now() + FILE_WATCHER_LATENCY * 2),
+            )?
+            .unwrap();
+        assert_eq!(entry.cell, "root");
+        assert_eq!(entry.path, "foo");
+        assert_eq!(entry.dir_mtimes.len(), 1);
+
+        let (key, dirs, restored) = state.validate(entry, &cells)?.unwrap();
+        assert_eq!(key, PackageListingKey(package.dupe()));
+        assert_eq!(dirs, vec![CellPath::testing_new("root//foo")]);
+        assert_eq!(PersistedPackageListing::new(&restored?), listing);
+
+        // Listings of modified directories aren't restored.
+        let mut entry = state
+            .fingerprint(
+                package.dupe(),
+                listing,
+                &last_sync(SystemTime::now() + FILE_WATCHER_LATENCY * 2),
+            )?
+            .unwrap();
+        entry.dir_mtimes[0].nanos = entry.dir_mtimes[0].nanos.wrapping_add(1) % 1_000_000_000;
+        assert!(state.validate(entry, &cells)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalidated_keys() {
+        let key = |package| PackageListingKey(PackageLabel::testing_new("root", package));
+        let restored = || {
+            HashMap::from_iter([
+                (
+                    key("foo"),
+                    vec![
+                        CellPath::testing_new("root//foo"),
+                        CellPath::testing_new("root//foo/bar"),
+                    ],
+                ),
+                (key("baz"), vec![CellPath::testing_new("root//baz")]),
+            ])
+        };
+        let changed = |dirs: &[&str]| {
+            ChangedDirs::Dirs(dirs.iter().map(|d| CellPath::testing_new(d)).collect())
+        };
+
+        let mut keys = restored();
+        assert_eq!(
+            invalidated_keys(&mut keys, &changed(&["root//foo/bar", "root//qux"]), false),
+            vec![key("foo")]
+        );
+        assert_eq!(keys.keys().collect::<Vec<_>>(), vec![&key("baz")]);
+        // Keys are only invalidated once.
+        assert!(invalidated_keys(&mut keys, &changed(&["root//foo/bar"]), false).is_empty());
+
+        let mut keys = restored();
+        assert_eq!(
+            invalidated_keys(&mut keys, &ChangedDirs::All, false).len(),
+            2
+        );
+        assert!(keys.is_empty());
+
+        let mut keys = restored();
+        assert_eq!(invalidated_keys(&mut keys, &changed(&[]), true).len(), 2);
+        assert!(keys.is_empty());
+    }
+
+    #[test]
+    fn test_config_fingerprint() -> anyhow::Result<()> {
+        let config = |buildfile: &str| -> anyhow::Result<LegacyBuckConfigs> {
+            Ok(LegacyBuckConfigs::new(
+                [(
+                    CellName::testing_new("root"),
+                    buck2_common::legacy_configs::testing::parse(
+                        &[(
+                            "config",
+                            &format!("[buildfile]\nname = {}\n[build]\nfoo = bar", buildfile),
+                        )],
+                        "config",
+                    )?,
+                )]
+                .into_iter()
+                .collect(),
+            ))
+        };
+
+        assert_eq!(
+            config_fingerprint(&config("BUCK")?),
+            config_fingerprint(&config("BUCK")?)
+        );
+        assert_ne!(
+            config_fingerprint(&config("BUCK")?),
+            config_fingerprint(&config("TARGETS")?)
+        );
+
+        Ok(())
+    }
+}
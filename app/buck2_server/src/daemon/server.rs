@@ -309,7 +309,7 @@ impl BuckdServer {
                 delegate,
                 shutdown_channel,
             },
-            daemon_state,
+            daemon_state: daemon_state.dupe(),
             command_channel,
             callbacks,
             log_reload_handle,
@@ -328,6 +328,8 @@ impl BuckdServer {
 
         server.await?;
 
+        daemon_state.persist_package_listings().await;
+
         Ok(())
     }
 
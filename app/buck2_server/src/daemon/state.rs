@@ -36,9 +36,11 @@ use buck2_core::is_open_source;
 use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_core::tag_result;
 use buck2_events::dispatch::EventDispatcher;
+use buck2_events::sink::otlp::OtlpFileSink;
 use buck2_events::sink::scribe;
 use buck2_events::sink::tee::TeeSink;
 use buck2_events::source::ChannelEventSource;
+use buck2_events::EventSink;
 use buck2_events::EventSinkWithStats;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::execute::blocking::BlockingExecutor;
@@ -46,6 +48,8 @@ use buck2_execute::execute::blocking::BuckBlockingExecutor;
 use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionManager;
+use buck2_execute_impl::executors::local_action_cache::LocalActionCache;
+use buck2_execute_impl::executors::local_action_cache::DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES;
 use buck2_execute_impl::materializers::deferred::AccessTimesUpdates;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializer;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
@@ -75,10 +79,12 @@ use crate::ctx::BaseServerCommandContext;
 use crate::daemon::check_working_dir;
 use crate::daemon::disk_state::delete_unknown_disk_state;
 use crate::daemon::disk_state::maybe_initialize_materializer_sqlite_db;
+use crate::daemon::disk_state::maybe_load_persisted_package_listings;
 use crate::daemon::disk_state::DiskStateOptions;
 use crate::daemon::forkserver::maybe_launch_forkserver;
 use crate::daemon::io_provider::create_io_provider;
 use crate::daemon::panic::DaemonStatePanicDiceDump;
+use crate::daemon::persisted_package_listings::PersistedPackageListings;
 use crate::daemon::server::BuckdServerInitPreferences;
 
 /// For a buckd process there is a single DaemonState created at startup and never destroyed.
@@ -135,6 +141,10 @@ pub struct DaemonStateData {
     #[allocative(skip)]
     pub scribe_sink: Option<Arc<dyn EventSinkWithStats>>,
 
+    /// Writes spans as OpenTelemetry traces, if `buck2.otlp_trace_file` is set.
+    #[allocative(skip)]
+    pub otlp_sink: Option<Arc<dyn EventSinkWithStats>>,
+
     /// Whether or not to hash all commands
     pub hash_all_commands: bool,
 
@@ -151,6 +161,9 @@ pub struct DaemonStateData {
     /// What buck2 state to store on disk, ex. materializer state on sqlite
     pub disk_state_options: DiskStateOptions,
 
+    /// Package listings persisted across daemon restarts, if enabled.
+    pub(crate) persisted_package_listings: Option<Arc<PersistedPackageListings>>,
+
     pub start_time: Instant,
 
     #[allocative(skip)]
@@ -169,6 +182,11 @@ pub struct DaemonStateData {
     /// If enabled, paranoid RE downloads.
     pub paranoid: Option<ParanoidDownloader>,
 
+    /// If enabled, an on-disk cache of the results of locally executed actions that persists
+    /// across daemon restarts.
+    #[allocative(skip)]
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
+
     /// Spawner
     pub spawner: Arc<BuckSpawner>,
 }
@@ -376,6 +394,13 @@ impl DaemonState {
             )
             .await?;
 
+            let persisted_package_listings = (blocking_executor.dupe()
+                as Arc<dyn BlockingExecutor>)
+                .execute_io_inline(|| {
+                    maybe_load_persisted_package_listings(&disk_state_options, &paths, fs.dupe())
+                })
+                .await?;
+
             let http_client = http_client_from_startup_config(&init_ctx.daemon_startup_config)
                 .context("Error creating HTTP client")?
                 .build();
@@ -391,6 +416,7 @@ impl DaemonState {
                 Some(paths.re_logs_dir()),
                 paths.buck_out_path(),
                 init_ctx.daemon_startup_config.paranoid,
+                digest_config,
             ));
             let materializer = Self::create_materializer(
                 fb,
@@ -464,6 +490,15 @@ impl DaemonState {
             )
             .context("failed to init scribe sink")?;
 
+            let otlp_sink: Option<Arc<dyn EventSinkWithStats>> = root_config
+                .get("buck2", "otlp_trace_file")
+                .map(|path| {
+                    let path = paths.project_root().root().as_path().join(path);
+                    anyhow::Ok(Arc::new(OtlpFileSink::create(&path)?) as _)
+                })
+                .transpose()
+                .context("failed to init OTLP trace sink")?;
+
             let enable_restarter = root_config
                 .parse::<RolloutPercentage>("buck2", "restarter")?
                 .unwrap_or_else(RolloutPercentage::never)
@@ -480,6 +515,34 @@ impl DaemonState {
                 None
             };
 
+            let local_action_cache = if root_config
+                .parse("buck2", "local_action_cache")?
+                .unwrap_or(false)
+            {
+                let max_bytes = root_config
+                    .parse("buck2", "local_action_cache_max_bytes")?
+                    .unwrap_or(DEFAULT_LOCAL_ACTION_CACHE_MAX_BYTES);
+                let path = paths.local_action_cache_path();
+                let cache = (blocking_executor.dupe() as Arc<dyn BlockingExecutor>)
+                    .execute_io_inline(|| {
+                        LocalActionCache::open(path, max_bytes, digest_config.cas_digest_config())
+                    })
+                    .await;
+                // The cache is only an optimization, so don't let it keep the daemon from starting.
+                match cache {
+                    Ok(cache) => Some(Arc::new(cache)),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Error opening local action cache, running without it: {:#}",
+                            e
+                        );
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
             // Kick off an initial sync eagerly. This gets Watchamn to start watching the path we care
             // about (potentially kicking off an initial crawl).
 
@@ -494,15 +557,18 @@ impl DaemonState {
                 materializer,
                 forkserver,
                 scribe_sink,
+                otlp_sink,
                 hash_all_commands,
                 use_network_action_output_cache,
                 disk_state_options,
+                persisted_package_listings,
                 start_time: std::time::Instant::now(),
                 create_unhashed_outputs_lock,
                 materializer_state_identity,
                 enable_restarter,
                 http_client,
                 paranoid,
+                local_action_cache,
                 spawner: Arc::new(BuckSpawner::new(daemon_state_data_rt)),
             }))
         })
@@ -616,11 +682,14 @@ impl DaemonState {
         facebook_only();
         let (events, sink) = buck2_events::create_source_sink_pair();
         let data = self.data()?;
-        let dispatcher = if let Some(scribe_sink) = data.scribe_sink.dupe() {
-            EventDispatcher::new(trace_id, TeeSink::new(scribe_sink.to_event_sync(), sink))
-        } else {
-            EventDispatcher::new(trace_id, sink)
-        };
+        let mut sink: Arc<dyn EventSink> = Arc::new(sink);
+        if let Some(otlp_sink) = data.otlp_sink.dupe() {
+            sink = Arc::new(TeeSink::new(otlp_sink.to_event_sync(), sink));
+        }
+        if let Some(scribe_sink) = data.scribe_sink.dupe() {
+            sink = Arc::new(TeeSink::new(scribe_sink.to_event_sync(), sink));
+        }
+        let dispatcher = EventDispatcher::new(trace_id, sink);
         Ok((events, dispatcher))
     }
 
@@ -672,6 +741,11 @@ impl DaemonState {
                 data.disk_state_options.sqlite_materializer_state
             ),
             format!("paranoid:{}", data.paranoid.is_some()),
+            format!("local-action-cache:{}", data.local_action_cache.is_some()),
+            format!(
+                "persist-dice-state:{}",
+                data.disk_state_options.persist_package_listings
+            ),
         ];
 
         dispatcher.instant_event(buck2_data::TagEvent { tags });
@@ -697,6 +771,20 @@ impl DaemonState {
         Ok(self.data.dupe()?)
     }
 
+    /// Write the package listings in the DICE graph to disk so that the next daemon can reuse them.
+    /// Called on graceful shutdown; failures are logged and otherwise ignored.
+    pub(crate) async fn persist_package_listings(&self) {
+        let data = match self.data() {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        if let Some(persisted) = &data.persisted_package_listings {
+            if let Err(e) = persisted.save(data.dice_manager.unsafe_dice()).await {
+                tracing::warn!("Failed to persist package listings: {:#}", e);
+            }
+        }
+    }
+
     fn validate_cwd(&self) -> anyhow::Result<()> {
         if let Some(working_directory) = &self.working_directory {
             let res = working_directory.is_stale().and_then(|stale| {
@@ -21,7 +21,9 @@ use buck2_common::file_ops::FileOps;
 use buck2_common::package_listing::dice::HasPackageListingResolver;
 use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::cells::cell_path::CellPathRef;
+use buck2_core::cells::paths::CellRelativePath;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_path::AbsPath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
@@ -48,6 +50,7 @@ use dice::DiceEquality;
 use dice::DiceTransaction;
 use dupe::Dupe;
 use futures::channel::mpsc::UnboundedSender;
+use futures::stream::FuturesUnordered;
 use futures::FutureExt;
 use futures::SinkExt;
 use futures::StreamExt;
@@ -60,6 +63,7 @@ use starlark::analysis::find_call_name::AstModuleFindCallName;
 use starlark::codemap::Span;
 use starlark::docs::Doc;
 use starlark::docs::DocItem;
+use starlark::docs::DocMember;
 use starlark::docs::DocModule;
 use starlark::docs::Identifier;
 use starlark::docs::Location;
@@ -166,6 +170,88 @@ impl DocsCacheManager {
     }
 }
 
+/// The build files, `.bzl` and `.bxl` files of the project, which find-references and rename
+/// search. Directories are listed through DICE, so they are only read again once the file watcher
+/// reports a change, and the list itself is reused as long as the DICE state is unchanged.
+struct WorkspaceFilesCache {
+    files: Mutex<Option<(DiceEquality, Vec<LspUrl>)>>,
+}
+
+impl WorkspaceFilesCache {
+    fn new() -> Self {
+        Self {
+            files: Mutex::new(None),
+        }
+    }
+
+    async fn get(
+        &self,
+        fs: &ProjectRoot,
+        dice_ctx: DiceTransaction,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        let mut files = self.files.lock().await;
+        match &*files {
+            Some((valid_at, files)) if dice_ctx.equivalent(valid_at) => Ok(files.clone()),
+            _ => {
+                let new_files = Self::find_starlark_files(fs, &dice_ctx).await?;
+                *files = Some((dice_ctx.equality_token(), new_files.clone()));
+                Ok(new_files)
+            }
+        }
+    }
+
+    /// Find all the build files, `.bzl` and `.bxl` files in the cells of the project, skipping
+    /// ignored paths and hidden directories. Nested cells aren't part of their parent cell's
+    /// listings, so each file is only found once.
+    async fn find_starlark_files(
+        fs: &ProjectRoot,
+        dice_ctx: &DiceTransaction,
+    ) -> anyhow::Result<Vec<LspUrl>> {
+        let cell_resolver = dice_ctx.get_cell_resolver().await?;
+        let file_ops = dice_ctx.file_ops();
+        let list_dir = |path: CellPath| {
+            let file_ops = &file_ops;
+            async move {
+                let listing = file_ops.read_dir(path.as_ref()).await;
+                (path, listing)
+            }
+        };
+
+        let mut queue = cell_resolver
+            .cells()
+            .map(|(name, _)| list_dir(CellPath::new(name, CellRelativePath::empty().to_buf())))
+            .collect::<FuturesUnordered<_>>();
+        let mut files = Vec::new();
+        while let Some((path, listing)) = queue.next().await {
+            // Unreadable directories just don't contribute any files.
+            let listing = match listing {
+                Ok(listing) => listing,
+                Err(_) => continue,
+            };
+            let buildfiles = cell_resolver.get(path.cell())?.buildfiles();
+            for entry in listing.included.iter() {
+                let name = entry.file_name.as_str();
+                let child = path.join(ForwardRelativePath::unchecked_new(name));
+                if entry.file_type.is_dir() {
+                    if !name.starts_with('.') {
+                        queue.push(list_dir(child));
+                    }
+                } else if entry.file_type.is_file()
+                    && (name.ends_with(".bzl")
+                        || name.ends_with(".bxl")
+                        || buildfiles
+                            .iter()
+                            .any(|buildfile| buildfile.as_str() == name))
+                {
+                    let relative_path = cell_resolver.resolve_path(child.as_ref())?;
+                    files.push(LspUrl::File(fs.resolve(&relative_path).into_path_buf()));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
 /// Store rendered starlark representations of Doc objects for builtin symbols,
 /// their names, and their real or virtual paths
 struct DocsCache {
@@ -174,6 +260,8 @@ struct DocsCache {
     global_urls: HashMap<String, LspUrl>,
     /// Mapping of starlark: urls to a synthesized starlark representation.
     native_starlark_files: HashMap<LspUrl, String>,
+    /// Mapping of global names to their documentation, for functions and properties.
+    global_docs: HashMap<String, DocMember>,
 }
 
 #[derive(buck2_error::Error, Debug)]
@@ -243,7 +331,18 @@ impl DocsCache {
     ) -> anyhow::Result<Self> {
         let mut global_urls = HashMap::with_capacity(builtin_symbols.len());
         let mut native_starlark_files = HashMap::new();
+        let mut global_docs = HashMap::new();
         for doc in builtin_symbols {
+            match &doc.item {
+                DocItem::Function(f) => {
+                    global_docs.insert(doc.id.name.clone(), DocMember::Function(f.clone()));
+                }
+                DocItem::Property(p) => {
+                    global_docs.insert(doc.id.name.clone(), DocMember::Property(p.clone()));
+                }
+                DocItem::Module(_) | DocItem::Object(_) => {}
+            }
+
             let url = match &doc.id.location {
                 Some(l) => location_lookup(l).await?,
                 None => {
@@ -275,6 +374,7 @@ impl DocsCache {
         Ok(Self {
             global_urls,
             native_starlark_files,
+            global_docs,
         })
     }
 
@@ -287,6 +387,10 @@ impl DocsCache {
     fn url_for_symbol(&self, symbol: &str) -> Option<&LspUrl> {
         self.global_urls.get(symbol)
     }
+
+    fn docs_for_symbol(&self, symbol: &str) -> Option<&DocMember> {
+        self.global_docs.get(symbol)
+    }
 }
 
 #[derive(Debug, buck2_error::Error)]
@@ -368,6 +472,7 @@ struct BuckLspContext<'a> {
     server_ctx: &'a dyn ServerCommandContextTrait,
     fs: ProjectRoot,
     docs_cache_manager: DocsCacheManager,
+    workspace_files_cache: WorkspaceFilesCache,
     runtime: Handle,
 }
 
@@ -396,6 +501,7 @@ impl<'a> BuckLspContext<'a> {
             server_ctx,
             fs,
             docs_cache_manager,
+            workspace_files_cache: WorkspaceFilesCache::new(),
             runtime: Handle::current(),
         })
     }
@@ -730,6 +836,17 @@ impl<'a> LspContext for BuckLspContext<'a> {
             }))
     }
 
+    fn get_workspace_files(&self, _workspace_root: Option<&Path>) -> anyhow::Result<Vec<LspUrl>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                self.with_dice_ctx(|dice_ctx| async {
+                    self.workspace_files_cache.get(&self.fs, dice_ctx).await
+                })
+                .await
+            }))
+    }
+
     fn get_url_for_global_symbol(
         &self,
         _current_file: &LspUrl,
@@ -747,6 +864,23 @@ impl<'a> LspContext for BuckLspContext<'a> {
             }))
     }
 
+    fn get_global_symbol_docs(
+        &self,
+        _current_file: &LspUrl,
+        symbol: &str,
+    ) -> anyhow::Result<Option<DocMember>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                let docs_cache = self
+                    .with_dice_ctx(|dice_ctx| async {
+                        self.docs_cache_manager.get_cache(dice_ctx).await
+                    })
+                    .await?;
+                Ok(docs_cache.docs_for_symbol(symbol).cloned())
+            }))
+    }
+
     fn render_as_load(
         &self,
         _target: &LspUrl,
@@ -922,6 +1056,7 @@ mod test {
     use starlark::docs::Doc;
     use starlark::docs::DocFunction;
     use starlark::docs::DocItem;
+    use starlark::docs::DocMember;
     use starlark::docs::Identifier;
     use starlark::docs::Location;
     use starlark_lsp::server::LspUrl;
@@ -987,6 +1122,10 @@ mod test {
             &LspUrl::try_from(Url::parse("file:/usr/local/dir/prelude.bzl")?)?,
             cache.url_for_symbol("prelude_function").unwrap()
         );
+        assert_eq!(
+            Some(&DocMember::Function(DocFunction::default())),
+            cache.docs_for_symbol("prelude_function")
+        );
 
         Ok(())
     }
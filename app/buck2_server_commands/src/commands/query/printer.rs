@@ -39,6 +39,8 @@ use serde::Serialize;
 use serde::Serializer;
 
 use crate::commands::query::QueryCommandError;
+use crate::dot::graphml::GraphMl;
+use crate::dot::mermaid::Mermaid;
 use crate::dot::targets::DotTargetGraph;
 use crate::dot::Dot;
 use crate::dot::DotCompact;
@@ -291,6 +293,16 @@ impl<'a> QueryResultPrinter<'a> {
                                     value: &files,
                                 },
                             )?,
+                            QueryEvaluationValue::TargetSetList(sets) => seq.serialize_entry(
+                                &arg,
+                                &target_set_list_json_printer(
+                                    target_call_stacks,
+                                    print_providers,
+                                    &self.attributes,
+                                    &sets,
+                                )
+                                .await?,
+                            )?,
                         },
                         Err(e) => {
                             seq.serialize_entry(
@@ -327,22 +339,36 @@ impl<'a> QueryResultPrinter<'a> {
         print_providers: ShouldPrintProviders<'b, T>,
     ) -> anyhow::Result<()> {
         match result {
-            QueryEvaluationValue::TargetSet(targets) => match self.output_format {
+            QueryEvaluationValue::TargetSet(targets) => {
+                self.print_target_set(&mut output, targets, call_stack, print_providers)
+                    .await?
+            }
+            QueryEvaluationValue::TargetSetList(sets) => match self.output_format {
                 QueryOutputFormat::Default => {
-                    for target in
-                        printable_targets(&targets, print_providers, &self.attributes, call_stack)
-                            .await?
-                    {
-                        writeln!(&mut output, "{}", target)?;
+                    // Separate the sets with an empty line.
+                    for (i, targets) in sets.iter().enumerate() {
+                        if i != 0 {
+                            writeln!(&mut output)?;
+                        }
+                        for target in printable_targets(
+                            targets,
+                            print_providers,
+                            &self.attributes,
+                            call_stack,
+                        )
+                        .await?
+                        {
+                            writeln!(&mut output, "{}", target)?;
+                        }
                     }
                 }
                 QueryOutputFormat::Json => {
                     let mut ser = serde_json::Serializer::pretty(&mut output);
-                    TargetSetJsonPrinter::new(
+                    target_set_list_json_printer(
                         call_stack,
                         print_providers,
                         &self.attributes,
-                        &targets,
+                        &sets,
                     )
                     .await?
                     .serialize(&mut ser)?;
@@ -350,23 +376,16 @@ impl<'a> QueryResultPrinter<'a> {
                     // need to add a newline to flush the output.
                     writeln!(&mut output)?
                 }
-                QueryOutputFormat::Dot => {
-                    Dot::render(
-                        &DotTargetGraph {
-                            targets,
-                            attributes: self.attributes.clone(),
-                        },
-                        &mut output,
-                    )?;
-                }
-                QueryOutputFormat::DotCompact => {
-                    DotCompact::render(
-                        &DotTargetGraph {
-                            targets,
-                            attributes: self.attributes.clone(),
-                        },
+                // Graphs show how the targets of the sets depend on each other anyway, so draw
+                // them all in one.
+                _ => {
+                    self.print_target_set(
                         &mut output,
-                    )?;
+                        TargetSet::union_all(sets),
+                        call_stack,
+                        print_providers,
+                    )
+                    .await?
                 }
             },
             QueryEvaluationValue::FileSet(files) => {
@@ -400,12 +419,96 @@ impl<'a> QueryResultPrinter<'a> {
                     QueryOutputFormat::DotCompact => {
                         unimplemented!("dot_compact output for files not implemented yet")
                     }
+                    QueryOutputFormat::Graphml => {
+                        return Err(QueryCommandError::FileSetAsGraph("graphml").into());
+                    }
+                    QueryOutputFormat::Mermaid => {
+                        return Err(QueryCommandError::FileSetAsGraph("mermaid").into());
+                    }
                 }
             }
         }
 
         Ok(())
     }
+
+    async fn print_target_set<'b, T: QueryTarget, W: std::io::Write>(
+        &self,
+        mut output: W,
+        targets: TargetSet<T>,
+        call_stack: bool,
+        print_providers: ShouldPrintProviders<'b, T>,
+    ) -> anyhow::Result<()> {
+        match self.output_format {
+            QueryOutputFormat::Default => {
+                for target in
+                    printable_targets(&targets, print_providers, &self.attributes, call_stack)
+                        .await?
+                {
+                    writeln!(&mut output, "{}", target)?;
+                }
+            }
+            QueryOutputFormat::Json => {
+                let mut ser = serde_json::Serializer::pretty(&mut output);
+                TargetSetJsonPrinter::new(call_stack, print_providers, &self.attributes, &targets)
+                    .await?
+                    .serialize(&mut ser)?;
+                std::mem::drop(ser);
+                // need to add a newline to flush the output.
+                writeln!(&mut output)?
+            }
+            QueryOutputFormat::Dot => {
+                Dot::render(
+                    &DotTargetGraph {
+                        targets,
+                        attributes: self.attributes.clone(),
+                    },
+                    &mut output,
+                )?;
+            }
+            QueryOutputFormat::DotCompact => {
+                DotCompact::render(
+                    &DotTargetGraph {
+                        targets,
+                        attributes: self.attributes.clone(),
+                    },
+                    &mut output,
+                )?;
+            }
+            QueryOutputFormat::Graphml => {
+                GraphMl::render(
+                    &DotTargetGraph {
+                        targets,
+                        attributes: self.attributes.clone(),
+                    },
+                    &mut output,
+                )?;
+            }
+            QueryOutputFormat::Mermaid => {
+                Mermaid::render(
+                    &DotTargetGraph {
+                        targets,
+                        attributes: self.attributes.clone(),
+                    },
+                    &mut output,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prints each set as its own JSON value, in a list.
+async fn target_set_list_json_printer<'a, T: QueryTarget>(
+    target_call_stacks: bool,
+    print_providers: ShouldPrintProviders<'a, T>,
+    attributes: &'a Option<RegexSet>,
+    sets: &'a [TargetSet<T>],
+) -> anyhow::Result<Vec<TargetSetJsonPrinter<'a, T>>> {
+    futures::future::try_join_all(sets.iter().map(|targets| {
+        TargetSetJsonPrinter::new(target_call_stacks, print_providers, attributes, targets)
+    }))
+    .await
 }
 
 async fn printable_targets<'a, T: QueryTarget>(
@@ -442,7 +545,7 @@ async fn print_action_node(
     output_attributes: &[String],
     cell_resolver: &CellResolver,
 ) -> anyhow::Result<()> {
-    // Dot/DotCompact output format don't make sense here.
+    // Graph output formats don't make sense here.
     let unstable_output_format = if json {
         QueryOutputFormat::Json
     } else {
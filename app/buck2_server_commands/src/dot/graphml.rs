@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Writing GraphML files (see <http://graphml.graphdrawing.org/specification.html>),
+//! which tools like yEd and Gephi can load along with the node attributes.
+
+use std::io::Write;
+
+use buck2_util::xml::escape_xml;
+use starlark_map::small_set::SmallSet;
+
+use crate::dot::DotDigraph;
+use crate::dot::DotNode;
+
+/// Key of the node label, which is always the node id. It is declared first, as `d0`.
+const LABEL_KEY: &str = "label";
+
+pub struct GraphMl {}
+
+impl GraphMl {
+    pub fn render<'a, T: DotDigraph<'a>, W: Write>(graph: &'a T, mut w: W) -> anyhow::Result<()> {
+        // Every data key must be declared before the graph, so collect the whole graph first.
+        let mut keys = SmallSet::new();
+        keys.insert(LABEL_KEY.to_owned());
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        graph.for_each_node(|node| {
+            let mut attrs = node.attrs()?;
+            // An attribute with the same name as the label would share its key, and so give the
+            // node two labels. Skip it.
+            attrs.extra.remove(LABEL_KEY);
+            for key in attrs.extra.keys() {
+                keys.insert(key.clone());
+            }
+            nodes.push((node.id(), attrs.extra));
+            graph.for_each_edge(node, |edge| {
+                edges.push((edge.from.to_owned(), edge.to.to_owned()));
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        for (i, key) in keys.iter().enumerate() {
+            writeln!(
+                w,
+                r#"  <key id="d{}" for="node" attr.name="{}" attr.type="string"/>"#,
+                i,
+                escape_xml(key)
+            )?;
+        }
+        writeln!(
+            w,
+            r#"  <graph id="{}" edgedefault="directed">"#,
+            escape_xml(graph.name())
+        )?;
+        for (id, extra) in &nodes {
+            let id = escape_xml(id);
+            writeln!(w, r#"    <node id="{}">"#, id)?;
+            writeln!(w, r#"      <data key="d0">{}</data>"#, id)?;
+            for (key, value) in extra {
+                // Every key was inserted above.
+                let i = keys.get_index_of(key).unwrap();
+                writeln!(
+                    w,
+                    r#"      <data key="d{}">{}</data>"#,
+                    i,
+                    escape_xml(value)
+                )?;
+            }
+            writeln!(w, "    </node>")?;
+        }
+        for (from, to) in &edges {
+            writeln!(
+                w,
+                r#"    <edge source="{}" target="{}"/>"#,
+                escape_xml(from),
+                escape_xml(to)
+            )?;
+        }
+        writeln!(w, "  </graph>")?;
+        writeln!(w, "</graphml>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dot::graphml::GraphMl;
+    use crate::dot::testing::TestGraph;
+
+    #[test]
+    fn test_render() {
+        let mut out = Vec::new();
+        GraphMl::render(&TestGraph::new(), &mut out).unwrap();
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="label" attr.type="string"/>
+  <key id="d1" for="node" attr.name="buck_type" attr.type="string"/>
+  <graph id="test_graph" edgedefault="directed">
+    <node id="//a:a">
+      <data key="d0">//a:a</data>
+      <data key="d1">&lt;a&gt; &amp; &quot;b&quot;</data>
+    </node>
+    <node id="//b:b">
+      <data key="d0">//b:b</data>
+    </node>
+    <edge source="//a:a" target="//b:b"/>
+  </graph>
+</graphml>
+"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Writing Mermaid flowcharts (see <https://mermaid.js.org/syntax/flowchart.html>),
+//! which render directly when pasted into Markdown documents.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::dot::DotDigraph;
+use crate::dot::DotNode;
+
+/// Escape text for use in a double-quoted Mermaid label, using Mermaid's entity codes.
+fn escape_label(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            // Mermaid decodes HTML entities, so `&` is escaped too.
+            '&' => res.push_str("#amp;"),
+            '#' => res.push_str("#35;"),
+            '"' => res.push_str("#quot;"),
+            '<' => res.push_str("#lt;"),
+            '>' => res.push_str("#gt;"),
+            '\n' => res.push_str("<br>"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+pub struct Mermaid {}
+
+impl Mermaid {
+    pub fn render<'a, T: DotDigraph<'a>, W: Write>(graph: &'a T, mut w: W) -> anyhow::Result<()> {
+        writeln!(w, "flowchart LR")?;
+
+        // Target labels are not valid Mermaid ids, so number the nodes.
+        let mut next_id: u32 = 0;
+        let mut ids: HashMap<String, u32> = HashMap::new();
+        let mut node_id = |name: &str| -> String {
+            let id = match ids.entry(name.to_owned()) {
+                Entry::Vacant(entry) => {
+                    next_id += 1;
+                    *entry.insert(next_id)
+                }
+                Entry::Occupied(entry) => *entry.get(),
+            };
+            format!("n{}", id)
+        };
+
+        graph.for_each_node(|node| {
+            let attrs = node.attrs()?;
+            // Mermaid nodes have no data, so attributes are shown as extra lines of the label.
+            let mut label = escape_label(&node.id());
+            for (key, value) in &attrs.extra {
+                label.push_str("<br>");
+                label.push_str(&escape_label(&format!("{} = {}", key, value)));
+            }
+            writeln!(w, "  {}[\"{}\"]", node_id(&node.id()), label)?;
+            graph.for_each_edge(node, |edge| {
+                writeln!(w, "  {} --> {}", node_id(edge.from), node_id(edge.to))?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dot::mermaid::escape_label;
+    use crate::dot::mermaid::Mermaid;
+    use crate::dot::testing::TestGraph;
+
+    #[test]
+    fn test_render() {
+        let mut out = Vec::new();
+        Mermaid::render(&TestGraph::new(), &mut out).unwrap();
+        assert_eq!(
+            r#"flowchart LR
+  n1["//a:a<br>buck_type = #lt;a#gt; #amp; #quot;b#quot;"]
+  n1 --> n2
+  n2["//b:b<br>label = b"]
+"#,
+            String::from_utf8(out).unwrap()
+        );
+
+        // Text that already looks like an entity is kept as is.
+        assert_eq!("#amp;lt; #35;quot;", escape_label("&lt; #quot;"));
+    }
+}
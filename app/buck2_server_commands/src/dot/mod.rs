@@ -28,6 +28,8 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use starlark_map::small_map::SmallMap;
 
+pub mod graphml;
+pub mod mermaid;
 pub mod targets;
 
 #[derive(Default, Debug)]
@@ -175,3 +177,86 @@ impl DotCompact {
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use starlark_map::small_map::SmallMap;
+
+    use crate::dot::DotDigraph;
+    use crate::dot::DotEdge;
+    use crate::dot::DotNode;
+    use crate::dot::DotNodeAttrs;
+
+    pub(crate) struct TestNode {
+        id: &'static str,
+        extra: Vec<(&'static str, &'static str)>,
+        deps: Vec<&'static str>,
+    }
+
+    impl DotNode for TestNode {
+        fn attrs(&self) -> anyhow::Result<DotNodeAttrs> {
+            Ok(DotNodeAttrs {
+                extra: self
+                    .extra
+                    .iter()
+                    .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                    .collect::<SmallMap<_, _>>(),
+                ..DotNodeAttrs::default()
+            })
+        }
+
+        fn id(&self) -> String {
+            self.id.to_owned()
+        }
+    }
+
+    /// `//a:a` depends on `//b:b`, and has an attribute needing escaping. `//b:b` has an
+    /// attribute named like the label some formats give every node.
+    pub(crate) struct TestGraph(Vec<TestNode>);
+
+    impl TestGraph {
+        pub(crate) fn new() -> Self {
+            Self(vec![
+                TestNode {
+                    id: "//a:a",
+                    extra: vec![("buck_type", "<a> & \"b\"")],
+                    deps: vec!["//b:b"],
+                },
+                TestNode {
+                    id: "//b:b",
+                    extra: vec![("label", "b")],
+                    deps: Vec::new(),
+                },
+            ])
+        }
+    }
+
+    impl<'a> DotDigraph<'a> for TestGraph {
+        type Node = TestNode;
+
+        fn name(&self) -> &str {
+            "test_graph"
+        }
+
+        fn for_each_node<F: FnMut(&Self::Node) -> anyhow::Result<()>>(
+            &'a self,
+            f: F,
+        ) -> anyhow::Result<()> {
+            self.0.iter().try_for_each(f)
+        }
+
+        fn for_each_edge<F: FnMut(&DotEdge) -> anyhow::Result<()>>(
+            &'a self,
+            node: &Self::Node,
+            mut f: F,
+        ) -> anyhow::Result<()> {
+            for dep in &node.deps {
+                f(&DotEdge {
+                    from: node.id,
+                    to: dep,
+                })?;
+            }
+            Ok(())
+        }
+    }
+}
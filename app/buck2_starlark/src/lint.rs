@@ -110,6 +110,7 @@ async fn lint_file(
                 severity: EvalSeverity::Error,
                 problem: format!("{:#}", message),
                 original: "".to_owned(),
+                fix: None,
             }])
         }
     }
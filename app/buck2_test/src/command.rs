@@ -9,6 +9,9 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -37,6 +40,7 @@ use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::abs_path::AbsPath;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::package::PackageLabel;
 use buck2_core::pattern::pattern_type::ConfiguredProvidersPatternExtra;
@@ -63,7 +67,9 @@ use buck2_server_ctx::pattern::target_platform_from_client_context;
 use buck2_server_ctx::template::run_server_command;
 use buck2_server_ctx::template::ServerCommandTemplate;
 use buck2_server_ctx::test_command::TEST_COMMAND;
+use buck2_test_api::data::TestNameFilter;
 use buck2_test_api::data::TestResult;
+use buck2_test_api::data::TestShard;
 use buck2_test_api::data::TestStatus;
 use buck2_test_api::protocol::TestExecutor;
 use dice::DiceComputations;
@@ -81,6 +87,7 @@ use indexmap::indexset;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use more_futures::cancellation::CancellationContext;
+use regex::Regex;
 use serde::Serialize;
 
 use crate::downward_api::BuckTestDownwardApi;
@@ -90,10 +97,26 @@ use crate::executor_launcher::OutOfProcessTestExecutor;
 use crate::local_resource_registry::LocalResourceRegistry;
 use crate::orchestrator::BuckTestOrchestrator;
 use crate::orchestrator::ExecutorMessage;
+use crate::report::write_json_lines;
+use crate::report::write_junit;
+use crate::report::TestResultRecord;
 use crate::session::TestSession;
 use crate::session::TestSessionOptions;
 use crate::translations::build_configured_target_handle;
 
+#[derive(Debug, buck2_error::Error)]
+enum TestShardError {
+    #[buck2(user)]
+    #[error("The shard count must be at least 1")]
+    ZeroShardCount,
+    #[buck2(user)]
+    #[error("Shard index {0} is out of range for {1} shards")]
+    IndexOutOfRange(u32, u32),
+    #[buck2(user)]
+    #[error("A shard index and a shard count must be passed together")]
+    Incomplete,
+}
+
 #[derive(Debug, Serialize)]
 pub(crate) struct TestReport {
     project_root: AbsNormPathBuf,
@@ -105,6 +128,8 @@ struct TestOutcome {
     executor_report: ExecutorReport,
     executor_stdout: String,
     executor_stderr: String,
+    /// Every result reported, if they were requested.
+    results: Vec<TestResultRecord>,
 }
 
 impl TestOutcome {
@@ -126,13 +151,20 @@ struct ExecutorReport {
     exit_code: Option<i32>,
     statuses: TestStatuses,
     info_messages: Vec<String>,
+    /// Whether to keep a record of every result in `results`, rather than just counting them.
+    keep_results: bool,
+    results: Vec<TestResultRecord>,
 }
 
 impl ExecutorReport {
-    fn ingest(&mut self, status: &ExecutorMessage) {
+    fn ingest(&mut self, status: &ExecutorMessage, session: &TestSession) -> anyhow::Result<()> {
         match status {
             ExecutorMessage::TestResult(res) => {
                 self.statuses.ingest(res);
+                if self.keep_results {
+                    let target = session.get(res.target)?.to_string();
+                    self.results.push(TestResultRecord::new(target, res));
+                }
             }
             ExecutorMessage::ExitCode(exit_code) => {
                 self.exit_code = Some(*exit_code);
@@ -141,6 +173,7 @@ impl ExecutorReport {
                 self.info_messages.push(message.clone());
             }
         }
+        Ok(())
     }
 }
 
@@ -185,6 +218,8 @@ struct TestStatuses {
     fatals: CounterWithExamples,
     listing_success: CounterWithExamples,
     listing_failed: CounterWithExamples,
+    flaky: CounterWithExamples,
+    reruns: CounterWithExamples,
 }
 impl TestStatuses {
     fn ingest(&mut self, result: &TestResult) {
@@ -196,9 +231,10 @@ impl TestStatuses {
             TestStatus::FATAL => self.fatals.add(&result.name),
             TestStatus::TIMEOUT => self.failed.add(&result.name),
             TestStatus::UNKNOWN => {}
-            TestStatus::RERUN => {}
+            TestStatus::RERUN => self.reruns.add(&result.name),
             TestStatus::LISTING_SUCCESS => self.listing_success.add(&result.name),
             TestStatus::LISTING_FAILED => self.listing_failed.add(&result.name),
+            TestStatus::FLAKY => self.flaky.add(&result.name),
         }
     }
 }
@@ -277,6 +313,7 @@ async fn test(
         .await?
         .filter(|s| !s.is_empty());
 
+    let use_internal_runner = test_executor_config.is_none();
     let (test_executor, test_executor_args) = match test_executor_config {
         Some(config) => {
             let test_executor = post_process_test_executor(config.as_ref())
@@ -292,6 +329,19 @@ async fn test(
         }
     };
 
+    // The internal test runner takes its default retry count from the config. It goes first so
+    // that a `--retries` passed after `--` on the command line wins.
+    let mut external_runner_args = Vec::new();
+    if use_internal_runner {
+        let retries: Option<usize> = ctx
+            .parse_legacy_config_property(cell_resolver.root_cell(), "test", "retries")
+            .await?;
+        if let Some(retries) = retries {
+            external_runner_args.push(format!("--retries={}", retries));
+        }
+    }
+    external_runner_args.extend(request.test_executor_args.iter().cloned());
+
     let parsed_patterns =
         parse_patterns_from_cli_args(&mut ctx, &request.target_patterns, cwd).await?;
     server_ctx.log_target_pattern(&parsed_patterns);
@@ -310,11 +360,14 @@ async fn test(
         .as_ref()
         .context("Missing `options`")?;
 
-    let session = TestSession::new(TestSessionOptions {
-        allow_re: options.allow_re,
-        force_use_project_relative_paths: options.force_use_project_relative_paths,
-        force_run_from_project_root: options.force_run_from_project_root,
-    });
+    let session = TestSession::new(
+        TestSessionOptions {
+            allow_re: options.allow_re,
+            force_use_project_relative_paths: options.force_use_project_relative_paths,
+            force_run_from_project_root: options.force_run_from_project_root,
+        },
+        test_name_filter(request)?,
+    );
 
     let build_opts = request
         .build_opts
@@ -324,7 +377,7 @@ async fn test(
         ctx,
         resolved_pattern,
         global_target_platform,
-        request.test_executor_args.clone(),
+        external_runner_args,
         Arc::new(TestLabelFiltering::new(
             request.included_labels.clone(),
             request.excluded_labels.clone(),
@@ -337,9 +390,23 @@ async fn test(
         working_dir_cell,
         build_opts.skip_incompatible_targets,
         MissingTargetBehavior::from_skip(build_opts.skip_missing_targets),
+        !request.junit_output.is_empty() || !request.json_lines_output.is_empty(),
     )
     .await?;
 
+    if !request.junit_output.is_empty() {
+        write_results(&request.junit_output, &test_outcome.results, |r, w| {
+            write_junit(r, w)
+        })
+        .context("Error writing JUnit test results")?;
+    }
+    if !request.json_lines_output.is_empty() {
+        write_results(&request.json_lines_output, &test_outcome.results, |r, w| {
+            write_json_lines(r, w)
+        })
+        .context("Error writing JSON lines test results")?;
+    }
+
     // TODO(bobyf) remap exit code for buck reserved exit code
     let exit_code = test_outcome.exit_code().context("No exit code available")?;
 
@@ -386,6 +453,20 @@ async fn test(
                 .listing_failed
                 .to_cli_proto_counter(),
         ),
+        flaky: Some(
+            test_outcome
+                .executor_report
+                .statuses
+                .flaky
+                .to_cli_proto_counter(),
+        ),
+        reruns: Some(
+            test_outcome
+                .executor_report
+                .statuses
+                .reruns
+                .to_cli_proto_counter(),
+        ),
     };
 
     Ok(TestResponse {
@@ -410,6 +491,7 @@ async fn test_targets(
     working_dir_cell: CellName,
     skip_incompatible_targets: bool,
     missing_target_behavior: MissingTargetBehavior,
+    keep_results: bool,
 ) -> anyhow::Result<TestOutcome> {
     let session = Arc::new(session);
     let (liveliness_observer, _guard) = LivelinessGuard::create();
@@ -450,6 +532,7 @@ async fn test_targets(
 
     let test_server = tokio::spawn({
         let test_status_sender = test_status_sender.clone();
+        let session = session.dupe();
         with_dispatcher_async(
             ctx.per_transaction_data().get_dispatcher().dupe(),
             // NOTE: This is will cancel if the liveliness guard indicates we should.
@@ -510,10 +593,15 @@ async fn test_targets(
                 // Wait for the tests to finish running.
 
                 let test_statuses = test_status_receiver
-                    .try_fold(ExecutorReport::default(), |mut acc, result| {
-                        acc.ingest(&result);
-                        future::ready(Ok(acc))
-                    })
+                    .try_fold(
+                        ExecutorReport {
+                            keep_results,
+                            ..ExecutorReport::default()
+                        },
+                        |mut acc, result| {
+                            future::ready(acc.ingest(&result, &session).map(|()| acc))
+                        },
+                    )
                     .await
                     .context("Did not receive all results from executor")?;
 
@@ -556,10 +644,12 @@ async fn test_targets(
     )));
 
     // TODO(bobyf, torozco) we can use cancellation handle here instead of liveliness observer
-    let (build_errors, executor_report) = test_server
+    let (build_errors, mut executor_report) = test_server
         .await
         .context("Failed to collect executor report")??;
 
+    let results = std::mem::take(&mut executor_report.results);
+
     let errors = build_errors
         .iter()
         .map(create_error_report)
@@ -571,6 +661,7 @@ async fn test_targets(
         executor_stdout: executor_output.stdout,
         executor_stderr: executor_output.stderr,
         executor_report,
+        results,
     })
 }
 
@@ -923,7 +1014,12 @@ fn run_tests<'a, 'b>(
 
     match maybe_handle {
         Ok(handle) => {
-            let fut = test_info.dispatch(handle, test_executor, working_dir_cell);
+            let fut = test_info.dispatch(
+                handle,
+                test_executor,
+                working_dir_cell,
+                session.test_filter().clone(),
+            );
 
             (async move {
                 fut.await
@@ -998,6 +1094,44 @@ impl TestLabelFiltering {
     }
 }
 
+/// Write the results to `path`, which the client resolved to an absolute path.
+fn write_results(
+    path: &str,
+    results: &[TestResultRecord],
+    write: impl FnOnce(&[TestResultRecord], &mut dyn Write) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(fs_util::create_file(AbsPath::new(Path::new(path))?)?);
+    write(results, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// The filter on test names to pass to the test runner, checking it is valid up front so a
+/// mistake fails the command rather than every test.
+fn test_name_filter(request: &TestRequest) -> anyhow::Result<TestNameFilter> {
+    for regex in request
+        .included_test_names
+        .iter()
+        .chain(&request.excluded_test_names)
+    {
+        Regex::new(regex).with_context(|| format!("Invalid test name regex `{}`", regex))?;
+    }
+    let shard = match (request.shard_index, request.shard_count) {
+        (None, None) => None,
+        (Some(_), Some(0)) => return Err(TestShardError::ZeroShardCount.into()),
+        (Some(index), Some(count)) if index >= count => {
+            return Err(TestShardError::IndexOutOfRange(index, count).into());
+        }
+        (Some(index), Some(count)) => Some(TestShard { index, count }),
+        _ => return Err(TestShardError::Incomplete.into()),
+    };
+    Ok(TestNameFilter {
+        include: request.included_test_names.clone(),
+        exclude: request.excluded_test_names.clone(),
+        shard,
+    })
+}
+
 fn post_process_test_executor(s: &str) -> anyhow::Result<PathBuf> {
     match s.split_once("$BUCK2_BINARY_DIR/") {
         Some(("", rest)) => {
@@ -1029,6 +1163,10 @@ fn post_process_test_executor(s: &str) -> anyhow::Result<PathBuf> {
 
 #[cfg(test)]
 mod tests {
+    use buck2_cli_proto::TestRequest;
+    use buck2_test_api::data::TestShard;
+
+    use crate::command::test_name_filter;
     use crate::command::TestLabelFiltering;
 
     #[test]
@@ -1097,4 +1235,26 @@ mod tests {
 
         assert!(conflicting_filter.is_excluded(vec!["include_me"]));
     }
+
+    #[test]
+    fn test_name_filter_shards() {
+        let shard = |index, count| {
+            test_name_filter(&TestRequest {
+                shard_index: index,
+                shard_count: count,
+                ..Default::default()
+            })
+            .map(|filter| filter.shard)
+        };
+
+        assert_eq!(shard(None, None).unwrap(), None);
+        assert_eq!(
+            shard(Some(1), Some(3)).unwrap(),
+            Some(TestShard { index: 1, count: 3 })
+        );
+        assert!(shard(Some(0), Some(0)).is_err());
+        assert!(shard(Some(3), Some(3)).is_err());
+        assert!(shard(Some(0), None).is_err());
+        assert!(shard(None, Some(3)).is_err());
+    }
 }
@@ -11,6 +11,7 @@
 
 use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -31,6 +32,7 @@ use buck2_build_api::interpreter::rule_defs::provider::builtin::external_runner_
 use buck2_build_api::interpreter::rule_defs::provider::builtin::external_runner_test_info::TestCommandMember;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::events::HasEvents;
+use buck2_common::file_ops::FileDigest;
 use buck2_common::liveliness_observer::LivelinessObserver;
 use buck2_common::local_resource_state::LocalResourceState;
 use buck2_core::cells::cell_root_path::CellRootPathBuf;
@@ -63,6 +65,7 @@ use buck2_execute::artifact::fs::ExecutorFs;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::digest_config::HasDigestConfig;
 use buck2_execute::execute::blocking::HasBlockingExecutor;
+use buck2_execute::execute::cache_uploader::CacheUploadInfo;
 use buck2_execute::execute::cache_uploader::NoOpCacheUploader;
 use buck2_execute::execute::claim::MutexClaimManager;
 use buck2_execute::execute::command_executor::CommandExecutor;
@@ -108,6 +111,7 @@ use buck2_test_api::data::PrepareForLocalExecutionResult;
 use buck2_test_api::data::RequiredLocalResources;
 use buck2_test_api::data::TestResult;
 use buck2_test_api::protocol::TestOrchestrator;
+use dashmap::DashMap;
 use derive_more::From;
 use dice::DiceTransaction;
 use dupe::Dupe;
@@ -120,6 +124,8 @@ use indexmap::IndexSet;
 use more_futures::cancellation::CancellationContext;
 use sorted_vector_map::SortedVectorMap;
 use starlark::values::FrozenRef;
+use tokio::sync::Mutex;
+use tokio::sync::OwnedMutexGuard;
 use uuid::Uuid;
 
 use crate::local_resource_api::LocalResourcesSetupResult;
@@ -147,6 +153,9 @@ pub struct BuckTestOrchestrator<'a> {
     digest_config: DigestConfig,
     cancellations: &'a CancellationContext<'a>,
     local_resource_state_registry: Arc<LocalResourceRegistry<'a>>,
+    /// Locks on the output roots of cacheable tests that are running, see
+    /// `CacheableOutputRootLock`.
+    cacheable_output_root_locks: DashMap<ForwardRelativePathBuf, Arc<Mutex<()>>>,
 }
 
 impl<'a> BuckTestOrchestrator<'a> {
@@ -191,6 +200,7 @@ impl<'a> BuckTestOrchestrator<'a> {
             digest_config,
             cancellations,
             local_resource_state_registry,
+            cacheable_output_root_locks: DashMap::new(),
         }
     }
 
@@ -232,6 +242,7 @@ impl<'a> BuckTestOrchestrator<'a> {
                 env,
                 pre_create_dirs,
                 &test_executor.executor_fs(),
+                test_info.cacheable(),
             )
             .await?;
 
@@ -242,6 +253,7 @@ impl<'a> BuckTestOrchestrator<'a> {
             inputs,
             supports_re,
             declared_outputs,
+            cacheable_output_root,
         } = test_executable_expanded;
 
         let executor_preference = self.executor_preference(supports_re)?;
@@ -271,6 +283,14 @@ impl<'a> BuckTestOrchestrator<'a> {
             vec![]
         };
 
+        let output_root_lock = match &cacheable_output_root {
+            Some(root) => Some(
+                CacheableOutputRootLock::acquire(&self.cacheable_output_root_locks, root.clone())
+                    .await,
+            ),
+            None => None,
+        };
+
         let execution_request = self
             .create_command_execution_request(
                 cwd,
@@ -284,7 +304,9 @@ impl<'a> BuckTestOrchestrator<'a> {
                 Some(executor_preference),
                 required_resources,
             )
-            .await?;
+            .await?
+            .with_cacheable_test(test_info.cacheable())
+            .with_allow_cache_upload(test_info.cacheable());
 
         let (stdout, stderr, status, timing, execution_kind, outputs) = self
             .execute_shared(&test_target, metadata, &test_executor, execution_request)
@@ -292,17 +314,10 @@ impl<'a> BuckTestOrchestrator<'a> {
 
         self.require_alive().await?;
 
-        let (outputs, paths_to_materialize) = outputs
-            .into_iter()
-            .map(|test_path| {
-                let project_path = fs.buck_out_path_resolver().resolve_test(&test_path);
-                let abs_path = fs.fs().resolve(&project_path);
-                let declared_output = DeclaredOutput {
-                    name: test_path.into_path(),
-                };
-                ((declared_output, Output::LocalPath(abs_path)), project_path)
-            })
-            .unzip();
+        let paths_to_materialize = outputs
+            .iter()
+            .map(|test_path| fs.buck_out_path_resolver().resolve_test(test_path))
+            .collect();
 
         // Request materialization in case this ran on RE. Eventually Tpx should be able to
         // understand remote outputs but currently we don't have this.
@@ -313,6 +328,55 @@ impl<'a> BuckTestOrchestrator<'a> {
             .await
             .context("Error materializing test outputs")?;
 
+        let outputs = if output_root_lock.is_some() {
+            // The test may run again as soon as the output root is unlocked, so
+            // give the test runner a copy of the outputs that belongs to this session.
+            let session_root = self
+                .session
+                .prefix()
+                .join(ForwardRelativePathBuf::unchecked_new(
+                    Uuid::new_v4().to_string(),
+                ));
+            let copies = outputs
+                .into_iter()
+                .map(|test_path| {
+                    let copy =
+                        BuckOutTestPath::new(session_root.clone(), test_path.clone().into_path());
+                    (test_path, copy)
+                })
+                .collect::<Vec<_>>();
+            self.dice
+                .get_blocking_executor()
+                .execute_io_inline(|| {
+                    for (test_path, copy) in &copies {
+                        fs.fs().copy(
+                            &fs.buck_out_path_resolver().resolve_test(test_path),
+                            &fs.buck_out_path_resolver().resolve_test(copy),
+                        )?;
+                    }
+                    Ok(())
+                })
+                .await
+                .context("Error copying test outputs")?;
+            copies.into_iter().map(|(_, copy)| copy).collect()
+        } else {
+            outputs
+        };
+        drop(output_root_lock);
+
+        let outputs = outputs
+            .into_iter()
+            .map(|test_path| {
+                let abs_path = fs
+                    .fs()
+                    .resolve(&fs.buck_out_path_resolver().resolve_test(&test_path));
+                let declared_output = DeclaredOutput {
+                    name: test_path.into_path(),
+                };
+                (declared_output, Output::LocalPath(abs_path))
+            })
+            .collect();
+
         Ok(ExecutionResult2 {
             status,
             stdout,
@@ -452,6 +516,9 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
                 env,
                 pre_create_dirs,
                 &executor.executor_fs(),
+                // The test runner executes this command itself, so it never comes from the cache
+                // and must not share the output root of cached runs.
+                false,
             )
             .await?;
 
@@ -462,6 +529,7 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
             inputs,
             supports_re: _,
             declared_outputs,
+            cacheable_output_root: _,
         } = test_executable_expanded;
 
         let execution_request = self
@@ -563,8 +631,6 @@ impl<'b> BuckTestOrchestrator<'b> {
             action_key_suffix,
         };
 
-        // For test execution, we currently do not do any cache queries
-
         let prepared_action = executor.prepare_action(&request, self.digest_config)?;
         let prepared_command = PreparedCommand {
             target: &test_target as _,
@@ -572,7 +638,45 @@ impl<'b> BuckTestOrchestrator<'b> {
             prepared_action: &prepared_action,
             digest_config: self.digest_config,
         };
-        let command = executor.exec_cmd(manager, &prepared_command, self.cancellations);
+        // Only tests that opted into caching are given a cache checker and uploader (see
+        // `get_command_executor`), so for other tests this just executes the command.
+        let command = async {
+            let manager = match executor
+                .action_cache(manager, &prepared_command, self.cancellations)
+                .await
+            {
+                ControlFlow::Break(result) => return result,
+                ControlFlow::Continue(manager) => manager,
+            };
+
+            let mut result = executor
+                .exec_cmd(manager, &prepared_command, self.cancellations)
+                .await;
+
+            if request.cacheable_test() && result.was_success() {
+                let upload_result = executor
+                    .cache_upload(
+                        &CacheUploadInfo {
+                            target: &test_target as _,
+                            digest_config: self.digest_config,
+                        },
+                        &result,
+                        None,
+                        &prepared_action.action_and_blobs,
+                    )
+                    .await;
+                match upload_result {
+                    Ok(upload_result) => result.did_cache_upload = upload_result.did_cache_upload,
+                    Err(e) => tracing::warn!(
+                        "Error uploading the result of `{}` to the cache: {:#}",
+                        test_target.target,
+                        e
+                    ),
+                }
+            }
+
+            result
+        };
 
         // instrument execution with a span.
         // TODO(brasselsprouts): migrate this into the executor to get better accuracy.
@@ -705,6 +809,7 @@ impl<'b> BuckTestOrchestrator<'b> {
         fs: &ArtifactFs,
         test_target_node: &ConfiguredTargetNode,
         executor_override: Option<&CommandExecutorConfig>,
+        cacheable: bool,
     ) -> anyhow::Result<CommandExecutor> {
         let executor_config = match executor_override {
             Some(o) => o,
@@ -717,14 +822,23 @@ impl<'b> BuckTestOrchestrator<'b> {
         let CommandExecutorResponse {
             executor,
             platform,
-            cache_checker: _,
-            cache_uploader: _,
+            cache_checker,
+            cache_uploader,
         } = self.dice.get_command_executor(fs, executor_config)?;
+        // Tests commonly depend on things that aren't declared as inputs, so we only cache the
+        // ones that say they can be.
+        let (cache_checker, cache_uploader) = if cacheable {
+            (cache_checker, cache_uploader)
+        } else {
+            (
+                Arc::new(NoOpCommandOptionalExecutor {}) as _,
+                Arc::new(NoOpCacheUploader {}) as _,
+            )
+        };
         let executor = CommandExecutor::new(
             executor,
-            // Caching is not enabled for tests yet. Use the NoOp
-            Arc::new(NoOpCommandOptionalExecutor {}),
-            Arc::new(NoOpCacheUploader {}),
+            cache_checker,
+            cache_uploader,
             fs.clone(),
             executor_config.options,
             platform,
@@ -808,6 +922,7 @@ impl<'b> BuckTestOrchestrator<'b> {
             fs,
             &node,
             resolved_executor_override.as_ref().map(|a| &***a),
+            test_info.cacheable(),
         )
         .context("Error constructing CommandExecutor")
     }
@@ -820,13 +935,22 @@ impl<'b> BuckTestOrchestrator<'b> {
         env: SortedVectorMap<String, ArgValue>,
         pre_create_dirs: Vec<DeclaredOutput>,
         executor_fs: &ExecutorFs<'_>,
+        cacheable: bool,
     ) -> anyhow::Result<ExpandedTestExecutable> {
-        let output_root = self
-            .session
-            .prefix()
-            .join(ForwardRelativePathBuf::unchecked_new(
-                Uuid::new_v4().to_string(),
-            ));
+        let output_root = if cacheable {
+            // The output paths are part of the command, so they have to be the same on every run
+            // for the test to be found in the cache.
+            let key = cacheable_test_key(test_target, &cmd, &env);
+            let key =
+                FileDigest::from_content(key.as_bytes(), self.digest_config.cas_digest_config());
+            ForwardRelativePathBuf::unchecked_new(format!("cacheable/{}", key.raw_digest()))
+        } else {
+            self.session
+                .prefix()
+                .join(ForwardRelativePathBuf::unchecked_new(
+                    Uuid::new_v4().to_string(),
+                ))
+        };
 
         let mut declared_outputs = IndexMap::<BuckOutTestPath, OutputCreationBehavior>::new();
 
@@ -881,6 +1005,7 @@ impl<'b> BuckTestOrchestrator<'b> {
             inputs,
             declared_outputs,
             supports_re,
+            cacheable_output_root: cacheable.then_some(output_root),
         })
     }
 
@@ -923,6 +1048,8 @@ impl<'b> BuckTestOrchestrator<'b> {
             .with_working_directory(cwd)
             .with_local_environment_inheritance(EnvironmentInheritance::test_allowlist())
             .with_disable_miniperf(true)
+            // Tests routinely access files they don't declare (e.g. via resources).
+            .with_disable_sandbox(true)
             .with_required_local_resources(required_local_resources)?;
         if let Some(timeout) = timeout {
             request = request.with_timeout(timeout)
@@ -1271,6 +1398,95 @@ struct ExpandedTestExecutable {
     inputs: IndexSet<ArtifactGroup>,
     supports_re: bool,
     declared_outputs: IndexMap<BuckOutTestPath, OutputCreationBehavior>,
+    /// The output root of a cacheable test, which is the same for every run of the test.
+    cacheable_output_root: Option<ForwardRelativePathBuf>,
+}
+
+/// A key for what a cacheable test runs, which its output root is named after. Every field is
+/// written with its length, so different commands can't produce the same key.
+fn cacheable_test_key(
+    test_target: &ConfiguredProvidersLabel,
+    cmd: &[ArgValue],
+    env: &SortedVectorMap<String, ArgValue>,
+) -> String {
+    fn push_field(key: &mut String, field: &str) {
+        key.push_str(&field.len().to_string());
+        key.push(':');
+        key.push_str(field);
+    }
+
+    fn push_arg(key: &mut String, arg: &ArgValue) {
+        match &arg.content {
+            ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(v)) => {
+                push_field(key, "verbatim");
+                push_field(key, v);
+            }
+            ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::ArgHandle(h)) => {
+                push_field(key, "arg");
+                push_field(key, &h.0.to_string());
+            }
+            ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::EnvHandle(h)) => {
+                push_field(key, "env");
+                push_field(key, &h.0);
+            }
+            ArgValueContent::DeclaredOutput(output) => {
+                push_field(key, "output");
+                push_field(key, output.name.as_str());
+            }
+        }
+        match &arg.format {
+            Some(format) => {
+                push_field(key, "format");
+                push_field(key, format);
+            }
+            None => push_field(key, "noformat"),
+        }
+    }
+
+    let mut key = String::new();
+    push_field(&mut key, &test_target.to_string());
+    push_field(&mut key, &cmd.len().to_string());
+    for arg in cmd {
+        push_arg(&mut key, arg);
+    }
+    push_field(&mut key, &env.len().to_string());
+    for (name, arg) in env {
+        push_field(&mut key, name);
+        push_arg(&mut key, arg);
+    }
+    key
+}
+
+/// Every run of a cacheable test writes its outputs to the same path, so runs of the same test
+/// take turns using that path. The lock is held until this is dropped.
+struct CacheableOutputRootLock<'a> {
+    locks: &'a DashMap<ForwardRelativePathBuf, Arc<Mutex<()>>>,
+    root: ForwardRelativePathBuf,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl<'a> CacheableOutputRootLock<'a> {
+    async fn acquire(
+        locks: &'a DashMap<ForwardRelativePathBuf, Arc<Mutex<()>>>,
+        root: ForwardRelativePathBuf,
+    ) -> CacheableOutputRootLock<'a> {
+        let lock = locks.entry(root.clone()).or_default().dupe();
+        let guard = lock.lock_owned().await;
+        Self {
+            locks,
+            root,
+            guard: Some(guard),
+        }
+    }
+}
+
+impl Drop for CacheableOutputRootLock<'_> {
+    fn drop(&mut self) {
+        self.guard.take();
+        // Nobody else holds or waits for this lock if the map has the only reference to it.
+        self.locks
+            .remove_if(&self.root, |_, lock| Arc::strong_count(lock) == 1);
+    }
 }
 
 fn create_prepare_for_local_execution_result(
@@ -1449,7 +1665,7 @@ mod tests {
         Ok((
             BuckTestOrchestrator::from_parts(
                 dice,
-                Arc::new(TestSession::new(Default::default())),
+                Arc::new(TestSession::new(Default::default(), Default::default())),
                 NoopLivelinessObserver::create(),
                 sender,
                 EventDispatcher::null(),
@@ -0,0 +1,313 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Writing the results of a test run for other tools to ingest, either as JUnit XML or
+//! as JSON lines with one result per line.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use buck2_test_api::data::TestResult;
+use buck2_test_api::data::TestStatus;
+use buck2_util::xml::escape_xml;
+use dupe::Dupe;
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde::Serializer;
+
+/// How much test output to keep per result. Test output can be very large, and the end
+/// is usually the interesting part.
+const MAX_DETAILS_LEN: usize = 64 * 1024;
+
+/// A test result as reported through the orchestrator, with the target resolved to its label.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct TestResultRecord {
+    /// The configured label of the target the test came from.
+    pub(crate) target: String,
+    pub(crate) name: String,
+    #[serde(serialize_with = "serialize_status")]
+    pub(crate) status: TestStatus,
+    pub(crate) duration_secs: Option<f64>,
+    pub(crate) message: Option<String>,
+    /// The end of the combined stdout and stderr of the test.
+    pub(crate) details: String,
+}
+
+impl TestResultRecord {
+    /// Only the end of the details is copied, so it is cheap to keep a record of every result
+    /// even when tests produce a lot of output.
+    pub(crate) fn new(target: String, result: &TestResult) -> Self {
+        Self {
+            target,
+            name: result.name.clone(),
+            status: result.status.dupe(),
+            duration_secs: result.duration.map(|d| d.as_secs_f64()),
+            message: result.msg.clone(),
+            details: excerpt(&result.details).to_owned(),
+        }
+    }
+
+    /// The message of the result, or its status if there is none.
+    fn message(&self) -> &str {
+        self.message
+            .as_deref()
+            .unwrap_or_else(|| status_name(&self.status))
+    }
+
+    fn outcome(&self) -> Option<JUnitOutcome> {
+        match self.status {
+            TestStatus::PASS | TestStatus::FLAKY => Some(JUnitOutcome::Pass),
+            TestStatus::FAIL | TestStatus::TIMEOUT => Some(JUnitOutcome::Failure),
+            TestStatus::FATAL | TestStatus::LISTING_FAILED | TestStatus::UNKNOWN => {
+                Some(JUnitOutcome::Error)
+            }
+            TestStatus::SKIP | TestStatus::OMITTED => Some(JUnitOutcome::Skipped),
+            // Reruns are failed attempts of a test which is reported again with its final
+            // status, and successful listings are not tests.
+            TestStatus::RERUN | TestStatus::LISTING_SUCCESS => None,
+        }
+    }
+}
+
+fn status_name(status: &TestStatus) -> &'static str {
+    match status {
+        TestStatus::PASS => "PASS",
+        TestStatus::FAIL => "FAIL",
+        TestStatus::SKIP => "SKIP",
+        TestStatus::OMITTED => "OMITTED",
+        TestStatus::FATAL => "FATAL",
+        TestStatus::TIMEOUT => "TIMEOUT",
+        TestStatus::UNKNOWN => "UNKNOWN",
+        TestStatus::RERUN => "RERUN",
+        TestStatus::LISTING_SUCCESS => "LISTING_SUCCESS",
+        TestStatus::LISTING_FAILED => "LISTING_FAILED",
+        TestStatus::FLAKY => "FLAKY",
+    }
+}
+
+fn serialize_status<S: Serializer>(status: &TestStatus, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(status_name(status))
+}
+
+/// The last `MAX_DETAILS_LEN` bytes of `s`, or a little less to stay on a char boundary.
+fn excerpt(s: &str) -> &str {
+    if s.len() <= MAX_DETAILS_LEN {
+        return s;
+    }
+    let mut start = s.len() - MAX_DETAILS_LEN;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    &s[start..]
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum JUnitOutcome {
+    Pass,
+    Failure,
+    Error,
+    Skipped,
+}
+
+#[derive(Default)]
+struct JUnitCounts {
+    tests: usize,
+    failures: usize,
+    errors: usize,
+    skipped: usize,
+    time: f64,
+}
+
+impl JUnitCounts {
+    fn add(&mut self, outcome: JUnitOutcome, record: &TestResultRecord) {
+        self.tests += 1;
+        match outcome {
+            JUnitOutcome::Pass => {}
+            JUnitOutcome::Failure => self.failures += 1,
+            JUnitOutcome::Error => self.errors += 1,
+            JUnitOutcome::Skipped => self.skipped += 1,
+        }
+        self.time += record.duration_secs.unwrap_or_default();
+    }
+
+    fn attrs(&self) -> String {
+        format!(
+            r#"tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}""#,
+            self.tests, self.failures, self.errors, self.skipped, self.time
+        )
+    }
+}
+
+/// Write the results as JSON, one result per line.
+pub(crate) fn write_json_lines(
+    results: &[TestResultRecord],
+    mut w: impl Write,
+) -> anyhow::Result<()> {
+    for result in results {
+        serde_json::to_writer(&mut w, result)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Write the results as JUnit XML, with a `testsuite` per target.
+pub(crate) fn write_junit(results: &[TestResultRecord], mut w: impl Write) -> anyhow::Result<()> {
+    let mut total = JUnitCounts::default();
+    let mut suites: IndexMap<&str, (JUnitCounts, Vec<(JUnitOutcome, &TestResultRecord)>)> =
+        IndexMap::new();
+    // Failed attempts by target and test name, reported with the final result.
+    let mut reruns: HashMap<(&str, &str), Vec<&TestResultRecord>> = HashMap::new();
+    for record in results {
+        if record.status == TestStatus::RERUN {
+            reruns
+                .entry((record.target.as_str(), record.name.as_str()))
+                .or_default()
+                .push(record);
+        }
+        let Some(outcome) = record.outcome() else {
+            continue;
+        };
+        total.add(outcome, record);
+        let (counts, cases) = suites.entry(&record.target).or_default();
+        counts.add(outcome, record);
+        cases.push((outcome, record));
+    }
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, "<testsuites {}>", total.attrs())?;
+    for (target, (counts, cases)) in &suites {
+        let target = escape_xml(target);
+        writeln!(w, r#"  <testsuite name="{}" {}>"#, target, counts.attrs())?;
+        for (outcome, record) in cases {
+            writeln!(
+                w,
+                r#"    <testcase classname="{}" name="{}" time="{:.3}">"#,
+                target,
+                escape_xml(&record.name),
+                record.duration_secs.unwrap_or_default()
+            )?;
+            let message = escape_xml(record.message());
+            match outcome {
+                JUnitOutcome::Pass => {}
+                JUnitOutcome::Failure => writeln!(w, r#"      <failure message="{}"/>"#, message)?,
+                JUnitOutcome::Error => writeln!(w, r#"      <error message="{}"/>"#, message)?,
+                JUnitOutcome::Skipped => writeln!(w, "      <skipped/>")?,
+            }
+            // Earlier attempts, using the Maven Surefire elements for flaky and rerun tests.
+            let rerun_element = match outcome {
+                JUnitOutcome::Pass => Some("flakyFailure"),
+                JUnitOutcome::Failure | JUnitOutcome::Error => Some("rerunFailure"),
+                JUnitOutcome::Skipped => None,
+            };
+            if let Some(rerun_element) = rerun_element {
+                for rerun in reruns
+                    .get(&(record.target.as_str(), record.name.as_str()))
+                    .into_iter()
+                    .flatten()
+                {
+                    writeln!(
+                        w,
+                        r#"      <{} message="{}" time="{:.3}"/>"#,
+                        rerun_element,
+                        escape_xml(rerun.message()),
+                        rerun.duration_secs.unwrap_or_default()
+                    )?;
+                }
+            }
+            if !record.details.is_empty() {
+                writeln!(
+                    w,
+                    "      <system-out>{}</system-out>",
+                    escape_xml(&record.details)
+                )?;
+            }
+            writeln!(w, "    </testcase>")?;
+        }
+        writeln!(w, "  </testsuite>")?;
+    }
+    writeln!(w, "</testsuites>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(target: &str, name: &str, status: TestStatus, details: &str) -> TestResultRecord {
+        TestResultRecord {
+            target: target.to_owned(),
+            name: name.to_owned(),
+            status,
+            duration_secs: Some(0.5),
+            message: None,
+            details: details.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_write_junit() {
+        let results = [
+            record("//a:a (cfg)", "a_pass", TestStatus::PASS, ""),
+            record("//b:b (cfg)", "b_fail", TestStatus::FAIL, "expected <1>\n"),
+            record("//a:a (cfg)", "a_skip", TestStatus::SKIP, ""),
+            record("//a:a (cfg)", "a_flaky", TestStatus::RERUN, ""),
+            record("//a:a (cfg)", "a_flaky", TestStatus::FLAKY, ""),
+        ];
+        let mut out = Vec::new();
+        write_junit(&results, &mut out).unwrap();
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="4" failures="1" errors="0" skipped="1" time="2.000">
+  <testsuite name="//a:a (cfg)" tests="3" failures="0" errors="0" skipped="1" time="1.500">
+    <testcase classname="//a:a (cfg)" name="a_pass" time="0.500">
+    </testcase>
+    <testcase classname="//a:a (cfg)" name="a_skip" time="0.500">
+      <skipped/>
+    </testcase>
+    <testcase classname="//a:a (cfg)" name="a_flaky" time="0.500">
+      <flakyFailure message="RERUN" time="0.500"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="//b:b (cfg)" tests="1" failures="1" errors="0" skipped="0" time="0.500">
+    <testcase classname="//b:b (cfg)" name="b_fail" time="0.500">
+      <failure message="FAIL"/>
+      <system-out>expected &lt;1&gt;
+</system-out>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_write_json_lines() {
+        let results = [
+            record("//a:a (cfg)", "a", TestStatus::PASS, ""),
+            record("//b:b (cfg)", "b", TestStatus::FAIL, "out"),
+        ];
+        let mut out = Vec::new();
+        write_json_lines(&results, &mut out).unwrap();
+        assert_eq!(
+            r#"{"target":"//a:a (cfg)","name":"a","status":"PASS","duration_secs":0.5,"message":null,"details":""}
+{"target":"//b:b (cfg)","name":"b","status":"FAIL","duration_secs":0.5,"message":null,"details":"out"}
+"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(excerpt("abc"), "abc");
+        let long = format!("é{}", "x".repeat(MAX_DETAILS_LEN - 1));
+        // The first char straddles the cut, so is dropped whole.
+        assert_eq!(excerpt(&long), "x".repeat(MAX_DETAILS_LEN - 1));
+    }
+}
@@ -15,6 +15,7 @@ use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_test_api::data::ConfiguredTargetHandle;
+use buck2_test_api::data::TestNameFilter;
 use chrono::Local;
 use dashmap::DashMap;
 use dupe::Dupe;
@@ -41,10 +42,12 @@ pub struct TestSession {
     /// Options overriding the behavior of tests executed in this session. This is primarily
     /// intended for unstable or debugging features.
     options: TestSessionOptions,
+    /// Which tests the test runner should run, passed to it with each test spec.
+    test_filter: TestNameFilter,
 }
 
 impl TestSession {
-    pub fn new(options: TestSessionOptions) -> Self {
+    pub fn new(options: TestSessionOptions, test_filter: TestNameFilter) -> Self {
         // NOTE: This is the format that Tpx has historically used. We don't really *have* to use
         // this considering we don't even put it in the same place (we do it in ./buck-out/v2/tmp,
         // but Tpx put it in /tmp), but it's a reasonable one.
@@ -57,6 +60,7 @@ impl TestSession {
             labels: DashMap::new(),
             prefix,
             options,
+            test_filter,
         }
     }
 
@@ -64,6 +68,10 @@ impl TestSession {
         self.options
     }
 
+    pub fn test_filter(&self) -> &TestNameFilter {
+        &self.test_filter
+    }
+
     pub fn prefix(&self) -> &ForwardRelativePath {
         self.prefix.as_ref()
     }
@@ -35,10 +35,14 @@ use crate::data::ExternalRunnerSpec;
 use crate::data::ExternalRunnerSpecValue;
 use crate::data::Output;
 use crate::data::TestExecutable;
+use crate::data::TestNameFilter;
 use crate::data::TestResult;
+use crate::data::TestShard;
 use crate::data::TestStatus;
 use crate::protocol::convert::host_sharing_requirements_from_grpc;
 use crate::protocol::convert::host_sharing_requirements_to_grpc;
+use crate::protocol::convert::weight_class_from_grpc;
+use crate::protocol::convert::weight_class_to_grpc;
 
 impl TryFrom<buck2_test_proto::DisplayMetadata> for DisplayMetadata {
     type Error = anyhow::Error;
@@ -213,6 +217,7 @@ impl TryFrom<i32> for TestStatus {
             buck2_test_proto::TestStatus::Rerun => TestStatus::RERUN,
             buck2_test_proto::TestStatus::ListingSuccess => TestStatus::LISTING_SUCCESS,
             buck2_test_proto::TestStatus::ListingFailed => TestStatus::LISTING_FAILED,
+            buck2_test_proto::TestStatus::Flaky => TestStatus::FLAKY,
         })
     }
 }
@@ -232,6 +237,7 @@ impl TryInto<i32> for TestStatus {
             TestStatus::RERUN => buck2_test_proto::TestStatus::Rerun,
             TestStatus::LISTING_SUCCESS => buck2_test_proto::TestStatus::ListingSuccess,
             TestStatus::LISTING_FAILED => buck2_test_proto::TestStatus::ListingFailed,
+            TestStatus::FLAKY => buck2_test_proto::TestStatus::Flaky,
         } as i32)
     }
 }
@@ -298,6 +304,9 @@ impl TryFrom<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             contacts,
             oncall,
             working_dir_cell,
+            test_filter,
+            weight_class,
+            local_resources,
         } = s;
 
         Ok(Self {
@@ -317,6 +326,13 @@ impl TryFrom<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             contacts,
             oncall,
             working_dir_cell: CellName::unchecked_new(&working_dir_cell)?,
+            // Filtering is optional, so a missing filter runs everything.
+            test_filter: test_filter.map(Into::into).unwrap_or_default(),
+            weight_class: weight_class
+                .map(weight_class_from_grpc)
+                .transpose()
+                .context("Invalid `weight_class`")?,
+            local_resources: local_resources.into_map(|r| r.into()),
         })
     }
 }
@@ -334,6 +350,9 @@ impl TryInto<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             contacts,
             oncall,
             working_dir_cell,
+            test_filter,
+            weight_class,
+            local_resources,
         } = self;
         Ok(buck2_test_proto::ExternalRunnerSpec {
             target: Some(target.try_into().context("Invalid `target`")?),
@@ -349,6 +368,12 @@ impl TryInto<buck2_test_proto::ExternalRunnerSpec> for ExternalRunnerSpec {
             contacts,
             oncall,
             working_dir_cell: working_dir_cell.as_str().to_owned(),
+            test_filter: Some(test_filter.into()),
+            weight_class: weight_class
+                .map(weight_class_to_grpc)
+                .transpose()
+                .context("Invalid `weight_class`")?,
+            local_resources: local_resources.into_map(|r| r.into()),
         })
     }
 }
@@ -386,6 +411,44 @@ impl TryInto<buck2_test_proto::ExternalRunnerSpecValue> for ExternalRunnerSpecVa
     }
 }
 
+impl From<buck2_test_proto::TestNameFilter> for TestNameFilter {
+    fn from(f: buck2_test_proto::TestNameFilter) -> Self {
+        Self {
+            include: f.include,
+            exclude: f.exclude,
+            shard: f.shard.map(Into::into),
+        }
+    }
+}
+
+impl From<TestNameFilter> for buck2_test_proto::TestNameFilter {
+    fn from(f: TestNameFilter) -> Self {
+        Self {
+            include: f.include,
+            exclude: f.exclude,
+            shard: f.shard.map(Into::into),
+        }
+    }
+}
+
+impl From<buck2_test_proto::TestShard> for TestShard {
+    fn from(s: buck2_test_proto::TestShard) -> Self {
+        Self {
+            index: s.index,
+            count: s.count,
+        }
+    }
+}
+
+impl From<TestShard> for buck2_test_proto::TestShard {
+    fn from(s: TestShard) -> Self {
+        Self {
+            index: s.index,
+            count: s.count,
+        }
+    }
+}
+
 impl From<DeclaredOutput> for buck2_test_proto::DeclaredOutput {
     fn from(o: DeclaredOutput) -> Self {
         Self {
@@ -824,6 +887,7 @@ mod tests {
     use std::time::Duration;
 
     use host_sharing::HostSharingRequirements;
+    use host_sharing::WeightClass;
     use sorted_vector_map::sorted_vector_map;
 
     use super::*;
@@ -873,6 +937,15 @@ mod tests {
             contacts: vec!["contact1".to_owned(), "contact2".to_owned()],
             oncall: Some("contact1".to_owned()),
             working_dir_cell: CellName::testing_new("qux"),
+            test_filter: TestNameFilter {
+                include: vec!["foo".to_owned()],
+                exclude: vec!["foo_slow".to_owned()],
+                shard: Some(TestShard { index: 1, count: 3 }),
+            },
+            weight_class: Some(WeightClass::Permits(4)),
+            local_resources: vec![LocalResourceType {
+                name: "device".to_owned(),
+            }],
         };
         assert_roundtrips::<buck2_test_proto::ExternalRunnerSpec, ExternalRunnerSpec>(&test_spec);
     }
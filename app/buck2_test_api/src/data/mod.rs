@@ -25,6 +25,7 @@ pub use buck2_test_proto::ExecutionDetails;
 use derive_more::From;
 use dupe::Dupe;
 use host_sharing::HostSharingRequirements;
+use host_sharing::WeightClass;
 use sorted_vector_map::SortedVectorMap;
 
 /// A handle generated by the TestOrchestrator. It can be used by the TestExecutor to access this
@@ -112,6 +113,8 @@ pub enum TestStatus {
     RERUN,
     LISTING_SUCCESS,
     LISTING_FAILED,
+    // Failed, then passed on a retry.
+    FLAKY,
 }
 
 /// The set of information about a test rule that is passed to the test executor
@@ -135,6 +138,34 @@ pub struct ExternalRunnerSpec {
     pub oncall: Option<String>,
     /// Cell of current working directory for test command.
     pub working_dir_cell: CellName,
+    /// Which of the tests in the spec to run.
+    pub test_filter: TestNameFilter,
+    /// How heavy the test declared itself to be, if it did.
+    pub weight_class: Option<WeightClass>,
+    /// Types of the local resources the test uses, which a runner should require when
+    /// executing it.
+    pub local_resources: Vec<LocalResourceType>,
+}
+
+/// Selects tests by their names, as given to `buck2 test`. Runners that report individual
+/// test cases apply it to the name of each case.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TestNameFilter {
+    /// Regexes for the names of tests to run. All tests are run if empty.
+    pub include: Vec<String>,
+    /// Regexes for the names of tests not to run, even if included.
+    pub exclude: Vec<String>,
+    /// Only run the tests whose name hashes to this shard.
+    pub shard: Option<TestShard>,
+}
+
+/// One of several parts that a test run is split into, e.g. to spread it across machines.
+#[derive(Clone, Copy, Debug, Dupe, PartialEq, Eq)]
+pub struct TestShard {
+    /// Which shard to run, counting from zero.
+    pub index: u32,
+    /// How many shards the tests are split into.
+    pub count: u32,
 }
 
 /// Command line argument or environment variable value
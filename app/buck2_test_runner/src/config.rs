@@ -27,6 +27,23 @@ pub struct Config {
     #[clap(long, hidden = true)]
     buck_test_info: String,
 
+    /// Run each test binary once as a whole, rather than listing the test cases of binaries
+    /// using a known framework (gtest, Rust libtest or pytest) and reporting each case.
+    #[clap(long)]
+    pub no_listing: bool,
+
+    /// How many listed test cases to run in each execution of a test binary. By default, the
+    /// cases of a binary run in as few executions as the size limit of their arguments allows;
+    /// use 1 to run each case in its own process.
+    #[clap(long, parse(try_from_str=try_parse_batch_size_from_str))]
+    pub batch_size: Option<usize>,
+
+    /// How many times to retry a failing test, or failing test case when listing. A test which
+    /// passes on a retry is reported as flaky. When given more than once, the last one wins, so
+    /// this overrides the `test.retries` buckconfig.
+    #[clap(long, default_value = "0", overrides_with = "retries")]
+    pub retries: usize,
+
     /// Passthrough argments to test binary.
     /// Available as a workaround for when test features are available.
     #[clap(long, multiple = true, allow_hyphen_values = true)]
@@ -70,3 +87,13 @@ fn try_parse_timeout_from_str(input: &str) -> anyhow::Result<Duration> {
     let seconds = input.parse().context("Could not parse provided timeout")?;
     Ok(Duration::from_secs(seconds))
 }
+
+fn try_parse_batch_size_from_str(input: &str) -> anyhow::Result<usize> {
+    let size = input
+        .parse()
+        .context("Could not parse provided batch size")?;
+    if size == 0 {
+        return Err(anyhow::anyhow!("Batch size must be at least 1"));
+    }
+    Ok(size)
+}
@@ -0,0 +1,106 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Choosing which tests to run by name, as asked for with each test spec.
+
+use std::hash::Hasher;
+
+use anyhow::Context;
+use buck2_test_api::data::TestNameFilter;
+use buck2_test_api::data::TestShard;
+use fnv::FnvHasher;
+use regex::RegexSet;
+
+/// A `TestNameFilter` with its regexes compiled.
+pub(crate) struct TestFilter {
+    /// Tests must match one of these to run, unless there are none.
+    include: RegexSet,
+    exclude: RegexSet,
+    shard: Option<TestShard>,
+}
+
+impl TestFilter {
+    pub(crate) fn new(filter: &TestNameFilter) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: RegexSet::new(&filter.include).context("Invalid included test name")?,
+            exclude: RegexSet::new(&filter.exclude).context("Invalid excluded test name")?,
+            shard: filter.shard,
+        })
+    }
+
+    /// Why the test with the given name should not run, or `None` if it should.
+    pub(crate) fn exclusion(&self, name: &str) -> Option<String> {
+        if !self.include.is_empty() && !self.include.is_match(name) {
+            return Some("Not matched by an included test name".to_owned());
+        }
+        if self.exclude.is_match(name) {
+            return Some("Matched by an excluded test name".to_owned());
+        }
+        if let Some(TestShard { index, count }) = self.shard {
+            let shard = shard_of(name, count);
+            if shard != index {
+                return Some(format!(
+                    "Belongs to shard {}, but shard {} of {} is being run",
+                    shard, index, count
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// The shard a test belongs to. Names are hashed with FNV-1a, which is defined independently
+/// of the Rust version and platform, so every machine running a shard agrees on its tests.
+fn shard_of(name: &str, count: u32) -> u32 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(name.as_bytes());
+    (hasher.finish() % u64::from(count)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str], shard: Option<TestShard>) -> TestFilter {
+        TestFilter::new(&TestNameFilter {
+            include: include.iter().map(|x| (*x).to_owned()).collect(),
+            exclude: exclude.iter().map(|x| (*x).to_owned()).collect(),
+            shard,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_include_exclude() {
+        let everything = filter(&[], &[], None);
+        assert_eq!(everything.exclusion("//foo:bar - a"), None);
+
+        let f = filter(&["foo", "baz"], &["slow$"], None);
+        assert_eq!(f.exclusion("//foo:bar - a"), None);
+        assert_eq!(f.exclusion("//baz:qux - a"), None);
+        assert!(f.exclusion("//qux:qux - a").is_some());
+        assert!(f.exclusion("//foo:bar - a_slow").is_some());
+    }
+
+    #[test]
+    fn test_shard() {
+        // Pin the hash, so a change that would move tests between shards is noticed.
+        assert_eq!(shard_of("//foo:bar - test_one", 3), 2);
+        assert_eq!(shard_of("//foo:bar - test_two", 3), 0);
+
+        let names = (0..100).map(|i| format!("//foo:bar - test_{}", i));
+        let shards = (0..4)
+            .map(|index| filter(&[], &[], Some(TestShard { index, count: 4 })))
+            .collect::<Vec<_>>();
+        for name in names {
+            let running = shards.iter().filter(|s| s.exclusion(&name).is_none());
+            assert_eq!(running.count(), 1, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Listing and running individual test cases for the test frameworks we know about.
+
+use std::collections::HashMap;
+
+use buck2_test_api::data::TestStatus;
+
+/// The most bytes of test case names to pass to one execution of a test binary. GoogleTest takes
+/// them all in a single argument, and Linux limits an argument to 128 KiB (`MAX_ARG_STRLEN`).
+const MAX_BATCH_BYTES: usize = 100 * 1024;
+
+/// Split test cases into batches of at most `batch_size` cases, and of few enough bytes that
+/// their `run_args` can be passed to a process. A case longer than that limit runs on its own.
+pub(crate) fn batches(cases: &[String], batch_size: usize) -> Vec<&[String]> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+    for (i, case) in cases.iter().enumerate() {
+        // Each case is followed by a separator or a NUL terminator.
+        let len = case.len() + 1;
+        if i > start && (i - start == batch_size || bytes + len > MAX_BATCH_BYTES) {
+            res.push(&cases[start..i]);
+            start = i;
+            bytes = 0;
+        }
+        bytes += len;
+    }
+    if start < cases.len() {
+        res.push(&cases[start..]);
+    }
+    res
+}
+
+/// A test framework whose binaries can list their test cases and run a subset of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TestFramework {
+    /// GoogleTest, using `--gtest_list_tests` and `--gtest_filter`.
+    GTest,
+    /// The Rust libtest harness, using `--list` and `--exact`.
+    LibTest,
+    /// pytest, using `--collect-only` and node ids.
+    Pytest,
+}
+
+impl TestFramework {
+    /// The framework for a test spec's `test_type`, if it is one we know how to list.
+    ///
+    /// The prelude's `python_test` sets `pyunit` whatever main module runs the tests, so binaries
+    /// that don't use pytest fail the listing and are run as a whole.
+    pub(crate) fn from_test_type(test_type: &str) -> Option<Self> {
+        match test_type {
+            "gtest" => Some(Self::GTest),
+            "rust" => Some(Self::LibTest),
+            "pyunit" | "pytest" => Some(Self::Pytest),
+            _ => None,
+        }
+    }
+
+    /// Arguments to make the test binary print its test cases instead of running them.
+    pub(crate) fn list_args(self) -> Vec<String> {
+        let args: &[&str] = match self {
+            Self::GTest => &["--gtest_list_tests"],
+            Self::LibTest => &["--list", "--format", "terse"],
+            Self::Pytest => &["--collect-only", "-q"],
+        };
+        args.iter().map(|x| (*x).to_owned()).collect()
+    }
+
+    /// Parse the test cases from the output of running with `list_args`.
+    pub(crate) fn parse_listing(self, stdout: &str) -> Vec<String> {
+        match self {
+            Self::GTest => {
+                // Suites are unindented and end with `.`, and their cases follow indented.
+                // Parameterized names are followed by a `# GetParam() = ...` comment.
+                let mut res = Vec::new();
+                let mut suite = None;
+                for line in stdout.lines() {
+                    let name = line.split('#').next().unwrap_or_default().trim_end();
+                    if name.is_empty() {
+                        continue;
+                    }
+                    if !name.starts_with(' ') {
+                        suite = name.ends_with('.').then(|| name.to_owned());
+                    } else if let Some(suite) = &suite {
+                        res.push(format!("{}{}", suite, name.trim()));
+                    }
+                }
+                res
+            }
+            Self::LibTest => stdout
+                .lines()
+                .filter_map(|line| line.strip_suffix(": test"))
+                .map(|x| x.to_owned())
+                .collect(),
+            // Node ids come first, followed by a blank line and a summary.
+            Self::Pytest => stdout
+                .lines()
+                .take_while(|line| !line.trim().is_empty())
+                .filter(|line| line.contains("::"))
+                .map(|x| x.trim().to_owned())
+                .collect(),
+        }
+    }
+
+    /// Arguments to run just the given test cases, in a way `parse_results` can understand.
+    pub(crate) fn run_args(self, cases: &[String]) -> Vec<String> {
+        match self {
+            Self::GTest => vec![format!("--gtest_filter={}", cases.join(":"))],
+            Self::LibTest => std::iter::once("--exact".to_owned())
+                .chain(cases.iter().cloned())
+                .collect(),
+            // `-rA` adds a line with the outcome of every test to the summary.
+            Self::Pytest => std::iter::once("-rA".to_owned())
+                .chain(cases.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// Parse the status of each test case from the output of running with `run_args`.
+    /// Cases which don't appear must be judged by the exit code of the whole run.
+    pub(crate) fn parse_results(self, stdout: &str) -> HashMap<String, TestStatus> {
+        let mut res = HashMap::new();
+        for line in stdout.lines() {
+            let (status, rest) = match self {
+                Self::GTest => {
+                    let Some((status, rest)) =
+                        line.strip_prefix('[').and_then(|x| x.split_once(']'))
+                    else {
+                        continue;
+                    };
+                    let status = match status.trim() {
+                        "OK" => TestStatus::PASS,
+                        "FAILED" => TestStatus::FAIL,
+                        "SKIPPED" => TestStatus::SKIP,
+                        _ => continue,
+                    };
+                    // e.g. `Suite.Case (0 ms)` or `Suite/0.Case, where GetParam() = 1 (0 ms)`.
+                    let name = rest.trim_start().split([' ', ',']).next();
+                    (status, name)
+                }
+                Self::LibTest => {
+                    let Some((name, status)) = line
+                        .strip_prefix("test ")
+                        .and_then(|x| x.split_once(" ... "))
+                    else {
+                        continue;
+                    };
+                    let status = match status.split(',').next().unwrap_or_default() {
+                        "ok" => TestStatus::PASS,
+                        "FAILED" => TestStatus::FAIL,
+                        "ignored" => TestStatus::SKIP,
+                        _ => continue,
+                    };
+                    (status, Some(name))
+                }
+                Self::Pytest => {
+                    let Some((status, rest)) = line.split_once(' ') else {
+                        continue;
+                    };
+                    let status = match status {
+                        "PASSED" | "XFAIL" => TestStatus::PASS,
+                        "FAILED" | "ERROR" | "XPASS" => TestStatus::FAIL,
+                        "SKIPPED" => TestStatus::SKIP,
+                        _ => continue,
+                    };
+                    (status, rest.split(" - ").next())
+                }
+            };
+            if let Some(name) = rest.filter(|x| !x.is_empty()) {
+                res.insert(name.to_owned(), status);
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gtest() {
+        let listing = "Running main() from gtest_main.cc\n\
+            Math.\n  Add\n  Sub\n\
+            Param/Math.\n  Mul/0  # GetParam() = 1\n";
+        let cases = TestFramework::GTest.parse_listing(listing);
+        assert_eq!(cases, vec!["Math.Add", "Math.Sub", "Param/Math.Mul/0"]);
+        assert_eq!(
+            TestFramework::GTest.run_args(&cases[..2]),
+            vec!["--gtest_filter=Math.Add:Math.Sub"]
+        );
+
+        let output = "[ RUN      ] Math.Add\n[       OK ] Math.Add (0 ms)\n\
+            [ RUN      ] Param/Math.Mul/0\n\
+            [  FAILED  ] Param/Math.Mul/0, where GetParam() = 1 (1 ms)\n\
+            [  FAILED  ] 1 test, listed below:\n";
+        let results = TestFramework::GTest.parse_results(output);
+        assert_eq!(results.get("Math.Add"), Some(&TestStatus::PASS));
+        assert_eq!(results.get("Param/Math.Mul/0"), Some(&TestStatus::FAIL));
+        assert_eq!(results.get("Math.Sub"), None);
+    }
+
+    #[test]
+    fn test_libtest() {
+        let listing = "tests::a: test\ntests::b: test\nbench_c: bench\n";
+        let cases = TestFramework::LibTest.parse_listing(listing);
+        assert_eq!(cases, vec!["tests::a", "tests::b"]);
+        assert_eq!(
+            TestFramework::LibTest.run_args(&cases),
+            vec!["--exact", "tests::a", "tests::b"]
+        );
+
+        let output = "running 2 tests\ntest tests::a ... ok\ntest tests::b ... FAILED\n\
+            test tests::c ... ignored, slow\n";
+        let results = TestFramework::LibTest.parse_results(output);
+        assert_eq!(results.get("tests::a"), Some(&TestStatus::PASS));
+        assert_eq!(results.get("tests::b"), Some(&TestStatus::FAIL));
+        assert_eq!(results.get("tests::c"), Some(&TestStatus::SKIP));
+    }
+
+    #[test]
+    fn test_pytest() {
+        let listing = "test_a.py::test_x\ntest_a.py::TestY::test_z\n\n2 tests collected in 0.01s\n";
+        let cases = TestFramework::Pytest.parse_listing(listing);
+        assert_eq!(cases, vec!["test_a.py::test_x", "test_a.py::TestY::test_z"]);
+        assert_eq!(
+            TestFramework::Pytest.run_args(&cases[..1]),
+            vec!["-rA", "test_a.py::test_x"]
+        );
+
+        let output = "==== short test summary info ====\nPASSED test_a.py::test_x\n\
+            FAILED test_a.py::TestY::test_z - assert 1 == 2\n";
+        let results = TestFramework::Pytest.parse_results(output);
+        assert_eq!(results.get("test_a.py::test_x"), Some(&TestStatus::PASS));
+        assert_eq!(
+            results.get("test_a.py::TestY::test_z"),
+            Some(&TestStatus::FAIL)
+        );
+    }
+
+    #[test]
+    fn test_from_test_type() {
+        // The types set by the prelude's `cxx_test`, `rust_test` and `python_test`.
+        assert_eq!(
+            TestFramework::from_test_type("gtest"),
+            Some(TestFramework::GTest)
+        );
+        assert_eq!(
+            TestFramework::from_test_type("rust"),
+            Some(TestFramework::LibTest)
+        );
+        assert_eq!(
+            TestFramework::from_test_type("pyunit"),
+            Some(TestFramework::Pytest)
+        );
+        assert_eq!(TestFramework::from_test_type("custom"), None);
+    }
+
+    #[test]
+    fn test_batches() {
+        let cases = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        assert_eq!(batches(&cases, 2), vec![&cases[..2], &cases[2..]]);
+        assert_eq!(batches(&cases, usize::MAX), vec![&cases[..]]);
+        assert!(batches(&[], 1).is_empty());
+
+        // Long names are split by size, however large the batch size.
+        let cases = vec!["x".repeat(60 * 1024), "y".repeat(60 * 1024), "z".to_owned()];
+        assert_eq!(batches(&cases, usize::MAX), vec![&cases[..1], &cases[1..]]);
+        let cases = vec!["x".repeat(200 * 1024), "z".to_owned()];
+        assert_eq!(batches(&cases, usize::MAX), vec![&cases[..1], &cases[1..]]);
+    }
+
+    #[test]
+    fn test_from_test_type() {
+        assert_eq!(
+            TestFramework::from_test_type("gtest"),
+            Some(TestFramework::GTest)
+        );
+        assert_eq!(TestFramework::from_test_type("custom"), None);
+    }
+}
@@ -12,6 +12,8 @@
 
 mod config;
 mod executor;
+mod filter;
+mod framework;
 mod runner;
 mod service;
 pub mod tcp;
@@ -7,14 +7,19 @@
  * of this source tree.
  */
 
+use std::borrow::Cow;
+
 use anyhow::Context;
+use buck2_data::command_execution_kind::Command;
 use buck2_test_api::data::ArgValue;
 use buck2_test_api::data::ArgValueContent;
 use buck2_test_api::data::ConfiguredTargetHandle;
 use buck2_test_api::data::DisplayMetadata;
 use buck2_test_api::data::ExecuteResponse;
+use buck2_test_api::data::ExecutionDetails;
 use buck2_test_api::data::ExecutionResult2;
 use buck2_test_api::data::ExecutionStatus;
+use buck2_test_api::data::ExecutionStream;
 use buck2_test_api::data::ExternalRunnerSpec;
 use buck2_test_api::data::ExternalRunnerSpecValue;
 use buck2_test_api::data::RequiredLocalResources;
@@ -29,6 +34,9 @@ use parking_lot::Mutex;
 
 use crate::config::Config;
 use crate::config::EnvValue;
+use crate::filter::TestFilter;
+use crate::framework;
+use crate::framework::TestFramework;
 
 pub type SpecReceiver = UnboundedReceiver<ExternalRunnerSpec>;
 
@@ -70,39 +78,20 @@ impl Buck2TestRunner {
         }
         let run_verdict = receiver
             .map(async move |spec| {
-                let name = format!(
-                    "{}//{}:{}",
-                    spec.target.cell, spec.target.package, spec.target.target
-                );
-                let target_handle = spec.target.handle.to_owned();
-
-                let execution_response = self
-                    .execute_test_from_spec(spec)
-                    .await
-                    .expect("Test execution request failed");
-
-                let execution_result = match execution_response {
-                    ExecuteResponse::Result(r) => r,
-                    ExecuteResponse::Cancelled => return TestStatus::OMITTED,
-                };
-
-                let test_result = get_test_result(name, target_handle, execution_result);
-                let test_status = test_result.status.clone();
-
-                self.report_test_result(test_result)
+                self.run_spec(spec)
                     .await
-                    .expect("Test result reporting failed");
-
-                test_status
+                    .expect("Test execution request failed")
             })
             // Use an arbitrarily large buffer -- execution throttling will be handled by the Buck2
-            // executor, so no need to hold back on requests here.
+            // executor, using the weights and local resources each test asks for, so no need to
+            // hold back on requests here.
             .buffer_unordered(10000)
-            // If any individual test failed, consider the entire run to have failed.
+            // If any individual test failed, even after retries, consider the entire run to have
+            // failed.
             .fold(
                 RunVerdict::Pass,
-                async move |mut run_verdict, test_status| {
-                    if test_status != TestStatus::PASS {
+                async move |mut run_verdict, test_statuses| {
+                    if !test_statuses.iter().all(is_success) {
                         run_verdict = RunVerdict::Fail;
                     }
                     run_verdict
@@ -115,27 +104,251 @@ impl Buck2TestRunner {
             .await
     }
 
-    async fn execute_test_from_spec(
+    /// Run the tests of a target, returning the status of every result reported for a test
+    /// which was meant to run. Executions that were cancelled have no status, since cancelling a
+    /// run doesn't fail it.
+    async fn run_spec(&self, spec: ExternalRunnerSpec) -> anyhow::Result<Vec<TestStatus>> {
+        let filter = TestFilter::new(&spec.test_filter)?;
+        let framework = if self.config.no_listing {
+            None
+        } else {
+            TestFramework::from_test_type(&spec.test_type)
+        };
+        match framework {
+            Some(framework) => {
+                self.run_testcases_from_spec(framework, &spec, &filter)
+                    .await
+            }
+            None => self.run_suite_from_spec(&spec, &filter).await,
+        }
+    }
+
+    /// Run the whole test binary, retrying it while it fails and retries remain, and report a
+    /// result for each attempt.
+    async fn run_suite_from_spec(
         &self,
-        spec: ExternalRunnerSpec,
-    ) -> anyhow::Result<ExecuteResponse> {
+        spec: &ExternalRunnerSpec,
+        filter: &TestFilter,
+    ) -> anyhow::Result<Vec<TestStatus>> {
+        if let Some(reason) = filter.exclusion(&target_name(spec)) {
+            self.report_test_result(omitted_test_result(spec, target_name(spec), reason))
+                .await
+                .expect("Test result reporting failed");
+            return Ok(Vec::new());
+        }
+
+        let mut attempt = 1;
+        loop {
+            let display_metadata = DisplayMetadata::Testing {
+                suite: spec.target.target.clone(),
+                testcases: Vec::new(),
+            };
+            let execution_result = match self
+                .execute_test_from_spec(spec, display_metadata, Vec::new())
+                .await?
+            {
+                ExecuteResponse::Result(r) => r,
+                ExecuteResponse::Cancelled => return Ok(Vec::new()),
+            };
+
+            let mut test_result =
+                get_test_result(target_name(spec), spec.target.handle, execution_result);
+            self.classify_attempt(&mut test_result, attempt);
+            let test_status = test_result.status.clone();
+
+            self.report_test_result(test_result)
+                .await
+                .expect("Test result reporting failed");
+
+            if test_status != TestStatus::RERUN {
+                return Ok(vec![test_status]);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// List the test cases in the test binary, then run them in batches, reporting a result
+    /// for each case. Failing cases are run again, in new batches, while retries remain. Cases
+    /// the filter excludes are reported as omitted without running.
+    ///
+    /// The test type doesn't guarantee that the binary uses the framework: e.g. `cxx_test` always
+    /// sets `gtest`, and Rust tests may opt out of libtest with `harness = false`. Binaries whose
+    /// listing fails or finds no cases are run as a whole instead.
+    async fn run_testcases_from_spec(
+        &self,
+        framework: TestFramework,
+        spec: &ExternalRunnerSpec,
+        filter: &TestFilter,
+    ) -> anyhow::Result<Vec<TestStatus>> {
+        let suite = spec.target.target.clone();
+        let listing = match self
+            .execute_test_from_spec(
+                spec,
+                DisplayMetadata::Listing(suite.clone()),
+                framework.list_args(),
+            )
+            .await?
+        {
+            ExecuteResponse::Result(r) => r,
+            ExecuteResponse::Cancelled => return Ok(Vec::new()),
+        };
+
+        let testcases = framework.parse_listing(&stream_text(&listing.stdout));
+        let mut listing_result = get_test_result(target_name(spec), spec.target.handle, listing);
+        if listing_result.status != TestStatus::PASS || testcases.is_empty() {
+            return self.run_suite_from_spec(spec, filter).await;
+        }
+        listing_result.status = TestStatus::LISTING_SUCCESS;
+        self.report_test_result(listing_result)
+            .await
+            .expect("Test result reporting failed");
+
+        self.orchestrator_client
+            .report_tests_discovered(spec.target.handle, suite.clone(), testcases.clone())
+            .await?;
+
+        let mut pending = Vec::with_capacity(testcases.len());
+        for testcase in testcases {
+            let name = testcase_name(spec, &testcase);
+            match filter.exclusion(&name) {
+                Some(reason) => self
+                    .report_test_result(omitted_test_result(spec, name, reason))
+                    .await
+                    .expect("Test result reporting failed"),
+                None => pending.push(testcase),
+            }
+        }
+
+        let mut statuses = Vec::with_capacity(pending.len());
+        let mut attempt = 1;
+        while !pending.is_empty() {
+            let batch_size = self.config.batch_size.unwrap_or(usize::MAX);
+            let batches = framework::batches(&pending, batch_size);
+            let batch_statuses = futures::future::try_join_all(
+                batches
+                    .iter()
+                    .map(|batch| self.run_testcase_batch(framework, spec, batch, attempt)),
+            )
+            .await?;
+
+            let mut retry = Vec::new();
+            // The cases of cancelled batches have no status, and aren't retried.
+            for (batch, batch_statuses) in batches.into_iter().zip(batch_statuses) {
+                for (testcase, status) in batch.iter().zip(batch_statuses.into_iter().flatten()) {
+                    if status == TestStatus::RERUN {
+                        retry.push(testcase.clone());
+                    } else {
+                        statuses.push(status);
+                    }
+                }
+            }
+            pending = retry;
+            attempt += 1;
+        }
+        Ok(statuses)
+    }
+
+    /// Run a batch of test cases, returning the status of each, or `None` if the execution was
+    /// cancelled.
+    async fn run_testcase_batch(
+        &self,
+        framework: TestFramework,
+        spec: &ExternalRunnerSpec,
+        testcases: &[String],
+        attempt: usize,
+    ) -> anyhow::Result<Option<Vec<TestStatus>>> {
         let display_metadata = DisplayMetadata::Testing {
-            suite: spec.target.target,
-            testcases: Vec::new(),
+            suite: spec.target.target.clone(),
+            testcases: testcases.to_vec(),
+        };
+        let execution_result = match self
+            .execute_test_from_spec(spec, display_metadata, framework.run_args(testcases))
+            .await?
+        {
+            ExecuteResponse::Result(r) => r,
+            ExecuteResponse::Cancelled => return Ok(None),
         };
 
-        let config_args = self.config.test_arg.iter().map(|arg| ArgValue {
-            content: ArgValueContent::ExternalRunnerSpecValue(ExternalRunnerSpecValue::Verbatim(
-                arg.to_owned(),
-            )),
-            format: None,
-        });
+        let case_statuses = framework.parse_results(&stream_text(&execution_result.stdout));
+        let batch_result = get_test_result(target_name(spec), spec.target.handle, execution_result);
+
+        let mut statuses = Vec::with_capacity(testcases.len());
+        for testcase in testcases {
+            // Cases the output doesn't mention, e.g. because a timeout interrupted them, get
+            // the status of the whole batch.
+            let status = case_statuses
+                .get(testcase)
+                .unwrap_or(&batch_result.status)
+                .clone();
+            // The output of the batch is only kept for the cases that need looking into, and the
+            // batch's duration isn't any single case's.
+            let details = if is_success(&status) {
+                String::new()
+            } else {
+                batch_result.details.clone()
+            };
+            let mut test_result = TestResult {
+                target: spec.target.handle,
+                name: testcase_name(spec, testcase),
+                status,
+                msg: batch_result.msg.clone(),
+                duration: None,
+                details,
+            };
+            self.classify_attempt(&mut test_result, attempt);
+            statuses.push(test_result.status.clone());
+            self.report_test_result(test_result)
+                .await
+                .expect("Test result reporting failed");
+        }
+        Ok(Some(statuses))
+    }
+
+    /// Account for retries in the result of the given attempt (starting at 1) at running a
+    /// test: a failure is reported as a rerun while retries remain, and a pass after a failure
+    /// as flaky. The message records which attempt the result came from.
+    fn classify_attempt(&self, test_result: &mut TestResult, attempt: usize) {
+        let max_attempts = self.config.retries + 1;
+        if !is_success(&test_result.status) {
+            if attempt < max_attempts {
+                test_result.msg = Some(format!(
+                    "Attempt {} of {} failed with {:?}, retrying",
+                    attempt, max_attempts, test_result.status
+                ));
+                test_result.status = TestStatus::RERUN;
+            } else if attempt > 1 {
+                test_result.msg = Some(format!("Failed all {} attempts", max_attempts));
+            }
+        } else if attempt > 1 && test_result.status == TestStatus::PASS {
+            test_result.msg = Some(format!("Passed on attempt {} of {}", attempt, max_attempts));
+            test_result.status = TestStatus::FLAKY;
+        }
+    }
+
+    async fn execute_test_from_spec(
+        &self,
+        spec: &ExternalRunnerSpec,
+        display_metadata: DisplayMetadata,
+        framework_args: Vec<String>,
+    ) -> anyhow::Result<ExecuteResponse> {
+        let config_args = self
+            .config
+            .test_arg
+            .iter()
+            .cloned()
+            .chain(framework_args)
+            .map(|arg| ArgValue {
+                content: ArgValueContent::ExternalRunnerSpecValue(
+                    ExternalRunnerSpecValue::Verbatim(arg),
+                ),
+                format: None,
+            });
 
         let command = spec
             .command
-            .into_iter()
+            .iter()
             .map(|spec_value| ArgValue {
-                content: ArgValueContent::ExternalRunnerSpecValue(spec_value),
+                content: ArgValueContent::ExternalRunnerSpecValue(spec_value.clone()),
                 format: None,
             })
             .chain(config_args)
@@ -155,12 +368,12 @@ impl Buck2TestRunner {
 
         let env = spec
             .env
-            .into_iter()
+            .iter()
             .map(|(key, value)| {
                 (
-                    key,
+                    key.clone(),
                     ArgValue {
-                        content: ArgValueContent::ExternalRunnerSpecValue(value),
+                        content: ArgValueContent::ExternalRunnerSpecValue(value.clone()),
                         format: None,
                     },
                 )
@@ -169,7 +382,12 @@ impl Buck2TestRunner {
             .collect();
 
         let target_handle = spec.target.handle;
-        let host_sharing_requirements = HostSharingRequirements::default();
+        // Tests share the host according to their weight, and wait for the local resources they
+        // need to be free, both of which the executor manages.
+        let host_sharing_requirements = spec.weight_class.map_or_else(
+            HostSharingRequirements::default,
+            HostSharingRequirements::Shared,
+        );
         let pre_create_dirs = Vec::new();
         let executor_override = None;
 
@@ -183,7 +401,9 @@ impl Buck2TestRunner {
                 host_sharing_requirements,
                 pre_create_dirs,
                 executor_override,
-                RequiredLocalResources { resources: vec![] },
+                RequiredLocalResources {
+                    resources: spec.local_resources.clone(),
+                },
             )
             .await
     }
@@ -195,6 +415,46 @@ impl Buck2TestRunner {
     }
 }
 
+/// The output of an execution, lossily decoded as UTF-8.
+fn stream_text(stream: &ExecutionStream) -> Cow<'_, str> {
+    match stream {
+        ExecutionStream::Inline(data) => String::from_utf8_lossy(data),
+    }
+}
+
+fn target_name(spec: &ExternalRunnerSpec) -> String {
+    format!(
+        "{}//{}:{}",
+        spec.target.cell, spec.target.package, spec.target.target
+    )
+}
+
+/// The name a test case of the spec is reported with, and filtered by.
+fn testcase_name(spec: &ExternalRunnerSpec, testcase: &str) -> String {
+    format!("{} - {}", target_name(spec), testcase)
+}
+
+/// The result for a test which the filter excluded from running. These aren't returned as
+/// statuses, since leaving tests out doesn't fail the run.
+fn omitted_test_result(spec: &ExternalRunnerSpec, name: String, reason: String) -> TestResult {
+    TestResult {
+        target: spec.target.handle,
+        name,
+        status: TestStatus::OMITTED,
+        msg: Some(reason),
+        duration: None,
+        details: String::new(),
+    }
+}
+
+/// Whether a status doesn't fail the run.
+fn is_success(status: &TestStatus) -> bool {
+    matches!(
+        status,
+        TestStatus::PASS | TestStatus::SKIP | TestStatus::LISTING_SUCCESS | TestStatus::FLAKY
+    )
+}
+
 fn get_test_result(
     name: String,
     target: ConfiguredTargetHandle,
@@ -207,11 +467,16 @@ fn get_test_result(
         },
         ExecutionStatus::TimedOut { .. } => TestStatus::TIMEOUT,
     };
+    let msg = if is_cached(&execution_result.execution_details) {
+        Some("Cached result of a previous run".to_owned())
+    } else {
+        None
+    };
     TestResult {
         target,
         name,
         status,
-        msg: None,
+        msg,
         duration: Some(execution_result.execution_time),
         details: format!(
             "---- STDOUT ----\n{:?}\n---- STDERR ----\n{:?}\n",
@@ -220,6 +485,19 @@ fn get_test_result(
     }
 }
 
+/// Whether the test wasn't executed because its result was found in a cache.
+fn is_cached(execution_details: &ExecutionDetails) -> bool {
+    match execution_details
+        .execution_kind
+        .as_ref()
+        .and_then(|kind| kind.command.as_ref())
+    {
+        Some(Command::LocalActionCacheCommand(..)) => true,
+        Some(Command::RemoteCommand(remote)) => remote.cache_hit,
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 enum RunVerdict {
     Pass,
@@ -22,3 +22,4 @@ pub mod rtabort;
 pub mod system_stats;
 pub mod thin_box;
 pub mod truncate;
+pub mod xml;
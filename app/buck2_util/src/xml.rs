@@ -0,0 +1,43 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+/// Escape text for XML content or a double-quoted attribute, dropping the control
+/// characters XML 1.0 cannot represent at all.
+pub fn escape_xml(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            c if c < ' ' => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xml::escape_xml;
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!("foo", escape_xml("foo"));
+        assert_eq!(
+            "&lt;a&gt; &amp; &quot;b&quot; &apos;c&apos;",
+            escape_xml(r#"<a> & "b" 'c'"#)
+        );
+        assert_eq!("a\tb\nc\r", escape_xml("a\tb\nc\r"));
+        assert_eq!("ab", escape_xml("a\x00\x1bb"));
+    }
+}
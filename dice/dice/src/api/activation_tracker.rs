@@ -8,6 +8,17 @@
  */
 
 use std::any::Any;
+use std::any::TypeId;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+use allocative::Allocative;
+use dupe::Dupe;
+use fnv::FnvHasher;
+
+use crate::api::key::Key;
 
 /// An ActivationTracker can be used to identify which keys were either reused or computed during a
 /// transaction.
@@ -21,6 +32,17 @@ pub trait ActivationTracker: Send + Sync + 'static {
         deps: &mut dyn Iterator<Item = &dyn Any>,
         activation_data: ActivationData,
     );
+
+    /// Receives the keys that were changed or injected by the transaction this tracker was
+    /// committed with, before any of its keys are activated. Those are the roots of the
+    /// invalidations that caused keys to be evaluated in this transaction. Keys injected with a
+    /// value equal to their previous one may be omitted.
+    fn keys_changed(&self, _changed: &[ChangedKey]) {}
+
+    /// Receives when a key was evaluated (as opposed to reused), along with the ids of its deps.
+    /// This is called in addition to `key_activated`, and is cheap enough to call for every key:
+    /// nothing gets formatted unless the tracker displays the key.
+    fn key_evaluated(&self, _key: EvaluatedKey, _deps: &mut dyn Iterator<Item = KeyId>) {}
 }
 
 /// Describes the kind of activation, and possibly carries data passed by the key's evaluation.
@@ -31,3 +53,55 @@ pub enum ActivationData {
     /// This key was reused. No data is passed.
     Reused,
 }
+
+/// Identifies a key in the callbacks of an `ActivationTracker`, so that keys can be matched up
+/// without formatting them. Equal keys have equal ids, and distinct keys have distinct ids barring
+/// hash collisions.
+#[derive(Allocative, Clone, Copy, Dupe, Debug, PartialEq, Eq, Hash)]
+pub struct KeyId(u64);
+
+impl KeyId {
+    pub fn new<K: Hash + 'static>(key: &K) -> Self {
+        let mut hasher = FnvHasher::default();
+        TypeId::of::<K>().hash(&mut hasher);
+        key.hash(&mut hasher);
+        KeyId(hasher.finish())
+    }
+}
+
+/// A key passed to `ActivationTracker::key_evaluated`.
+#[derive(Clone)]
+pub struct EvaluatedKey {
+    /// The short name of the type of the key, as returned by `Key::key_type_name`.
+    pub type_name: &'static str,
+    pub id: KeyId,
+    /// The key itself, cheap to hold onto until it needs to be displayed.
+    pub key: Arc<dyn Display + Send + Sync>,
+    /// Whether the key had a value before this evaluation, i.e. it was recomputed rather than
+    /// computed for the first time.
+    pub recomputed: bool,
+}
+
+/// A key that was changed or injected by a transaction.
+#[derive(Allocative, Clone, Debug, PartialEq, Eq)]
+pub struct ChangedKey {
+    /// The short name of the type of the key, as returned by `Key::key_type_name`.
+    pub type_name: &'static str,
+    pub id: KeyId,
+    /// The display form of the key.
+    pub key: String,
+    /// Whether the key was given a new value via `changed_to`, as opposed to being invalidated
+    /// via `changed`.
+    pub injected: bool,
+}
+
+impl ChangedKey {
+    pub(crate) fn new<K: Key>(key: &K, injected: bool) -> Self {
+        Self {
+            type_name: K::key_type_name(),
+            id: KeyId::new(key),
+            key: key.to_string(),
+            injected,
+        }
+    }
+}
@@ -194,6 +194,7 @@ use futures::future::Future;
 use serde::Serializer;
 
 use crate::api::cycles::DetectCycles;
+use crate::api::key::Key;
 use crate::api::transaction::DiceTransactionUpdater;
 use crate::api::user_data::UserComputationData;
 use crate::metrics::Metrics;
@@ -264,6 +265,12 @@ impl Dice {
         self.implementation.metrics()
     }
 
+    /// The keys of type `K` whose value is up to date at the latest version, along with that
+    /// value. Used to persist computations across restarts.
+    pub fn current_values<K: Key>(&self) -> Vec<(K, K::Value)> {
+        self.implementation.current_values()
+    }
+
     /// Wait until all active versions have exited.
     pub fn wait_for_idle(&self) -> impl Future<Output = ()> + 'static {
         self.implementation.wait_for_idle()
@@ -282,6 +289,16 @@ impl DiceDataBuilder {
         self.0.set(val);
     }
 
+    /// Evict the values of nodes that weren't used in recent versions once the memory owned by
+    /// the values held by the graph exceeds this many bytes. Evicted nodes keep their edges and
+    /// are recomputed on demand.
+    ///
+    /// Only supported by the modern implementation: the legacy implementation keeps every value
+    /// until it is invalidated, so this returns an error for it.
+    pub fn set_memory_budget(&mut self, bytes: usize) -> anyhow::Result<()> {
+        self.0.set_memory_budget(bytes)
+    }
+
     pub fn build(self, detect_cycles: DetectCycles) -> Arc<Dice> {
         self.0.build(detect_cycles)
     }
@@ -62,6 +62,17 @@ pub trait Key: Allocative + Debug + Display + Clone + Eq + Hash + Send + Sync +
         true
     }
 
+    /// A hash of the computed value. Values evicted to stay within a memory budget keep this
+    /// hash, so that a recomputation with an equal hash doesn't invalidate graph nodes depending
+    /// on this node.
+    ///
+    /// Like `equality`, returning the same hash for values that are not equal would result in
+    /// inconsistent graph state. The default is `None`, in which case an evicted value is only
+    /// reused if none of its dependencies changed.
+    fn value_hash(_x: &Self::Value) -> Option<u64> {
+        None
+    }
+
     fn storage_type() -> StorageType {
         StorageType::LastN(1)
     }
@@ -52,6 +52,12 @@ pub trait ProjectionKey:
         true
     }
 
+    /// See `Key::value_hash`.
+    fn value_hash(x: &Self::Value) -> Option<u64> {
+        let _ = x;
+        None
+    }
+
     fn storage_type() -> StorageType {
         StorageType::LastN(1)
     }
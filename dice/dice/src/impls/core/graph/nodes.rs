@@ -67,8 +67,23 @@ impl VersionedGraphNode {
 #[derive(Allocative, Clone)] // TODO(bobyf) remove need to clone
 pub(crate) struct OccupiedGraphNode {
     key: DiceKey,
-    res: DiceValidValue,
+    /// The computed value, or `None` if it was evicted to save memory. Evicted nodes keep their
+    /// metadata so that invalidations still propagate through them, and so that a recomputation
+    /// at a version where the node or all of its deps are known to be verified can reinstate the
+    /// value without dirtying its rdeps.
+    res: Option<DiceValidValue>,
+    /// The `Key::value_hash` of the value that was evicted, if any, so that a recomputation can
+    /// still be compared against it.
+    evicted_hash: Option<u64>,
     metadata: NodeMetadata,
+    /// The newest version at which the value was computed or requested.
+    last_used: VersionNumber,
+    /// Memory uniquely owned by `res`, or `None` if it wasn't measured. Values are only measured
+    /// when the graph has a memory budget to enforce.
+    value_bytes: Option<usize>,
+    /// Pinned values are never evicted. This is the case for injected values, which can't be
+    /// recomputed.
+    pinned: bool,
 }
 
 /// Meta data about a DICE node, which are its edges and history information
@@ -88,15 +103,24 @@ impl OccupiedGraphNode {
     ) -> Self {
         Self {
             key,
-            res,
+            res: Some(res),
+            evicted_hash: None,
+            value_bytes: None,
             metadata: NodeMetadata {
                 hist,
                 deps,
                 rdeps: VersionedRevDependencies::new(),
             },
+            last_used: VersionNumber::ZERO,
+            pinned: false,
         }
     }
 
+    pub(crate) fn pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+
     pub(crate) fn metadata(&self) -> &NodeMetadata {
         &self.metadata
     }
@@ -130,15 +154,64 @@ impl OccupiedGraphNode {
         changed_since
     }
 
-    pub(crate) fn val(&self) -> &DiceValidValue {
-        &self.res
+    /// The value of this node, or `None` if it was evicted.
+    pub(crate) fn val(&self) -> Option<&DiceValidValue> {
+        self.res.as_ref()
     }
 
-    pub(crate) fn computed_val(&self) -> DiceComputedValue {
-        DiceComputedValue::new(
-            MaybeValidDiceValue::valid(self.res.dupe()),
+    pub(crate) fn computed_val(&self) -> Option<DiceComputedValue> {
+        Some(DiceComputedValue::new(
+            MaybeValidDiceValue::valid(self.res.as_ref()?.dupe()),
             Arc::new(self.metadata.hist.clone()),
-        )
+        ))
+    }
+
+    pub(crate) fn last_used(&self) -> VersionNumber {
+        self.last_used
+    }
+
+    /// Records that the value was requested or computed at the given version.
+    pub(crate) fn mark_used(&mut self, v: VersionNumber) {
+        self.last_used = std::cmp::max(self.last_used, v);
+    }
+
+    /// Puts back a value that was evicted. No-op if the value is still present.
+    pub(crate) fn reinstate(&mut self, value: DiceValidValue) {
+        if self.res.is_none() {
+            self.res = Some(value);
+            self.evicted_hash = None;
+            self.value_bytes = None;
+        }
+    }
+
+    /// The hash of the evicted value, if the value was evicted and its key provides hashes.
+    pub(crate) fn evicted_hash(&self) -> Option<u64> {
+        self.evicted_hash
+    }
+
+    /// Measures the value held by this node, unless it was already measured.
+    pub(crate) fn measure_value(&mut self) {
+        if self.value_bytes.is_none() {
+            self.value_bytes = Some(self.res.as_ref().map_or(0, |v| v.unique_allocated_bytes()));
+        }
+    }
+
+    /// The size of the value held by this node, in bytes, or 0 if it was evicted or not measured.
+    pub(crate) fn value_bytes(&self) -> usize {
+        self.value_bytes.unwrap_or(0)
+    }
+
+    pub(crate) fn is_evictable(&self) -> bool {
+        !self.pinned && self.res.is_some()
+    }
+
+    /// Drops the value, keeping the history, edges and the value's hash. Returns the number of
+    /// bytes reclaimed.
+    pub(crate) fn evict(&mut self) -> usize {
+        if let Some(res) = self.res.take() {
+            self.evicted_hash = res.value_hash();
+        }
+        self.value_bytes.replace(0).unwrap_or(0)
     }
 }
 
@@ -57,39 +57,97 @@ pub(crate) struct VersionedGraph {
     /// VacantGraphEntries can only be present when no other entries are present for the key at
     /// any version.
     pub(crate) last_n: HashMap<DiceKey, SortedVectorMap<VersionNumber, VersionedGraphNode>>,
+    /// Total of `OccupiedGraphNode::value_bytes` over the graph, kept up to date as values are
+    /// stored and evicted so that checking the memory budget doesn't need to walk the graph.
+    value_bytes: usize,
+    /// Whether to measure the values as they are stored. Measuring walks the value, so it is
+    /// only done when there is a memory budget to enforce.
+    measure_values: bool,
 }
 
 impl VersionedGraph {
     pub(crate) fn new() -> Self {
+        Self::with_value_sizes(false)
+    }
+
+    pub(crate) fn with_value_sizes(measure_values: bool) -> Self {
         Self {
             last_n: Default::default(),
+            value_bytes: 0,
+            measure_values,
+        }
+    }
+
+    /// The memory held by the values stored for the given key.
+    fn key_value_bytes(&self, k: DiceKey) -> usize {
+        if !self.measure_values {
+            return 0;
         }
+        self.last_n.get(&k).map_or(0, |versioned| {
+            versioned
+                .values()
+                .map(|node| match node {
+                    VersionedGraphNode::Occupied(occ) => occ.value_bytes(),
+                    VersionedGraphNode::Vacant(_) => 0,
+                })
+                .sum()
+        })
+    }
+
+    /// Records that the values stored for `k` changed from `bytes_before` bytes.
+    fn update_value_bytes(&mut self, k: DiceKey, bytes_before: usize) {
+        if !self.measure_values {
+            return;
+        }
+        if let Some(versioned) = self.last_n.get_mut(&k) {
+            for node in versioned.values_mut() {
+                if let VersionedGraphNode::Occupied(occ) = node {
+                    occ.measure_value();
+                }
+            }
+        }
+        self.value_bytes = self.value_bytes + self.key_value_bytes(k) - bytes_before;
     }
 
     /// gets the cache entry corresponding to the cache entry if up to date.
     /// returns 'None' if entry is missing or versions are out of date.
-    pub(crate) fn get(&self, key: VersionedGraphKey) -> VersionedGraphResult {
+    pub(crate) fn get(&mut self, key: VersionedGraphKey) -> VersionedGraphResult {
+        let res = self.lookup(key);
+        if res.unpack_match().is_some() {
+            if let Some(VersionedGraphNode::Occupied(entry)) = self.get_internal(key) {
+                entry.mark_used(key.v);
+            }
+        }
+        res
+    }
+
+    fn lookup(&self, key: VersionedGraphKey) -> VersionedGraphResult {
         fn handle_occupied(
             key: VersionedGraphKey,
             entry: &OccupiedGraphNode,
         ) -> VersionedGraphResult {
-            match entry.metadata().hist.get_history(&key.v) {
-                HistoryState::Verified => VersionedGraphResult::Match(entry.computed_val()),
-                HistoryState::Unknown(verified_versions) => {
+            // evicted values have to be recomputed, unless there is nothing to reuse anyways
+            match (entry.metadata().hist.get_history(&key.v), entry.val()) {
+                (HistoryState::Verified, Some(_)) => {
+                    VersionedGraphResult::Match(entry.computed_val().unwrap())
+                }
+                (HistoryState::Unknown(verified_versions), Some(val)) => {
                     VersionedGraphResult::CheckDeps(VersionedGraphResultMismatch {
-                        entry: entry.val().dupe(),
+                        entry: val.dupe(),
                         verified_versions,
                         deps_to_validate: entry.metadata().deps.deps(),
                     })
                 }
-                HistoryState::Dirty => VersionedGraphResult::Compute,
+                (HistoryState::Dirty, _) | (_, None) => {
+                    VersionedGraphResult::Compute { recompute: true }
+                }
             }
         }
 
         fn handle_vacant() -> VersionedGraphResult {
             // vacant entries only occur if no other graph entries are
             // present, so we know this has to be dirty
-            VersionedGraphResult::Compute
+            VersionedGraphResult::Compute { recompute: false }
         }
 
         if let Some(versioned) = self.last_n.get(&key.k) {
@@ -109,15 +167,15 @@ impl VersionedGraph {
                 // to a different result. TODO add some per ctx result caching for old versions
                 versioned
                     .range((Bound::Included(key.v), Bound::Unbounded))
-                    .find_map(|(v, e)| match e {
-                        VersionedGraphNode::Occupied(e) => Some((v, e)),
+                    .find_map(|(_v, e)| match e {
+                        VersionedGraphNode::Occupied(e) => Some((e, e.val()?)),
                         VersionedGraphNode::Vacant(_) => None,
                     })
                     .map_or_else(
-                        || VersionedGraphResult::Compute,
-                        |(_, entry)| {
+                        || VersionedGraphResult::Compute { recompute: false },
+                        |(entry, val)| {
                             VersionedGraphResult::CheckDeps(VersionedGraphResultMismatch {
-                                entry: entry.val().dupe(),
+                                entry: val.dupe(),
                                 verified_versions: entry.metadata().hist.get_verified_ranges(),
                                 deps_to_validate: entry.metadata().deps.deps(),
                             })
@@ -125,7 +183,7 @@ impl VersionedGraph {
                     )
             }
         } else {
-            VersionedGraphResult::Compute
+            VersionedGraphResult::Compute { recompute: false }
         }
     }
 
@@ -162,6 +220,77 @@ impl VersionedGraph {
         }
     }
 
+    /// The keys with a value that is up to date at the given version, along with that value.
+    pub(crate) fn current_values(&self, v: VersionNumber) -> Vec<(DiceKey, DiceValidValue)> {
+        self.last_n
+            .iter()
+            .filter_map(|(k, versioned)| match versioned.iter().last()?.1 {
+                VersionedGraphNode::Occupied(o) => match o.metadata().hist.get_history(&v) {
+                    HistoryState::Verified => Some((*k, o.val()?.dupe())),
+                    HistoryState::Unknown(..) | HistoryState::Dirty => None,
+                },
+                VersionedGraphNode::Vacant(..) => None,
+            })
+            .collect()
+    }
+
+    /// Evicts the values of the least recently used nodes if the values held by the graph exceed
+    /// `budget` bytes. Only the values are dropped; the edges and history are kept so that
+    /// invalidations still propagate, and the value is recomputed when next requested.
+    /// Values used at or after `keep_since` are never evicted, nor are injected values.
+    ///
+    /// This is O(1) while within budget. Once over budget, it walks the graph to find the least
+    /// recently used values, and evicts down to `EVICTION_LOW_WATER_MARK` of the budget so that
+    /// the walk isn't repeated on every subsequent version.
+    pub(crate) fn evict(&mut self, budget: usize, keep_since: VersionNumber) -> EvictionStats {
+        let mut stats = EvictionStats::default();
+        if self.value_bytes <= budget {
+            stats.retained_bytes = self.value_bytes;
+            return stats;
+        }
+
+        let mut candidates = Vec::new();
+        for (k, versioned) in self.last_n.iter() {
+            for (v, node) in versioned.iter() {
+                if let VersionedGraphNode::Occupied(occ) = node {
+                    if occ.is_evictable() && occ.last_used() < keep_since {
+                        candidates.push((occ.last_used(), *k, *v));
+                    }
+                }
+            }
+        }
+
+        let target = (budget as f64 * EVICTION_LOW_WATER_MARK) as usize;
+        let mut retained_bytes = self.value_bytes;
+        candidates.sort_unstable_by_key(|(last_used, _, _)| *last_used);
+        for (_, k, v) in candidates {
+            if retained_bytes <= target {
+                break;
+            }
+            if let Some(VersionedGraphNode::Occupied(occ)) = self
+                .last_n
+                .get_mut(&k)
+                .and_then(|versioned| versioned.get_mut(&v))
+            {
+                let reclaimed = occ.evict();
+                retained_bytes -= reclaimed;
+                stats.evicted += 1;
+                stats.reclaimed_bytes += reclaimed;
+            }
+        }
+
+        debug!(
+            msg = "evicted dice values",
+            evicted = stats.evicted,
+            reclaimed_bytes = stats.reclaimed_bytes,
+            retained_bytes = retained_bytes
+        );
+
+        self.value_bytes = retained_bytes;
+        stats.retained_bytes = retained_bytes;
+        stats
+    }
+
     /// updates the cached value based on the given key and versions. The value
     /// is only updated if the version of the new value is of a newer
     /// version than what is stored.
@@ -175,6 +304,8 @@ impl VersionedGraph {
         deps: Arc<Vec<DiceKey>>,
         storage_type: StorageType,
     ) -> (DiceComputedValue, bool) {
+        let mut reusable = reusable;
+        let bytes_before = self.key_value_bytes(key.k);
         let StorageType::LastN(num_to_keep) = storage_type;
         // persistent keys, if any changes, are committed at the moment when the version
         // is increased. therefore, it must be the case that the current update for the
@@ -220,7 +351,13 @@ impl VersionedGraph {
             }
         }
 
-        if let Some(key_of_e) = nearest {
+        if let (ValueReusable::EqualityBased, Some(key_of_e)) = (&reusable, nearest) {
+            if let Some(verified) = self.verified_with_unchanged_deps(key, key_of_e) {
+                reusable = ValueReusable::VersionBased(verified);
+            }
+        }
+
+        let res = if let Some(key_of_e) = nearest {
             self.update_entry(
                 key_of_e,
                 key,
@@ -243,6 +380,50 @@ impl VersionedGraph {
                 ),
                 true,
             )
+        };
+
+        self.update_value_bytes(key.k, bytes_before);
+        res
+    }
+
+    /// An evicted value can't be compared against its recomputation. But if the entry at
+    /// `key_of_e` was evicted and none of the deps it was computed from changed since it was last
+    /// verified, the recomputation must have produced an equal value, so it can be reused without
+    /// dirtying its rdeps. This is the same check `CheckDeps` does for values that are present.
+    /// Returns the versions at which the entry and all those deps were verified in that case.
+    fn verified_with_unchanged_deps(
+        &mut self,
+        key: VersionedGraphKey,
+        key_of_e: VersionNumber,
+    ) -> Option<VersionRanges> {
+        let (mut verified, deps) = match self.last_n.get(&key.k)?.get(&key_of_e)? {
+            VersionedGraphNode::Occupied(entry) if entry.val().is_none() => {
+                match entry.metadata().hist.get_history(&key.v) {
+                    HistoryState::Unknown(verified) => (verified, entry.metadata().deps.deps()),
+                    HistoryState::Verified | HistoryState::Dirty => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        for dep in deps.iter() {
+            match self.get_internal(VersionedGraphKey::new(key.v, dep.dupe()))? {
+                VersionedGraphNode::Occupied(occ) => {
+                    let hist = &occ.metadata().hist;
+                    // deps that weren't recomputed at this version may still change
+                    if !matches!(hist.get_history(&key.v), HistoryState::Verified) {
+                        return None;
+                    }
+                    verified = verified.intersect(&hist.get_verified_ranges());
+                }
+                VersionedGraphNode::Vacant(_) => return None,
+            }
+        }
+
+        if verified.is_empty() {
+            None
+        } else {
+            Some(verified)
         }
     }
 
@@ -292,10 +473,11 @@ impl VersionedGraph {
         let since = latest_dep_verified.unwrap_or(v);
         let mut hist = CellHistory::verified(since);
         hist.propagate_from_deps_version(since, first_dep_dirtied);
-        let entry =
+        let mut entry =
             OccupiedGraphNode::new(key, value, VersionedDependencies::new(since, deps), hist);
+        entry.mark_used(v);
 
-        let res = entry.computed_val();
+        let res = entry.computed_val().unwrap();
 
         self.last_n
             .get_mut(&key)
@@ -320,12 +502,14 @@ impl VersionedGraph {
     ) -> (DiceComputedValue, bool) {
         let versioned_map = self.last_n.get_mut(&key.k).unwrap();
         let (ret, map_fixup) = match versioned_map.get_mut(&key_of_e).unwrap() {
-            VersionedGraphNode::Occupied(entry) if reusable.is_reusable(&value, entry) => {
+            VersionedGraphNode::Occupied(entry) if reusable.is_reusable(&value, entry, key.v) => {
                 debug!("marking graph entry as unchanged");
+                entry.reinstate(value);
+                entry.mark_used(key.v);
                 let since =
                     entry.mark_unchanged(key.v, latest_dep_verified, first_dep_dirtied, deps);
 
-                let ret = entry.computed_val();
+                let ret = entry.computed_val().unwrap();
 
                 (ret, MapFixup::Reused { since, key_of_e })
             }
@@ -338,14 +522,15 @@ impl VersionedGraph {
 
                 hist.propagate_from_deps_version(key.v, first_dep_dirtied);
 
-                let new = OccupiedGraphNode::new(
+                let mut new = OccupiedGraphNode::new(
                     key.k,
                     value,
                     VersionedDependencies::new(since, deps),
                     hist,
                 );
+                new.mark_used(key.v);
 
-                let ret = new.computed_val();
+                let ret = new.computed_val().unwrap();
 
                 (
                     ret,
@@ -372,6 +557,13 @@ impl VersionedGraph {
         key: VersionedGraphKey,
         invalidate: InvalidateKind,
     ) -> bool {
+        let bytes_before = self.key_value_bytes(key.k);
+        let changed = self.invalidate_key(key, invalidate);
+        self.update_value_bytes(key.k, bytes_before);
+        changed
+    }
+
+    fn invalidate_key(&mut self, key: VersionedGraphKey, invalidate: InvalidateKind) -> bool {
         let rdeps = {
             match invalidate {
                 invalidate @ (InvalidateKind::ForceDirty | InvalidateKind::Invalidate) => {
@@ -433,7 +625,7 @@ impl VersionedGraph {
 
                         match entry {
                             Some(VersionedGraphNode::Occupied(occ)) => {
-                                if !occ.val().equality(&value) {
+                                if !occ.val().map_or(false, |old| old.equality(&value)) {
                                     occ.metadata()
                                         .rdeps
                                         .rdeps()
@@ -463,7 +655,8 @@ impl VersionedGraph {
                             value,
                             VersionedDependencies::new(since, Arc::new(vec![])),
                             hist,
-                        );
+                        )
+                        .pinned();
 
                         MapFixup::NewEntry {
                             since,
@@ -473,12 +666,15 @@ impl VersionedGraph {
                             num_to_keep,
                         }
                     } else {
-                        let entry = VersionedGraphNode::Occupied(OccupiedGraphNode::new(
-                            key.k,
-                            value,
-                            VersionedDependencies::new(key.v, Arc::new(vec![])),
-                            CellHistory::verified(key.v),
-                        ));
+                        let entry = VersionedGraphNode::Occupied(
+                            OccupiedGraphNode::new(
+                                key.k,
+                                value,
+                                VersionedDependencies::new(key.v, Arc::new(vec![])),
+                                CellHistory::verified(key.v),
+                            )
+                            .pinned(),
+                        );
 
                         versioned_map.insert(key.v, entry);
 
@@ -527,6 +723,20 @@ impl VersionedGraph {
     }
 }
 
+/// The fraction of the memory budget that eviction brings the graph down to once it is exceeded.
+const EVICTION_LOW_WATER_MARK: f64 = 0.9;
+
+/// The outcome of a call to `VersionedGraph::evict`
+#[derive(Default, Debug, PartialEq, Eq)]
+pub(crate) struct EvictionStats {
+    /// The number of values evicted
+    pub(crate) evicted: usize,
+    /// The memory held by the evicted values
+    pub(crate) reclaimed_bytes: usize,
+    /// The memory held by the values still in the graph
+    pub(crate) retained_bytes: usize,
+}
+
 pub(crate) enum ValueReusable {
     /// Directly compare the values for equality to determine if the node can be reused
     EqualityBased,
@@ -535,9 +745,26 @@ pub(crate) enum ValueReusable {
 }
 
 impl ValueReusable {
-    fn is_reusable(&self, new_value: &DiceValidValue, value: &OccupiedGraphNode) -> bool {
+    fn is_reusable(
+        &self,
+        new_value: &DiceValidValue,
+        value: &OccupiedGraphNode,
+        v: VersionNumber,
+    ) -> bool {
         match self {
-            ValueReusable::EqualityBased => new_value.equality(value.val()),
+            ValueReusable::EqualityBased => match value.val() {
+                Some(old) => new_value.equality(old),
+                // An evicted value can only be compared by its hash. Otherwise, if the node is
+                // verified at the version it was recomputed at, then the recomputation must have
+                // produced an equal value.
+                None => match value.evicted_hash() {
+                    Some(hash) if new_value.value_hash() == Some(hash) => true,
+                    _ => matches!(
+                        value.metadata().hist.get_history(&v),
+                        HistoryState::Verified
+                    ),
+                },
+            },
             ValueReusable::VersionBased(hist) => !hist
                 .intersect(&value.metadata().hist.get_verified_ranges())
                 .is_empty(),
@@ -654,6 +881,7 @@ pub(crate) mod testing {
     impl VersionedCacheResultAssertsExt for VersionedGraphResult {
         fn assert_compute(&self) {
             self.unpack_compute()
+                .map(|_| ())
                 .unwrap_or_else(|| panic!("expected Compute, but was {}", self.variant_name()))
         }
 
@@ -718,6 +946,294 @@ mod tests {
         }
     }
 
+    #[derive(Allocative, Clone, Dupe, Debug, Display, PartialEq, Eq, Hash)]
+    struct HashedK;
+
+    #[async_trait]
+    impl Key for HashedK {
+        type Value = usize;
+
+        async fn compute(
+            &self,
+            _ctx: &mut DiceComputations,
+            _cancellations: &CancellationContext,
+        ) -> Self::Value {
+            unimplemented!("test")
+        }
+
+        fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+            x == y
+        }
+
+        fn value_hash(x: &Self::Value) -> Option<u64> {
+            Some(*x as u64)
+        }
+    }
+
+    #[test]
+    fn evict_drops_cold_values_and_reinstates_them() {
+        let mut cache = VersionedGraph::with_value_sizes(true);
+        let value = |v| DiceValidValue::testing_new(DiceKeyValue::<K>::new(v));
+        let dep = DiceKey { index: 0 };
+        let rdep = DiceKey { index: 1 };
+        let injected = DiceKey { index: 2 };
+
+        cache.update(
+            VersionedGraphKey::new(VersionNumber::new(0), dep),
+            value(1),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![]),
+            StorageType::LastN(1),
+        );
+        cache.update(
+            VersionedGraphKey::new(VersionNumber::new(1), rdep),
+            value(2),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![dep]),
+            StorageType::LastN(1),
+        );
+        cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(0), injected),
+            InvalidateKind::Update(value(3), StorageType::LastN(1)),
+        );
+
+        // within budget, nothing is evicted
+        let stats = cache.evict(usize::MAX, VersionNumber::new(5));
+        assert_eq!(stats.evicted, 0);
+        assert!(stats.retained_bytes > 0);
+
+        // only values not used since the given version are evicted, and never injected ones
+        let stats = cache.evict(0, VersionNumber::new(1));
+        assert_eq!(stats.evicted, 1);
+        assert!(stats.reclaimed_bytes > 0);
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(1), dep))
+            .assert_compute();
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(1), rdep))
+            .assert_match();
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(1), injected))
+            .assert_match();
+
+        // invalidations still propagate through evicted nodes
+        assert!(cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(2), dep),
+            InvalidateKind::Invalidate
+        ));
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(2), rdep))
+            .assert_compute();
+
+        // recomputing at a version where the node is verified reinstates the value without
+        // recording a change
+        assert!(
+            !cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(1), dep),
+                    value(1),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+        assert!(
+            cache
+                .get(VersionedGraphKey::new(VersionNumber::new(1), dep))
+                .assert_match()
+                .value()
+                .equality(&value(1))
+        );
+
+        let stats = cache.evict(0, VersionNumber::new(5));
+        assert_eq!(stats.evicted, 2);
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(1), injected))
+            .assert_match();
+    }
+
+    #[test]
+    fn evicted_value_with_unchanged_deps_is_reused() {
+        let mut cache = VersionedGraph::with_value_sizes(true);
+        let value = |v| DiceValidValue::testing_new(DiceKeyValue::<K>::new(v));
+        let injected = DiceKey { index: 0 };
+        let dep = DiceKey { index: 1 };
+        let rdep = DiceKey { index: 2 };
+
+        cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(0), injected),
+            InvalidateKind::Update(value(1), StorageType::LastN(1)),
+        );
+        cache.update(
+            VersionedGraphKey::new(VersionNumber::new(0), dep),
+            value(10),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![injected]),
+            StorageType::LastN(1),
+        );
+        cache.update(
+            VersionedGraphKey::new(VersionNumber::new(0), rdep),
+            value(20),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![dep]),
+            StorageType::LastN(1),
+        );
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(1), dep))
+            .assert_match();
+
+        let stats = cache.evict(0, VersionNumber::new(1));
+        assert_eq!(stats.evicted, 1);
+
+        assert!(cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(2), injected),
+            InvalidateKind::Update(value(2), StorageType::LastN(1)),
+        ));
+        cache
+            .get(VersionedGraphKey::new(VersionNumber::new(2), rdep))
+            .assert_compute();
+
+        // the dep recomputes to an equal value, so the evicted rdep can't have changed either
+        assert!(
+            !cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(2), dep),
+                    value(10),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![injected]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+        assert!(
+            !cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(2), rdep),
+                    value(20),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![dep]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+        assert!(
+            cache
+                .get(VersionedGraphKey::new(VersionNumber::new(2), rdep))
+                .assert_match()
+                .value()
+                .equality(&value(20))
+        );
+
+        // once a dep changes, the recomputed value is recorded as a change
+        assert!(cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(3), injected),
+            InvalidateKind::Update(value(3), StorageType::LastN(1)),
+        ));
+        cache.evict(0, VersionNumber::new(3));
+        assert!(
+            cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(3), dep),
+                    value(11),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![injected]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+        assert!(
+            cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(3), rdep),
+                    value(21),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![dep]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+    }
+
+    #[test]
+    fn evicted_value_is_compared_by_hash() {
+        let mut cache = VersionedGraph::with_value_sizes(true);
+        let injected = DiceKey { index: 0 };
+        let hashed = DiceKey { index: 1 };
+        let unhashed = DiceKey { index: 2 };
+        let hashed_value = |v| DiceValidValue::testing_new(DiceKeyValue::<HashedK>::new(v));
+        let unhashed_value = |v| DiceValidValue::testing_new(DiceKeyValue::<K>::new(v));
+
+        cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(0), injected),
+            InvalidateKind::Update(unhashed_value(1), StorageType::LastN(1)),
+        );
+        cache.update(
+            VersionedGraphKey::new(VersionNumber::new(0), hashed),
+            hashed_value(10),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![injected]),
+            StorageType::LastN(1),
+        );
+        cache.update(
+            VersionedGraphKey::new(VersionNumber::new(0), unhashed),
+            unhashed_value(10),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![injected]),
+            StorageType::LastN(1),
+        );
+
+        let stats = cache.evict(0, VersionNumber::new(1));
+        assert_eq!(stats.evicted, 2);
+
+        assert!(cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(1), injected),
+            InvalidateKind::Update(unhashed_value(2), StorageType::LastN(1)),
+        ));
+
+        // the dep changed, but the recomputed value hashes the same as the evicted one
+        assert!(
+            !cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(1), hashed),
+                    hashed_value(10),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![injected]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+        // without a hash, there is nothing to compare against
+        assert!(
+            cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(1), unhashed),
+                    unhashed_value(10),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![injected]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+
+        assert!(cache.invalidate(
+            VersionedGraphKey::new(VersionNumber::new(2), injected),
+            InvalidateKind::Update(unhashed_value(3), StorageType::LastN(1)),
+        ));
+        cache.evict(0, VersionNumber::new(2));
+        assert!(
+            cache
+                .update(
+                    VersionedGraphKey::new(VersionNumber::new(2), hashed),
+                    hashed_value(11),
+                    ValueReusable::EqualityBased,
+                    Arc::new(vec![injected]),
+                    StorageType::LastN(1),
+                )
+                .1
+        );
+    }
+
     #[test]
     fn latest_only_stores_latest_only() {
         let mut cache = VersionedGraph::new();
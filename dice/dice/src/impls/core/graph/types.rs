@@ -51,7 +51,10 @@ pub(crate) enum VersionedGraphResult {
     CheckDeps(VersionedGraphResultMismatch),
     /// An entry that is known to require re-evaluation because it was marked as dirty at the
     /// requested version or that it was missing
-    Compute,
+    Compute {
+        /// Whether the key had been computed before, as opposed to never being computed.
+        recompute: bool,
+    },
 }
 
 #[cfg(test)]
@@ -72,6 +75,7 @@ pub(crate) mod testing {
     impl VersionedCacheResultAssertsExt for VersionedGraphResult {
         fn assert_compute(&self) {
             self.unpack_compute()
+                .map(|_| ())
                 .unwrap_or_else(|| panic!("expected Compute, but was {}", self.variant_name()))
         }
         fn assert_match(&self) -> &DiceComputedValue {
@@ -37,14 +37,26 @@ pub(super) struct CoreState {
     version_tracker: VersionTracker,
     graph: VersionedGraph,
     pending_termination_tasks: Vec<DiceTask>,
+    /// The memory that values in the graph may hold before cold ones are evicted
+    memory_budget: Option<usize>,
+    evicted_value_count: u64,
+    evicted_value_bytes: u64,
 }
 
 impl CoreState {
+    #[cfg(test)]
     pub(super) fn new() -> Self {
+        Self::with_memory_budget(None)
+    }
+
+    pub(super) fn with_memory_budget(memory_budget: Option<usize>) -> Self {
         Self {
             version_tracker: VersionTracker::new(),
-            graph: VersionedGraph::new(),
+            graph: VersionedGraph::with_value_sizes(memory_budget.is_some()),
             pending_termination_tasks: Vec::new(),
+            memory_budget,
+            evicted_value_count: 0,
+            evicted_value_bytes: 0,
         }
     }
 
@@ -68,12 +80,30 @@ impl CoreState {
             );
         }
         if changes_recorded {
-            version_update.commit()
+            let v = version_update.commit();
+            self.maybe_evict();
+            v
         } else {
             version_update.undo()
         }
     }
 
+    /// Evicts values that weren't used by the previous version nor by any ongoing transaction,
+    /// if the graph holds more than the memory budget.
+    fn maybe_evict(&mut self) {
+        if let Some(budget) = self.memory_budget {
+            let mut keep_since = self.version_tracker.current();
+            keep_since.dec();
+            if let Some(oldest_active) = self.version_tracker.oldest_active() {
+                keep_since = std::cmp::min(keep_since, oldest_active);
+            }
+
+            let stats = self.graph.evict(budget, keep_since);
+            self.evicted_value_count += stats.evicted as u64;
+            self.evicted_value_bytes += stats.reclaimed_bytes as u64;
+        }
+    }
+
     pub(super) fn ctx_at_version(&mut self, v: VersionNumber) -> (VersionEpoch, SharedCache) {
         self.version_tracker.at(v)
     }
@@ -127,9 +157,16 @@ impl CoreState {
         self.version_tracker.write().commit();
 
         // Do the actual drop on a different thread because we may have to drop a lot of stuff
-        // here.
-        let map = std::mem::take(&mut self.graph.last_n);
-        std::thread::spawn(move || drop(map));
+        // here. Replacing the whole graph also resets the size of the values it holds.
+        let graph = std::mem::replace(
+            &mut self.graph,
+            VersionedGraph::with_value_sizes(self.memory_budget.is_some()),
+        );
+        std::thread::spawn(move || drop(graph));
+    }
+
+    pub(super) fn current_values(&self) -> Vec<(DiceKey, DiceValidValue)> {
+        self.graph.current_values(self.version_tracker.current())
     }
 
     pub(super) fn metrics(&self) -> Metrics {
@@ -146,6 +183,8 @@ impl CoreState {
             key_count: self.graph.last_n.len(),
             currently_active_key_count: currently_running_key_count,
             active_transaction_count: active_transaction_count as u32, // probably won't support more than u32 transactions
+            evicted_value_count: self.evicted_value_count,
+            evicted_value_bytes: self.evicted_value_bytes,
         }
     }
 
@@ -172,9 +211,12 @@ mod tests {
 
     use crate::api::computations::DiceComputations;
     use crate::api::key::Key;
+    use crate::api::storage_type::StorageType;
     use crate::arc::Arc;
     use crate::impls::cache::DiceTaskRef;
     use crate::impls::core::graph::history::CellHistory;
+    use crate::impls::core::graph::storage::ValueReusable;
+    use crate::impls::core::graph::types::VersionedGraphKey;
     use crate::impls::core::internals::CoreState;
     use crate::impls::key::DiceKey;
     use crate::impls::key::ParentKey;
@@ -397,6 +439,39 @@ mod tests {
         assert_eq!(core.get_tasks_pending_cancellation().len(), 2);
     }
 
+    #[test]
+    fn drop_everything_resets_value_sizes() {
+        let value = |v| DiceValidValue::testing_new(DiceKeyValue::<K>::new(v));
+        // room for a single value
+        let mut core = CoreState::with_memory_budget(Some(value(0).unique_allocated_bytes()));
+
+        core.update_state((10..13).map(|index| {
+            (
+                DiceKey { index },
+                ChangeType::UpdateValue(value(index as usize), StorageType::LastN(1)),
+            )
+        }));
+        core.unstable_drop_everything();
+
+        let v = core.current_version();
+        let (epoch, _ctx) = core.ctx_at_version(v);
+        core.update_computed(
+            VersionedGraphKey::new(v, DiceKey { index: 0 }),
+            epoch,
+            StorageType::LastN(1),
+            value(0),
+            ValueReusable::EqualityBased,
+            Arc::new(vec![]),
+        )
+        .unwrap();
+        core.drop_ctx_at_version(v);
+
+        // the dropped values no longer count towards the budget, so the cold value is kept
+        core.update_state([(DiceKey { index: 1 }, ChangeType::Invalidate)]);
+        core.update_state([(DiceKey { index: 2 }, ChangeType::Invalidate)]);
+        assert_eq!(core.metrics().evicted_value_count, 0);
+    }
+
     #[derive(Allocative, Clone, Debug, Display, Eq, PartialEq, Hash)]
     struct K;
 
@@ -21,9 +21,9 @@ pub(super) struct StateProcessor {
 }
 
 impl StateProcessor {
-    pub(super) fn spawn() -> CoreStateHandle {
+    pub(super) fn spawn(memory_budget: Option<usize>) -> CoreStateHandle {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-        let state = CoreState::new();
+        let state = CoreState::with_memory_budget(memory_budget);
 
         std::thread::Builder::new()
             .name("buck2-dice".to_owned())
@@ -115,6 +115,9 @@ impl StateProcessor {
             StateRequest::Metrics { resp } => {
                 let _ignored = resp.send(self.state.metrics());
             }
+            StateRequest::CurrentValues { resp } => {
+                let _ignored = resp.send(self.state.current_values());
+            }
             StateRequest::Introspection { resp } => {
                 let _ignored = resp.send(self.state.introspection());
             }
@@ -93,6 +93,11 @@ pub(crate) enum StateRequest {
     UnstableDropEverything,
     /// Collect metrics
     Metrics { resp: Sender<Metrics> },
+    /// Collects the values that are up to date at the current version
+    CurrentValues {
+        #[derivative(Debug = "ignore")]
+        resp: Sender<Vec<(DiceKey, DiceValidValue)>>,
+    },
     /// Collects the introspectable dice state
     Introspection {
         #[derivative(Debug = "ignore")]
@@ -120,7 +125,7 @@ impl CoreStateHandle {
 
 impl Dupe for CoreStateHandle {}
 
-/// Start processing state
-pub(crate) fn init_state() -> CoreStateHandle {
-    StateProcessor::spawn()
+/// Start processing state, evicting cold values when they hold more than `memory_budget` bytes
+pub(crate) fn init_state(memory_budget: Option<usize>) -> CoreStateHandle {
+    StateProcessor::spawn(memory_budget)
 }
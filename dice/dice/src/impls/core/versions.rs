@@ -79,6 +79,11 @@ impl VersionTracker {
             .map(|data| (data.ref_count, &data.per_transaction_data))
     }
 
+    /// The oldest version that any transaction is currently at
+    pub(crate) fn oldest_active(&self) -> Option<VersionNumber> {
+        self.active_versions.keys().min().copied()
+    }
+
     /// hands out the current "latest" committed version's associated transaction context
     pub(crate) fn current(&self) -> VersionNumber {
         self.current
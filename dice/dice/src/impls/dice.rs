@@ -16,6 +16,7 @@ use dupe::Dupe;
 
 use crate::api::cycles::DetectCycles;
 use crate::api::data::DiceData;
+use crate::api::key::Key;
 use crate::api::user_data::UserComputationData;
 use crate::impls::core::state::init_state;
 use crate::impls::core::state::CoreStateHandle;
@@ -39,25 +40,43 @@ impl Debug for DiceModern {
     }
 }
 
-pub(crate) struct DiceModernDataBuilder(DiceData);
+pub(crate) struct DiceModernDataBuilder {
+    data: DiceData,
+    memory_budget: Option<usize>,
+}
 
 impl DiceModernDataBuilder {
     pub(crate) fn new() -> Self {
-        Self(DiceData::new())
+        Self {
+            data: DiceData::new(),
+            memory_budget: None,
+        }
     }
 
     pub fn set<K: Send + Sync + 'static>(&mut self, val: K) {
-        self.0.set(val);
+        self.data.set(val);
+    }
+
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(bytes);
     }
 
     pub fn build(self, _detect_cycles: DetectCycles) -> Arc<DiceModern> {
-        DiceModern::new(self.0)
+        DiceModern::with_memory_budget(self.data, self.memory_budget)
     }
 }
 
 impl DiceModern {
+    #[cfg(test)]
     pub(crate) fn new(global_data: DiceData) -> Arc<Self> {
-        let state_handle = init_state();
+        Self::with_memory_budget(global_data, None)
+    }
+
+    pub(crate) fn with_memory_budget(
+        global_data: DiceData,
+        memory_budget: Option<usize>,
+    ) -> Arc<Self> {
+        let state_handle = init_state(memory_budget);
 
         Arc::new(DiceModern {
             key_index: Default::default(),
@@ -90,6 +109,23 @@ impl DiceModern {
         tokio::task::block_in_place(|| rx.blocking_recv().unwrap())
     }
 
+    pub fn current_values<K: Key>(&self) -> Vec<(K, K::Value)> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        self.state_handle
+            .request(StateRequest::CurrentValues { resp: tx });
+
+        let values = tokio::task::block_in_place(|| rx.blocking_recv().unwrap());
+        values
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let key = self.key_index.get(key).as_any().downcast_ref::<K>()?;
+                let value = value.downcast_ref::<K::Value>()?;
+                Some((key.clone(), value.dupe()))
+            })
+            .collect()
+    }
+
     pub fn to_introspectable(&self) -> GraphIntrospectable {
         let (tx, rx) = tokio::sync::oneshot::channel();
 
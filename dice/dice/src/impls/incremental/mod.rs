@@ -188,9 +188,14 @@ impl IncrementalEngine {
 
         match state_result {
             VersionedGraphResult::Match(entry) => task_state.lookup_matches(entry),
-            VersionedGraphResult::Compute => {
-                self.compute(k, eval, &events_dispatcher, task_state.lookup_dirtied(eval))
-                    .await
+            VersionedGraphResult::Compute { recompute } => {
+                self.compute(
+                    k,
+                    eval,
+                    &events_dispatcher,
+                    task_state.lookup_dirtied(eval, recompute),
+                )
+                .await
             }
 
             VersionedGraphResult::CheckDeps(mismatch) => {
@@ -22,6 +22,7 @@ use dupe::Dupe;
 use fnv::FnvHasher;
 use more_futures::cancellation::CancellationContext;
 
+use crate::api::activation_tracker::KeyId;
 use crate::api::computations::DiceComputations;
 use crate::api::key::Key;
 use crate::api::projection::DiceProjectionComputations;
@@ -85,6 +86,13 @@ impl DiceKeyErased {
         }
     }
 
+    pub(crate) fn key_id(&self) -> KeyId {
+        match self {
+            DiceKeyErased::Key(k) => k.key_id(),
+            DiceKeyErased::Projection(proj) => KeyId::new(&(proj.base, proj.proj.key_id())),
+        }
+    }
+
     pub(crate) fn as_any(&self) -> &dyn Any {
         match self {
             DiceKeyErased::Key(k) => k.as_any(),
@@ -264,6 +272,8 @@ pub(crate) trait DiceKeyDyn: Allocative + Display + Send + Sync + 'static {
 
     fn hash(&self) -> u64;
 
+    fn key_id(&self) -> KeyId;
+
     fn as_any(&self) -> &dyn Any;
 
     fn clone_arc(&self) -> Arc<dyn DiceKeyDyn>;
@@ -295,6 +305,10 @@ where
         hash::key_hash(self)
     }
 
+    fn key_id(&self) -> KeyId {
+        KeyId::new(self)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -323,6 +337,8 @@ pub(crate) trait DiceProjectionDyn: Allocative + Display + Send + Sync + 'static
 
     fn hash(&self) -> u64;
 
+    fn key_id(&self) -> KeyId;
+
     fn as_any(&self) -> &dyn Any;
 
     fn clone_arc(&self) -> Arc<dyn DiceProjectionDyn>;
@@ -358,6 +374,10 @@ where
         hash::key_hash(self)
     }
 
+    fn key_id(&self) -> KeyId {
+        KeyId::new(self)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
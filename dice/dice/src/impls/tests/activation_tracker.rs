@@ -24,19 +24,27 @@ use crate::api::key::Key;
 use crate::api::user_data::UserComputationData;
 use crate::ActivationData;
 use crate::ActivationTracker;
+use crate::ChangedKey;
 use crate::DiceDataBuilder;
+use crate::EvaluatedKey;
 use crate::InjectedKey;
+use crate::KeyId;
 
 #[derive(Default, Allocative)]
 struct Tracker {
     /// Key, deps, data, reused
     state: Mutex<Vec<(Kind, Vec<Kind>, Option<Data>, bool)>>,
+    changed: Mutex<Vec<ChangedKey>>,
+    /// Evaluated key as displayed, deps, recomputed
+    evaluated: Mutex<Vec<(String, Vec<KeyId>, bool)>>,
 }
 
 impl Tracker {
     fn new() -> Self {
         Self {
             state: Mutex::new(Vec::new()),
+            changed: Mutex::new(Vec::new()),
+            evaluated: Mutex::new(Vec::new()),
         }
     }
 }
@@ -60,6 +68,18 @@ impl ActivationTracker for Tracker {
             reused,
         ));
     }
+
+    fn keys_changed(&self, changed: &[ChangedKey]) {
+        self.changed.lock().unwrap().extend(changed.iter().cloned());
+    }
+
+    fn key_evaluated(&self, key: EvaluatedKey, deps: &mut dyn Iterator<Item = KeyId>) {
+        self.evaluated.lock().unwrap().push((
+            format!("{}: {}", key.type_name, key.key),
+            deps.collect(),
+            key.recomputed,
+        ));
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Dupe, Clone, Allocative)]
@@ -171,6 +191,30 @@ async fn test_events_impl(builder: DiceDataBuilder) -> anyhow::Result<()> {
                 (Kind::Stage1, vec![Kind::Stage0], Some(Data), false),
             ]
         );
+        assert_eq!(
+            &*activation_tracker.changed.lock().unwrap(),
+            &[ChangedKey {
+                type_name: "Injected",
+                id: KeyId::new(&Injected),
+                key: "Injected".to_owned(),
+                injected: true,
+            }]
+        );
+        assert_eq!(
+            &*activation_tracker.evaluated.lock().unwrap(),
+            &[
+                (
+                    "Stage0: Stage0".to_owned(),
+                    vec![KeyId::new(&Injected)],
+                    false
+                ),
+                (
+                    "Stage1: Stage1".to_owned(),
+                    vec![KeyId::new(&Stage0)],
+                    false
+                ),
+            ]
+        );
     }
 
     {
@@ -195,6 +239,14 @@ async fn test_events_impl(builder: DiceDataBuilder) -> anyhow::Result<()> {
                 (Kind::Stage1, vec![Kind::Stage0], None, true),
             ]
         );
+        assert_eq!(
+            &*activation_tracker.evaluated.lock().unwrap(),
+            &[(
+                "Stage0: Stage0".to_owned(),
+                vec![KeyId::new(&Injected)],
+                true
+            )]
+        );
     }
 
     Ok(())
@@ -209,3 +261,30 @@ async fn test_events_legacy() -> anyhow::Result<()> {
 async fn test_events_modern() -> anyhow::Result<()> {
     test_events_impl(Dice::modern()).await
 }
+
+#[tokio::test]
+async fn test_changed_key_ids_modern() -> anyhow::Result<()> {
+    let dice = Dice::modern().build(DetectCycles::Enabled);
+    let activation_tracker = Arc::new(Tracker::new());
+
+    let mut updater = dice.updater();
+    updater.changed(vec![Stage0])?;
+    updater
+        .commit_with_data(UserComputationData {
+            activation_tracker: Some(activation_tracker.dupe()),
+            ..Default::default()
+        })
+        .await;
+
+    assert_eq!(
+        &*activation_tracker.changed.lock().unwrap(),
+        &[ChangedKey {
+            type_name: "Stage0",
+            id: KeyId::new(&Stage0),
+            key: "Stage0".to_owned(),
+            injected: false,
+        }]
+    );
+
+    Ok(())
+}
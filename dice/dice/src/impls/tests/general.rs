@@ -600,6 +600,46 @@ async fn user_cycle_detector_is_present_modern() -> anyhow::Result<()> {
     user_cycle_detector_is_present(Dice::modern().build(DetectCycles::Disabled)).await
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn current_values_legacy() -> anyhow::Result<()> {
+    current_values(Dice::builder().build(DetectCycles::Disabled)).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn current_values_modern() -> anyhow::Result<()> {
+    current_values(Dice::modern().build(DetectCycles::Disabled)).await
+}
+
+async fn current_values(dice: Arc<Dice>) -> anyhow::Result<()> {
+    let ctx = dice.updater().commit().await;
+    assert_eq!(ctx.compute(&K(2)).await?.unwrap(), K(3));
+    drop(ctx);
+
+    let mut values = dice.current_values::<K>();
+    values.sort_by_key(|(k, _)| k.0);
+    assert_eq!(
+        values
+            .into_iter()
+            .map(|(k, v)| (k, v.unwrap()))
+            .collect::<Vec<_>>(),
+        vec![(K(0), K(0)), (K(1), K(1)), (K(2), K(3))]
+    );
+
+    // Invalidating K(1) also invalidates K(2), which depends on it.
+    let mut updater = dice.updater();
+    updater.changed(vec![K(1)])?;
+    drop(updater.commit().await);
+
+    let values = dice.current_values::<K>();
+    assert_eq!(
+        values.into_iter().map(|(k, _)| k).collect::<Vec<_>>(),
+        vec![K(0)]
+    );
+    assert!(dice.current_values::<Foo>().is_empty());
+
+    Ok(())
+}
+
 async fn user_cycle_detector_is_present(dice: Arc<Dice>) -> anyhow::Result<()> {
     #[derive(Clone, Copy, Dupe, Display, Debug, Eq, PartialEq, Hash, Allocative)]
     #[display(fmt = "{:?}", self)]
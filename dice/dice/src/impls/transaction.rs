@@ -14,6 +14,7 @@ use derivative::Derivative;
 use dupe::Dupe;
 use tokio::sync::oneshot;
 
+use crate::api::activation_tracker::ChangedKey;
 use crate::api::error::DiceError;
 use crate::api::error::DiceResult;
 use crate::api::key::Key;
@@ -94,7 +95,8 @@ impl TransactionUpdater {
         let user_data = self.user_data.dupe();
         let dice = self.dice.dupe();
 
-        let (transaction, guard) = self.commit_to_state().await;
+        let (transaction, guard, changed_keys) = self.commit_to_state().await;
+        report_changed_keys(&dice, &user_data, &changed_keys);
 
         BaseComputeCtx::new(transaction, user_data, dice, guard)
     }
@@ -104,7 +106,8 @@ impl TransactionUpdater {
     pub(crate) async fn commit_with_data(self, extra: UserComputationData) -> BaseComputeCtx {
         let dice = self.dice.dupe();
 
-        let (transaction, guard) = self.commit_to_state().await;
+        let (transaction, guard, changed_keys) = self.commit_to_state().await;
+        report_changed_keys(&dice, &extra, &changed_keys);
 
         BaseComputeCtx::new(transaction, Arc::new(extra), dice, guard)
     }
@@ -134,7 +137,13 @@ impl TransactionUpdater {
             .request(StateRequest::UnstableDropEverything)
     }
 
-    async fn commit_to_state(self) -> (SharedLiveTransactionCtx, ActiveTransactionGuard) {
+    async fn commit_to_state(
+        self,
+    ) -> (
+        SharedLiveTransactionCtx,
+        ActiveTransactionGuard,
+        Vec<(DiceKey, bool)>,
+    ) {
         let (tx, rx) = oneshot::channel();
         self.dice.state_handle.request(StateRequest::UpdateState {
             changes: self.scheduled_changes.changes.into_iter().collect(),
@@ -150,7 +159,32 @@ impl TransactionUpdater {
             resp: tx,
         });
 
-        rx.await.unwrap()
+        let (transaction, guard) = rx.await.unwrap();
+        (transaction, guard, self.scheduled_changes.changed_keys)
+    }
+}
+
+/// Report the changed keys to the activation tracker, if any. Keys are only formatted here, so
+/// transactions pay nothing for this when there is no tracker.
+fn report_changed_keys(
+    dice: &DiceModern,
+    user_data: &UserComputationData,
+    changed_keys: &[(DiceKey, bool)],
+) {
+    if let Some(activation_tracker) = &user_data.activation_tracker {
+        let changed_keys = changed_keys
+            .iter()
+            .map(|(key, injected)| {
+                let key = dice.key_index.get(*key);
+                ChangedKey {
+                    type_name: key.key_type_name(),
+                    id: key.key_id(),
+                    key: key.to_string(),
+                    injected: *injected,
+                }
+            })
+            .collect::<Vec<_>>();
+        activation_tracker.keys_changed(&changed_keys);
     }
 }
 
@@ -182,6 +216,8 @@ impl Drop for ActiveTransactionGuardInner {
 #[derive(Allocative)]
 struct Changes {
     changes: HashMap<DiceKey, ChangeType>,
+    /// The changed keys in the order they were recorded, and whether each was injected.
+    changed_keys: Vec<(DiceKey, bool)>,
     dice: Arc<DiceModern>,
 }
 
@@ -189,17 +225,20 @@ impl Changes {
     pub(crate) fn new(dice: Arc<DiceModern>) -> Self {
         Self {
             changes: HashMap::default(),
+            changed_keys: Vec::new(),
             dice,
         }
     }
 
     pub(crate) fn change<K: Key>(&mut self, key: K, change: ChangeType) -> DiceResult<()> {
+        let injected = matches!(change, ChangeType::UpdateValue(..));
         let key = self.dice.key_index.index_key(key);
         if self.changes.insert(key, change).is_some() {
             Err(DiceError::duplicate(
                 self.dice.key_index.get(key).dupe().downcast::<K>().unwrap(),
             ))
         } else {
+            self.changed_keys.push((key, injected));
             Ok(())
         }
     }
@@ -31,7 +31,6 @@ impl Debug for DiceValidValue {
 }
 
 impl DiceValidValue {
-    #[cfg(test)]
     pub(crate) fn downcast_ref<V: Any>(&self) -> Option<&V> {
         self.0.downcast_ref()
     }
@@ -40,6 +39,18 @@ impl DiceValidValue {
     pub(crate) fn equality(&self, other: &DiceValidValue) -> bool {
         self.0.equality(&*other.0)
     }
+
+    /// Dynamic version of `Key::value_hash`.
+    pub(crate) fn value_hash(&self) -> Option<u64> {
+        self.0.value_hash()
+    }
+
+    /// Memory owned by this value alone, as measured by allocative. Memory behind shared
+    /// pointers isn't counted since it may be retained by other values too.
+    pub(crate) fn unique_allocated_bytes(&self) -> usize {
+        let value = self.0.as_allocative();
+        std::mem::size_of_val(value) + allocative::size_of_unique_allocated_data(value)
+    }
 }
 
 /// Type erased value that may be transient, or whose dependencies are transient
@@ -150,6 +161,8 @@ pub(crate) trait DiceValueDyn: Allocative + Any + Send + Sync + 'static {
     /// Panics if called with incompatible values.
     fn equality(&self, other: &dyn DiceValueDyn) -> bool;
     fn validity(&self) -> bool;
+    fn value_hash(&self) -> Option<u64>;
+    fn as_allocative(&self) -> &dyn Allocative;
 }
 
 impl dyn DiceValueDyn {
@@ -187,6 +200,14 @@ where
     fn validity(&self) -> bool {
         K::validity(&self.value)
     }
+
+    fn value_hash(&self) -> Option<u64> {
+        K::value_hash(&self.value)
+    }
+
+    fn as_allocative(&self) -> &dyn Allocative {
+        self
+    }
 }
 
 #[derive(Allocative)]
@@ -218,6 +239,14 @@ where
     fn validity(&self) -> bool {
         K::validity(&self.value)
     }
+
+    fn value_hash(&self) -> Option<u64> {
+        K::value_hash(&self.value)
+    }
+
+    fn as_allocative(&self) -> &dyn Allocative {
+        self
+    }
 }
 
 #[cfg(test)]
@@ -29,6 +29,7 @@ use crate::result::CancellableResult;
 use crate::result::Cancelled;
 use crate::ActivationData;
 use crate::ActivationTracker;
+use crate::EvaluatedKey;
 
 /// Represents when we are in a spawned dice task worker and are currently waiting for the previous
 /// cancelled instance of this task to finish cancelling.
@@ -143,7 +144,12 @@ impl<'a, 'b> DiceWorkerStateLookupNode<'a, 'b> {
         }
     }
 
-    pub(crate) fn lookup_dirtied(self, eval: &AsyncEvaluator) -> DiceWorkerStateComputing<'a, 'b> {
+    /// `recompute` is whether the key had been computed before.
+    pub(crate) fn lookup_dirtied(
+        self,
+        eval: &AsyncEvaluator,
+        recompute: bool,
+    ) -> DiceWorkerStateComputing<'a, 'b> {
         debug!(msg = "lookup requires recompute.");
 
         self.internals.computing();
@@ -157,6 +163,7 @@ impl<'a, 'b> DiceWorkerStateLookupNode<'a, 'b> {
         DiceWorkerStateComputing {
             cycles,
             internals: self.internals,
+            recompute,
         }
     }
 
@@ -199,6 +206,7 @@ impl<'a, 'b> DiceWorkerStateCheckingDeps<'a, 'b> {
         DiceWorkerStateComputing {
             cycles: self.cycles,
             internals: self.internals,
+            recompute: true,
         }
     }
 
@@ -224,6 +232,7 @@ impl<'a, 'b> DiceWorkerStateCheckingDeps<'a, 'b> {
             _prevent_cancellation: guard,
             internals: self.internals,
             activation_info,
+            recompute: true,
         })
     }
 
@@ -240,6 +249,8 @@ impl<'a, 'b> DiceWorkerStateCheckingDeps<'a, 'b> {
 pub(crate) struct DiceWorkerStateComputing<'a, 'b> {
     cycles: KeyComputingUserCycleDetectorData,
     internals: &'a mut DiceTaskHandle<'b>,
+    /// Whether the key had been computed before.
+    recompute: bool,
 }
 
 impl<'a, 'b> DiceWorkerStateComputing<'a, 'b> {
@@ -253,6 +264,7 @@ impl<'a, 'b> DiceWorkerStateComputing<'a, 'b> {
             self.cycles,
             DiceWorkerStateEvaluating {
                 internals: self.internals,
+                recompute: self.recompute,
             },
         )
     }
@@ -261,6 +273,7 @@ impl<'a, 'b> DiceWorkerStateComputing<'a, 'b> {
 /// When the spawned dice worker is currently actively evaluating the `Key::compute` function
 pub(crate) struct DiceWorkerStateEvaluating<'a, 'b> {
     internals: &'a mut DiceTaskHandle<'b>,
+    recompute: bool,
 }
 
 impl<'a, 'b> DiceWorkerStateEvaluating<'a, 'b> {
@@ -295,6 +308,7 @@ impl<'a, 'b> DiceWorkerStateEvaluating<'a, 'b> {
                 _prevent_cancellation: guard,
                 internals: self.internals,
                 activation_info,
+                recompute: self.recompute,
             },
             result,
         })
@@ -314,6 +328,8 @@ pub(crate) struct DiceWorkerStateFinished<'a, 'b> {
     _prevent_cancellation: DisableCancellationGuard,
     internals: &'a mut DiceTaskHandle<'b>,
     activation_info: Option<ActivationInfo>,
+    /// Whether the key had been computed before, reported if it was evaluated.
+    recompute: bool,
 }
 
 impl<'a, 'b> DiceWorkerStateFinished<'a, 'b> {
@@ -321,6 +337,17 @@ impl<'a, 'b> DiceWorkerStateFinished<'a, 'b> {
         debug!(msg = "Update caches complete");
 
         if let Some(activation_info) = self.activation_info.take() {
+            if let ActivationData::Evaluated(..) = activation_info.activation_data {
+                activation_info.activation_tracker.key_evaluated(
+                    EvaluatedKey {
+                        type_name: activation_info.key.key_type_name(),
+                        id: activation_info.key.key_id(),
+                        key: Arc::new(activation_info.key.dupe()),
+                        recomputed: self.recompute,
+                    },
+                    &mut activation_info.deps.iter().map(|k| k.key_id()),
+                );
+            }
             activation_info.activation_tracker.key_activated(
                 activation_info.key.as_any(),
                 &mut activation_info.deps.iter().map(|k| k.as_any()),
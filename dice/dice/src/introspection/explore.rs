@@ -0,0 +1,361 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Answers questions about a DICE graph, such as why a key is dirty or what depends on it, from
+//! the nodes produced by `serialize_dense_graph`.
+
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+
+use crate::introspection::graph::HistoryState;
+use crate::introspection::graph::KeyID;
+use crate::introspection::graph::SerializedGraphNode;
+use crate::introspection::graph::SerializedGraphNodesForKey;
+use crate::introspection::graph::VersionNumber;
+use crate::HashMap;
+use crate::HashSet;
+
+/// The state of a key as of the last version it was recorded at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    /// The value of the key was computed or verified at this version.
+    Verified(VersionNumber),
+    /// The key was invalidated at this version, and hasn't been recomputed since.
+    Dirty {
+        version: VersionNumber,
+        forced: bool,
+    },
+    /// We have no value or history for this key.
+    Unknown,
+}
+
+/// Why a key is dirty: the deps that were invalidated along with it. A key with no dirty deps
+/// was itself changed or injected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirtyReason {
+    pub key: KeyID,
+    pub version: VersionNumber,
+    pub dirty_deps: Vec<DirtyReason>,
+    /// Whether this key was already explained elsewhere in the tree, in which case its deps are
+    /// omitted.
+    pub repeated: bool,
+}
+
+/// An index over the nodes of a DICE graph.
+pub struct GraphExplorer {
+    keys: Vec<SerializedGraphNodesForKey>,
+    by_id: HashMap<KeyID, usize>,
+    rdeps: HashMap<KeyID, Vec<KeyID>>,
+    latest_version: Option<VersionNumber>,
+}
+
+impl GraphExplorer {
+    pub fn new(keys: Vec<SerializedGraphNodesForKey>) -> Self {
+        let by_id = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (k.id, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut rdeps = HashMap::<KeyID, Vec<KeyID>>::default();
+        let mut latest_version = None;
+        for key in &keys {
+            if let Some(node) = latest_node(key) {
+                for dep in node.deps.iter().flatten() {
+                    rdeps.entry(*dep).or_default().push(key.id);
+                }
+            }
+            for (version, node) in &key.nodes {
+                let history = node.iter().flat_map(|n| n.history.history.keys());
+                latest_version = latest_version.max(history.chain([version]).max().copied());
+            }
+        }
+        for dependents in rdeps.values_mut() {
+            dependents.sort_by_key(|k| k.0);
+        }
+
+        Self {
+            keys,
+            by_id,
+            rdeps,
+            latest_version,
+        }
+    }
+
+    pub fn key(&self, id: KeyID) -> Option<&SerializedGraphNodesForKey> {
+        self.by_id.get(&id).map(|i| &self.keys[*i])
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// The most recent version recorded anywhere in the graph.
+    pub fn latest_version(&self) -> Option<VersionNumber> {
+        self.latest_version
+    }
+
+    /// Keys whose display form is exactly `pattern` or, if there are none, contains it.
+    pub fn find(&self, pattern: &str) -> Vec<KeyID> {
+        let exact = self.keys_matching(|k| k.key == pattern);
+        if !exact.is_empty() {
+            return exact;
+        }
+        self.keys_matching(|k| k.key.contains(pattern))
+    }
+
+    fn keys_matching(&self, f: impl Fn(&SerializedGraphNodesForKey) -> bool) -> Vec<KeyID> {
+        let mut res = self
+            .keys
+            .iter()
+            .filter(|k| f(k))
+            .map(|k| k.id)
+            .collect::<Vec<_>>();
+        res.sort_by_key(|k| k.0);
+        res
+    }
+
+    pub fn state(&self, id: KeyID) -> KeyState {
+        let last = self
+            .key(id)
+            .and_then(latest_node)
+            .and_then(|n| n.history.history.iter().next_back());
+        match last {
+            Some((v, HistoryState::Verified)) => KeyState::Verified(*v),
+            Some((v, HistoryState::Dirty)) => KeyState::Dirty {
+                version: *v,
+                forced: false,
+            },
+            Some((v, HistoryState::ForceDirty)) => KeyState::Dirty {
+                version: *v,
+                forced: true,
+            },
+            None => KeyState::Unknown,
+        }
+    }
+
+    /// The keys this key depended on when it was last computed.
+    pub fn deps(&self, id: KeyID) -> Vec<KeyID> {
+        let mut deps = self
+            .key(id)
+            .and_then(latest_node)
+            .and_then(|n| n.deps.as_ref())
+            .map(|deps| deps.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        deps.sort_by_key(|k| k.0);
+        deps
+    }
+
+    /// Explain why a key is dirty, or `None` if it isn't. The explanation is a tree of the deps
+    /// that were invalidated at the same version, down to the keys that were changed.
+    pub fn why_dirty(&self, id: KeyID) -> Option<DirtyReason> {
+        match self.state(id) {
+            KeyState::Dirty { version, .. } => {
+                Some(self.dirty_reason(id, version, &mut HashSet::default()))
+            }
+            KeyState::Verified(..) | KeyState::Unknown => None,
+        }
+    }
+
+    fn dirty_reason(
+        &self,
+        id: KeyID,
+        version: VersionNumber,
+        seen: &mut HashSet<KeyID>,
+    ) -> DirtyReason {
+        if !seen.insert(id) {
+            return DirtyReason {
+                key: id,
+                version,
+                dirty_deps: Vec::new(),
+                repeated: true,
+            };
+        }
+
+        let dirty_deps = self
+            .deps(id)
+            .into_iter()
+            .filter(|dep| self.is_dirty_at(*dep, version))
+            .map(|dep| self.dirty_reason(dep, version, seen))
+            .collect();
+
+        DirtyReason {
+            key: id,
+            version,
+            dirty_deps,
+            repeated: false,
+        }
+    }
+
+    fn is_dirty_at(&self, id: KeyID, version: VersionNumber) -> bool {
+        match self.state(id) {
+            KeyState::Dirty { version: v, .. } => v == version,
+            KeyState::Verified(..) | KeyState::Unknown => false,
+        }
+    }
+
+    /// All the keys that transitively depend on this key, with their distance from it, closest
+    /// first.
+    pub fn rdeps(&self, id: KeyID) -> Vec<(KeyID, usize)> {
+        let mut res = Vec::new();
+        let mut seen = HashSet::default();
+        seen.insert(id);
+        let mut queue = VecDeque::from([(id, 0)]);
+        while let Some((key, depth)) = queue.pop_front() {
+            for rdep in self.rdeps.get(&key).into_iter().flatten() {
+                if seen.insert(*rdep) {
+                    res.push((*rdep, depth + 1));
+                    queue.push_back((*rdep, depth + 1));
+                }
+            }
+        }
+        res
+    }
+
+    /// The keys that were computed at the given version, as opposed to being reused or verified
+    /// unchanged.
+    pub fn recomputed(&self, version: VersionNumber) -> Vec<KeyID> {
+        self.keys_matching(|k| matches!(k.nodes.get(&version), Some(Some(..))))
+    }
+
+    /// The versions at which any key was computed, oldest first.
+    pub fn versions(&self) -> BTreeSet<VersionNumber> {
+        self.keys
+            .iter()
+            .flat_map(|k| k.nodes.iter())
+            .filter(|(_, n)| n.is_some())
+            .map(|(v, _)| *v)
+            .collect()
+    }
+}
+
+/// The node for the most recently computed value of a key.
+fn latest_node(key: &SerializedGraphNodesForKey) -> Option<&SerializedGraphNode> {
+    key.nodes.values().rev().find_map(|n| n.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::introspection::graph::CellHistory;
+    use crate::introspection::graph::GraphNodeKind;
+    use crate::introspection::graph::NodeID;
+
+    fn key(
+        id: usize,
+        computed_at: usize,
+        deps: &[usize],
+        history: &[(usize, HistoryState)],
+    ) -> SerializedGraphNodesForKey {
+        let node = SerializedGraphNode {
+            node_id: NodeID(id),
+            kind: GraphNodeKind::Occupied,
+            history: CellHistory {
+                history: history
+                    .iter()
+                    .map(|(v, s)| (VersionNumber(*v), s.clone()))
+                    .collect(),
+            },
+            deps: Some(deps.iter().map(|d| KeyID(*d)).collect()),
+            rdeps: None,
+        };
+        SerializedGraphNodesForKey {
+            id: KeyID(id),
+            key: format!("Key({})", id),
+            type_name: "Key".to_owned(),
+            nodes: BTreeMap::from([(VersionNumber(computed_at), Some(node))]),
+        }
+    }
+
+    /// 0 depends on 1 and 2, which both depend on 3. 3 was changed at v2, invalidating 1 and 0.
+    /// 2 was recomputed at v2.
+    fn graph() -> GraphExplorer {
+        GraphExplorer::new(vec![
+            key(
+                0,
+                1,
+                &[1, 2],
+                &[(1, HistoryState::Verified), (2, HistoryState::Dirty)],
+            ),
+            key(
+                1,
+                1,
+                &[3],
+                &[(1, HistoryState::Verified), (2, HistoryState::Dirty)],
+            ),
+            key(2, 2, &[3], &[(2, HistoryState::Verified)]),
+            key(
+                3,
+                1,
+                &[],
+                &[(1, HistoryState::Verified), (2, HistoryState::Dirty)],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_find() {
+        let graph = graph();
+        assert_eq!(graph.find("Key(2)"), vec![KeyID(2)]);
+        assert_eq!(graph.find("Key").len(), 4);
+        assert_eq!(graph.find("Other"), Vec::new());
+    }
+
+    #[test]
+    fn test_state() {
+        let graph = graph();
+        assert_eq!(graph.latest_version(), Some(VersionNumber(2)));
+        assert_eq!(graph.state(KeyID(2)), KeyState::Verified(VersionNumber(2)));
+        assert_eq!(
+            graph.state(KeyID(1)),
+            KeyState::Dirty {
+                version: VersionNumber(2),
+                forced: false
+            }
+        );
+        assert_eq!(graph.state(KeyID(4)), KeyState::Unknown);
+    }
+
+    #[test]
+    fn test_why_dirty() {
+        let graph = graph();
+        assert_eq!(graph.why_dirty(KeyID(2)), None);
+
+        let reason = graph.why_dirty(KeyID(0)).unwrap();
+        assert_eq!(reason.key, KeyID(0));
+        assert_eq!(reason.dirty_deps.len(), 1);
+        let dep = &reason.dirty_deps[0];
+        assert_eq!(dep.key, KeyID(1));
+        assert_eq!(dep.dirty_deps.len(), 1);
+        assert_eq!(dep.dirty_deps[0].key, KeyID(3));
+        assert!(dep.dirty_deps[0].dirty_deps.is_empty());
+    }
+
+    #[test]
+    fn test_rdeps() {
+        let graph = graph();
+        assert_eq!(
+            graph.rdeps(KeyID(3)),
+            vec![(KeyID(1), 1), (KeyID(2), 1), (KeyID(0), 2)]
+        );
+        assert_eq!(graph.rdeps(KeyID(0)), Vec::new());
+    }
+
+    #[test]
+    fn test_recomputed() {
+        let graph = graph();
+        assert_eq!(graph.recomputed(VersionNumber(2)), vec![KeyID(2)]);
+        assert_eq!(
+            graph.versions().into_iter().collect::<Vec<_>>(),
+            vec![VersionNumber(1), VersionNumber(2)]
+        );
+    }
+}
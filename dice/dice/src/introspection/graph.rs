@@ -135,7 +135,7 @@ impl Serialize for GraphIntrospectable {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Dupe, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Dupe, Copy)]
 #[serde(transparent)]
 pub struct KeyID(pub usize);
 
@@ -144,6 +144,7 @@ pub struct KeyID(pub usize);
 pub struct NodeID(pub usize);
 
 #[derive(
+    Debug,
     PartialEq,
     Eq,
     Hash,
@@ -16,6 +16,7 @@ use crate::introspection::graph::LegacyIntrospectable;
 use crate::Dice;
 use crate::DiceImplementation;
 
+pub mod explore;
 pub mod graph;
 pub(crate) mod introspect;
 
@@ -17,6 +17,9 @@ use futures::FutureExt;
 use parking_lot::Mutex;
 
 use crate::api::activation_tracker::ActivationData;
+use crate::api::activation_tracker::ChangedKey;
+use crate::api::activation_tracker::EvaluatedKey;
+use crate::api::activation_tracker::KeyId;
 use crate::api::cycles::DetectCycles;
 use crate::api::data::DiceData;
 use crate::api::error::DiceErrorImpl;
@@ -106,19 +109,32 @@ impl ComputationData {
         self,
         k: &K::Key,
         deps: &BothDeps,
-        reused: bool,
+        activation: KeyActivation,
     ) {
         if let Some(v) = &self.user_data.cycle_detector {
             v.finished_computing_key(K::to_key_any(k))
         }
 
         if let Some(v) = &self.user_data.activation_tracker {
+            if activation != KeyActivation::Reused {
+                v.key_evaluated(
+                    EvaluatedKey {
+                        type_name: K::key_type_name(),
+                        id: KeyId::new(k),
+                        key: Arc::new(k.clone()),
+                        recomputed: activation == KeyActivation::Recomputed,
+                    },
+                    &mut deps.deps.iter().map(|d| d.key_id()),
+                );
+            }
+
             let mut iter = deps.deps.iter().map(|d| d.to_key_any());
 
-            let activation_data = if reused {
-                ActivationData::Reused
-            } else {
-                ActivationData::Evaluated(self.evaluation_data.lock().take())
+            let activation_data = match activation {
+                KeyActivation::Reused => ActivationData::Reused,
+                KeyActivation::Computed | KeyActivation::Recomputed => {
+                    ActivationData::Evaluated(self.evaluation_data.lock().take())
+                }
             };
 
             v.key_activated(K::to_key_any(k), &mut iter, activation_data);
@@ -126,6 +142,17 @@ impl ComputationData {
     }
 }
 
+/// How a key got its value, as reported to the activation tracker.
+#[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq)]
+pub(crate) enum KeyActivation {
+    /// The previous value was reused since none of its deps changed.
+    Reused,
+    /// The key was evaluated for the first time.
+    Computed,
+    /// The key had been computed before and was evaluated again.
+    Recomputed,
+}
+
 /// A context for computations to request for additional dependencies. The
 /// dependencies accessed are tracked for caching, if enabled based on
 /// 'Strategy'.
@@ -246,6 +273,7 @@ impl DiceComputationsImplLegacy {
             let dice = self.dice.dupe();
             changes.change(
                 k.clone(),
+                false,
                 Box::new(move |version| {
                     debug!(msg = "marking value as changed", version = %version, key = %k);
                     let cache = dice.find_cache::<K>();
@@ -271,6 +299,7 @@ impl DiceComputationsImplLegacy {
             let dice = self.dice.dupe();
             changes.change(
                 k.clone(),
+                true,
                 Box::new(move |version| {
                     let cache = dice.find_cache::<K>();
                     debug!(msg = "marking value as updated", version = %version, key = %k);
@@ -293,9 +322,11 @@ impl DiceComputationsImplLegacy {
 
         // hold onto the prev version until we get the new one below so we don't increment minor
         // version needlessly.
-        let _prev_v = eval.commit();
+        let (_prev_v, changed_keys) = eval.commit();
 
-        this.dice.make_ctx(this.extra)
+        let ctx = this.dice.make_ctx(this.extra);
+        ctx.report_changed_keys(&changed_keys);
+        ctx
     }
 
     /// Same as `commit`, but replacing the user data with the given
@@ -313,14 +344,22 @@ impl DiceComputationsImplLegacy {
 
         // hold onto the prev version until we get the new one below so we don't increment minor
         // version needlessly.
-        let _prev_v = eval.commit();
+        let (_prev_v, changed_keys) = eval.commit();
 
-        this.dice.make_ctx(ComputationData {
+        let ctx = this.dice.make_ctx(ComputationData {
             user_data: Arc::new(extra),
             cycle_detector: this.extra.cycle_detector.take(),
             user_cycle_detector_guard: None,
             evaluation_data: Mutex::new(None),
-        })
+        });
+        ctx.report_changed_keys(&changed_keys);
+        ctx
+    }
+
+    fn report_changed_keys(&self, changed_keys: &[ChangedKey]) {
+        if let Some(activation_tracker) = &self.per_transaction_data().activation_tracker {
+            activation_tracker.keys_changed(changed_keys);
+        }
     }
 
     pub(crate) fn get_version(&self) -> VersionNumber {
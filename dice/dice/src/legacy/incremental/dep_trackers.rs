@@ -181,6 +181,7 @@ mod internals {
     use cmp_any::PartialEqAny;
     use dupe::Dupe;
 
+    use crate::api::activation_tracker::KeyId;
     use crate::api::error::DiceResult;
     use crate::introspection::graph::AnyKey;
     use crate::legacy::ctx::ComputationData;
@@ -226,6 +227,10 @@ mod internals {
             K::to_key_any(self.node.key())
         }
 
+        fn key_id(&self) -> KeyId {
+            KeyId::new(self.node.key())
+        }
+
         fn hash(&self, mut state: &mut dyn Hasher) {
             self.node.key().hash(&mut state);
             self.version.hash(&mut state);
@@ -25,6 +25,7 @@ use dupe::Dupe;
 use parking_lot::RwLock;
 use parking_lot::RwLockReadGuard;
 
+use crate::api::activation_tracker::KeyId;
 use crate::api::error::DiceResult;
 use crate::introspection::graph::AnyKey;
 use crate::legacy::ctx::ComputationData;
@@ -87,6 +88,9 @@ pub(crate) trait ComputedDependency: Allocative + Debug + Send + Sync {
 
     fn to_key_any(&self) -> &dyn Any;
 
+    /// Identifies the key of this dependency to the activation tracker.
+    fn key_id(&self) -> KeyId;
+
     fn hash(&self, state: &mut dyn Hasher);
 
     fn is_valid(&self) -> bool;
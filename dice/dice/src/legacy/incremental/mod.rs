@@ -54,8 +54,10 @@ use crate::api::projection::DiceProjectionComputations;
 use crate::api::projection::ProjectionKey;
 use crate::api::user_data::UserComputationData;
 use crate::impls::core::graph::history::CellHistory;
+use crate::impls::core::graph::history::HistoryState;
 use crate::introspection::graph::EngineForIntrospection;
 use crate::legacy::ctx::ComputationData;
+use crate::legacy::ctx::KeyActivation;
 use crate::legacy::dice_futures::dice_future::DiceFuture;
 use crate::legacy::dice_futures::dice_task::DiceTask;
 use crate::legacy::dice_futures::future_handle::WeakDiceFutureHandle;
@@ -69,6 +71,7 @@ use crate::legacy::incremental::graph::GraphNode;
 use crate::legacy::incremental::graph::VersionedGraph;
 use crate::legacy::incremental::graph::VersionedGraphKey;
 use crate::legacy::incremental::graph::VersionedGraphKeyRef;
+use crate::legacy::incremental::graph::VersionedGraphNodeInternal;
 use crate::legacy::incremental::graph::VersionedGraphResult;
 use crate::legacy::incremental::graph::VersionedGraphResultMismatch;
 use crate::legacy::incremental::transaction_ctx::TransactionCtx;
@@ -210,6 +213,25 @@ where
         }
     }
 
+    /// The keys with a value that is up to date at the given version, along with that value.
+    pub(crate) fn current_values(&self, version: VersionNumber) -> Vec<(K::Key, K::Value)> {
+        self.versioned_cache
+            .iter()
+            .filter_map(|e| {
+                let node = match e.value().iter().last()?.1 {
+                    VersionedGraphNodeInternal::Occupied(node) => GraphNode::occupied(node.dupe()),
+                    VersionedGraphNodeInternal::Transient(..)
+                    | VersionedGraphNodeInternal::Vacant(..) => return None,
+                };
+                let history = node.read_meta().hist.get_history(&version);
+                match history {
+                    HistoryState::Verified => Some((e.key().clone(), node.val().dupe())),
+                    HistoryState::Unknown(..) | HistoryState::Dirty => None,
+                }
+            })
+            .collect()
+    }
+
     fn invalidate_rdeps(version: VersionNumber, invalidated: GraphNode<K>) {
         let mut queue = {
             let metadata = invalidated.read_meta();
@@ -463,12 +485,22 @@ where
                         DidDepsChange::Changed | DidDepsChange::NoDeps => {
                             debug!("dependencies changed. recomputing...");
                             ev.engine
-                                .compute(&ev.k, eval_ctx, extra, &cancellation)
+                                .compute(
+                                    &ev.k,
+                                    eval_ctx,
+                                    extra,
+                                    KeyActivation::Recomputed,
+                                    &cancellation,
+                                )
                                 .await
                         }
                         DidDepsChange::NoChange(unchanged_both_deps) => {
                             debug!("dependencies are unchanged, reusing entry");
-                            extra.finished_computing_key::<K>(&ev.k, &unchanged_both_deps, true);
+                            extra.finished_computing_key::<K>(
+                                &ev.k,
+                                &unchanged_both_deps,
+                                KeyActivation::Reused,
+                            );
                             CancellableResult::Ok(ev.engine.reuse(
                                 ev.k.clone(),
                                 &eval_ctx,
@@ -478,13 +510,34 @@ where
                         }
                     }
                 }
-                VersionedGraphResult::Dirty | VersionedGraphResult::None => {
+                VersionedGraphResult::Dirty => {
                     let mut extra = extra;
                     extra.start_computing_key::<K>(&ev.k);
 
                     debug!("dirtied. recomputing...");
                     ev.engine
-                        .compute(&ev.k, eval_ctx, extra, &cancellation)
+                        .compute(
+                            &ev.k,
+                            eval_ctx,
+                            extra,
+                            KeyActivation::Recomputed,
+                            &cancellation,
+                        )
+                        .await
+                }
+                VersionedGraphResult::None => {
+                    let mut extra = extra;
+                    extra.start_computing_key::<K>(&ev.k);
+
+                    debug!("not in cache. computing...");
+                    ev.engine
+                        .compute(
+                            &ev.k,
+                            eval_ctx,
+                            extra,
+                            KeyActivation::Computed,
+                            &cancellation,
+                        )
                         .await
                 }
             };
@@ -549,6 +602,7 @@ where
         k: &K::Key,
         transaction_ctx: Arc<TransactionCtx>,
         extra: ComputationData,
+        activation: KeyActivation,
         cancellation: &CancellationContext<'_>,
     ) -> CancellableResult<GraphNode<K>> {
         let desc = K::key_type_name();
@@ -587,7 +641,7 @@ where
         };
 
         debug!(msg = "evaluation finished. updating caches");
-        extra.finished_computing_key::<K>(k, &both_deps, false);
+        extra.finished_computing_key::<K>(k, &both_deps, activation);
 
         let (entry, _old) = self.versioned_cache.update_computed_value(
             VersionedGraphKey::new(v, k.clone()),
@@ -1082,6 +1136,7 @@ pub(crate) mod testing {
     use derivative::Derivative;
     use parking_lot::RwLock;
 
+    use crate::api::activation_tracker::KeyId;
     use crate::legacy::incremental::dep_trackers::testing::Dep;
     use crate::legacy::incremental::dep_trackers::testing::DepExt;
     use crate::legacy::incremental::graph::storage_properties::StorageProperties;
@@ -1163,6 +1218,10 @@ pub(crate) mod testing {
                     K::to_key_any(&self.0.0)
                 }
 
+                fn key_id(&self) -> KeyId {
+                    KeyId::new(&self.0.0)
+                }
+
                 fn hash(&self, mut state: &mut dyn Hasher) {
                     self.0.hash(&mut state);
                 }
@@ -1273,6 +1332,7 @@ mod tests {
     use tokio::sync::Notify;
     use tokio::sync::RwLock as AsyncRwLock;
 
+    use crate::api::activation_tracker::KeyId;
     use crate::api::error::DiceError;
     use crate::api::error::DiceResult;
     use crate::api::storage_type::StorageType;
@@ -1604,6 +1664,10 @@ mod tests {
                 unimplemented!()
             }
 
+            fn key_id(&self) -> KeyId {
+                KeyId::new(&self.0)
+            }
+
             fn hash(&self, mut state: &mut dyn Hasher) {
                 self.0.hash(&mut state)
             }
@@ -16,6 +16,7 @@ use anymap::Map;
 use parking_lot::Mutex;
 use parking_lot::MutexGuard;
 
+use crate::api::activation_tracker::ChangedKey;
 use crate::api::error::DiceError;
 use crate::api::error::DiceResult;
 use crate::api::key::Key;
@@ -114,8 +115,9 @@ impl TransactionCtx {
         }
     }
 
-    pub(crate) fn commit(self) -> VersionGuard {
-        let is_changed = {
+    /// Apply the recorded changes, returning the keys whose value did change.
+    pub(crate) fn commit(self) -> (VersionGuard, Vec<ChangedKey>) {
+        let changed_keys = {
             let mut changed = self.changes();
             let version_for_writes = self.get_version_for_writes();
             let num_changes = changed.ops().len();
@@ -126,10 +128,15 @@ impl TransactionCtx {
                 num_changes = num_changes
             );
 
-            changed.ops().drain(..).fold(false, |has_change, change| {
-                change(version_for_writes) || has_change
-            })
+            let keys = std::mem::take(&mut changed.changed_keys);
+            changed
+                .ops()
+                .drain(..)
+                .zip(keys)
+                .filter_map(|(change, key)| change(version_for_writes).then_some(key))
+                .collect::<Vec<_>>()
         };
+        let is_changed = !changed_keys.is_empty();
 
         if is_changed {
             debug!(
@@ -142,7 +149,7 @@ impl TransactionCtx {
             self.version_for_writes.rollback()
         }
 
-        self.version_guard
+        (self.version_guard, changed_keys)
     }
 }
 
@@ -152,6 +159,8 @@ pub(crate) struct Changes {
     keys: Map<dyn Any + Sync + Send>,
     #[allocative(skip)] // TODO(nga): measure.
     changes: Vec<Box<dyn FnOnce(VersionNumber) -> bool + Send>>,
+    /// The key of each of `changes`.
+    changed_keys: Vec<ChangedKey>,
 }
 
 impl Changes {
@@ -159,12 +168,14 @@ impl Changes {
         Self {
             keys: Map::new(),
             changes: vec![],
+            changed_keys: vec![],
         }
     }
 
     pub(crate) fn change<K: Key>(
         &mut self,
         key: K,
+        injected: bool,
         change: Box<dyn FnOnce(VersionNumber) -> bool + Send>,
     ) -> DiceResult<()> {
         let map = self
@@ -174,6 +185,7 @@ impl Changes {
         if !map.insert(key.clone()) {
             Err(DiceError::duplicate(Arc::new(key)))
         } else {
+            self.changed_keys.push(ChangedKey::new(&key, injected));
             self.changes.push(change);
             Ok(())
         }
@@ -196,6 +196,21 @@ impl DiceLegacy {
             .find_cache(|| IncrementalEngine::new(ProjectionKeyProperties::<P>::new(self)))
     }
 
+    pub(crate) fn current_values<K>(&self) -> Vec<(K, K::Value)>
+    where
+        K: Key,
+    {
+        let version = self.global_versions.current();
+        match self
+            .map
+            .read()
+            .find_cache_opt::<StoragePropertiesForKey<K>>()
+        {
+            Some(cache) => cache.current_values(version.version),
+            None => Vec::new(),
+        }
+    }
+
     pub(crate) fn unstable_take(self: &Arc<DiceLegacy>) -> DiceMap {
         debug!(msg = "clearing all Dice state");
         let mut map = self.map.write();
@@ -214,6 +229,8 @@ impl DiceLegacy {
             active_transaction_count: self
                 .active_transaction_count
                 .load(std::sync::atomic::Ordering::SeqCst),
+            evicted_value_count: 0,
+            evicted_value_bytes: 0,
         }
     }
 
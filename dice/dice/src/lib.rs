@@ -238,6 +238,9 @@ use serde::Serializer;
 
 pub use crate::api::activation_tracker::ActivationData;
 pub use crate::api::activation_tracker::ActivationTracker;
+pub use crate::api::activation_tracker::ChangedKey;
+pub use crate::api::activation_tracker::EvaluatedKey;
+pub use crate::api::activation_tracker::KeyId;
 pub use crate::api::computations::DiceComputations;
 pub use crate::api::computations::DiceComputationsParallel;
 pub use crate::api::cycles::DetectCycles;
@@ -337,6 +340,13 @@ impl DiceImplementation {
         }
     }
 
+    pub fn current_values<K: Key>(&self) -> Vec<(K, K::Value)> {
+        match self {
+            DiceImplementation::Legacy(dice) => dice.current_values(),
+            DiceImplementation::Modern(dice) => dice.current_values(),
+        }
+    }
+
     /// Wait until all active versions have exited.
     pub fn wait_for_idle(&self) -> impl Future<Output = ()> + 'static {
         match self {
@@ -374,6 +384,18 @@ impl DiceDataBuilderImpl {
         }
     }
 
+    pub fn set_memory_budget(&mut self, bytes: usize) -> anyhow::Result<()> {
+        match self {
+            DiceDataBuilderImpl::Legacy(_) => Err(anyhow::anyhow!(
+                "A memory budget is only supported by the modern DICE implementation"
+            )),
+            DiceDataBuilderImpl::Modern(d) => {
+                d.set_memory_budget(bytes);
+                Ok(())
+            }
+        }
+    }
+
     pub fn build(self, detect_cycles: DetectCycles) -> Arc<Dice> {
         Dice::new(match self {
             DiceDataBuilderImpl::Legacy(d) => DiceImplementation::Legacy(d.build(detect_cycles)),
@@ -14,4 +14,8 @@ pub struct Metrics {
     /// The number of keys currently active in the per transaction cache
     pub currently_active_key_count: usize,
     pub active_transaction_count: u32,
+    /// The number of values evicted to stay within the memory budget since startup
+    pub evicted_value_count: u64,
+    /// The memory reclaimed by evicting values since startup, as measured by allocative
+    pub evicted_value_bytes: u64,
 }
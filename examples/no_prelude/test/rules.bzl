@@ -0,0 +1,24 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+def _sh_test_impl(ctx: AnalysisContext) -> list[Provider]:
+    return [
+        DefaultInfo(),
+        ExternalRunnerTestInfo(
+            type = "custom",
+            command = ["sh", ctx.attrs.script],
+            cacheable = ctx.attrs.cacheable,
+        ),
+    ]
+
+sh_test = rule(
+    impl = _sh_test_impl,
+    attrs = {
+        "cacheable": attrs.bool(default = False),
+        "script": attrs.source(),
+    },
+)
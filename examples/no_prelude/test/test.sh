@@ -0,0 +1,2 @@
+#!/bin/sh
+echo "Running a cacheable test"
@@ -101,6 +101,7 @@ impl Default for HostSharingRequirements {
 pub struct HostSharingGuard {
     _run_guard: SharedSemaphoreReleaser,
     _name_guard: Option<SharedSemaphoreReleaser>,
+    _memory_guard: Option<SharedSemaphoreReleaser>,
 }
 
 /// Used to ensure that host resources are properly reserved before executing a command spec.
@@ -108,6 +109,17 @@ pub struct HostSharingBroker {
     permits: SharedSemaphore,
     num_machine_permits: usize,
     named_semaphores: NamedSemaphores,
+    host_sharing_strategy: HostSharingStrategy,
+    memory_budget: Option<MemoryBudget>,
+}
+
+/// The memory available to commands, in mebibytes. Commands reserve the memory they need from
+/// there while they run.
+struct MemoryBudget {
+    mebibytes: SharedSemaphore,
+    total_mebibytes: usize,
+    /// What commands that don't declare how much memory they need reserve.
+    default_mebibytes: usize,
 }
 
 pub struct RequestedPermits {
@@ -144,28 +156,71 @@ impl HostSharingBroker {
     }
 
     pub fn new(host_sharing_strategy: HostSharingStrategy, num_machine_permits: usize) -> Self {
-        let permits = match host_sharing_strategy {
-            HostSharingStrategy::Fifo => SharedSemaphore::new(true, num_machine_permits),
-            HostSharingStrategy::SmallerTasksFirst => {
-                SharedSemaphore::new(false, num_machine_permits)
-            }
-        };
-
         Self {
-            permits,
+            permits: host_sharing_strategy.new_semaphore(num_machine_permits),
             num_machine_permits,
             named_semaphores: NamedSemaphores::new(),
+            host_sharing_strategy,
+            memory_budget: None,
         }
     }
 
+    /// Also schedule commands according to the memory they need, so that the memory reserved by
+    /// the commands running concurrently does not exceed `bytes`. Commands that don't declare how
+    /// much memory they need reserve `default_command_bytes`.
+    pub fn with_memory_budget(mut self, bytes: u64, default_command_bytes: u64) -> Self {
+        let total_mebibytes = to_mebibytes(bytes);
+        self.memory_budget = Some(MemoryBudget {
+            mebibytes: self.host_sharing_strategy.new_semaphore(total_mebibytes),
+            total_mebibytes,
+            default_mebibytes: to_mebibytes(default_command_bytes),
+        });
+        self
+    }
+
     pub fn num_machine_permits(&self) -> usize {
         self.num_machine_permits
     }
 
+    /// The amount of the memory budget, in mebibytes, a command that needs `bytes` (or doesn't
+    /// say, if `None`) reserves. Like permits, this is capped to the whole budget, or the command
+    /// would never run.
+    pub fn requested_memory(&self, bytes: Option<u64>) -> usize {
+        match &self.memory_budget {
+            Some(budget) => bytes
+                .map_or(budget.default_mebibytes, to_mebibytes)
+                .min(budget.total_mebibytes),
+            None => 0,
+        }
+    }
+
     pub async fn acquire(
         &self,
         host_sharing_requirements: &HostSharingRequirements,
     ) -> HostSharingGuard {
+        self.acquire_with_memory(host_sharing_requirements, None)
+            .await
+    }
+
+    /// Like `acquire`, but also reserve `memory_bytes` from the memory budget, if there is one.
+    /// Commands that don't declare how much memory they need (`None`) reserve the default amount.
+    pub async fn acquire_with_memory(
+        &self,
+        host_sharing_requirements: &HostSharingRequirements,
+        memory_bytes: Option<u64>,
+    ) -> HostSharingGuard {
+        // Acquire memory first, so that commands waiting on memory don't hold permits that
+        // other commands could use.
+        let _memory_guard = match &self.memory_budget {
+            Some(budget) => Some(
+                budget
+                    .mebibytes
+                    .acquire(self.requested_memory(memory_bytes))
+                    .await,
+            ),
+            None => None,
+        };
+
         match host_sharing_requirements {
             HostSharingRequirements::Shared(weight_class) => {
                 let permits = self.requested_permits(weight_class).into_count();
@@ -173,6 +228,7 @@ impl HostSharingBroker {
                 HostSharingGuard {
                     _run_guard,
                     _name_guard: None,
+                    _memory_guard,
                 }
             }
             HostSharingRequirements::ExclusiveAccess => {
@@ -180,6 +236,7 @@ impl HostSharingBroker {
                 HostSharingGuard {
                     _run_guard,
                     _name_guard: None,
+                    _memory_guard,
                 }
             }
             HostSharingRequirements::OnePerToken(identifier, weight_class) => {
@@ -194,20 +251,37 @@ impl HostSharingBroker {
                 HostSharingGuard {
                     _run_guard,
                     _name_guard,
+                    _memory_guard,
                 }
             }
         }
     }
 }
 
+fn to_mebibytes(bytes: u64) -> usize {
+    usize::try_from(bytes.div_ceil(1024 * 1024)).unwrap_or(usize::MAX)
+}
+
 /// Determines whether a fair or unfair semaphore is used to manage host sharing
+#[derive(Clone, Copy)]
 pub enum HostSharingStrategy {
     SmallerTasksFirst,
     Fifo,
 }
 
+impl HostSharingStrategy {
+    fn new_semaphore(self, permits: usize) -> SharedSemaphore {
+        match self {
+            HostSharingStrategy::Fifo => SharedSemaphore::new(true, permits),
+            HostSharingStrategy::SmallerTasksFirst => SharedSemaphore::new(false, permits),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use futures::FutureExt;
+
     use super::*;
 
     #[test]
@@ -227,6 +301,60 @@ mod tests {
         assert_eq!(4, permits);
     }
 
+    #[test]
+    fn test_requested_memory() {
+        let broker = HostSharingBroker::new(HostSharingStrategy::SmallerTasksFirst, 2);
+        assert_eq!(broker.requested_memory(Some(1024 * 1024)), 0);
+        assert_eq!(broker.requested_memory(None), 0);
+
+        let broker = broker.with_memory_budget(1 << 30, 256 << 20);
+        assert_eq!(broker.requested_memory(Some(1024 * 1024)), 1);
+        // This rounds up.
+        assert_eq!(broker.requested_memory(Some(1024 * 1024 + 1)), 2);
+        // This is capped to the budget.
+        assert_eq!(broker.requested_memory(Some(4 << 30)), 1024);
+        assert_eq!(broker.requested_memory(None), 256);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let broker = HostSharingBroker::new(HostSharingStrategy::Fifo, 10)
+            .with_memory_budget(3 << 20, 1 << 20);
+        let requirements = HostSharingRequirements::default();
+
+        let first = broker
+            .acquire_with_memory(&requirements, Some(2 << 20))
+            .now_or_never()
+            .unwrap();
+
+        // This reserves the default, which still fits.
+        let second = broker
+            .acquire_with_memory(&requirements, None)
+            .now_or_never()
+            .unwrap();
+        assert!(
+            broker
+                .acquire_with_memory(&requirements, None)
+                .now_or_never()
+                .is_none()
+        );
+        drop(second);
+
+        assert!(
+            broker
+                .acquire_with_memory(&requirements, Some(2 << 20))
+                .now_or_never()
+                .is_none()
+        );
+        drop(first);
+        assert!(
+            broker
+                .acquire_with_memory(&requirements, Some(2 << 20))
+                .now_or_never()
+                .is_some()
+        );
+    }
+
     #[test]
     fn test_percentage() {
         let broker = HostSharingBroker::new(HostSharingStrategy::SmallerTasksFirst, 10);
@@ -0,0 +1,418 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The storage services: ContentAddressableStorage, ByteStream and ActionCache.
+
+use std::collections::VecDeque;
+
+use futures::stream::StreamExt;
+use re_grpc_proto::build::bazel::remote::execution::v2::action_cache_server::ActionCache;
+use re_grpc_proto::build::bazel::remote::execution::v2::batch_read_blobs_response;
+use re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_response;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::ContentAddressableStorage;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
+use re_grpc_proto::build::bazel::remote::execution::v2::Directory;
+use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetTreeRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetTreeResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::UpdateActionResultRequest;
+use re_grpc_proto::google::bytestream::byte_stream_server::ByteStream;
+use re_grpc_proto::google::bytestream::QueryWriteStatusRequest;
+use re_grpc_proto::google::bytestream::QueryWriteStatusResponse;
+use re_grpc_proto::google::bytestream::ReadRequest;
+use re_grpc_proto::google::bytestream::ReadResponse;
+use re_grpc_proto::google::bytestream::WriteRequest;
+use re_grpc_proto::google::bytestream::WriteResponse;
+use re_grpc_proto::google::rpc::Code;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+use tonic::Streaming;
+
+use crate::rpc_status;
+use crate::BoxedStream;
+use crate::FakeReServer;
+
+/// Size of the chunks we send back on ByteStream reads.
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A parsed ByteStream resource name, i.e. `.../blobs/{hash}/{size}` or
+/// `.../compressed-blobs/{compressor}/{hash}/{size}`, possibly followed by extra path components.
+#[derive(Debug, PartialEq)]
+struct ResourceName {
+    compressor: compressor::Value,
+    digest: Digest,
+}
+
+fn parse_resource_name(resource_name: &str) -> Result<ResourceName, Status> {
+    let invalid =
+        || Status::invalid_argument(format!("Unsupported resource name: `{}`", resource_name));
+
+    let parts: Vec<&str> = resource_name.split('/').collect();
+    let kind = parts
+        .iter()
+        .position(|p| *p == "blobs" || *p == "compressed-blobs")
+        .ok_or_else(invalid)?;
+
+    let (compressor, rest) = if parts[kind] == "blobs" {
+        (compressor::Value::Identity, &parts[kind + 1..])
+    } else {
+        match parts.get(kind + 1) {
+            Some(&"zstd") => (compressor::Value::Zstd, &parts[kind + 2..]),
+            _ => return Err(invalid()),
+        }
+    };
+
+    match rest {
+        [hash, size, ..] => Ok(ResourceName {
+            compressor,
+            digest: Digest {
+                hash: (*hash).to_owned(),
+                size_bytes: size.parse().map_err(|_| invalid())?,
+            },
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+fn decompress(compressor: i32, data: Vec<u8>, expected_size: i64) -> Result<Vec<u8>, Status> {
+    match compressor::Value::from_i32(compressor) {
+        Some(compressor::Value::Identity) => Ok(data),
+        Some(compressor::Value::Zstd) => zstd::bulk::decompress(&data, expected_size as usize)
+            .map_err(|e| Status::invalid_argument(format!("Invalid zstd data: {}", e))),
+        other => Err(Status::invalid_argument(format!(
+            "Unsupported compressor: {:?}",
+            other
+        ))),
+    }
+}
+
+impl FakeReServer {
+    fn blob_or_not_found(&self, digest: &Digest) -> Result<Vec<u8>, Status> {
+        self.storage
+            .get(digest)
+            .ok_or_else(|| Status::not_found(format!("Blob not found: {}", digest.hash)))
+    }
+}
+
+#[tonic::async_trait]
+impl ContentAddressableStorage for FakeReServer {
+    async fn find_missing_blobs(
+        &self,
+        request: Request<FindMissingBlobsRequest>,
+    ) -> Result<Response<FindMissingBlobsResponse>, Status> {
+        let missing_blob_digests = request
+            .into_inner()
+            .blob_digests
+            .into_iter()
+            .filter(|d| !self.storage.contains(d))
+            .collect();
+        Ok(Response::new(FindMissingBlobsResponse {
+            missing_blob_digests,
+        }))
+    }
+
+    async fn batch_update_blobs(
+        &self,
+        request: Request<BatchUpdateBlobsRequest>,
+    ) -> Result<Response<BatchUpdateBlobsResponse>, Status> {
+        let responses = request
+            .into_inner()
+            .requests
+            .into_iter()
+            .map(|r| {
+                let digest = r.digest.unwrap_or_default();
+                let res = decompress(r.compressor, r.data, digest.size_bytes)
+                    .and_then(|data| self.storage.put_checked(&digest, data));
+                batch_update_blobs_response::Response {
+                    digest: Some(digest),
+                    status: Some(rpc_status(res)),
+                }
+            })
+            .collect();
+        Ok(Response::new(BatchUpdateBlobsResponse { responses }))
+    }
+
+    async fn batch_read_blobs(
+        &self,
+        request: Request<BatchReadBlobsRequest>,
+    ) -> Result<Response<BatchReadBlobsResponse>, Status> {
+        // We always reply uncompressed, which every client has to accept.
+        let responses = request
+            .into_inner()
+            .digests
+            .into_iter()
+            .map(|digest| match self.storage.get(&digest) {
+                Some(data) => batch_read_blobs_response::Response {
+                    digest: Some(digest),
+                    data,
+                    ..Default::default()
+                },
+                None => batch_read_blobs_response::Response {
+                    digest: Some(digest),
+                    status: Some(re_grpc_proto::google::rpc::Status {
+                        code: Code::NotFound as i32,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            })
+            .collect();
+        Ok(Response::new(BatchReadBlobsResponse { responses }))
+    }
+
+    type GetTreeStream = BoxedStream<GetTreeResponse>;
+
+    async fn get_tree(
+        &self,
+        request: Request<GetTreeRequest>,
+    ) -> Result<Response<Self::GetTreeStream>, Status> {
+        let root_digest = request
+            .into_inner()
+            .root_digest
+            .ok_or_else(|| Status::invalid_argument("Missing root_digest"))?;
+
+        // Everything fits in a single page, so ignore the paging parameters.
+        let mut directories = Vec::new();
+        let mut queue = VecDeque::from([root_digest]);
+        while let Some(digest) = queue.pop_front() {
+            let directory: Directory = self
+                .storage
+                .get_message(&digest)
+                .map_err(|_| Status::not_found(format!("Directory not found: {}", digest.hash)))?;
+            queue.extend(
+                directory
+                    .directories
+                    .iter()
+                    .filter_map(|d| d.digest.clone()),
+            );
+            directories.push(directory);
+        }
+
+        let response = GetTreeResponse {
+            directories,
+            next_page_token: String::new(),
+        };
+        Ok(Response::new(
+            futures::stream::once(async move { Ok(response) }).boxed(),
+        ))
+    }
+}
+
+#[tonic::async_trait]
+impl ByteStream for FakeReServer {
+    type ReadStream = BoxedStream<ReadResponse>;
+
+    async fn read(
+        &self,
+        request: Request<ReadRequest>,
+    ) -> Result<Response<Self::ReadStream>, Status> {
+        let request = request.into_inner();
+        let resource = parse_resource_name(&request.resource_name)?;
+        let data = self.blob_or_not_found(&resource.digest)?;
+
+        // Offsets and limits refer to the uncompressed data, even for compressed reads.
+        let offset = usize::try_from(request.read_offset)
+            .ok()
+            .filter(|o| *o <= data.len())
+            .ok_or_else(|| Status::out_of_range("Invalid read_offset"))?;
+        let end = match request.read_limit {
+            0 => data.len(),
+            limit if limit < 0 => return Err(Status::invalid_argument("Negative read_limit")),
+            limit => std::cmp::min(data.len(), offset.saturating_add(limit as usize)),
+        };
+        let data = data[offset..end].to_vec();
+
+        let data = match resource.compressor {
+            compressor::Value::Identity => data,
+            _ => zstd::bulk::compress(&data, 1)
+                .map_err(|e| Status::internal(format!("Error compressing blob: {}", e)))?,
+        };
+
+        let chunks = data
+            .chunks(READ_CHUNK_SIZE)
+            .map(|c| Ok(ReadResponse { data: c.to_vec() }))
+            .collect::<Vec<_>>();
+        Ok(Response::new(futures::stream::iter(chunks).boxed()))
+    }
+
+    async fn write(
+        &self,
+        request: Request<Streaming<WriteRequest>>,
+    ) -> Result<Response<WriteResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut resource_name = None;
+        let mut data = Vec::new();
+        while let Some(req) = stream.next().await {
+            let req = req?;
+            if req.write_offset != data.len() as i64 {
+                return Err(Status::invalid_argument("Unexpected write_offset"));
+            }
+            resource_name.get_or_insert(req.resource_name);
+            data.extend(req.data);
+            if req.finish_write {
+                break;
+            }
+        }
+
+        let resource = parse_resource_name(
+            &resource_name.ok_or_else(|| Status::invalid_argument("Empty write"))?,
+        )?;
+        let committed_size = data.len() as i64;
+        let data = decompress(resource.compressor as i32, data, resource.digest.size_bytes)?;
+        self.storage.put_checked(&resource.digest, data)?;
+        Ok(Response::new(WriteResponse { committed_size }))
+    }
+
+    async fn query_write_status(
+        &self,
+        _request: Request<QueryWriteStatusRequest>,
+    ) -> Result<Response<QueryWriteStatusResponse>, Status> {
+        Err(Status::unimplemented("QueryWriteStatus"))
+    }
+}
+
+#[tonic::async_trait]
+impl ActionCache for FakeReServer {
+    async fn get_action_result(
+        &self,
+        request: Request<GetActionResultRequest>,
+    ) -> Result<Response<ActionResult>, Status> {
+        let digest = request.into_inner().action_digest.unwrap_or_default();
+        self.storage
+            .action_result(&digest.hash)
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(digest.hash))
+    }
+
+    async fn update_action_result(
+        &self,
+        request: Request<UpdateActionResultRequest>,
+    ) -> Result<Response<ActionResult>, Status> {
+        let request = request.into_inner();
+        let digest = request
+            .action_digest
+            .ok_or_else(|| Status::invalid_argument("Missing action_digest"))?;
+        let action_result = request
+            .action_result
+            .ok_or_else(|| Status::invalid_argument("Missing action_result"))?;
+
+        // Like most caches, refuse results that reference blobs we don't have.
+        for d in referenced_blobs(&action_result) {
+            if !self.storage.contains(d) {
+                return Err(Status::failed_precondition(format!(
+                    "Missing blob: {}",
+                    d.hash
+                )));
+            }
+        }
+
+        self.storage
+            .put_action_result(digest.hash, action_result.clone());
+        Ok(Response::new(action_result))
+    }
+}
+
+/// Blobs an `ActionResult` points at directly. The contents of output directories are not
+/// checked, since that would require walking their trees.
+fn referenced_blobs(action_result: &ActionResult) -> impl Iterator<Item = &Digest> {
+    action_result
+        .output_files
+        .iter()
+        .filter_map(|f| f.digest.as_ref())
+        .chain(
+            action_result
+                .output_directories
+                .iter()
+                .filter_map(|d| d.tree_digest.as_ref()),
+        )
+        .chain(action_result.stdout_digest.as_ref())
+        .chain(action_result.stderr_digest.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resource_name() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_resource_name("instance/blobs/abc/12")?,
+            ResourceName {
+                compressor: compressor::Value::Identity,
+                digest: Digest {
+                    hash: "abc".to_owned(),
+                    size_bytes: 12,
+                },
+            }
+        );
+        assert_eq!(
+            parse_resource_name("uploads/some-uuid/compressed-blobs/zstd/abc/12/extra")?,
+            ResourceName {
+                compressor: compressor::Value::Zstd,
+                digest: Digest {
+                    hash: "abc".to_owned(),
+                    size_bytes: 12,
+                },
+            }
+        );
+        assert!(parse_resource_name("compressed-blobs/deflate/abc/12").is_err());
+        assert!(parse_resource_name("blobs/abc/notanumber").is_err());
+        assert!(parse_resource_name("blobs/abc").is_err());
+        Ok(())
+    }
+
+    async fn read(
+        server: &FakeReServer,
+        digest: &Digest,
+        read_offset: i64,
+        read_limit: i64,
+    ) -> Result<Vec<u8>, Status> {
+        let request = ReadRequest {
+            resource_name: format!("blobs/{}/{}", digest.hash, digest.size_bytes),
+            read_offset,
+            read_limit,
+        };
+        let mut stream = ByteStream::read(server, Request::new(request))
+            .await?
+            .into_inner();
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            data.extend(chunk?.data);
+        }
+        Ok(data)
+    }
+
+    #[tokio::test]
+    async fn test_read_limits() -> anyhow::Result<()> {
+        let server = FakeReServer::default();
+        let digest = server.storage.put(b"hello world".to_vec());
+
+        assert_eq!(read(&server, &digest, 0, 0).await?, b"hello world");
+        assert_eq!(read(&server, &digest, 6, 3).await?, b"wor");
+        assert_eq!(read(&server, &digest, 6, i64::MAX).await?, b"world");
+        assert_eq!(
+            read(&server, &digest, 0, -1).await.unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+        assert_eq!(
+            read(&server, &digest, 12, 0).await.unwrap_err().code(),
+            tonic::Code::OutOfRange
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,715 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The Execution service, which runs actions locally in a fresh temporary directory.
+
+use std::collections::BTreeSet;
+use std::path::Component;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+
+use futures::stream::StreamExt;
+use prost::Message;
+use re_grpc_proto::build::bazel::remote::execution::v2::execution_server::Execution;
+use re_grpc_proto::build::bazel::remote::execution::v2::execution_stage;
+use re_grpc_proto::build::bazel::remote::execution::v2::Action;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use re_grpc_proto::build::bazel::remote::execution::v2::Command;
+use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
+use re_grpc_proto::build::bazel::remote::execution::v2::Directory;
+use re_grpc_proto::build::bazel::remote::execution::v2::DirectoryNode;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteOperationMetadata;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutedActionMetadata;
+use re_grpc_proto::build::bazel::remote::execution::v2::FileNode;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputDirectory;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputFile;
+use re_grpc_proto::build::bazel::remote::execution::v2::SymlinkNode;
+use re_grpc_proto::build::bazel::remote::execution::v2::Tree;
+use re_grpc_proto::build::bazel::remote::execution::v2::WaitExecutionRequest;
+use re_grpc_proto::google::longrunning::operation;
+use re_grpc_proto::google::longrunning::Operation;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+use crate::rpc_status;
+use crate::BoxedStream;
+use crate::FakeReServer;
+use crate::Storage;
+
+const EXECUTE_OPERATION_METADATA_TYPE_URL: &str =
+    "type.googleapis.com/build.bazel.remote.execution.v2.ExecuteOperationMetadata";
+const EXECUTE_RESPONSE_TYPE_URL: &str =
+    "type.googleapis.com/build.bazel.remote.execution.v2.ExecuteResponse";
+
+/// Name reported as the worker in the `ExecutedActionMetadata` of actions we run.
+const WORKER_NAME: &str = "re_fake_server";
+
+fn io_error(context: &str, path: &Path, e: std::io::Error) -> Status {
+    Status::internal(format!("{} `{}`: {}", context, path.display(), e))
+}
+
+/// Run filesystem work, which blocks, off the async runtime.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Status> + Send + 'static,
+) -> Result<T, Status> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(format!("Blocking task failed: {}", e)))?
+}
+
+/// Check that `name`, from a `Directory` we were sent, is a single path component, so that the
+/// node it names can't end up outside of its directory.
+fn check_name(name: &str) -> Result<&str, Status> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains('/') => Ok(name),
+        _ => Err(Status::invalid_argument(format!(
+            "Invalid node name: `{}`",
+            name
+        ))),
+    }
+}
+
+/// Check that `path`, from a `Command` we were sent, is relative and doesn't go up, so that it
+/// can't end up outside of the directory the action runs in.
+fn check_relative_path(path: &str) -> Result<&str, Status> {
+    if Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(path)
+    } else {
+        Err(Status::invalid_argument(format!(
+            "Invalid path: `{}`",
+            path
+        )))
+    }
+}
+
+impl FakeReServer {
+    async fn execute_action(
+        &self,
+        action_digest: &Digest,
+        skip_cache_lookup: bool,
+    ) -> ExecuteResponse {
+        if !skip_cache_lookup {
+            if let Some(result) = self.storage.action_result(&action_digest.hash) {
+                return ExecuteResponse {
+                    result: Some(result),
+                    cached_result: true,
+                    ..Default::default()
+                };
+            }
+        }
+
+        match self.run_action(action_digest).await {
+            Ok(result) => ExecuteResponse {
+                result: Some(result),
+                ..Default::default()
+            },
+            Err(status) => ExecuteResponse {
+                status: Some(rpc_status(Err(status))),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn run_action(&self, action_digest: &Digest) -> Result<ActionResult, Status> {
+        let storage = &self.storage;
+        let action: Action = storage.get_message(action_digest)?;
+        let command: Command = storage.get_message(&action.command_digest.unwrap_or_default())?;
+        let program = command
+            .arguments
+            .first()
+            .ok_or_else(|| Status::invalid_argument("Command has no arguments"))?;
+        let working_directory = check_relative_path(&command.working_directory)?.to_owned();
+        let outputs = output_paths(&command)
+            .into_iter()
+            .map(|o| check_relative_path(o).map(str::to_owned))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let input_fetch_start = SystemTime::now();
+        let (dir, working_directory) = {
+            let storage = storage.clone();
+            let input_root_digest = action.input_root_digest.clone().unwrap_or_default();
+            let outputs = outputs.clone();
+            blocking(move || {
+                let dir = tempfile::tempdir()
+                    .map_err(|e| Status::internal(format!("Error creating temp dir: {}", e)))?;
+                materialize(&storage, &input_root_digest, dir.path())?;
+
+                // REv2 leaves it to the server to create the parent directories of outputs.
+                let working_directory = dir.path().join(working_directory);
+                for output in &outputs {
+                    if let Some(parent) = working_directory.join(output).parent() {
+                        std::fs::create_dir_all(parent)
+                            .map_err(|e| io_error("Error creating directory", parent, e))?;
+                    }
+                }
+                Ok((dir, working_directory))
+            })
+            .await?
+        };
+        let input_fetch_completed = SystemTime::now();
+
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(&command.arguments[1..])
+            .current_dir(&working_directory)
+            .env_clear()
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        // Actions that don't set their own PATH still need to find basic tools.
+        if let Some(path) = std::env::var_os("PATH") {
+            cmd.env("PATH", path);
+        }
+        for var in &command.environment_variables {
+            cmd.env(&var.name, &var.value);
+        }
+
+        tracing::debug!(
+            "Executing action {}: {:?}",
+            action_digest.hash,
+            command.arguments
+        );
+        storage.executed_actions.fetch_add(1, Ordering::Relaxed);
+
+        let execution_start = SystemTime::now();
+        let output = cmd.output();
+        let output = match action
+            .timeout
+            .and_then(|t| std::time::Duration::try_from(t).ok())
+            .filter(|t| !t.is_zero())
+        {
+            Some(timeout) => tokio::time::timeout(timeout, output)
+                .await
+                .map_err(|_| Status::deadline_exceeded("Action timed out"))?,
+            None => output.await,
+        }
+        .map_err(|e| Status::invalid_argument(format!("Error spawning `{}`: {}", program, e)))?;
+        let execution_completed = SystemTime::now();
+
+        let mut result = ActionResult {
+            exit_code: output.status.code().unwrap_or(-1),
+            stdout_digest: Some(storage.put(output.stdout)),
+            stderr_digest: Some(storage.put(output.stderr)),
+            ..Default::default()
+        };
+
+        let output_upload_start = SystemTime::now();
+        let mut result = {
+            let storage = storage.clone();
+            blocking(move || {
+                for output in &outputs {
+                    collect_output(&storage, &working_directory, output, &mut result)?;
+                }
+                // Deleting the temp dir blocks too.
+                drop(dir);
+                Ok(result)
+            })
+            .await?
+        };
+        let output_upload_completed = SystemTime::now();
+
+        result.execution_metadata = Some(ExecutedActionMetadata {
+            worker: WORKER_NAME.to_owned(),
+            worker_start_timestamp: Some(input_fetch_start.into()),
+            input_fetch_start_timestamp: Some(input_fetch_start.into()),
+            input_fetch_completed_timestamp: Some(input_fetch_completed.into()),
+            execution_start_timestamp: Some(execution_start.into()),
+            execution_completed_timestamp: Some(execution_completed.into()),
+            output_upload_start_timestamp: Some(output_upload_start.into()),
+            output_upload_completed_timestamp: Some(output_upload_completed.into()),
+            worker_completed_timestamp: Some(output_upload_completed.into()),
+            ..Default::default()
+        });
+
+        if result.exit_code == 0 && !action.do_not_cache {
+            storage.put_action_result(action_digest.hash.clone(), result.clone());
+        }
+
+        Ok(result)
+    }
+}
+
+/// All the outputs a command declares, whichever REv2 version it was written against.
+fn output_paths(command: &Command) -> BTreeSet<&str> {
+    command
+        .output_files
+        .iter()
+        .chain(&command.output_directories)
+        .chain(&command.output_paths)
+        .map(|p| p.as_str())
+        .collect()
+}
+
+/// Write out the directory with this digest, and everything under it, at `path`.
+fn materialize(storage: &Storage, digest: &Digest, path: &Path) -> Result<(), Status> {
+    let directory: Directory = storage.get_message(digest)?;
+    std::fs::create_dir_all(path).map_err(|e| io_error("Error creating directory", path, e))?;
+
+    for file in &directory.files {
+        let file_digest = file.digest.clone().unwrap_or_default();
+        let data = storage.get(&file_digest).ok_or_else(|| {
+            Status::failed_precondition(format!("Missing blob: {}", file_digest.hash))
+        })?;
+        let file_path = path.join(check_name(&file.name)?);
+        std::fs::write(&file_path, data)
+            .map_err(|e| io_error("Error writing file", &file_path, e))?;
+        if file.is_executable {
+            set_executable(&file_path)?;
+        }
+    }
+
+    for dir in &directory.directories {
+        materialize(
+            storage,
+            &dir.digest.clone().unwrap_or_default(),
+            &path.join(check_name(&dir.name)?),
+        )?;
+    }
+
+    for symlink in &directory.symlinks {
+        let link_path = path.join(check_name(&symlink.name)?);
+        create_symlink(&symlink.target, &link_path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<(), Status> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| io_error("Error setting permissions on", path, e))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> Result<(), Status> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &str, link_path: &Path) -> Result<(), Status> {
+    std::os::unix::fs::symlink(target, link_path)
+        .map_err(|e| io_error("Error creating symlink", link_path, e))
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: &str, link_path: &Path) -> Result<(), Status> {
+    Err(Status::unimplemented(format!(
+        "Symlinks are not supported on this platform: `{}`",
+        link_path.display()
+    )))
+}
+
+/// Upload the output at `path` (relative to `working_directory`) and add it to `result`. Outputs
+/// the action did not produce are skipped: it's up to the client to decide whether that's an
+/// error.
+fn collect_output(
+    storage: &Storage,
+    working_directory: &Path,
+    path: &str,
+    result: &mut ActionResult,
+) -> Result<(), Status> {
+    let full_path = working_directory.join(path);
+    let metadata = match std::fs::metadata(&full_path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(io_error("Error reading output", &full_path, e)),
+    };
+
+    if metadata.is_dir() {
+        let mut children = Vec::new();
+        let root = upload_directory(storage, &full_path, &mut children)?;
+        let tree = Tree {
+            root: Some(root),
+            children,
+        };
+        result.output_directories.push(OutputDirectory {
+            path: path.to_owned(),
+            tree_digest: Some(storage.put_message(&tree)),
+            ..Default::default()
+        });
+    } else {
+        let data = std::fs::read(&full_path)
+            .map_err(|e| io_error("Error reading output", &full_path, e))?;
+        result.output_files.push(OutputFile {
+            path: path.to_owned(),
+            digest: Some(storage.put(data)),
+            is_executable: is_executable(&metadata),
+            ..Default::default()
+        });
+    }
+
+    Ok(())
+}
+
+/// Upload the contents of the directory at `path` and return its `Directory` message. Every
+/// directory below it is appended to `children`, as a `Tree` wants.
+fn upload_directory(
+    storage: &Storage,
+    path: &Path,
+    children: &mut Vec<Directory>,
+) -> Result<Directory, Status> {
+    let mut entries = std::fs::read_dir(path)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| io_error("Error listing directory", path, e))?;
+    // REv2 wants the nodes of a directory sorted by name.
+    entries.sort_by_key(|e| e.file_name());
+
+    let mut directory = Directory::default();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let entry_path = entry.path();
+        let metadata = std::fs::symlink_metadata(&entry_path)
+            .map_err(|e| io_error("Error reading output", &entry_path, e))?;
+
+        if metadata.file_type().is_symlink() {
+            let target = std::fs::read_link(&entry_path)
+                .map_err(|e| io_error("Error reading symlink", &entry_path, e))?;
+            directory.symlinks.push(SymlinkNode {
+                name,
+                target: target.to_string_lossy().into_owned(),
+                ..Default::default()
+            });
+        } else if metadata.is_dir() {
+            let child = upload_directory(storage, &entry_path, children)?;
+            let digest = storage.put_message(&child);
+            children.push(child);
+            directory.directories.push(DirectoryNode {
+                name,
+                digest: Some(digest),
+            });
+        } else {
+            let data = std::fs::read(&entry_path)
+                .map_err(|e| io_error("Error reading output", &entry_path, e))?;
+            directory.files.push(FileNode {
+                name,
+                digest: Some(storage.put(data)),
+                is_executable: is_executable(&metadata),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(directory)
+}
+
+fn operation(name: String, metadata: ExecuteOperationMetadata) -> Operation {
+    Operation {
+        name,
+        metadata: Some(prost_types::Any {
+            type_url: EXECUTE_OPERATION_METADATA_TYPE_URL.to_owned(),
+            value: metadata.encode_to_vec(),
+        }),
+        done: false,
+        result: None,
+    }
+}
+
+#[tonic::async_trait]
+impl Execution for FakeReServer {
+    type ExecuteStream = BoxedStream<Operation>;
+
+    async fn execute(
+        &self,
+        request: Request<ExecuteRequest>,
+    ) -> Result<Response<Self::ExecuteStream>, Status> {
+        let request = request.into_inner();
+        let action_digest = request
+            .action_digest
+            .ok_or_else(|| Status::invalid_argument("Missing action_digest"))?;
+        let name = format!("operations/{}", action_digest.hash);
+
+        // The action runs before we reply, so there is no point streaming progress as it
+        // happens. Still report the executing stage so clients see a realistic sequence.
+        let executing = operation(
+            name.clone(),
+            ExecuteOperationMetadata {
+                stage: execution_stage::Value::Executing as i32,
+                action_digest: Some(action_digest.clone()),
+                ..Default::default()
+            },
+        );
+
+        let response = self
+            .execute_action(&action_digest, request.skip_cache_lookup)
+            .await;
+        let mut done = operation(
+            name,
+            ExecuteOperationMetadata {
+                stage: execution_stage::Value::Completed as i32,
+                action_digest: Some(action_digest),
+                ..Default::default()
+            },
+        );
+        done.done = true;
+        done.result = Some(operation::Result::Response(prost_types::Any {
+            type_url: EXECUTE_RESPONSE_TYPE_URL.to_owned(),
+            value: response.encode_to_vec(),
+        }));
+
+        Ok(Response::new(
+            futures::stream::iter([Ok(executing), Ok(done)]).boxed(),
+        ))
+    }
+
+    type WaitExecutionStream = BoxedStream<Operation>;
+
+    async fn wait_execution(
+        &self,
+        _request: Request<WaitExecutionRequest>,
+    ) -> Result<Response<Self::WaitExecutionStream>, Status> {
+        // Execute only returns once the operation is done, so there is never anything to wait
+        // for.
+        Err(Status::unimplemented("WaitExecution"))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use re_grpc_proto::build::bazel::remote::execution::v2::command::EnvironmentVariable;
+    use re_grpc_proto::build::bazel::remote::execution::v2::execution_client::ExecutionClient;
+    use re_grpc_proto::google::rpc::Code;
+
+    use super::*;
+    use crate::digest;
+
+    /// Store the action running `script` with `sh`, with a single input file, and return its
+    /// digest.
+    fn store_action(server: &FakeReServer, script: &str, outputs: &[&str]) -> Digest {
+        let storage = &server.storage;
+        let input = storage.put(b"input".to_vec());
+        let input_root = storage.put_message(&Directory {
+            files: vec![FileNode {
+                name: "input.txt".to_owned(),
+                digest: Some(input),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let command = storage.put_message(&Command {
+            arguments: vec!["sh".to_owned(), "-c".to_owned(), script.to_owned()],
+            environment_variables: vec![EnvironmentVariable {
+                name: "GREETING".to_owned(),
+                value: "hello".to_owned(),
+            }],
+            output_paths: outputs.iter().map(|o| (*o).to_owned()).collect(),
+            ..Default::default()
+        });
+        storage.put_message(&Action {
+            command_digest: Some(command),
+            input_root_digest: Some(input_root),
+            timeout: Some(prost_types::Duration {
+                seconds: 0,
+                nanos: 500_000_000,
+            }),
+            ..Default::default()
+        })
+    }
+
+    async fn execute(
+        server: &FakeReServer,
+        action_digest: Digest,
+        skip_cache_lookup: bool,
+    ) -> anyhow::Result<ExecuteResponse> {
+        let addr = server.spawn().await?;
+        let mut client = ExecutionClient::connect(format!("http://{}", addr)).await?;
+        let mut stream = client
+            .execute(ExecuteRequest {
+                action_digest: Some(action_digest),
+                skip_cache_lookup,
+                ..Default::default()
+            })
+            .await?
+            .into_inner();
+
+        while let Some(op) = stream.message().await? {
+            if let Some(operation::Result::Response(any)) = op.result {
+                assert_eq!(any.type_url, EXECUTE_RESPONSE_TYPE_URL);
+                return Ok(ExecuteResponse::decode(&any.value[..])?);
+            }
+        }
+        Err(anyhow::anyhow!("Execute stream ended without a response"))
+    }
+
+    #[tokio::test]
+    async fn test_execute() -> anyhow::Result<()> {
+        let server = FakeReServer::default();
+        let action = store_action(
+            &server,
+            "cp input.txt out/file && mkdir -p dir/sub && echo $GREETING > dir/sub/f && echo stdout",
+            &["out/file", "dir", "missing"],
+        );
+
+        let response = execute(&server, action.clone(), false).await?;
+        assert!(!response.cached_result);
+        let result = response.result.unwrap_or_default();
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(
+            server.blob(&result.stdout_digest.unwrap_or_default().hash),
+            Some(b"stdout\n".to_vec())
+        );
+
+        assert_eq!(result.output_files.len(), 1);
+        assert_eq!(result.output_files[0].path, "out/file");
+        assert_eq!(
+            server.blob(
+                &result.output_files[0]
+                    .digest
+                    .clone()
+                    .unwrap_or_default()
+                    .hash
+            ),
+            Some(b"input".to_vec())
+        );
+
+        assert_eq!(result.output_directories.len(), 1);
+        assert_eq!(result.output_directories[0].path, "dir");
+        let tree = Tree::decode(
+            &server
+                .blob(
+                    &result.output_directories[0]
+                        .tree_digest
+                        .clone()
+                        .unwrap_or_default()
+                        .hash,
+                )
+                .unwrap_or_default()[..],
+        )?;
+        let root = tree.root.unwrap_or_default();
+        assert_eq!(root.directories.len(), 1);
+        assert_eq!(root.directories[0].name, "sub");
+        assert_eq!(tree.children.len(), 1);
+        let file = &tree.children[0].files[0];
+        assert_eq!(file.name, "f");
+        assert_eq!(
+            server.blob(&file.digest.clone().unwrap_or_default().hash),
+            Some(b"hello\n".to_vec())
+        );
+
+        // The second time around, the result comes from the cache unless we ask otherwise.
+        let response = execute(&server, action.clone(), false).await?;
+        assert!(response.cached_result);
+        assert_eq!(server.executed_actions(), 1);
+
+        let response = execute(&server, action, true).await?;
+        assert!(!response.cached_result);
+        assert_eq!(server.executed_actions(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_failure_is_not_cached() -> anyhow::Result<()> {
+        let server = FakeReServer::default();
+        let action = store_action(&server, "exit 3", &[]);
+
+        let response = execute(&server, action.clone(), false).await?;
+        assert_eq!(response.result.unwrap_or_default().exit_code, 3);
+        assert!(server.action_result(&action.hash).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_timeout() -> anyhow::Result<()> {
+        let server = FakeReServer::default();
+        let action = store_action(&server, "sleep 10", &[]);
+
+        let response = execute(&server, action, false).await?;
+        assert_eq!(
+            response.status.unwrap_or_default().code,
+            Code::DeadlineExceeded as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_invalid_names() -> anyhow::Result<()> {
+        let server = FakeReServer::default();
+        let storage = &server.storage;
+        let input = storage.put(b"input".to_vec());
+        let command = storage.put_message(&Command {
+            arguments: vec!["true".to_owned()],
+            ..Default::default()
+        });
+        for name in ["..", "../escaped", "/tmp/escaped", "a/b", ""] {
+            let input_root = storage.put_message(&Directory {
+                files: vec![FileNode {
+                    name: name.to_owned(),
+                    digest: Some(input.clone()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            });
+            let action = storage.put_message(&Action {
+                command_digest: Some(command.clone()),
+                input_root_digest: Some(input_root),
+                ..Default::default()
+            });
+            let response = execute(&server, action, false).await?;
+            assert_eq!(
+                response.status.unwrap_or_default().code,
+                Code::InvalidArgument as i32,
+                "{}",
+                name
+            );
+        }
+
+        let command = storage.put_message(&Command {
+            arguments: vec!["true".to_owned()],
+            output_paths: vec!["../out".to_owned()],
+            ..Default::default()
+        });
+        let action = storage.put_message(&Action {
+            command_digest: Some(command),
+            input_root_digest: Some(storage.put_message(&Directory::default())),
+            ..Default::default()
+        });
+        let response = execute(&server, action, false).await?;
+        assert_eq!(
+            response.status.unwrap_or_default().code,
+            Code::InvalidArgument as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_missing_input() -> anyhow::Result<()> {
+        let server = FakeReServer::default();
+        let response = execute(&server, digest(b"not an action"), false).await?;
+        assert_eq!(
+            response.status.unwrap_or_default().code,
+            Code::FailedPrecondition as i32
+        );
+
+        Ok(())
+    }
+}
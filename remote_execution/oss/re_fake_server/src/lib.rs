@@ -0,0 +1,232 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A small REv2 server that keeps the CAS and ActionCache in memory and executes actions
+//! locally in a temporary directory.
+//!
+//! This is meant for tests: it lets the remote execution client, hybrid execution and cache
+//! uploads be exercised end-to-end without a network. It is not sandboxed in any way, so don't
+//! point it at actions you wouldn't run locally.
+
+mod cas;
+mod execution;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::stream::Stream;
+use prost::Message;
+use re_grpc_proto::build::bazel::remote::execution::v2::action_cache_server::ActionCacheServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_server::Capabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_server::CapabilitiesServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::ContentAddressableStorageServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::digest_function;
+use re_grpc_proto::build::bazel::remote::execution::v2::execution_server::ExecutionServer;
+use re_grpc_proto::build::bazel::remote::execution::v2::symlink_absolute_path_strategy;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionCacheUpdateCapabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use re_grpc_proto::build::bazel::remote::execution::v2::CacheCapabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutionCapabilities;
+use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::ServerCapabilities;
+use re_grpc_proto::build::bazel::semver::SemVer;
+use re_grpc_proto::google::bytestream::byte_stream_server::ByteStreamServer;
+use sha2::Digest as _;
+use sha2::Sha256;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+type BoxedStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+/// Largest `BatchUpdateBlobs` / `BatchReadBlobs` we advertise. This stays well below tonic's
+/// default 4MiB message limit so that request overhead never pushes a batch over it.
+const MAX_BATCH_TOTAL_SIZE_BYTES: i64 = 2 * 1024 * 1024;
+
+/// An in-memory REv2 CAS, ActionCache and Execution service. Cloning it gives another handle to
+/// the same storage.
+#[derive(Clone, Default)]
+pub struct FakeReServer {
+    storage: Arc<Storage>,
+}
+
+impl FakeReServer {
+    /// Serve on an ephemeral local port in the background, returning the address to connect to.
+    /// Clients should use `grpc://{addr}` as their CAS, engine and action cache address.
+    pub async fn spawn(&self) -> anyhow::Result<SocketAddr> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(self.clone().serve(listener));
+        Ok(addr)
+    }
+
+    /// Serve on `listener` until the server fails.
+    pub async fn serve(self, listener: TcpListener) -> anyhow::Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(CapabilitiesServer::new(self.clone()))
+            .add_service(ContentAddressableStorageServer::new(self.clone()))
+            .add_service(ByteStreamServer::new(self.clone()))
+            .add_service(ActionCacheServer::new(self.clone()))
+            .add_service(ExecutionServer::new(self))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await?;
+        Ok(())
+    }
+
+    /// Contents of the blob with this (SHA256) hash, if it was uploaded or produced by an action.
+    pub fn blob(&self, hash: &str) -> Option<Vec<u8>> {
+        self.storage.blob(hash)
+    }
+
+    /// The cached result for the action with this hash, if any.
+    pub fn action_result(&self, hash: &str) -> Option<ActionResult> {
+        self.storage.action_result(hash)
+    }
+
+    /// Number of actions that were actually executed, as opposed to served from the cache.
+    pub fn executed_actions(&self) -> usize {
+        self.storage.executed_actions.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Default)]
+struct Storage {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+    action_results: Mutex<HashMap<String, ActionResult>>,
+    executed_actions: AtomicUsize,
+}
+
+impl Storage {
+    fn blob(&self, hash: &str) -> Option<Vec<u8>> {
+        self.blobs.lock().unwrap().get(hash).cloned()
+    }
+
+    /// Fetch a blob, treating the empty blob as always present like REv2 requires.
+    fn get(&self, digest: &Digest) -> Option<Vec<u8>> {
+        if digest.size_bytes == 0 {
+            return Some(Vec::new());
+        }
+        self.blob(&digest.hash)
+    }
+
+    fn contains(&self, digest: &Digest) -> bool {
+        digest.size_bytes == 0 || self.blobs.lock().unwrap().contains_key(&digest.hash)
+    }
+
+    /// Store a blob we produced ourselves and return its digest.
+    fn put(&self, data: Vec<u8>) -> Digest {
+        let digest = digest(&data);
+        self.blobs.lock().unwrap().insert(digest.hash.clone(), data);
+        digest
+    }
+
+    /// Store a blob sent by a client after checking it matches its digest, like a real CAS
+    /// would.
+    fn put_checked(&self, expected: &Digest, data: Vec<u8>) -> Result<(), Status> {
+        let actual = digest(&data);
+        if &actual != expected {
+            return Err(Status::invalid_argument(format!(
+                "Digest mismatch: expected {}/{}, got {}/{}",
+                expected.hash, expected.size_bytes, actual.hash, actual.size_bytes
+            )));
+        }
+        self.blobs.lock().unwrap().insert(actual.hash, data);
+        Ok(())
+    }
+
+    fn put_message<M: Message>(&self, message: &M) -> Digest {
+        self.put(message.encode_to_vec())
+    }
+
+    /// Fetch and decode a message. Missing inputs are a `FAILED_PRECONDITION` in REv2.
+    fn get_message<M: Message + Default>(&self, digest: &Digest) -> Result<M, Status> {
+        let data = self
+            .get(digest)
+            .ok_or_else(|| Status::failed_precondition(format!("Missing blob: {}", digest.hash)))?;
+        M::decode(&data[..]).map_err(|e| {
+            Status::invalid_argument(format!("Invalid message in blob {}: {}", digest.hash, e))
+        })
+    }
+
+    fn action_result(&self, hash: &str) -> Option<ActionResult> {
+        self.action_results.lock().unwrap().get(hash).cloned()
+    }
+
+    fn put_action_result(&self, hash: String, action_result: ActionResult) {
+        self.action_results
+            .lock()
+            .unwrap()
+            .insert(hash, action_result);
+    }
+}
+
+fn digest(data: &[u8]) -> Digest {
+    Digest {
+        hash: hex::encode(Sha256::digest(data)),
+        size_bytes: data.len() as i64,
+    }
+}
+
+fn rpc_status(res: Result<(), Status>) -> re_grpc_proto::google::rpc::Status {
+    match res {
+        Ok(()) => re_grpc_proto::google::rpc::Status::default(),
+        Err(status) => re_grpc_proto::google::rpc::Status {
+            code: status.code() as i32,
+            message: status.message().to_owned(),
+            ..Default::default()
+        },
+    }
+}
+
+#[tonic::async_trait]
+impl Capabilities for FakeReServer {
+    async fn get_capabilities(
+        &self,
+        _request: Request<GetCapabilitiesRequest>,
+    ) -> Result<Response<ServerCapabilities>, Status> {
+        Ok(Response::new(ServerCapabilities {
+            cache_capabilities: Some(CacheCapabilities {
+                digest_functions: vec![digest_function::Value::Sha256 as i32],
+                action_cache_update_capabilities: Some(ActionCacheUpdateCapabilities {
+                    update_enabled: true,
+                }),
+                max_batch_total_size_bytes: MAX_BATCH_TOTAL_SIZE_BYTES,
+                symlink_absolute_path_strategy: symlink_absolute_path_strategy::Value::Allowed
+                    as i32,
+                supported_compressors: vec![compressor::Value::Zstd as i32],
+                supported_batch_update_compressors: vec![compressor::Value::Zstd as i32],
+                ..Default::default()
+            }),
+            execution_capabilities: Some(ExecutionCapabilities {
+                digest_function: digest_function::Value::Sha256 as i32,
+                exec_enabled: true,
+                ..Default::default()
+            }),
+            low_api_version: Some(SemVer {
+                major: 2,
+                ..Default::default()
+            }),
+            high_api_version: Some(SemVer {
+                major: 2,
+                minor: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }))
+    }
+}
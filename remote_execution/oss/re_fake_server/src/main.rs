@@ -0,0 +1,43 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Runs a `FakeReServer` as a standalone process, so that buck2 can be pointed at it.
+
+use std::net::SocketAddr;
+
+use clap::Parser;
+use re_fake_server::FakeReServer;
+use tokio::net::TcpListener;
+
+/// A local REv2 server for testing. Actions run unsandboxed on this machine.
+#[derive(Debug, Parser)]
+struct Opts {
+    /// Address to listen on.
+    #[clap(long, default_value = "127.0.0.1:8980")]
+    bind: SocketAddr,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let listener = TcpListener::bind(opts.bind).await?;
+    let addr = listener.local_addr()?;
+
+    println!(
+        "Listening on {}. To use it from buck2, add to your .buckconfig:",
+        addr
+    );
+    println!();
+    println!("[buck2_re_client]");
+    for key in ["engine_address", "cas_address", "action_cache_address"] {
+        println!("{} = grpc://{}", key, addr);
+    }
+
+    FakeReServer::default().serve(listener).await
+}
@@ -30,22 +30,31 @@ use re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_reque
 use re_grpc_proto::build::bazel::remote::execution::v2::capabilities_client::CapabilitiesClient;
 use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
 use re_grpc_proto::build::bazel::remote::execution::v2::content_addressable_storage_client::ContentAddressableStorageClient;
+use re_grpc_proto::build::bazel::remote::execution::v2::digest_function;
 use re_grpc_proto::build::bazel::remote::execution::v2::execution_client::ExecutionClient;
 use re_grpc_proto::build::bazel::remote::execution::v2::execution_stage;
+use re_grpc_proto::build::bazel::remote::execution::v2::priority_capabilities::PriorityRange;
+use re_grpc_proto::build::bazel::remote::execution::v2::symlink_absolute_path_strategy;
 use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
 use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::BatchReadBlobsResponse;
 use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::BatchUpdateBlobsResponse;
 use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
+use re_grpc_proto::build::bazel::remote::execution::v2::Directory;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteOperationMetadata;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteRequest as GExecuteRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse as GExecuteResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutedActionMetadata;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutionPolicy;
 use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsResponse;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputDirectory;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputFile;
 use re_grpc_proto::build::bazel::remote::execution::v2::ResultsCachePolicy;
+use re_grpc_proto::build::bazel::remote::execution::v2::UpdateActionResultRequest;
 use re_grpc_proto::google::bytestream::byte_stream_client::ByteStreamClient;
 use re_grpc_proto::google::bytestream::ReadRequest;
 use re_grpc_proto::google::bytestream::ReadResponse;
@@ -55,6 +64,9 @@ use re_grpc_proto::google::longrunning::operation::Result as OpResult;
 use re_grpc_proto::google::rpc::Code;
 use re_grpc_proto::google::rpc::Status;
 use regex::Regex;
+use sha1::Sha1;
+use sha2::Digest as _;
+use sha2::Sha256;
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
@@ -69,6 +81,7 @@ use tonic::transport::Channel;
 use tonic::transport::Identity;
 use tonic::transport::Uri;
 
+use crate::compression::*;
 use crate::error::*;
 use crate::metadata::*;
 use crate::request::*;
@@ -126,6 +139,165 @@ fn ttimestamp_from(ts: Option<::prost_types::Timestamp>) -> TTimestamp {
     }
 }
 
+fn ttimestamp_to(ts: TTimestamp) -> Option<::prost_types::Timestamp> {
+    // An unset timestamp is read back as the unix epoch, so map it back to unset.
+    if ts.seconds == 0 && ts.nanos == 0 {
+        return None;
+    }
+
+    Some(::prost_types::Timestamp {
+        seconds: ts.seconds,
+        nanos: ts.nanos,
+    })
+}
+
+/// Digest of a blob using the digest function Buck2 is configured with.
+fn digest_blob(digest_function: DigestFunction, blob: &[u8]) -> TDigest {
+    let hash = match digest_function {
+        DigestFunction::Sha256 => hex::encode(Sha256::digest(blob)),
+        DigestFunction::Sha1 => hex::encode(Sha1::digest(blob)),
+        DigestFunction::Blake3 => blake3::hash(blob).to_hex().to_string(),
+    };
+
+    TDigest {
+        hash,
+        size_in_bytes: blob.len() as i64,
+        ..Default::default()
+    }
+}
+
+/// The REv2 `DigestFunction.Value` of BLAKE3, which was added to REv2 after the vendored
+/// remote_execution.proto was copied.
+const DIGEST_FUNCTION_BLAKE3: i32 = 9;
+
+/// The REv2 `DigestFunction.Value` of a digest function.
+fn digest_function_to(digest_function: DigestFunction) -> i32 {
+    match digest_function {
+        DigestFunction::Sha1 => digest_function::Value::Sha1 as i32,
+        DigestFunction::Sha256 => digest_function::Value::Sha256 as i32,
+        DigestFunction::Blake3 => DIGEST_FUNCTION_BLAKE3,
+    }
+}
+
+/// Check the directories among the blobs we are about to upload against what the remote supports,
+/// so that we fail with a clear error instead of having the remote reject the actions using them.
+/// Blobs aren't labelled, so we treat as directories those that decode to a `Directory` and encode
+/// back to the same bytes, as directories serialized by Buck2 do.
+fn check_uploaded_directories(
+    capabilities: &RECapabilities,
+    blobs: &[InlinedBlobWithDigest],
+) -> anyhow::Result<()> {
+    if capabilities.absolute_symlinks_allowed && capabilities.supported_node_properties.is_none() {
+        return Ok(());
+    }
+
+    for blob in blobs {
+        let directory = match Directory::decode(blob.blob.as_slice()) {
+            Ok(directory) if directory.encode_to_vec() == blob.blob => directory,
+            _ => continue,
+        };
+        check_directory(capabilities, &directory).with_context(|| {
+            format!("Directory `{}` is not supported by the remote", blob.digest)
+        })?;
+    }
+
+    Ok(())
+}
+
+fn check_directory(capabilities: &RECapabilities, directory: &Directory) -> anyhow::Result<()> {
+    if !capabilities.absolute_symlinks_allowed {
+        if let Some(symlink) = directory
+            .symlinks
+            .iter()
+            .find(|s| s.target.starts_with('/'))
+        {
+            return Err(anyhow::anyhow!(
+                "Symlink `{}` has absolute target `{}`, which the remote disallows",
+                symlink.name,
+                symlink.target
+            ));
+        }
+    }
+
+    if let Some(supported) = &capabilities.supported_node_properties {
+        let nodes = directory
+            .files
+            .iter()
+            .map(|f| (f.name.as_str(), &f.node_properties))
+            .chain(
+                directory
+                    .symlinks
+                    .iter()
+                    .map(|s| (s.name.as_str(), &s.node_properties)),
+            )
+            .chain(std::iter::once((".", &directory.node_properties)));
+        for (name, properties) in nodes {
+            let properties = match properties {
+                Some(properties) => properties,
+                None => continue,
+            };
+            // `mtime` and `unix_mode` have their own fields, but are advertised like the others.
+            let property_names = properties
+                .properties
+                .iter()
+                .map(|p| p.name.as_str())
+                .chain(properties.mtime.as_ref().map(|_| "mtime"))
+                .chain(properties.unix_mode.as_ref().map(|_| "unix_mode"));
+            for property in property_names {
+                if !supported.iter().any(|s| s == property) {
+                    return Err(anyhow::anyhow!(
+                        "`{}` has node property `{}`, but the remote only supports {:?}",
+                        name,
+                        property,
+                        supported
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the remote supports the digest function Buck2 is configured with, since every
+/// digest we send is computed with it. Remotes that don't advertise any are assumed to.
+fn check_digest_function(configured: DigestFunction, supported: &[i32]) -> anyhow::Result<()> {
+    if supported.is_empty() || supported.contains(&digest_function_to(configured)) {
+        return Ok(());
+    }
+
+    Err(anyhow::anyhow!(
+        "Buck2 is configured to use the {:?} digest function, but the remote only supports {:?}. \
+        Set `buck2.digest_algorithms` to one the remote supports.",
+        configured,
+        supported
+            .iter()
+            .map(|f| digest_function::Value::from_i32(*f)
+                .map_or_else(|| f.to_string(), |f| format!("{:?}", f)))
+            .collect::<Vec<_>>()
+    ))
+}
+
+/// Fit a priority into the ranges the remote supports, picking the closest supported value if it
+/// is out of range. A priority of 0 means "use the default" and is always left alone, as is
+/// everything if the remote did not advertise any ranges.
+fn clamp_priority(priority: i32, ranges: &[PriorityRange]) -> i32 {
+    if priority == 0
+        || ranges.is_empty()
+        || ranges
+            .iter()
+            .any(|r| r.min_priority <= priority && priority <= r.max_priority)
+    {
+        return priority;
+    }
+
+    ranges
+        .iter()
+        .flat_map(|r| [r.min_priority, r.max_priority])
+        .min_by_key(|p| (*p as i64 - priority as i64).abs())
+        .unwrap_or(priority)
+}
+
 async fn create_tls_config(opts: &Buck2OssReConfiguration) -> anyhow::Result<ClientTlsConfig> {
     let config = ClientTlsConfig::new();
 
@@ -205,6 +377,39 @@ pub struct RECapabilities {
     max_msg_size: usize,
     /// Does the remote server support execution.
     exec_enabled: bool,
+    /// Compressors to use for CAS transfers.
+    compressors: Compressors,
+    /// Digest function Buck2 is configured with, which we also use when hashing blobs ourselves
+    /// (see `upload_blob`).
+    digest_function: DigestFunction,
+    /// Whether the remote lets clients write to the action cache.
+    action_cache_update_enabled: bool,
+    /// Priority ranges the remote supports for executions. Empty if it did not say.
+    execution_priorities: Vec<PriorityRange>,
+    /// Priority ranges the remote supports for cached results. Empty if it did not say.
+    cache_priorities: Vec<PriorityRange>,
+    /// Whether the remote accepts input symlinks with absolute targets.
+    absolute_symlinks_allowed: bool,
+    /// Node properties the remote supports on the files, symlinks and directories of input trees,
+    /// or `None` if we don't know.
+    supported_node_properties: Option<Vec<String>>,
+}
+
+impl Default for RECapabilities {
+    /// What we assume if we are told not to query capabilities.
+    fn default() -> Self {
+        RECapabilities {
+            max_msg_size: DEFAULT_MAX_MSG_SIZE,
+            exec_enabled: true,
+            compressors: Compressors::IDENTITY,
+            digest_function: DigestFunction::Sha256,
+            action_cache_update_enabled: true,
+            execution_priorities: Vec::new(),
+            cache_priorities: Vec::new(),
+            absolute_symlinks_allowed: true,
+            supported_node_properties: None,
+        }
+    }
 }
 
 struct InstanceName(Option<String>);
@@ -228,7 +433,10 @@ impl InstanceName {
 pub struct REClientBuilder;
 
 impl REClientBuilder {
-    pub async fn build_and_connect(opts: &Buck2OssReConfiguration) -> anyhow::Result<REClient> {
+    pub async fn build_and_connect(
+        opts: &Buck2OssReConfiguration,
+        digest_function: DigestFunction,
+    ) -> anyhow::Result<REClient> {
         // We just always create this just in case, so that we implicitly validate it if set.
         let tls_config = create_tls_config(opts)
             .await
@@ -292,11 +500,17 @@ impl REClientBuilder {
         let instance_name = InstanceName(opts.instance_name.clone());
 
         let capabilities = if opts.capabilities.unwrap_or(true) {
-            Self::fetch_rbe_capabilities(&mut grpc_clients, &instance_name).await?
+            Self::fetch_rbe_capabilities(
+                &mut grpc_clients,
+                &instance_name,
+                opts.compression.unwrap_or(true),
+                digest_function,
+            )
+            .await?
         } else {
             RECapabilities {
-                exec_enabled: true,
-                max_msg_size: DEFAULT_MAX_MSG_SIZE,
+                digest_function,
+                ..Default::default()
             }
         };
 
@@ -310,9 +524,9 @@ impl REClientBuilder {
     async fn fetch_rbe_capabilities(
         clients: &mut GRPCClients,
         instance_name: &InstanceName,
+        compression: bool,
+        digest_function: DigestFunction,
     ) -> anyhow::Result<RECapabilities> {
-        // TODO use more of the capabilities of the remote build executor
-
         let resp = clients
             .capabilities_client
             .get_capabilities(GetCapabilitiesRequest {
@@ -323,25 +537,45 @@ impl REClientBuilder {
             .into_inner();
         // Default is a reasonable size for the gRPC transport
         // with enough room for headers.
-        let mut max_msg_size = DEFAULT_MAX_MSG_SIZE;
-        let mut exec_enabled = true;
+        let mut capabilities = RECapabilities::default();
+        let mut digest_functions = Vec::new();
 
         if let Some(cache_cap) = resp.cache_capabilities {
             let size = cache_cap.max_batch_total_size_bytes as usize;
             // A value of 0 means no limit is set
             if size != 0 {
-                max_msg_size = size;
+                capabilities.max_msg_size = size;
+            }
+            if compression {
+                capabilities.compressors = Compressors::negotiate(&cache_cap);
+            }
+            digest_functions.extend(cache_cap.digest_functions.iter().copied());
+            // If the remote doesn't say, try and let it reject the writes.
+            if let Some(update_cap) = cache_cap.action_cache_update_capabilities {
+                capabilities.action_cache_update_enabled = update_cap.update_enabled;
+            }
+            if let Some(priority_cap) = cache_cap.cache_priority_capabilities {
+                capabilities.cache_priorities = priority_cap.priorities;
             }
+            capabilities.absolute_symlinks_allowed = cache_cap.symlink_absolute_path_strategy
+                != symlink_absolute_path_strategy::Value::Disallowed as i32;
         }
 
         if let Some(exec_cap) = resp.execution_capabilities {
-            exec_enabled = exec_cap.exec_enabled;
+            capabilities.exec_enabled = exec_cap.exec_enabled;
+            if exec_cap.digest_function != digest_function::Value::Unknown as i32 {
+                digest_functions.push(exec_cap.digest_function);
+            }
+            if let Some(priority_cap) = exec_cap.execution_priority_capabilities {
+                capabilities.execution_priorities = priority_cap.priorities;
+            }
+            capabilities.supported_node_properties = Some(exec_cap.supported_node_properties);
         }
 
-        Ok(RECapabilities {
-            max_msg_size,
-            exec_enabled,
-        })
+        check_digest_function(digest_function, &digest_functions)?;
+        capabilities.digest_function = digest_function;
+
+        Ok(capabilities)
     }
 }
 
@@ -499,10 +733,33 @@ impl REClient {
 
     pub async fn write_action_result(
         &self,
-        _metadata: RemoteExecutionMetadata,
-        _request: WriteActionResultRequest,
+        metadata: RemoteExecutionMetadata,
+        request: WriteActionResultRequest,
     ) -> anyhow::Result<WriteActionResultResponse> {
-        Err(anyhow::anyhow!("Not supported"))
+        if !self.capabilities.action_cache_update_enabled {
+            return Err(anyhow::anyhow!(
+                "Remote does not allow clients to write to the action cache"
+            ));
+        }
+
+        let mut client = self.grpc_clients.action_cache_client.clone();
+
+        let res = client
+            .update_action_result(with_internal_metadata(
+                UpdateActionResultRequest {
+                    instance_name: self.instance_name.as_str().to_owned(),
+                    action_digest: Some(tdigest_to(request.action_digest)),
+                    action_result: Some(convert_taction_result2(request.action_result)),
+                    ..Default::default()
+                },
+                metadata,
+            ))
+            .await?;
+
+        Ok(WriteActionResultResponse {
+            actual_action_result: convert_action_result(res.into_inner())?,
+            ttl_seconds: 0,
+        })
     }
 
     pub async fn execute_with_progress(
@@ -510,20 +767,10 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         mut execute_request: ExecuteRequest,
     ) -> anyhow::Result<BoxStream<'static, anyhow::Result<ExecuteWithProgressResponse>>> {
-        // TODO(aloiscochard): Map those properly in the request
-        // use crate::proto::build::bazel::remote::execution::v2::ExecutionPolicy;
-
         let mut client = self.grpc_clients.execution_client.clone();
 
-        let action_digest = tdigest_to(execute_request.action_digest.clone());
-
-        let request = GExecuteRequest {
-            instance_name: self.instance_name.as_str().to_owned(),
-            skip_cache_lookup: false,
-            execution_policy: None,
-            results_cache_policy: Some(ResultsCachePolicy { priority: 0 }),
-            action_digest: Some(action_digest.clone()),
-        };
+        let request =
+            convert_execute_request(&self.instance_name, &self.capabilities, &execute_request);
 
         let stream = client
             .execute(with_internal_metadata(request, metadata))
@@ -627,10 +874,19 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: UploadRequest,
     ) -> anyhow::Result<UploadResponse> {
+        check_uploaded_directories(
+            &self.capabilities,
+            request
+                .inlined_blobs_with_digest
+                .as_deref()
+                .unwrap_or_default(),
+        )?;
+
         upload_impl(
             &self.instance_name,
             request,
             self.capabilities.max_msg_size,
+            self.capabilities.compressors,
             |re_request| async {
                 let metadata = metadata.clone();
                 let mut cas_client = self.grpc_clients.cas_client.clone();
@@ -655,11 +911,23 @@ impl REClient {
 
     pub async fn upload_blob(
         &self,
-        _blob: Vec<u8>,
-        _metadata: RemoteExecutionMetadata,
+        blob: Vec<u8>,
+        metadata: RemoteExecutionMetadata,
     ) -> anyhow::Result<TDigest> {
-        // TODO(aloiscochard)
-        Err(anyhow::anyhow!("Not implemented (RE upload_blob)"))
+        let digest = digest_blob(self.capabilities.digest_function, &blob);
+        self.upload(
+            metadata,
+            UploadRequest {
+                inlined_blobs_with_digest: Some(vec![InlinedBlobWithDigest {
+                    digest: digest.clone(),
+                    blob,
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            },
+        )
+        .await?;
+        Ok(digest)
     }
 
     pub async fn download(
@@ -671,6 +939,7 @@ impl REClient {
             &self.instance_name,
             request,
             self.capabilities.max_msg_size,
+            self.capabilities.compressors,
             |re_request| async {
                 let metadata = metadata.clone();
                 let mut client = self.grpc_clients.cas_client.clone();
@@ -848,6 +1117,11 @@ fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionR
             execution_dir: "".to_owned(),
             execution_attempts: 0,
             last_queued_timestamp: Default::default(),
+            auxiliary_metadata: execution_metadata.auxiliary_metadata.into_map(|any| TAny {
+                type_url: any.type_url,
+                value: any.value,
+                ..Default::default()
+            }),
             ..Default::default()
         },
         ..Default::default()
@@ -856,10 +1130,101 @@ fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionR
     Ok(action_result)
 }
 
+fn convert_execute_request(
+    instance_name: &InstanceName,
+    capabilities: &RECapabilities,
+    execute_request: &ExecuteRequest,
+) -> GExecuteRequest {
+    // The priority is a property of the action, so we use it both for how soon the action runs
+    // and for how long its result is kept. In both cases, lower values mean more important.
+    let priority = execute_request
+        .execution_policy
+        .as_ref()
+        .map_or(0, |p| p.priority);
+
+    GExecuteRequest {
+        instance_name: instance_name.as_str().to_owned(),
+        skip_cache_lookup: execute_request.skip_cache_lookup,
+        execution_policy: Some(ExecutionPolicy {
+            priority: clamp_priority(priority, &capabilities.execution_priorities),
+        }),
+        results_cache_policy: Some(ResultsCachePolicy {
+            priority: clamp_priority(priority, &capabilities.cache_priorities),
+        }),
+        action_digest: Some(tdigest_to(execute_request.action_digest.clone())),
+    }
+}
+
+fn convert_taction_result2(t_action_result: TActionResult2) -> ActionResult {
+    let t_execution_metadata = t_action_result.execution_metadata;
+
+    let output_files = t_action_result
+        .output_files
+        .into_map(|output_file| OutputFile {
+            path: output_file.name,
+            digest: Some(tdigest_to(output_file.digest.digest)),
+            is_executable: output_file.executable,
+            ..Default::default()
+        });
+
+    let output_directories = t_action_result
+        .output_directories
+        .into_map(|output_directory| OutputDirectory {
+            path: output_directory.path,
+            tree_digest: Some(tdigest_to(output_directory.tree_digest)),
+            ..Default::default()
+        });
+
+    ActionResult {
+        output_files,
+        output_directories,
+        exit_code: t_action_result.exit_code,
+        stdout_raw: t_action_result.stdout_raw.unwrap_or_default(),
+        stdout_digest: t_action_result.stdout_digest.map(tdigest_to),
+        stderr_raw: t_action_result.stderr_raw.unwrap_or_default(),
+        stderr_digest: t_action_result.stderr_digest.map(tdigest_to),
+        execution_metadata: Some(ExecutedActionMetadata {
+            worker: t_execution_metadata.worker,
+            queued_timestamp: ttimestamp_to(t_execution_metadata.queued_timestamp),
+            worker_start_timestamp: ttimestamp_to(t_execution_metadata.worker_start_timestamp),
+            worker_completed_timestamp: ttimestamp_to(
+                t_execution_metadata.worker_completed_timestamp,
+            ),
+            input_fetch_start_timestamp: ttimestamp_to(
+                t_execution_metadata.input_fetch_start_timestamp,
+            ),
+            input_fetch_completed_timestamp: ttimestamp_to(
+                t_execution_metadata.input_fetch_completed_timestamp,
+            ),
+            execution_start_timestamp: ttimestamp_to(
+                t_execution_metadata.execution_start_timestamp,
+            ),
+            execution_completed_timestamp: ttimestamp_to(
+                t_execution_metadata.execution_completed_timestamp,
+            ),
+            output_upload_start_timestamp: ttimestamp_to(
+                t_execution_metadata.output_upload_start_timestamp,
+            ),
+            output_upload_completed_timestamp: ttimestamp_to(
+                t_execution_metadata.output_upload_completed_timestamp,
+            ),
+            auxiliary_metadata: t_execution_metadata.auxiliary_metadata.into_map(|any| {
+                ::prost_types::Any {
+                    type_url: any.type_url,
+                    value: any.value,
+                }
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
 async fn download_impl<Byt, BytRet, Cas>(
     instance_name: &InstanceName,
     request: DownloadRequest,
     max_msg_size: usize,
+    compressors: Compressors,
     cas_f: impl Fn(BatchReadBlobsRequest) -> Cas,
     bystream_fut: impl Fn(ReadRequest) -> Byt + Sync + Send + Copy,
 ) -> anyhow::Result<DownloadResponse>
@@ -873,8 +1238,9 @@ where
         let size_in_bytes = digest.size_in_bytes;
 
         let resource_name = format!(
-            "{}blobs/{}/{}",
+            "{}{}/{}/{}",
             instance_name.as_resource_prefix(),
+            resource_kind(compressors.bytestream),
             hash,
             size_in_bytes
         );
@@ -911,7 +1277,7 @@ where
             let read_blob_req = BatchReadBlobsRequest {
                 instance_name: instance_name.as_str().to_owned(),
                 digests: std::mem::take(&mut curr_digests),
-                acceptable_compressors: vec![compressor::Value::Identity as i32],
+                acceptable_compressors: compressors.acceptable_for_batch_read(),
             };
             requests.push(read_blob_req);
         }
@@ -922,7 +1288,7 @@ where
         let read_blob_req = BatchReadBlobsRequest {
             instance_name: instance_name.as_str().to_owned(),
             digests: std::mem::take(&mut curr_digests),
-            acceptable_compressors: vec![compressor::Value::Identity as i32],
+            acceptable_compressors: compressors.acceptable_for_batch_read(),
         };
         requests.push(read_blob_req);
     }
@@ -935,7 +1301,9 @@ where
         for r in resp.responses.into_iter() {
             let digest = tdigest_from(r.digest.context("Response digest not found.")?);
             check_status(r.status.unwrap_or_default())?;
-            batched_blobs_response.insert(digest, r.data);
+            let data = decompress(r.compressor, r.data, digest.size_in_bytes)
+                .with_context(|| format!("Failed to decode digest data for `{}`", digest))?;
+            batched_blobs_response.insert(digest, data);
         }
     }
 
@@ -954,13 +1322,17 @@ where
     for digest in inlined_digests {
         let data = if digest.size_in_bytes as usize >= max_msg_size {
             let mut accum = vec![];
+            let mut decoder = StreamDecoder::new(compressors.bytestream, digest.size_in_bytes)?;
             let mut responses = bystream_fut(digest.clone()).await?;
             while let Some(resp) = responses.next().await {
                 let data = resp
                     .with_context(|| format!("Failed to fetch inline digest: {digest}"))?
                     .data;
-                accum.extend_from_slice(&data);
+                accum.extend_from_slice(&decoder.decode(data)?);
             }
+            decoder
+                .finish()
+                .with_context(|| format!("Failed to fetch inline digest: {digest}"))?;
             accum
         } else {
             get(&digest)?
@@ -999,15 +1371,21 @@ where
                     .await
                     .with_context(|| format!("Error writing: {}", req.named_digest.digest))?;
             } else {
+                let mut decoder = StreamDecoder::new(
+                    compressors.bytestream,
+                    req.named_digest.digest.size_in_bytes,
+                )?;
                 let mut responses = bystream_fut(req.named_digest.digest.clone()).await?;
                 while let Some(resp) = responses.next().await {
                     let data = resp
                         .with_context(|| format!("Failed to fetch file: {:?}", file))?
                         .data;
+                    let data = decoder.decode(data)?;
                     file.write_all(&data).await.with_context(|| {
                         format!("Error writing chunk of: {}", req.named_digest.digest)
                     })?;
                 }
+                decoder.finish()?;
             }
             file.flush().await.context("Error flushing")?;
             anyhow::Ok(())
@@ -1032,6 +1410,7 @@ async fn upload_impl<Byt, Cas>(
     instance_name: &InstanceName,
     request: UploadRequest,
     max_msg_size: usize,
+    compressors: Compressors,
     cas_f: impl Fn(BatchUpdateBlobsRequest) -> Cas + Sync + Send + Copy,
     bystream_fut: impl Fn(Vec<WriteRequest>) -> Byt + Sync + Send + Copy,
 ) -> anyhow::Result<UploadResponse>
@@ -1057,29 +1436,13 @@ where
         }
 
         let data = blob.blob;
-        let client_uuid = uuid::Uuid::new_v4().to_string();
-        let resource_name = format!(
-            "{}uploads/{}/blobs/{}/{}",
-            instance_name.as_resource_prefix(),
-            client_uuid,
-            hash,
-            size
-        );
+        let resource_name = upload_resource_name(instance_name, compressors, &hash, size);
         let fut = async move {
-            // Number of complete (non-partial) messages
-            let mut upload_segments = vec![];
-            for (i, chunk) in data.chunks(max_msg_size).enumerate() {
-                upload_segments.push(WriteRequest {
-                    resource_name: resource_name.to_owned(),
-                    write_offset: (i * max_msg_size) as i64,
-                    finish_write: false,
-                    data: chunk.to_owned(),
-                });
-            }
-            upload_segments.last_mut().unwrap().finish_write = true;
+            let data = compress_blocking(compressors.bytestream, data).await?;
+            let upload_segments = write_requests(&resource_name, &data, max_msg_size);
 
             let resp = bystream_fut(upload_segments).await?;
-            if resp.committed_size != size {
+            if !is_valid_committed_size(&resp, size, compressors, data.len()) {
                 return Err(anyhow::anyhow!(
                     "Failed to upload inline blob: invalid committed_size from WriteResponse"
                 ));
@@ -1099,15 +1462,41 @@ where
             batched_blob_updates.push(BatchUploadRequest::File(file));
             continue;
         }
-        let client_uuid = uuid::Uuid::new_v4().to_string();
-        let resource_name = format!(
-            "{}uploads/{}/blobs/{}/{}",
-            instance_name.as_resource_prefix(),
-            client_uuid,
-            hash.clone(),
-            size
-        );
+        let resource_name = upload_resource_name(instance_name, compressors, &hash, size);
         let fut = async move {
+            if compressors.bytestream != compressor::Value::Identity {
+                // The file is compressed as it is read, so that we never hold both the whole file
+                // and its compressed form in memory.
+                let chunks =
+                    compress_file_blocking(compressors.bytestream, name.clone(), max_msg_size)
+                        .await?;
+                let sent = chunks.iter().map(|c| c.len()).sum::<usize>();
+                let mut upload_segments = Vec::with_capacity(chunks.len());
+                let mut write_offset = 0;
+                for data in chunks {
+                    let length = data.len() as i64;
+                    upload_segments.push(WriteRequest {
+                        resource_name: resource_name.to_owned(),
+                        write_offset,
+                        finish_write: false,
+                        data,
+                    });
+                    write_offset += length;
+                }
+                upload_segments
+                    .last_mut()
+                    .with_context(|| format!("Compressed `{name}` to no segments"))?
+                    .finish_write = true;
+
+                let resp = bystream_fut(upload_segments).await?;
+                if !is_valid_committed_size(&resp, size, compressors, sent) {
+                    return Err(anyhow::anyhow!(
+                        "Failed to upload `{name}`: invalid committed_size from WriteResponse"
+                    ));
+                }
+                return Ok(vec![hash]);
+            }
+
             let mut file = tokio::fs::File::open(&name)
                 .await
                 .with_context(|| format!("Opening `{name}` for reading failed"))?;
@@ -1137,7 +1526,7 @@ where
                 .finish_write = true;
 
             let resp = bystream_fut(upload_segments).await?;
-            if resp.committed_size != size {
+            if !is_valid_committed_size(&resp, size, compressors, size as usize) {
                 return Err(anyhow::anyhow!(
                     "Failed to upload `{name}`: invalid committed_size from WriteResponse"
                 ));
@@ -1161,8 +1550,9 @@ where
                     BatchUploadRequest::Blob(blob) => {
                         re_request.requests.push(Request {
                             digest: Some(tdigest_to(blob.digest.clone())),
-                            data: blob.blob.clone(),
-                            compressor: compressor::Value::Identity as i32,
+                            data: compress_blocking(compressors.batch_update, blob.blob.clone())
+                                .await?,
+                            compressor: compressors.batch_update as i32,
                         });
                     }
                     BatchUploadRequest::File(file) => {
@@ -1175,8 +1565,8 @@ where
 
                         re_request.requests.push(Request {
                             digest: Some(tdigest_to(file.digest.clone())),
-                            data,
-                            compressor: compressor::Value::Identity as i32,
+                            data: compress_blocking(compressors.batch_update, data).await?,
+                            compressor: compressors.batch_update as i32,
                         });
                     }
                 }
@@ -1223,6 +1613,55 @@ where
     Ok(UploadResponse {})
 }
 
+fn upload_resource_name(
+    instance_name: &InstanceName,
+    compressors: Compressors,
+    hash: &str,
+    size: i64,
+) -> String {
+    format!(
+        "{}uploads/{}/{}/{}/{}",
+        instance_name.as_resource_prefix(),
+        uuid::Uuid::new_v4(),
+        resource_kind(compressors.bytestream),
+        hash,
+        size
+    )
+}
+
+/// Split data to upload over ByteStream into chunks. Offsets are in terms of the data that is
+/// sent, so for a compressed upload they are offsets into the compressed data.
+fn write_requests(resource_name: &str, data: &[u8], max_msg_size: usize) -> Vec<WriteRequest> {
+    let mut upload_segments = vec![];
+    for (i, chunk) in data.chunks(max_msg_size).enumerate() {
+        upload_segments.push(WriteRequest {
+            resource_name: resource_name.to_owned(),
+            write_offset: (i * max_msg_size) as i64,
+            finish_write: false,
+            data: chunk.to_owned(),
+        });
+    }
+    if let Some(last) = upload_segments.last_mut() {
+        last.finish_write = true;
+    }
+    upload_segments
+}
+
+/// An uncompressed upload commits the whole blob. A compressed upload commits the compressed
+/// bytes we sent, or reports -1 if the blob was already present.
+fn is_valid_committed_size(
+    resp: &WriteResponse,
+    size: i64,
+    compressors: Compressors,
+    sent: usize,
+) -> bool {
+    if compressors.bytestream == compressor::Value::Identity {
+        return resp.committed_size == size;
+    }
+    // Some servers report the uncompressed size of the blob instead, which is fine too.
+    resp.committed_size == -1 || resp.committed_size == sent as i64 || resp.committed_size == size
+}
+
 fn with_internal_metadata<T>(t: T, metadata: RemoteExecutionMetadata) -> tonic::Request<T> {
     // This is pretty ugly, but the protobuf spec that defines this is internal, so considering
     // field numbers need to be stable anyway (= low risk), and this is not used in prod (= low
@@ -1285,8 +1724,10 @@ fn substitute_env_vars_impl(
 
 #[cfg(test)]
 mod tests {
+    use re_fake_server::FakeReServer;
     use re_grpc_proto::build::bazel::remote::execution::v2::batch_read_blobs_response;
     use re_grpc_proto::build::bazel::remote::execution::v2::batch_update_blobs_response;
+    use re_grpc_proto::build::bazel::remote::execution::v2::CacheCapabilities;
 
     use super::*;
     use crate::NamedDigest;
@@ -1358,6 +1799,7 @@ mod tests {
             &InstanceName(None),
             req,
             10000,
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1464,6 +1906,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large file download
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1545,6 +1988,7 @@ mod tests {
             &InstanceName(None),
             req,
             100000,
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1618,6 +2062,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // intentionally small value to keep data in the test blobs small
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1673,6 +2118,7 @@ mod tests {
             &InstanceName(None),
             req,
             100000,
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 async move {
@@ -1711,6 +2157,7 @@ mod tests {
             &InstanceName(Some("instance".to_owned())),
             req,
             0,
+            Compressors::IDENTITY,
             |_req| async { panic!("not called") },
             |req| async move {
                 assert_eq!(req.resource_name, "instance/blobs/aa/0");
@@ -1780,6 +2227,7 @@ mod tests {
             &InstanceName(None),
             req,
             10000,
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1862,6 +2310,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large file upload
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1935,6 +2384,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large inlined upload
+            Compressors::IDENTITY,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1995,6 +2445,7 @@ mod tests {
             &InstanceName(None), // TODO
             req,
             10,
+            Compressors::IDENTITY,
             |_req| async move {
                 panic!("This should not be called as there are no blobs to upload in batch");
             },
@@ -2055,6 +2506,7 @@ mod tests {
             &InstanceName(None),
             req,
             3,
+            Compressors::IDENTITY,
             |_req| async move {
                 panic!("Not called");
             },
@@ -2095,6 +2547,7 @@ mod tests {
             &InstanceName(None),
             req,
             0,
+            Compressors::IDENTITY,
             |_req| async move {
                 panic!("Not called");
             },
@@ -2140,6 +2593,7 @@ mod tests {
             &InstanceName(Some("instance".to_owned())),
             req,
             1,
+            Compressors::IDENTITY,
             |_req| async move {
                 panic!("Not called");
             },
@@ -2154,6 +2608,620 @@ mod tests {
         Ok(())
     }
 
+    async fn connect_to(cas: &FakeReServer) -> anyhow::Result<REClient> {
+        let address = format!("grpc://{}", cas.spawn().await?);
+        REClientBuilder::build_and_connect(
+            &Buck2OssReConfiguration {
+                cas_address: Some(address.clone()),
+                engine_address: Some(address.clone()),
+                action_cache_address: Some(address),
+                ..Default::default()
+            },
+            DigestFunction::Sha256,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_upload_blob() -> anyhow::Result<()> {
+        let cas = FakeReServer::default();
+        let client = connect_to(&cas).await?;
+
+        let digest = client
+            .upload_blob(b"hello".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        assert_eq!(
+            digest.hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(digest.size_in_bytes, 5);
+        assert_eq!(cas.blob(&digest.hash), Some(b"hello".to_vec()));
+
+        // Large enough to go through the ByteStream service.
+        let large = vec![7; DEFAULT_MAX_MSG_SIZE + 1];
+        let digest = client
+            .upload_blob(large.clone(), RemoteExecutionMetadata::default())
+            .await?;
+        assert_eq!(digest.size_in_bytes, large.len() as i64);
+        assert_eq!(cas.blob(&digest.hash), Some(large));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_action_result() -> anyhow::Result<()> {
+        let cas = FakeReServer::default();
+        let client = connect_to(&cas).await?;
+
+        let output = client
+            .upload_blob(b"output".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        let stdout = client
+            .upload_blob(b"stdout".to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        let action_digest = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let action_result = TActionResult2 {
+            output_files: vec![TFile {
+                digest: DigestWithStatus {
+                    digest: output.clone(),
+                    status: tstatus_ok(),
+                    ..Default::default()
+                },
+                name: "out/file".to_owned(),
+                executable: true,
+                ..Default::default()
+            }],
+            exit_code: 0,
+            stdout_digest: Some(stdout.clone()),
+            stderr_raw: Some(b"stderr".to_vec()),
+            execution_metadata: TExecutedActionMetadata {
+                worker: "local".to_owned(),
+                execution_start_timestamp: TTimestamp {
+                    seconds: 10,
+                    nanos: 20,
+                    ..Default::default()
+                },
+                auxiliary_metadata: vec![TAny {
+                    type_url: "dep_file".to_owned(),
+                    value: vec![1, 2, 3],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let res = client
+            .write_action_result(
+                RemoteExecutionMetadata::default(),
+                WriteActionResultRequest {
+                    action_digest: action_digest.clone(),
+                    action_result,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let stored = cas
+            .action_result(&action_digest.hash)
+            .context("Action result was not stored")?;
+        assert_eq!(stored.output_files.len(), 1);
+        assert_eq!(stored.output_files[0].path, "out/file");
+        assert_eq!(stored.output_files[0].digest, Some(tdigest_to(output)));
+        assert!(stored.output_files[0].is_executable);
+        assert_eq!(stored.stdout_digest, Some(tdigest_to(stdout)));
+        assert_eq!(stored.stderr_raw, b"stderr");
+        let metadata = stored.execution_metadata.context("No execution metadata")?;
+        assert_eq!(metadata.worker, "local");
+        assert_eq!(
+            metadata.execution_start_timestamp,
+            Some(::prost_types::Timestamp {
+                seconds: 10,
+                nanos: 20
+            })
+        );
+        assert_eq!(metadata.queued_timestamp, None);
+
+        // What the cache returns makes it back to the caller, including auxiliary metadata
+        // such as dep file entries.
+        let actual = res.actual_action_result;
+        assert_eq!(actual.output_files[0].name, "out/file");
+        assert_eq!(actual.execution_metadata.worker, "local");
+        let aux = &actual.execution_metadata.auxiliary_metadata;
+        assert_eq!(aux.len(), 1);
+        assert_eq!(aux[0].type_url, "dep_file");
+        assert_eq!(aux[0].value, vec![1, 2, 3]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_action_result_missing_blob() -> anyhow::Result<()> {
+        let cas = FakeReServer::default();
+        let client = connect_to(&cas).await?;
+
+        let action_digest = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+        let res = client
+            .write_action_result(
+                RemoteExecutionMetadata::default(),
+                WriteActionResultRequest {
+                    action_digest: action_digest.clone(),
+                    action_result: TActionResult2 {
+                        stdout_digest: Some(digest_blob(DigestFunction::Sha256, b"never uploaded")),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        assert!(res.is_err());
+        assert!(cas.action_result(&action_digest.hash).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_action_result_disabled() -> anyhow::Result<()> {
+        let cas = FakeReServer::default();
+        let mut client = connect_to(&cas).await?;
+        client.capabilities.action_cache_update_enabled = false;
+
+        let res = client
+            .write_action_result(
+                RemoteExecutionMetadata::default(),
+                WriteActionResultRequest::default(),
+            )
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_with_fake_server() -> anyhow::Result<()> {
+        use re_grpc_proto::build::bazel::remote::execution::v2::Action;
+        use re_grpc_proto::build::bazel::remote::execution::v2::Command;
+
+        let server = FakeReServer::default();
+        let client = connect_to(&server).await?;
+
+        let command = Command {
+            arguments: vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                "echo hello > out".to_owned(),
+            ],
+            output_paths: vec!["out".to_owned()],
+            ..Default::default()
+        };
+        let command_digest = client
+            .upload_blob(command.encode_to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+        let input_root_digest = client
+            .upload_blob(
+                Directory::default().encode_to_vec(),
+                RemoteExecutionMetadata::default(),
+            )
+            .await?;
+        let action = Action {
+            command_digest: Some(tdigest_to(command_digest)),
+            input_root_digest: Some(tdigest_to(input_root_digest)),
+            ..Default::default()
+        };
+        let action_digest = client
+            .upload_blob(action.encode_to_vec(), RemoteExecutionMetadata::default())
+            .await?;
+
+        // The first run executes the action, the second one hits the cache.
+        for cached in [false, true] {
+            let responses: Vec<_> = client
+                .execute_with_progress(
+                    RemoteExecutionMetadata::default(),
+                    ExecuteRequest {
+                        action_digest: action_digest.clone(),
+                        ..Default::default()
+                    },
+                )
+                .await?
+                .try_collect()
+                .await?;
+            let response = responses
+                .into_iter()
+                .find_map(|r| r.execute_response)
+                .context("No execute response")?;
+
+            assert_eq!(response.cached_result, cached);
+            assert_eq!(response.action_digest, action_digest);
+            let action_result = response.action_result;
+            assert_eq!(action_result.exit_code, 0);
+            assert_eq!(action_result.output_files.len(), 1);
+            let output = &action_result.output_files[0];
+            assert_eq!(output.name, "out");
+            assert_eq!(
+                server.blob(&output.digest.digest.hash),
+                Some(b"hello\n".to_vec())
+            );
+        }
+        assert_eq!(server.executed_actions(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_digest_blob() {
+        assert_eq!(
+            digest_blob(DigestFunction::Sha256, b"hello").hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(
+            digest_blob(DigestFunction::Sha1, b"hello").hash,
+            "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"
+        );
+        assert_eq!(
+            digest_blob(DigestFunction::Blake3, b"hello").hash,
+            blake3::hash(b"hello").to_hex().as_str()
+        );
+    }
+
+    #[test]
+    fn test_check_digest_function() -> anyhow::Result<()> {
+        check_digest_function(DigestFunction::Blake3, &[])?;
+        check_digest_function(
+            DigestFunction::Sha1,
+            &[
+                digest_function::Value::Sha1 as i32,
+                digest_function::Value::Sha256 as i32,
+            ],
+        )?;
+        assert!(
+            check_digest_function(
+                DigestFunction::Blake3,
+                &[digest_function::Value::Sha256 as i32]
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_uploaded_directories() -> anyhow::Result<()> {
+        use re_grpc_proto::build::bazel::remote::execution::v2::NodeProperties;
+        use re_grpc_proto::build::bazel::remote::execution::v2::NodeProperty;
+        use re_grpc_proto::build::bazel::remote::execution::v2::SymlinkNode;
+
+        let blob = |directory: Directory| InlinedBlobWithDigest {
+            blob: directory.encode_to_vec(),
+            ..Default::default()
+        };
+        let absolute_symlink = blob(Directory {
+            symlinks: vec![SymlinkNode {
+                name: "link".to_owned(),
+                target: "/usr/bin/python3".to_owned(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let node_property = blob(Directory {
+            node_properties: Some(NodeProperties {
+                properties: vec![NodeProperty {
+                    name: "owner".to_owned(),
+                    value: "root".to_owned(),
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let blobs = [absolute_symlink, node_property];
+
+        check_uploaded_directories(&RECapabilities::default(), &blobs)?;
+
+        let capabilities = RECapabilities {
+            absolute_symlinks_allowed: false,
+            ..Default::default()
+        };
+        let err = check_uploaded_directories(&capabilities, &blobs[..1]).unwrap_err();
+        assert!(format!("{:#}", err).contains("absolute target `/usr/bin/python3`"));
+        check_uploaded_directories(&capabilities, &blobs[1..])?;
+
+        let capabilities = RECapabilities {
+            supported_node_properties: Some(vec!["mtime".to_owned()]),
+            ..Default::default()
+        };
+        check_uploaded_directories(&capabilities, &blobs[..1])?;
+        let err = check_uploaded_directories(&capabilities, &blobs[1..]).unwrap_err();
+        assert!(format!("{:#}", err).contains("node property `owner`"));
+
+        // Blobs that aren't directories are left alone.
+        check_uploaded_directories(
+            &capabilities,
+            &[InlinedBlobWithDigest {
+                blob: b"/usr/bin/python3".to_vec(),
+                ..Default::default()
+            }],
+        )?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_unsupported_digest_function() -> anyhow::Result<()> {
+        let cas = FakeReServer::default();
+        let address = format!("grpc://{}", cas.spawn().await?);
+        let res = REClientBuilder::build_and_connect(
+            &Buck2OssReConfiguration {
+                cas_address: Some(address.clone()),
+                engine_address: Some(address.clone()),
+                action_cache_address: Some(address),
+                ..Default::default()
+            },
+            DigestFunction::Blake3,
+        )
+        .await;
+        assert!(res.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_clamp_priority() {
+        let ranges = vec![
+            PriorityRange {
+                min_priority: -10,
+                max_priority: -5,
+            },
+            PriorityRange {
+                min_priority: 1,
+                max_priority: 3,
+            },
+        ];
+        assert_eq!(clamp_priority(0, &ranges), 0);
+        assert_eq!(clamp_priority(2, &ranges), 2);
+        assert_eq!(clamp_priority(-7, &ranges), -7);
+        assert_eq!(clamp_priority(10, &ranges), 3);
+        assert_eq!(clamp_priority(-20, &ranges), -10);
+        assert_eq!(clamp_priority(-2, &ranges), 1);
+        assert_eq!(clamp_priority(100, &[]), 100);
+    }
+
+    #[test]
+    fn test_convert_execute_request() {
+        let capabilities = RECapabilities {
+            execution_priorities: vec![PriorityRange {
+                min_priority: 1,
+                max_priority: 5,
+            }],
+            ..Default::default()
+        };
+        let execute_request = ExecuteRequest {
+            action_digest: TDigest {
+                hash: "aa".to_owned(),
+                size_in_bytes: 3,
+                ..Default::default()
+            },
+            skip_cache_lookup: true,
+            execution_policy: Some(TExecutionPolicy {
+                priority: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let request = convert_execute_request(
+            &InstanceName(Some("instance".to_owned())),
+            &capabilities,
+            &execute_request,
+        );
+        assert_eq!(request.instance_name, "instance");
+        assert!(request.skip_cache_lookup);
+        assert_eq!(
+            request.execution_policy,
+            Some(ExecutionPolicy { priority: 5 })
+        );
+        // No ranges were advertised for the cache, so the priority goes through as-is.
+        assert_eq!(
+            request.results_cache_policy,
+            Some(ResultsCachePolicy { priority: 10 })
+        );
+        assert_eq!(
+            request.action_digest,
+            Some(Digest {
+                hash: "aa".to_owned(),
+                size_bytes: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_negotiate_compressors() {
+        let compressors = Compressors::negotiate(&CacheCapabilities {
+            supported_compressors: vec![
+                compressor::Value::Deflate as i32,
+                compressor::Value::Zstd as i32,
+            ],
+            supported_batch_update_compressors: vec![compressor::Value::Deflate as i32],
+            ..Default::default()
+        });
+        assert_eq!(compressors.bytestream, compressor::Value::Zstd);
+        assert_eq!(compressors.batch_update, compressor::Value::Identity);
+
+        let compressors = Compressors::negotiate(&CacheCapabilities::default());
+        assert_eq!(compressors, Compressors::IDENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_download_zstd() -> anyhow::Result<()> {
+        let digest1 = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let digest2 = TDigest {
+            hash: "xl".to_owned(),
+            size_in_bytes: 18,
+            ..Default::default()
+        };
+        let blob_data2 = vec![
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+        ];
+
+        let req = DownloadRequest {
+            inlined_digests: Some(vec![digest1.clone(), digest2.clone()]),
+            ..Default::default()
+        };
+
+        let res = BatchReadBlobsResponse {
+            responses: vec![batch_read_blobs_response::Response {
+                digest: Some(tdigest_to(digest1.clone())),
+                data: zstd::bulk::compress(b"aaa", 0)?,
+                compressor: compressor::Value::Zstd as i32,
+                ..Default::default()
+            }],
+        };
+
+        let compressed2 = zstd::bulk::compress(&blob_data2, 0)?;
+
+        let res = download_impl(
+            &InstanceName(None),
+            req,
+            10, // kept small to simulate a large download
+            Compressors {
+                bytestream: compressor::Value::Zstd,
+                batch_update: compressor::Value::Identity,
+            },
+            |req| {
+                let res = res.clone();
+                async move {
+                    assert_eq!(
+                        req.acceptable_compressors,
+                        vec![
+                            compressor::Value::Identity as i32,
+                            compressor::Value::Zstd as i32
+                        ]
+                    );
+                    Ok(res)
+                }
+            },
+            |req| {
+                let compressed2 = compressed2.clone();
+                async move {
+                    assert_eq!(req.resource_name, "compressed-blobs/zstd/xl/18");
+                    assert_eq!(req.read_limit, 0);
+                    let responses = compressed2
+                        .chunks(4)
+                        .map(|data| {
+                            Ok(ReadResponse {
+                                data: data.to_vec(),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    anyhow::Ok(Box::pin(futures::stream::iter(responses)))
+                }
+            },
+        )
+        .await?;
+
+        let inlined_blobs = res.inlined_blobs.unwrap();
+        assert_eq!(inlined_blobs.len(), 2);
+        assert_eq!(inlined_blobs[0].blob, b"aaa");
+        assert_eq!(inlined_blobs[1].blob, blob_data2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_zstd() -> anyhow::Result<()> {
+        let digest1 = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+        let blob_data1 = b"aaa".to_vec();
+
+        let digest2 = TDigest {
+            hash: "xl".to_owned(),
+            size_in_bytes: 18,
+            ..Default::default()
+        };
+        let blob_data2 = vec![
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+        ];
+
+        let req = UploadRequest {
+            inlined_blobs_with_digest: Some(vec![
+                InlinedBlobWithDigest {
+                    blob: blob_data2.clone(),
+                    digest: digest2.clone(),
+                    ..Default::default()
+                },
+                InlinedBlobWithDigest {
+                    blob: blob_data1.clone(),
+                    digest: digest1.clone(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let res = BatchUpdateBlobsResponse {
+            responses: vec![batch_update_blobs_response::Response {
+                digest: Some(tdigest_to(digest1.clone())),
+                status: Some(Status::default()),
+            }],
+        };
+
+        upload_impl(
+            &InstanceName(None),
+            req,
+            10, // kept small to simulate a large inlined upload
+            Compressors {
+                bytestream: compressor::Value::Zstd,
+                batch_update: compressor::Value::Zstd,
+            },
+            |req| {
+                let res = res.clone();
+                let blob_data1 = blob_data1.clone();
+                async move {
+                    assert_eq!(req.requests.len(), 1);
+                    assert_eq!(req.requests[0].compressor, compressor::Value::Zstd as i32);
+                    assert_eq!(
+                        zstd::stream::decode_all(&*req.requests[0].data)?,
+                        blob_data1
+                    );
+                    Ok(res)
+                }
+            },
+            |write_reqs| {
+                let blob_data2 = blob_data2.clone();
+                async move {
+                    assert!(write_reqs.len() > 1);
+                    let mut compressed = vec![];
+                    for (i, req) in write_reqs.iter().enumerate() {
+                        assert!(req.resource_name.ends_with("/compressed-blobs/zstd/xl/18"));
+                        assert_eq!(req.write_offset, compressed.len() as i64);
+                        assert_eq!(req.finish_write, i == write_reqs.len() - 1);
+                        compressed.extend_from_slice(&req.data);
+                    }
+                    assert_eq!(zstd::stream::decode_all(&*compressed)?, blob_data2);
+                    // The blob was already present.
+                    anyhow::Ok(WriteResponse { committed_size: -1 })
+                }
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
     #[test]
     fn test_substitute_env_vars() {
         let getter = |s: &str| match s {
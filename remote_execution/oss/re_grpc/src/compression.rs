@@ -0,0 +1,352 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io;
+use std::io::Read;
+use std::io::Write;
+
+use anyhow::Context;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+use re_grpc_proto::build::bazel::remote::execution::v2::CacheCapabilities;
+
+/// Level used when compressing blobs with zstd. Low levels are much cheaper to compress and still
+/// give most of the size reduction on build outputs.
+const ZSTD_LEVEL: i32 = 1;
+
+/// Compressors to use when talking to the CAS, as negotiated with the remote through
+/// `GetCapabilities`. Identity is always supported, so that is what we fall back to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Compressors {
+    /// Compressor for ByteStream reads and writes, and accepted in `BatchReadBlobs` responses.
+    pub(crate) bytestream: compressor::Value,
+    /// Compressor for data inlined in `BatchUpdateBlobs` requests.
+    pub(crate) batch_update: compressor::Value,
+}
+
+impl Compressors {
+    pub(crate) const IDENTITY: Compressors = Compressors {
+        bytestream: compressor::Value::Identity,
+        batch_update: compressor::Value::Identity,
+    };
+
+    /// Pick the compressors to use given what the remote advertises. We only know how to use
+    /// zstd, so anything else means we send and receive uncompressed data.
+    pub(crate) fn negotiate(cache_cap: &CacheCapabilities) -> Self {
+        let pick = |supported: &[i32]| {
+            if supported.contains(&(compressor::Value::Zstd as i32)) {
+                compressor::Value::Zstd
+            } else {
+                compressor::Value::Identity
+            }
+        };
+
+        Compressors {
+            bytestream: pick(&cache_cap.supported_compressors),
+            batch_update: pick(&cache_cap.supported_batch_update_compressors),
+        }
+    }
+
+    /// Compressors we are willing to receive in `BatchReadBlobs` responses.
+    pub(crate) fn acceptable_for_batch_read(&self) -> Vec<i32> {
+        if self.bytestream == compressor::Value::Identity {
+            vec![compressor::Value::Identity as i32]
+        } else {
+            vec![compressor::Value::Identity as i32, self.bytestream as i32]
+        }
+    }
+}
+
+/// Path component to use in ByteStream resource names for blobs transferred with `compressor`,
+/// i.e. `blobs` or `compressed-blobs/zstd`.
+pub(crate) fn resource_kind(compressor: compressor::Value) -> &'static str {
+    match compressor {
+        compressor::Value::Identity => "blobs",
+        compressor::Value::Zstd => "compressed-blobs/zstd",
+        compressor::Value::Deflate => "compressed-blobs/deflate",
+    }
+}
+
+pub(crate) fn compress(compressor: compressor::Value, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    match compressor {
+        compressor::Value::Identity => Ok(data),
+        compressor::Value::Zstd => {
+            zstd::bulk::compress(&data, ZSTD_LEVEL).context("Error compressing blob with zstd")
+        }
+        other => Err(anyhow::anyhow!("Unsupported compressor: {:?}", other)),
+    }
+}
+
+/// Like `compress`, but off the async runtime, since compressing large blobs takes a while.
+pub(crate) async fn compress_blocking(
+    compressor: compressor::Value,
+    data: Vec<u8>,
+) -> anyhow::Result<Vec<u8>> {
+    if compressor == compressor::Value::Identity {
+        return Ok(data);
+    }
+    tokio::task::spawn_blocking(move || compress(compressor, data))
+        .await
+        .context("Compression task failed")?
+}
+
+/// Compress everything `reader` yields, a buffer at a time, and return the compressed data split
+/// into chunks of at most `chunk_size` bytes, ready to be sent as ByteStream writes.
+pub(crate) fn compress_chunked(
+    compressor: compressor::Value,
+    reader: impl Read,
+    chunk_size: usize,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut reader: Box<dyn Read> = match compressor {
+        compressor::Value::Identity => Box::new(reader),
+        compressor::Value::Zstd => Box::new(
+            zstd::stream::read::Encoder::new(reader, ZSTD_LEVEL)
+                .context("Error creating zstd encoder")?,
+        ),
+        other => return Err(anyhow::anyhow!("Unsupported compressor: {:?}", other)),
+    };
+
+    let mut chunks = Vec::new();
+    loop {
+        let mut chunk = Vec::with_capacity(chunk_size);
+        (&mut reader)
+            .take(chunk_size as u64)
+            .read_to_end(&mut chunk)
+            .context("Error compressing stream")?;
+        if chunk.is_empty() {
+            break;
+        }
+        chunks.push(chunk);
+    }
+    Ok(chunks)
+}
+
+/// Like `compress_chunked`, for the file at `path`, off the async runtime.
+pub(crate) async fn compress_file_blocking(
+    compressor: compressor::Value,
+    path: String,
+    chunk_size: usize,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)
+            .with_context(|| format!("Opening `{path}` for reading failed"))?;
+        compress_chunked(compressor, io::BufReader::new(file), chunk_size)
+            .with_context(|| format!("Error reading from {path}"))
+    })
+    .await
+    .context("Compression task failed")?
+}
+
+/// Decompress a blob received with `compressor` (as found on the wire) and check that it has the
+/// size its digest says it should have.
+pub(crate) fn decompress(
+    compressor: i32,
+    data: Vec<u8>,
+    expected_size: i64,
+) -> anyhow::Result<Vec<u8>> {
+    let data = match compressor::Value::from_i32(compressor) {
+        Some(compressor::Value::Identity) => return Ok(data),
+        Some(compressor::Value::Zstd) => zstd::bulk::decompress(&data, expected_size as usize)
+            .context("Error decompressing blob with zstd")?,
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unsupported compressor in response: {:?}",
+                other
+            ));
+        }
+    };
+
+    if data.len() as i64 != expected_size {
+        return Err(anyhow::anyhow!(
+            "Decompressed blob has size {} but expected {}",
+            data.len(),
+            expected_size
+        ));
+    }
+
+    Ok(data)
+}
+
+/// Output of a `StreamDecoder`, which refuses to hold more data than the blob being decoded
+/// should have, so that a bad stream can't make us decompress an unbounded amount of data.
+pub(crate) struct BoundedBuffer {
+    data: Vec<u8>,
+    remaining: u64,
+}
+
+impl Write for BoundedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Decompressed stream is larger than expected",
+            ));
+        }
+        self.remaining -= buf.len() as u64;
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decodes the chunks of a ByteStream read as they arrive.
+pub(crate) enum StreamDecoder {
+    Identity,
+    Zstd {
+        decoder: zstd::stream::write::Decoder<'static, BoundedBuffer>,
+        decoded: i64,
+        expected_size: i64,
+    },
+}
+
+impl StreamDecoder {
+    /// Create a decoder for a blob whose digest says it has `expected_size` bytes. Decoding fails
+    /// as soon as the stream produces more than that.
+    pub(crate) fn new(compressor: compressor::Value, expected_size: i64) -> anyhow::Result<Self> {
+        match compressor {
+            compressor::Value::Identity => Ok(StreamDecoder::Identity),
+            compressor::Value::Zstd => Ok(StreamDecoder::Zstd {
+                decoder: zstd::stream::write::Decoder::new(BoundedBuffer {
+                    data: Vec::new(),
+                    remaining: expected_size.max(0) as u64,
+                })
+                .context("Error creating zstd decoder")?,
+                decoded: 0,
+                expected_size,
+            }),
+            other => Err(anyhow::anyhow!("Unsupported compressor: {:?}", other)),
+        }
+    }
+
+    /// Feed a chunk from the stream and return whatever data could be decoded so far.
+    pub(crate) fn decode(&mut self, chunk: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        match self {
+            StreamDecoder::Identity => Ok(chunk),
+            StreamDecoder::Zstd {
+                decoder,
+                decoded,
+                expected_size,
+            } => {
+                decoder
+                    .write_all(&chunk)
+                    .and_then(|()| decoder.flush())
+                    .with_context(|| {
+                        format!(
+                            "Error decompressing stream with zstd, expected size {expected_size}"
+                        )
+                    })?;
+                let out = std::mem::take(&mut decoder.get_mut().data);
+                *decoded += out.len() as i64;
+                Ok(out)
+            }
+        }
+    }
+
+    /// Check the stream decoded to the size its digest says it should have. Identity streams are
+    /// passed through as-is.
+    pub(crate) fn finish(self) -> anyhow::Result<()> {
+        match self {
+            StreamDecoder::Identity => Ok(()),
+            StreamDecoder::Zstd {
+                decoded,
+                expected_size,
+                ..
+            } => {
+                if decoded != expected_size {
+                    return Err(anyhow::anyhow!(
+                        "Decompressed stream has size {} but expected {}",
+                        decoded,
+                        expected_size
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Data that compresses, but not so well that it fits in a single small chunk.
+    fn data() -> Vec<u8> {
+        let mut state = 1u64;
+        (0..100_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                b'a' + (state >> 60) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compress_roundtrip() -> anyhow::Result<()> {
+        let data = data();
+        let compressed = compress(compressor::Value::Zstd, data.clone())?;
+        assert!(compressed.len() < data.len());
+        assert_eq!(
+            decompress(
+                compressor::Value::Zstd as i32,
+                compressed.clone(),
+                data.len() as i64
+            )?,
+            data
+        );
+        assert!(decompress(compressor::Value::Zstd as i32, compressed, 10).is_err());
+
+        assert_eq!(compress(compressor::Value::Identity, data.clone())?, data);
+        assert!(compress(compressor::Value::Deflate, data).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress_chunked() -> anyhow::Result<()> {
+        let data = data();
+        let chunks = compress_chunked(compressor::Value::Zstd, data.as_slice(), 1000)?;
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= 1000));
+        assert_eq!(zstd::bulk::decompress(&chunks.concat(), data.len())?, data);
+
+        let chunks = compress_chunked(compressor::Value::Identity, data.as_slice(), 1000)?;
+        assert_eq!(chunks.len(), 100);
+        assert_eq!(chunks.concat(), data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_decoder() -> anyhow::Result<()> {
+        let data = data();
+        let compressed = compress(compressor::Value::Zstd, data.clone())?;
+
+        let mut decoder = StreamDecoder::new(compressor::Value::Zstd, data.len() as i64)?;
+        let mut decoded = Vec::new();
+        for chunk in compressed.chunks(100) {
+            decoded.extend(decoder.decode(chunk.to_vec())?);
+        }
+        decoder.finish()?;
+        assert_eq!(decoded, data);
+
+        // A stream that decodes to more than expected fails as soon as it goes over.
+        let mut decoder = StreamDecoder::new(compressor::Value::Zstd, 1000)?;
+        let err = compressed
+            .chunks(100)
+            .try_for_each(|chunk| decoder.decode(chunk.to_vec()).map(|_| ()))
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("larger than expected"));
+
+        // A stream that decodes to less than expected fails at the end.
+        let mut decoder = StreamDecoder::new(compressor::Value::Zstd, data.len() as i64 + 1)?;
+        decoder.decode(compressed)?;
+        assert!(decoder.finish().is_err());
+        Ok(())
+    }
+}
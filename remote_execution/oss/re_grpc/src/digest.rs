@@ -15,6 +15,14 @@ use anyhow::Context;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+/// The hash function Buck2 computes digests with. The remote has to support it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DigestFunction {
+    Sha1,
+    Sha256,
+    Blake3,
+}
+
 #[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
 pub struct TDigest {
     pub hash: String,
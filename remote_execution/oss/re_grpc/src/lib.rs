@@ -8,6 +8,7 @@
  */
 
 mod client;
+mod compression;
 mod digest;
 mod error;
 mod grpc;
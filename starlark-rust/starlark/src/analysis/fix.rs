@@ -0,0 +1,145 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Applying the automatic fixes attached to lints.
+
+use crate::analysis::types::LintEdit;
+use crate::analysis::types::LintFix;
+use crate::codemap::CodeMap;
+use crate::codemap::Span;
+
+fn overlaps(a: Span, b: Span) -> bool {
+    (a.begin() < b.end() && b.begin() < a.end()) || a.begin() == b.begin()
+}
+
+/// Apply fixes to the source they were produced from, returning the new source.
+///
+/// Fixes are applied in order. A fix with an edit overlapping one already accepted
+/// is skipped entirely, unless the edits are identical (e.g. two unused symbols
+/// both removing the same `load` statement). Skipped fixes will usually be reported
+/// again on the next lint run.
+pub fn apply_fixes<'a>(source: &str, fixes: impl IntoIterator<Item = &'a LintFix>) -> String {
+    let mut accepted: Vec<&LintEdit> = Vec::new();
+    for fix in fixes {
+        let mut new = Vec::new();
+        let mut ok = true;
+        for edit in &fix.edits {
+            if accepted.contains(&edit) || new.contains(&edit) {
+                continue;
+            }
+            if accepted
+                .iter()
+                .chain(new.iter())
+                .any(|x| overlaps(x.span, edit.span))
+            {
+                ok = false;
+                break;
+            }
+            new.push(edit);
+        }
+        if ok {
+            accepted.extend(new);
+        }
+    }
+    accepted.sort_by_key(|x| x.span.begin());
+
+    let mut res = String::with_capacity(source.len());
+    let mut pos = 0;
+    for edit in accepted {
+        res.push_str(&source[pos..edit.span.begin().get() as usize]);
+        res.push_str(&edit.replacement);
+        pos = edit.span.end().get() as usize;
+    }
+    res.push_str(&source[pos..]);
+    res
+}
+
+/// The span of the statement at `span` without any trailing newline, which statement
+/// spans may include.
+pub(crate) fn statement_span(codemap: &CodeMap, span: Span) -> Span {
+    let text = codemap.source_span(span);
+    Span::new(span.begin(), span.begin() + text.trim_end().len() as u32)
+}
+
+/// The span of the whole lines containing the statement at `span`, including the final
+/// newline, or `None` if the lines contain anything else apart from a trailing comment.
+pub(crate) fn statement_lines(codemap: &CodeMap, span: Span) -> Option<Span> {
+    let end = statement_span(codemap, span).end();
+    let first = codemap.line_span(codemap.find_line(span.begin()));
+    let last = codemap.line_span(codemap.find_line(end));
+    let before = codemap.source_span(Span::new(first.begin(), span.begin()));
+    let after = codemap.source_span(Span::new(end, last.end()));
+    if before.trim().is_empty() && (after.trim().is_empty() || after.trim().starts_with('#')) {
+        Some(Span::new(first.begin(), last.end()))
+    } else {
+        None
+    }
+}
+
+/// An edit deleting the statement at `span`. If the statement is on lines of its own
+/// the whole lines are removed, otherwise just the statement text.
+pub(crate) fn delete_statement(codemap: &CodeMap, span: Span) -> LintEdit {
+    LintEdit {
+        span: statement_lines(codemap, span).unwrap_or_else(|| statement_span(codemap, span)),
+        replacement: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codemap::Pos;
+
+    fn edit(begin: u32, end: u32, replacement: &str) -> LintEdit {
+        LintEdit {
+            span: Span::new(Pos::new(begin), Pos::new(end)),
+            replacement: replacement.to_owned(),
+        }
+    }
+
+    fn fix(edits: Vec<LintEdit>) -> LintFix {
+        LintFix {
+            description: "fix".to_owned(),
+            edits,
+        }
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let fixes = [
+            fix(vec![edit(4, 5, "y")]),
+            fix(vec![edit(0, 1, "a"), edit(9, 10, "")]),
+            // Overlaps the first fix, so is skipped along with its other edit.
+            fix(vec![edit(4, 6, ""), edit(2, 3, "!")]),
+            // Identical to an accepted edit.
+            fix(vec![edit(9, 10, "")]),
+        ];
+        assert_eq!(apply_fixes("x = x + 1;", &fixes), "a = y + 1");
+    }
+
+    #[test]
+    fn test_delete_statement() {
+        let codemap = CodeMap::new("x.bzl".to_owned(), "a\n  b  # b\nc; d\n".to_owned());
+        let delete = |begin, end| {
+            let edit = delete_statement(&codemap, Span::new(Pos::new(begin), Pos::new(end)));
+            apply_fixes(codemap.source(), &[fix(vec![edit])])
+        };
+        assert_eq!(delete(4, 5), "a\nc; d\n");
+        assert_eq!(delete(0, 2), "  b  # b\nc; d\n");
+        assert_eq!(delete(14, 15), "a\n  b  # b\nc; \n");
+    }
+}
@@ -26,6 +26,10 @@ use starlark_syntax::syntax::ast::Stmt;
 use starlark_syntax::syntax::module::AstModuleFields;
 use thiserror::Error;
 
+use crate::analysis::fix::delete_statement;
+use crate::analysis::fix::statement_lines;
+use crate::analysis::fix::statement_span;
+use crate::analysis::types::LintEdit;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -237,22 +241,52 @@ fn reachable(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) ->
 // If you have a definition which ends with return, or a loop which ends with continue
 // that is a useless statement that just
 fn redundant(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
-    fn check(is_loop: bool, codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
+    // A redundant statement can be deleted, unless it is the only statement in its block,
+    // in which case it becomes a `pass`.
+    fn lint(codemap: &CodeMap, x: &AstStmt, alone: bool, problem: FlowIssue) -> LintT<FlowIssue> {
+        let lint = LintT::new(codemap, x.span, problem);
+        let span = statement_span(codemap, x.span);
+        let text = codemap.source_span(span);
+        if alone {
+            let edit = LintEdit {
+                span,
+                replacement: "pass".to_owned(),
+            };
+            lint.with_fix(format!("Replace `{}` with `pass`", text), vec![edit])
+        } else if statement_lines(codemap, x.span).is_some() {
+            let edit = delete_statement(codemap, x.span);
+            lint.with_fix(format!("Remove `{}`", text), vec![edit])
+        } else {
+            lint
+        }
+    }
+
+    fn check(
+        is_loop: bool,
+        codemap: &CodeMap,
+        x: &AstStmt,
+        alone: bool,
+        res: &mut Vec<LintT<FlowIssue>>,
+    ) {
         match &**x {
             Stmt::Continue if is_loop => {
-                res.push(LintT::new(codemap, x.span, FlowIssue::RedundantContinue))
+                res.push(lint(codemap, x, alone, FlowIssue::RedundantContinue))
             }
             Stmt::Return(None) if !is_loop => {
-                res.push(LintT::new(codemap, x.span, FlowIssue::RedundantReturn))
+                res.push(lint(codemap, x, alone, FlowIssue::RedundantReturn))
             }
-            Stmt::Statements(xs) if !xs.is_empty() => {
-                check(is_loop, codemap, xs.last().unwrap(), res)
-            }
-            Stmt::If(_, x) => check(is_loop, codemap, x, res),
+            Stmt::Statements(xs) if !xs.is_empty() => check(
+                is_loop,
+                codemap,
+                xs.last().unwrap(),
+                alone && xs.len() == 1,
+                res,
+            ),
+            Stmt::If(_, x) => check(is_loop, codemap, x, true, res),
             Stmt::IfElse(_, x_y) => {
                 let (x, y) = &**x_y;
-                check(is_loop, codemap, x, res);
-                check(is_loop, codemap, y, res);
+                check(is_loop, codemap, x, true, res);
+                check(is_loop, codemap, y, true, res);
             }
             _ => {}
         }
@@ -260,8 +294,8 @@ fn redundant(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
 
     fn f(codemap: &CodeMap, x: &AstStmt, res: &mut Vec<LintT<FlowIssue>>) {
         match &**x {
-            Stmt::For(ForP { body, .. }) => check(true, codemap, body, res),
-            Stmt::Def(DefP { body, .. }) => check(false, codemap, body, res),
+            Stmt::For(ForP { body, .. }) => check(true, codemap, body, true, res),
+            Stmt::Def(DefP { body, .. }) => check(false, codemap, body, true, res),
             _ => {}
         }
         // We always want to look inside everything for other types of violation
@@ -331,6 +365,7 @@ mod tests {
     use starlark_syntax::slice_vec_ext::SliceExt;
 
     use super::*;
+    use crate::analysis::apply_fixes;
     use crate::syntax::Dialect;
 
     fn module(x: &str) -> AstModule {
@@ -479,6 +514,47 @@ def test7():
         );
     }
 
+    #[test]
+    fn test_fix_redundant() {
+        let m = module(
+            r#"
+def test():
+    foo
+    return # Done.
+def test2():
+    if x:
+        return
+    else:
+        y + 1
+def test3():
+    for x in xs:
+        foo; continue
+"#,
+        );
+        let mut res = Vec::new();
+        redundant(m.codemap(), m.statement(), &mut res);
+        let fixes = res.into_iter().filter_map(|x| x.fix).collect::<Vec<_>>();
+        assert_eq!(
+            fixes.map(|x| x.description.as_str()),
+            &["Remove `return`", "Replace `return` with `pass`"]
+        );
+        assert_eq!(
+            apply_fixes(m.codemap().source(), &fixes),
+            r#"
+def test():
+    foo
+def test2():
+    if x:
+        pass
+    else:
+        y + 1
+def test3():
+    for x in xs:
+        foo; continue
+"#
+        );
+    }
+
     #[test]
     fn test_lint_misplaced_load() {
         let m = module(
@@ -32,6 +32,7 @@ use starlark_syntax::syntax::ast::Stmt;
 use starlark_syntax::syntax::module::AstModuleFields;
 use thiserror::Error;
 
+use crate::analysis::types::LintEdit;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -101,14 +102,24 @@ fn match_bad_type_equality(
             if (*op == BinOp::Equal || *op == BinOp::NotEqual) && is_type_call(lhs) =>
         {
             if let Some(replacement) = lookup_type(rhs, types) {
-                res.push(LintT::new(
-                    codemap,
-                    x.span,
-                    Incompatibility::IncompatibleTypeCheck(
-                        x.to_string(),
-                        format!("{}{}type({})", lhs.node, op, replacement),
+                let edit = LintEdit {
+                    span: rhs.span,
+                    replacement: format!("type({})", replacement),
+                };
+                res.push(
+                    LintT::new(
+                        codemap,
+                        x.span,
+                        Incompatibility::IncompatibleTypeCheck(
+                            x.to_string(),
+                            format!("{}{}type({})", lhs.node, op, replacement),
+                        ),
+                    )
+                    .with_fix(
+                        format!("Replace `{}` with `{}`", rhs.node, edit.replacement),
+                        vec![edit],
                     ),
-                ))
+                )
             }
         }
         _ => {}
@@ -213,6 +224,7 @@ mod tests {
     use starlark_syntax::slice_vec_ext::SliceExt;
 
     use super::*;
+    use crate::analysis::apply_fixes;
     use crate::syntax::Dialect;
 
     fn module(x: &str) -> AstModule {
@@ -240,6 +252,19 @@ def foo():
         );
     }
 
+    #[test]
+    fn test_fix_incompatible() {
+        let m = module("x = type(a) != list\n");
+        let mut res = Vec::new();
+        bad_type_equality(&m, &mut res);
+        let fixes = res.into_iter().map(|x| x.fix.unwrap()).collect::<Vec<_>>();
+        assert_eq!(fixes[0].description, "Replace `list` with `type([])`");
+        assert_eq!(
+            apply_fixes(m.codemap().source(), &fixes),
+            "x = type(a) != type([])\n"
+        );
+    }
+
     #[test]
     fn test_lint_duplicate_top_level_assign() {
         let m = module(
@@ -30,6 +30,7 @@ use std::mem;
 
 use dupe::Dupe;
 use maplit::hashset;
+use starlark_syntax::slice_vec_ext::SliceExt;
 use starlark_syntax::syntax::ast::AssignP;
 use starlark_syntax::syntax::ast::AstAssignIdent;
 use starlark_syntax::syntax::ast::AstAssignTarget;
@@ -44,8 +45,12 @@ use starlark_syntax::syntax::ast::ForP;
 use starlark_syntax::syntax::ast::LoadArgP;
 use starlark_syntax::syntax::ast::Stmt;
 use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
 use thiserror::Error;
 
+use crate::analysis::fix::delete_statement;
+use crate::analysis::types::LintEdit;
+use crate::analysis::types::LintFix;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
@@ -555,7 +560,57 @@ pub(crate) fn lint(
         loop_depth: 0,
     };
     state.module(module);
-    state.warnings
+    let mut warnings = state.warnings;
+    fix_unused_loads(module, &mut warnings);
+    warnings
+}
+
+/// Attach fixes to the unused `load` warnings. Which text to remove depends on which
+/// other symbols in the same `load` are also unused, so this is done once we know them all.
+fn fix_unused_loads(module: &AstModule, warnings: &mut [LintT<NameWarning>]) {
+    let unused: HashMap<Span, usize> = warnings
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| matches!(x.problem, NameWarning::UnusedLoad(_)))
+        .map(|(i, x)| (x.location.span, i))
+        .collect();
+    if unused.is_empty() {
+        return;
+    }
+
+    let codemap = module.codemap();
+    for stmt in top_level_stmts(module.statement()) {
+        let Stmt::Load(load) = &**stmt else {
+            continue;
+        };
+        let args = &load.args;
+        let indices: Vec<Option<usize>> = args.map(|x| unused.get(&x.local.span).copied());
+        let last_used = indices.iter().rposition(Option::is_none);
+        for (j, i) in indices.iter().enumerate() {
+            let Some(i) = *i else {
+                continue;
+            };
+            let edit = match last_used {
+                // If every symbol is unused, remove the whole statement.
+                None => delete_statement(codemap, stmt.span),
+                // Symbols before the last used one take the text up to the next symbol,
+                // those after it take the text since the previous symbol, so the remaining
+                // arguments keep their separators and the edits never overlap.
+                Some(last_used) => LintEdit {
+                    span: if j < last_used {
+                        Span::new(args[j].span().begin(), args[j + 1].span().begin())
+                    } else {
+                        Span::new(args[j - 1].span().end(), args[j].span().end())
+                    },
+                    replacement: String::new(),
+                },
+            };
+            warnings[i].fix = Some(LintFix {
+                description: format!("Remove unused `load` of `{}`", args[j].local.ident),
+                edits: vec![edit],
+            });
+        }
+    }
 }
 
 #[cfg(test)]
@@ -563,6 +618,7 @@ mod tests {
     use starlark_syntax::slice_vec_ext::SliceExt;
 
     use super::*;
+    use crate::analysis::apply_fixes;
     use crate::syntax::Dialect;
 
     impl NameWarning {
@@ -630,6 +686,39 @@ def foo():
         assert_eq!(res, &["_no2", "_no4", "_no6", "no1", "no3", "no5"]);
     }
 
+    #[test]
+    fn test_fix_unused_load() {
+        let m = module(
+            r#"
+load("a", "no1", "a", "no2", "b", "no3", "no4")
+load(
+    "b",
+    "c",
+    no5 = "d",
+)
+load("c", "no6", "no7")
+print(a, b, c)
+"#,
+        );
+        let fixes = lint(&m, None)
+            .into_iter()
+            .filter_map(|x| x.fix)
+            .collect::<Vec<_>>();
+        assert_eq!(fixes.len(), 7);
+        assert_eq!(fixes[0].description, "Remove unused `load` of `no1`");
+        assert_eq!(
+            apply_fixes(m.codemap().source(), &fixes),
+            r#"
+load("a", "a", "b")
+load(
+    "b",
+    "c",
+)
+print(a, b, c)
+"#
+        );
+    }
+
     #[test]
     fn test_lint_duplicate_assign() {
         let m = module(
@@ -21,10 +21,12 @@ use starlark_syntax::syntax::ast::Expr;
 use starlark_syntax::syntax::module::AstModuleFields;
 use thiserror::Error;
 
+use crate::analysis::types::LintEdit;
 use crate::analysis::types::LintT;
 use crate::analysis::types::LintWarning;
 use crate::analysis::EvalSeverity;
 use crate::codemap::CodeMap;
+use crate::codemap::Span;
 use crate::syntax::AstModule;
 
 #[derive(Error, Debug)]
@@ -60,11 +62,20 @@ fn match_dict_copy(codemap: &CodeMap, x: &AstExpr, res: &mut Vec<LintT<Performan
     match &**x {
         Expr::Call(fun, args) if args.len() == 1 => match (&***fun, &*args[0]) {
             (Expr::Identifier(f), Argument::KwArgs(arg)) if f.node.ident == "dict" => {
-                res.push(LintT::new(
-                    codemap,
-                    x.span,
-                    Performance::DictWithoutStarStar(x.to_string(), format!("dict({})", arg.node)),
-                ))
+                let replacement = format!("dict({})", arg.node);
+                // Drop the `**`, keeping the argument as written.
+                let edit = LintEdit {
+                    span: Span::new(args[0].span.begin(), arg.span.begin()),
+                    replacement: String::new(),
+                };
+                res.push(
+                    LintT::new(
+                        codemap,
+                        x.span,
+                        Performance::DictWithoutStarStar(x.to_string(), replacement.clone()),
+                    )
+                    .with_fix(format!("Replace with `{}`", replacement), vec![edit]),
+                )
             }
             _ => {}
         },
@@ -133,6 +144,7 @@ mod tests {
     use starlark_syntax::slice_vec_ext::SliceExt;
 
     use super::*;
+    use crate::analysis::apply_fixes;
     use crate::syntax::Dialect;
 
     fn module(x: &str) -> AstModule {
@@ -159,6 +171,18 @@ def foo(extra, **kwargs):
         );
     }
 
+    #[test]
+    fn test_fix_dict_issue() {
+        let m = module("x = dict(** kwargs)\n");
+        let mut res = Vec::new();
+        check_call_expr(&m, &mut res);
+        let fixes = res.into_iter().map(|x| x.fix.unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            apply_fixes(m.codemap().source(), &fixes),
+            "x = dict(kwargs)\n"
+        );
+    }
+
     #[test]
     fn test_lint_matches_any_function() {
         let mut res = Vec::new();
@@ -40,6 +40,25 @@ pub(crate) struct LintT<T> {
     pub location: FileSpan,
     pub original: String,
     pub problem: T,
+    pub fix: Option<LintFix>,
+}
+
+/// A single replacement of source text, part of a [`LintFix`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintEdit {
+    /// The text to replace, in the same file as the lint.
+    pub span: Span,
+    /// The text to put in its place.
+    pub replacement: String,
+}
+
+/// An automatic fix for a lint, which can be applied without user input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFix {
+    /// A short description of the fix, e.g. ``Remove unused `load` of `x` ``.
+    pub description: String,
+    /// Non-overlapping edits, all of which must be applied together.
+    pub edits: Vec<LintEdit>,
 }
 
 /// A lint produced by `AstModule::lint`.
@@ -56,6 +75,8 @@ pub struct Lint {
     pub problem: String,
     /// The source code at [`location`](Lint::location).
     pub original: String,
+    /// An automatic fix, if the problem can be fixed mechanically.
+    pub fix: Option<LintFix>,
 }
 
 impl Display for Lint {
@@ -77,9 +98,19 @@ impl<T: LintWarning> LintT<T> {
             original: location.file.source_span(span).to_owned(),
             location,
             problem,
+            fix: None,
         }
     }
 
+    /// Attach a fix made of the given edits.
+    pub(crate) fn with_fix(mut self, description: impl Into<String>, edits: Vec<LintEdit>) -> Self {
+        self.fix = Some(LintFix {
+            description: description.into(),
+            edits,
+        });
+        self
+    }
+
     pub(crate) fn erase(self) -> Lint {
         Lint {
             location: self.location,
@@ -87,6 +118,7 @@ impl<T: LintWarning> LintT<T> {
             severity: self.problem.severity(),
             problem: self.problem.to_string(),
             original: self.original,
+            fix: self.fix,
         }
     }
 }
@@ -647,7 +647,8 @@ impl DocParam {
         Some(indented)
     }
 
-    fn render_as_code(&self) -> String {
+    /// Render the parameter as it would appear in a `def` statement. Used by LSP.
+    pub fn render_as_code(&self) -> String {
         match self {
             DocParam::Arg {
                 name,
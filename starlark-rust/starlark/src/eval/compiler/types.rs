@@ -33,10 +33,13 @@ use crate::eval::compiler::Compiler;
 use crate::eval::runtime::frame_span::FrameSpan;
 use crate::eval::runtime::frozen_file_span::FrozenFileSpan;
 use crate::typing::Ty;
+use crate::values::function::NativeFunction;
+use crate::values::function::SpecialBuiltinFunction;
 use crate::values::types::ellipsis::Ellipsis;
 use crate::values::typing::type_compiled::compiled::TypeCompiled;
 use crate::values::FrozenValue;
 use crate::values::Value;
+use crate::values::ValueLike;
 
 #[derive(Debug, thiserror::Error)]
 enum TypesError {
@@ -50,8 +53,8 @@ enum TypesError {
     ModuleVariableNotSet(String),
     #[error("Type payload not set (internal error)")]
     TypePayloadNotSet,
-    #[error("[] can only be applied to list function in type expression")]
-    TypeIndexOnNonList,
+    #[error("[] can only be applied to list or set function in type expression")]
+    TypeIndexOnNonListOrSet,
     #[error("[,] can only be applied to dict function in type expression")]
     TypeIndexOnNonDict,
     #[error("[,...] can only be applied to tuple function in type expression")]
@@ -168,9 +171,16 @@ impl<'v> Compiler<'v, '_, '_> {
             TypeExprUnpackP::Path(ident, rem) => self.eval_path_as_type(ident, &rem),
             TypeExprUnpackP::Index(a, i) => {
                 let a = self.eval_ident_in_type_expr(a)?;
-                if !a.ptr_eq(Constants::get().fn_list.0.to_value()) {
+                // `set` is not a constant, because it is a library extension.
+                let is_set = a.downcast_ref::<NativeFunction>().map_or(false, |f| {
+                    matches!(
+                        f.special_builtin_function,
+                        Some(SpecialBuiltinFunction::Set)
+                    )
+                });
+                if !a.ptr_eq(Constants::get().fn_list.0.to_value()) && !is_set {
                     return Err(EvalException::new(
-                        TypesError::TypeIndexOnNonList.into(),
+                        TypesError::TypeIndexOnNonListOrSet.into(),
                         expr.span,
                         &self.codemap,
                     ));
@@ -20,4 +20,5 @@ pub(crate) mod globals;
 pub(crate) mod list;
 pub(crate) mod min_max;
 pub(crate) mod other;
+pub(crate) mod set;
 pub(crate) mod zip;
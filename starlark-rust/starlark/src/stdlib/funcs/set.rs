@@ -0,0 +1,125 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use allocative::Allocative;
+use once_cell::sync::Lazy;
+use starlark_derive::starlark_module;
+use starlark_map::small_set::SmallSet;
+
+use crate as starlark;
+use crate::codemap::Span;
+use crate::codemap::Spanned;
+use crate::environment::GlobalsBuilder;
+use crate::typing::error::TypingOrInternalError;
+use crate::typing::function::TyCustomFunctionImpl;
+use crate::typing::Arg;
+use crate::typing::Param;
+use crate::typing::Ty;
+use crate::typing::TyFunction;
+use crate::typing::TypingOracleCtx;
+use crate::values::function::SpecialBuiltinFunction;
+use crate::values::set::value::FrozenSet;
+use crate::values::set::Set;
+use crate::values::set::SetRef;
+use crate::values::typing::StarlarkIter;
+use crate::values::Heap;
+use crate::values::Value;
+use crate::values::ValueOfUnchecked;
+
+#[derive(Allocative, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+struct SetType;
+
+impl TyCustomFunctionImpl for SetType {
+    fn has_type_attr(&self) -> bool {
+        true
+    }
+
+    fn validate_call(
+        &self,
+        span: Span,
+        args: &[Spanned<Arg>],
+        oracle: TypingOracleCtx,
+    ) -> Result<Ty, TypingOrInternalError> {
+        static SET: Lazy<TyFunction> = Lazy::new(|| {
+            TyFunction::new_with_type_attr(
+                vec![Param::pos_only(Ty::iter(Ty::any())).optional()],
+                Ty::any_set(),
+                Ty::any_set(),
+            )
+        });
+
+        oracle.validate_fn_call(span, &SET, args)?;
+
+        if let Some(arg) = args.get(0) {
+            // This is infallible after the check above.
+            if let Arg::Pos(arg_ty) = &arg.node {
+                // This is also infallible.
+                let item = oracle.iter_item(Spanned { span, node: arg_ty })?;
+                return Ok(Ty::set(item));
+            }
+        }
+
+        Ok(Ty::any_set())
+    }
+}
+
+#[starlark_module]
+pub(crate) fn register_set(globals: &mut GlobalsBuilder) {
+    /// [set](
+    /// https://bazel.build/rules/lib/core/set
+    /// ): construct a set.
+    ///
+    /// `set(x)` returns a new set containing the unique elements of the
+    /// iterable sequence x, in the order they first appear. The elements must
+    /// be hashable.
+    ///
+    /// With no argument, `set()` returns a new empty set.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set()                   == set([])
+    /// list(set([3, 1, 3, 2])) == [3, 1, 2]
+    /// len(set("abc".elems())) == 3
+    /// # "#);
+    /// # starlark::assert::fail(r#"
+    /// set([[1]]) # error: not hashable
+    /// # "#, r#"not hashable"#);
+    /// ```
+    #[starlark(
+    as_type = FrozenSet,
+    speculative_exec_safe,
+    special_builtin_function = SpecialBuiltinFunction::Set,
+    ty_custom_function = SetType,
+    )]
+    fn set<'v>(
+        #[starlark(require = pos)] a: Option<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let Some(a) = a else {
+            return Ok(Set::default());
+        };
+        if let Some(xs) = SetRef::from_value(a.get()) {
+            return Ok(xs.clone());
+        }
+        let it = a.get().iterate(heap)?;
+        let mut content = SmallSet::with_capacity(it.size_hint().0);
+        for x in it {
+            content.insert_hashed(x.get_hashed()?);
+        }
+        Ok(Set::new(content))
+    }
+}
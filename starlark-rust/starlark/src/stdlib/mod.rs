@@ -31,12 +31,14 @@ pub(crate) mod internal;
 pub(crate) mod json;
 pub(crate) mod list;
 pub(crate) mod partial;
+pub(crate) mod set;
 pub(crate) mod string;
 pub(crate) mod structs;
 
 pub use extra::PrintHandler;
 
 use crate::stdlib::funcs::globals::register_globals;
+use crate::stdlib::funcs::set::register_set;
 use crate::stdlib::internal::register_internal;
 use crate::values::enumeration::globals::register_enum;
 use crate::values::record::globals::register_record;
@@ -60,6 +62,8 @@ pub enum LibraryExtension {
     RecordType,
     /// Definitions to support the `enum` type, the `enum()` constructor.
     EnumType,
+    /// Definitions to support the `set` type, the `set()` constructor.
+    SetType,
     /// A function `map(f, xs)` which applies `f` to each element of `xs` and returns the result.
     Map,
     /// A function `filter(f, xs)` which applies `f` to each element of `xs` and returns those for which `f` returns `True`.
@@ -97,7 +101,7 @@ impl LibraryExtension {
     pub(crate) fn all() -> &'static [Self] {
         use LibraryExtension::*;
         &[
-            StructType, RecordType, EnumType, Map, Filter, Partial, Debug, Print, Pprint,
+            StructType, RecordType, EnumType, SetType, Map, Filter, Partial, Debug, Print, Pprint,
             Breakpoint, Json, Typing, Internal, CallStack,
         ]
     }
@@ -109,6 +113,7 @@ impl LibraryExtension {
             StructType => structs::global(builder),
             RecordType => register_record(builder),
             EnumType => register_enum(builder),
+            SetType => register_set(builder),
             Map => extra::map(builder),
             Filter => extra::filter(builder),
             Partial => partial::partial(builder),
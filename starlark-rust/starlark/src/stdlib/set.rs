@@ -0,0 +1,505 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Methods for the `set` type.
+
+use std::mem;
+
+use starlark_derive::starlark_module;
+use starlark_map::small_set::SmallSet;
+
+use crate as starlark;
+use crate::environment::MethodsBuilder;
+use crate::values::none::NoneType;
+use crate::values::set::Set;
+use crate::values::set::SetMut;
+use crate::values::set::SetRef;
+use crate::values::tuple::UnpackTuple;
+use crate::values::typing::StarlarkIter;
+use crate::values::Heap;
+use crate::values::Value;
+use crate::values::ValueOfUnchecked;
+
+/// Collect the elements of an iterable argument to a set method.
+fn to_set<'v>(
+    iterable: ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>,
+    heap: &'v Heap,
+) -> anyhow::Result<Set<'v>> {
+    if let Some(set) = SetRef::from_value(iterable.get()) {
+        return Ok(set.clone());
+    }
+    let it = iterable.get().iterate(heap)?;
+    let mut content = SmallSet::with_capacity(it.size_hint().0);
+    for x in it {
+        content.insert_hashed(x.get_hashed()?);
+    }
+    Ok(Set::new(content))
+}
+
+/// Collect the elements of all the iterable arguments to a set method.
+fn to_sets<'v>(
+    iterables: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+    heap: &'v Heap,
+) -> anyhow::Result<Vec<Set<'v>>> {
+    iterables
+        .items
+        .into_iter()
+        .map(|x| to_set(x, heap))
+        .collect()
+}
+
+#[starlark_module]
+pub(crate) fn set_methods(registry: &mut MethodsBuilder) {
+    /// [set.add](
+    /// https://bazel.build/rules/lib/core/set#add
+    /// ): add an element to a set.
+    ///
+    /// `S.add(x)` adds `x` to the set S, if it is not already present, and
+    /// returns `None`. It fails if the set is frozen, if there are active
+    /// iterators, or if `x` is unhashable.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.add(3)
+    /// x.add(1)
+    /// x == set([1, 2, 3])
+    /// # "#);
+    /// ```
+    fn add<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let value = value.get_hashed()?;
+        SetMut::from_value(this)?.insert_hashed(value);
+        Ok(NoneType)
+    }
+
+    /// [set.clear](
+    /// https://bazel.build/rules/lib/core/set#clear
+    /// ): clear a set.
+    ///
+    /// `S.clear()` removes all the elements of the set S and returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.clear()
+    /// x == set()
+    /// # "#);
+    /// ```
+    fn clear(this: Value) -> anyhow::Result<NoneType> {
+        SetMut::from_value(this)?.clear();
+        Ok(NoneType)
+    }
+
+    /// [set.difference](
+    /// https://bazel.build/rules/lib/core/set#difference
+    /// ): elements not in the other iterables.
+    ///
+    /// `S.difference(*others)` returns a new set containing the elements of S
+    /// which are not in any of the `others`. It is also available as the `-`
+    /// operator, which requires both sides to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2, 3]).difference([2], (3, 4)) == set([1])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let others = to_sets(others, heap)?;
+        let mut res = this.clone();
+        res.retain(|v| !others.iter().any(|o| o.contains_hashed(v)));
+        Ok(res)
+    }
+
+    /// [set.difference_update](
+    /// https://bazel.build/rules/lib/core/set#difference_update
+    /// ): remove the elements of the other iterables.
+    ///
+    /// `S.difference_update(*others)` removes from S the elements found in
+    /// any of the `others`, and returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// x.difference_update([2], (3, 4))
+    /// x == set([1])
+    /// # "#);
+    /// ```
+    fn difference_update<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let others = to_sets(others, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        for other in &others {
+            for v in other.iter_hashed() {
+                this.remove_hashed(v);
+            }
+        }
+        Ok(NoneType)
+    }
+
+    /// [set.discard](
+    /// https://bazel.build/rules/lib/core/set#discard
+    /// ): remove an element from a set, if present.
+    ///
+    /// `S.discard(x)` removes `x` from the set S if it is present, and
+    /// returns `None`. Unlike `remove`, it does nothing if `x` is not in S.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.discard(2)
+    /// x.discard(3)
+    /// x == set([1])
+    /// # "#);
+    /// ```
+    fn discard<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let value = value.get_hashed()?;
+        SetMut::from_value(this)?.remove_hashed(value);
+        Ok(NoneType)
+    }
+
+    /// [set.intersection](
+    /// https://bazel.build/rules/lib/core/set#intersection
+    /// ): elements also in all the other iterables.
+    ///
+    /// `S.intersection(*others)` returns a new set containing the elements of
+    /// S which are in all of the `others`. It is also available as the `&`
+    /// operator, which requires both sides to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2, 3]).intersection([2, 3], (3, 4)) == set([3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn intersection<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let others = to_sets(others, heap)?;
+        let mut res = this.clone();
+        res.retain(|v| others.iter().all(|o| o.contains_hashed(v)));
+        Ok(res)
+    }
+
+    /// [set.intersection_update](
+    /// https://bazel.build/rules/lib/core/set#intersection_update
+    /// ): only keep the elements also in all the other iterables.
+    ///
+    /// `S.intersection_update(*others)` removes from S the elements which are
+    /// not in all of the `others`, and returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// x.intersection_update([2, 3], (3, 4))
+    /// x == set([3])
+    /// # "#);
+    /// ```
+    fn intersection_update<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let others = to_sets(others, heap)?;
+        SetMut::from_value(this)?.retain(|v| others.iter().all(|o| o.contains_hashed(v)));
+        Ok(NoneType)
+    }
+
+    /// [set.isdisjoint](
+    /// https://bazel.build/rules/lib/core/set#isdisjoint
+    /// ): test whether a set has no elements in common with an iterable.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).isdisjoint([3, 4])
+    /// not set([1, 2]).isdisjoint([2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn isdisjoint<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        let other = to_set(other, heap)?;
+        Ok(!this.iter_hashed().any(|v| other.contains_hashed(v)))
+    }
+
+    /// [set.issubset](
+    /// https://bazel.build/rules/lib/core/set#issubset
+    /// ): test whether every element of a set is in an iterable.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).issubset([1, 2, 3])
+    /// set([1, 2]).issubset(set([2, 1]))
+    /// not set([1, 4]).issubset([1, 2, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issubset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(this.is_subset(&to_set(other, heap)?))
+    }
+
+    /// [set.issuperset](
+    /// https://bazel.build/rules/lib/core/set#issuperset
+    /// ): test whether every element of an iterable is in a set.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2, 3]).issuperset([1, 2])
+    /// not set([1, 2, 3]).issuperset([1, 4])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issuperset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        Ok(to_set(other, heap)?.is_subset(&this))
+    }
+
+    /// [set.pop](
+    /// https://bazel.build/rules/lib/core/set#pop
+    /// ): remove the first element of a set and return it.
+    ///
+    /// `S.pop()` removes the element of S which was added first, and returns
+    /// it. It fails if the set is empty.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([3, 1, 2])
+    /// x.pop() == 3 and x == set([1, 2])
+    /// # "#);
+    /// ```
+    fn pop<'v>(this: Value<'v>) -> anyhow::Result<Value<'v>> {
+        match SetMut::from_value(this)?.pop_first() {
+            Some(x) => Ok(x),
+            None => Err(anyhow::anyhow!("Cannot .pop() on an empty set")),
+        }
+    }
+
+    /// [set.remove](
+    /// https://bazel.build/rules/lib/core/set#remove
+    /// ): remove an element from a set.
+    ///
+    /// `S.remove(x)` removes `x` from the set S and returns `None`. It fails
+    /// if `x` is not in S.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.remove(2)
+    /// x == set([1])
+    /// # "#);
+    /// # starlark::assert::fail(r#"
+    /// set([1, 2]).remove(3) # error: not found
+    /// # "#, r#"not found"#);
+    /// ```
+    fn remove<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut me = SetMut::from_value(this)?;
+        if me.remove_hashed(value.get_hashed()?) {
+            Ok(NoneType)
+        } else {
+            mem::drop(me);
+            Err(anyhow::anyhow!(
+                "Element `{}` not found in set `{}`",
+                value.to_repr(),
+                this.to_repr()
+            ))
+        }
+    }
+
+    /// [set.symmetric_difference](
+    /// https://bazel.build/rules/lib/core/set#symmetric_difference
+    /// ): elements in either the set or an iterable, but not both.
+    ///
+    /// `S.symmetric_difference(x)` returns a new set containing the elements
+    /// which are either in S or in `x`, but not in both. It is also available as
+    /// the `^` operator, which requires both sides to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2]).symmetric_difference([2, 3]) == set([1, 3])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn symmetric_difference<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos)] other: ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let other = to_set(other, heap)?;
+        let mut res = this.clone();
+        for v in other.iter_hashed() {
+            if !res.remove_hashed(v) {
+                res.insert_hashed(v);
+            }
+        }
+        Ok(res)
+    }
+
+    /// [set.symmetric_difference_update](
+    /// https://bazel.build/rules/lib/core/set#symmetric_difference_update
+    /// ): keep the elements in either the set or an iterable, but not both.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.symmetric_difference_update([2, 3])
+    /// x == set([1, 3])
+    /// # "#);
+    /// ```
+    fn symmetric_difference_update<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] other: ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let other = to_set(other, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        for v in other.iter_hashed() {
+            if !this.remove_hashed(v) {
+                this.insert_hashed(v);
+            }
+        }
+        Ok(NoneType)
+    }
+
+    /// [set.union](
+    /// https://bazel.build/rules/lib/core/set#union
+    /// ): elements in the set or any of the other iterables.
+    ///
+    /// `S.union(*others)` returns a new set containing the elements of S
+    /// followed by the new elements of each of the `others`. It is also
+    /// available as the `|` operator, which requires both sides to be sets.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// set([1, 2]).union([2, 3], (4,)) == set([1, 2, 3, 4])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn union<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let others = to_sets(others, heap)?;
+        let mut res = this.clone();
+        for other in &others {
+            for v in other.iter_hashed() {
+                res.insert_hashed(v);
+            }
+        }
+        Ok(res)
+    }
+
+    /// [set.update](
+    /// https://bazel.build/rules/lib/core/set#update
+    /// ): add the elements of other iterables to a set.
+    ///
+    /// `S.update(*others)` adds the elements of each of the `others` to S, and
+    /// returns `None`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.update([2, 3], (4,))
+    /// x == set([1, 2, 3, 4])
+    /// # "#);
+    /// ```
+    fn update<'v>(
+        this: Value<'v>,
+        #[starlark(args)] others: UnpackTuple<ValueOfUnchecked<'v, StarlarkIter<Value<'v>>>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<NoneType> {
+        let others = to_sets(others, heap)?;
+        let mut this = SetMut::from_value(this)?;
+        for other in &others {
+            for v in other.iter_hashed() {
+                this.insert_hashed(v);
+            }
+        }
+        Ok(NoneType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_error_codes() {
+        assert::fail("set().pop()", "empty");
+        assert::fail("set([1]).remove(2)", "not found");
+        assert::fail("set([1]).add([])", "not hashable");
+    }
+
+    #[test]
+    fn test_update_self() {
+        assert::is_true(
+            r#"
+x = set([1, 2])
+x.update(x)
+x.symmetric_difference_update(x)
+x == set()
+"#,
+        );
+    }
+
+    #[test]
+    fn test_typing() {
+        assert::pass(
+            r#"
+def f(x: set[int]) -> set[int]:
+    return x | set([1])
+f(set([2]))
+"#,
+        );
+        assert::fail(
+            r#"
+def f(x: set[int]):
+    pass
+f(set(["a"]))
+"#,
+            "does not match the type annotation",
+        );
+    }
+}
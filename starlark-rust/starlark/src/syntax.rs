@@ -19,5 +19,6 @@
 
 pub use starlark_syntax::dialect::Dialect;
 pub use starlark_syntax::dialect::DialectTypes;
+pub use starlark_syntax::syntax::format::FormatOptions;
 pub use starlark_syntax::syntax::AstLoad;
 pub use starlark_syntax::syntax::AstModule;
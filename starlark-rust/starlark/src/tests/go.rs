@@ -58,8 +58,7 @@ fn test_go() {
         test_case!("builtin.star"),
         &[
             "[] not in {123: \"\"}", // We disagree, see test_not_in_unhashable
-            // Set, unsupported
-            "set(",
+            // We have different methods on sets
             "(myset)",
             "(myset,",
             // Has fields, unsupported
@@ -122,6 +121,7 @@ fn test_go() {
             "frozen list",        // Our freeze does nothing
             "called recursively", // We allow recursion
             "hf",                 // We don't support hasfield
+            "closures",           // Our bound methods are equal if they bind equal values
         ],
     ));
     // Skip int.star, a lot of bit mask stuff, floats and int's outside our range
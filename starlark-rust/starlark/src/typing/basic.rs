@@ -58,6 +58,8 @@ pub enum TyBasic {
     Tuple(TyTuple),
     /// A dictionary, with key and value types
     Dict(ArcTy, ArcTy),
+    /// A set.
+    Set(ArcTy),
     /// Custom type.
     Custom(TyCustom),
 }
@@ -98,6 +100,16 @@ impl TyBasic {
         Self::dict(Ty::any(), Ty::any())
     }
 
+    /// Create a set type.
+    pub(crate) fn set(item: Ty) -> Self {
+        TyBasic::Set(ArcTy::new(item))
+    }
+
+    /// `set[typing.Any]`.
+    pub(crate) fn any_set() -> Self {
+        TyBasic::Set(ArcTy::any())
+    }
+
     /// Create a iterable type.
     pub(crate) fn iter(item: Ty) -> Self {
         TyBasic::Iter(ArcTy::new(item))
@@ -122,6 +134,7 @@ impl TyBasic {
             TyBasic::List(_) => Some("list"),
             TyBasic::Tuple(_) => Some("tuple"),
             TyBasic::Dict(..) => Some("dict"),
+            TyBasic::Set(_) => Some("set"),
             TyBasic::Type => Some("type"),
             TyBasic::Custom(c) => c.as_name(),
             TyBasic::Any | TyBasic::Iter(_) | TyBasic::Callable => None,
@@ -172,6 +185,7 @@ impl Display for TyBasic {
             TyBasic::List(x) => write!(f, "list[{}]", x),
             TyBasic::Tuple(tuple) => Display::fmt(tuple, f),
             TyBasic::Dict(k, v) => write!(f, "dict[{}, {}]", k, v),
+            TyBasic::Set(x) => write!(f, "set[{}]", x),
             TyBasic::Type => write!(f, "type"),
             TyBasic::Custom(c) => Display::fmt(c, f),
         }
@@ -419,6 +419,20 @@ def test():
     );
 }
 
+#[test]
+fn test_set_add() {
+    TypeCheck::new().ty("x").check(
+        "set_add",
+        r#"
+def test():
+    x = set([1])
+    x.add("")
+    x.discard(None)
+    x.remove(2.0)
+"#,
+    );
+}
+
 #[test]
 fn test_list_function() {
     TypeCheck::new().ty("x").check(
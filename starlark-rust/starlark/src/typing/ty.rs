@@ -276,6 +276,15 @@ impl Ty {
         Self::dict(Ty::any(), Ty::any())
     }
 
+    /// Create a set type.
+    pub fn set(item: Ty) -> Self {
+        Ty::basic(TyBasic::set(item))
+    }
+
+    pub(crate) fn any_set() -> Self {
+        Self::set(Ty::any())
+    }
+
     /// Create a tuple of two elements
     pub fn tuple2(a: Ty, b: Ty) -> Self {
         Ty::tuple(vec![a, b])
@@ -398,6 +407,7 @@ impl Ty {
                 ArcTy::union2(x_k, y_k),
                 ArcTy::union2(x_v, y_v),
             )),
+            (TyBasic::Set(x), TyBasic::Set(y)) => Either::Left(TyBasic::Set(ArcTy::union2(x, y))),
             (TyBasic::Custom(x), TyBasic::Custom(y)) => match TyCustom::union2(x, y) {
                 Ok(u) => Either::Left(TyBasic::Custom(u)),
                 Err((x, y)) => Either::Right((TyBasic::Custom(x), TyBasic::Custom(y))),
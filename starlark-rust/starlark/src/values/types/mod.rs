@@ -35,6 +35,7 @@ pub mod list_or_tuple;
 pub mod none;
 pub mod range;
 pub mod record;
+pub mod set;
 pub mod starlark_value_as_type;
 pub mod string;
 pub mod structs;
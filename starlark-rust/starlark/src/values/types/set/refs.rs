@@ -0,0 +1,128 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::cell::RefMut;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+use either::Either;
+
+use crate::coerce::coerce;
+use crate::typing::Ty;
+use crate::values::set::value::FrozenSetData;
+use crate::values::set::value::SetGen;
+use crate::values::set::Set;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::UnpackValue;
+use crate::values::Value;
+use crate::values::ValueError;
+use crate::values::ValueLike;
+
+/// Borrowed `Set`.
+pub struct SetRef<'v> {
+    pub(crate) aref: Either<Ref<'v, Set<'v>>, &'v Set<'v>>,
+}
+
+/// Mutably borrowed `Set`.
+pub struct SetMut<'v> {
+    pub(crate) aref: RefMut<'v, Set<'v>>,
+}
+
+impl<'v> SetRef<'v> {
+    /// Downcast the value to a set.
+    pub fn from_value(x: Value<'v>) -> Option<SetRef<'v>> {
+        if x.unpack_frozen().is_some() {
+            x.downcast_ref::<SetGen<FrozenSetData>>().map(|x| SetRef {
+                aref: Either::Right(coerce(&x.0)),
+            })
+        } else {
+            let ptr = x.downcast_ref::<SetGen<RefCell<Set<'v>>>>()?;
+            Some(SetRef {
+                aref: Either::Left(ptr.0.borrow()),
+            })
+        }
+    }
+}
+
+impl<'v> SetMut<'v> {
+    /// Downcast the value to a mutable set reference.
+    #[inline]
+    pub fn from_value(x: Value<'v>) -> anyhow::Result<SetMut> {
+        #[derive(thiserror::Error, Debug)]
+        #[error("Value is not set, value type: `{0}`")]
+        struct NotSetError(&'static str);
+
+        #[cold]
+        #[inline(never)]
+        fn error<'v>(x: Value<'v>) -> anyhow::Error {
+            if x.downcast_ref::<SetGen<FrozenSetData>>().is_some() {
+                ValueError::CannotMutateImmutableValue.into()
+            } else {
+                NotSetError(x.get_type()).into()
+            }
+        }
+
+        let ptr = x.downcast_ref::<SetGen<RefCell<Set<'v>>>>();
+        match ptr {
+            None => Err(error(x)),
+            Some(ptr) => match ptr.0.try_borrow_mut() {
+                Ok(x) => Ok(SetMut { aref: x }),
+                Err(_) => Err(ValueError::MutationDuringIteration.into()),
+            },
+        }
+    }
+}
+
+impl<'v> Deref for SetRef<'v> {
+    type Target = Set<'v>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.aref
+    }
+}
+
+impl<'v> Deref for SetMut<'v> {
+    type Target = Set<'v>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.aref
+    }
+}
+
+impl<'v> DerefMut for SetMut<'v> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.aref
+    }
+}
+
+impl<'v> StarlarkTypeRepr for SetRef<'v> {
+    fn starlark_type_repr() -> Ty {
+        Set::<'v>::starlark_type_repr()
+    }
+}
+
+impl<'v> UnpackValue<'v> for SetRef<'v> {
+    fn expected() -> String {
+        "set".to_owned()
+    }
+
+    fn unpack_value(value: Value<'v>) -> Option<SetRef<'v>> {
+        SetRef::from_value(value)
+    }
+}
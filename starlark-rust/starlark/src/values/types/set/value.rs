@@ -0,0 +1,535 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::mem;
+use std::ops::Deref;
+
+use allocative::Allocative;
+use display_container::fmt_container;
+use serde::Serialize;
+use starlark_derive::starlark_value;
+use starlark_derive::StarlarkDocs;
+use starlark_map::small_set::SmallSet;
+
+use crate as starlark;
+use crate::any::ProvidesStaticType;
+use crate::coerce::coerce;
+use crate::coerce::Coerce;
+use crate::collections::Hashed;
+use crate::environment::Methods;
+use crate::environment::MethodsStatic;
+use crate::typing::Ty;
+use crate::values::dict::refcell::unleak_borrow;
+use crate::values::error::ValueError;
+use crate::values::layout::avalue::alloc_static;
+use crate::values::layout::avalue::AValueImpl;
+use crate::values::layout::avalue::Simple;
+use crate::values::layout::heap::repr::AValueRepr;
+use crate::values::set::SetRef;
+use crate::values::type_repr::SetType;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocFrozenValue;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenHeap;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+use crate::values::ValueLike;
+
+#[derive(
+    Clone,
+    Default,
+    Trace,
+    Debug,
+    ProvidesStaticType,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "extension")]
+pub(crate) struct SetGen<T>(pub(crate) T);
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_set(f, self.0.content().iter())
+    }
+}
+
+impl<'v> Display for Set<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_set(f, self.iter())
+    }
+}
+
+fn fmt_set<T: Display>(
+    f: &mut fmt::Formatter<'_>,
+    mut items: impl ExactSizeIterator<Item = T>,
+) -> fmt::Result {
+    if items.len() == 0 {
+        write!(f, "set()")
+    } else {
+        fmt_container(f, "set([", "])", &mut items)
+    }
+}
+
+/// Define the set type.
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub struct Set<'v> {
+    /// The data stored by the set. The values must all be hashable.
+    content: SmallSet<Value<'v>>,
+}
+
+impl<'v> StarlarkTypeRepr for Set<'v> {
+    fn starlark_type_repr() -> Ty {
+        SetType::<Value<'v>>::starlark_type_repr()
+    }
+}
+
+#[derive(Clone, Default, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub(crate) struct FrozenSetData {
+    /// The data stored by the set. The values must all be hashable.
+    pub(crate) content: SmallSet<FrozenValue>,
+}
+
+/// Alias is used in `StarlarkDocs` derive.
+pub(crate) type FrozenSet = SetGen<FrozenSetData>;
+
+pub(crate) type MutableSet<'v> = SetGen<RefCell<Set<'v>>>;
+
+pub(crate) static VALUE_EMPTY_FROZEN_SET: AValueRepr<AValueImpl<Simple, SetGen<FrozenSetData>>> =
+    alloc_static(
+        Simple,
+        SetGen(FrozenSetData {
+            content: SmallSet::new(),
+        }),
+    );
+
+unsafe impl<'v> Coerce<Set<'v>> for FrozenSetData {}
+
+impl<'v> AllocValue<'v> for Set<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl StarlarkTypeRepr for FrozenSetData {
+    fn starlark_type_repr() -> Ty {
+        Ty::set(Ty::any())
+    }
+}
+
+impl AllocFrozenValue for FrozenSetData {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        if self.content.is_empty() {
+            FrozenValue::new_repr(&VALUE_EMPTY_FROZEN_SET)
+        } else {
+            heap.alloc_simple(SetGen(self))
+        }
+    }
+}
+
+impl<'v> Set<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Create a set from values which are already hashed.
+    pub fn new(content: SmallSet<Value<'v>>) -> Self {
+        Self { content }
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the values in the set, in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.iter().copied()
+    }
+
+    /// Iterate through the values in the set, retaining their hashes.
+    pub fn iter_hashed<'a>(&'a self) -> impl Iterator<Item = Hashed<Value<'v>>> + 'a
+    where
+        'v: 'a,
+    {
+        self.content.iter_hashed().map(|v| v.copied())
+    }
+
+    /// Does the set contain the value? Will be [`Err`] if the value is not hashable.
+    pub fn contains(&self, value: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.contains_hashed(value.get_hashed()?))
+    }
+
+    /// Does the set contain the prehashed value?
+    pub fn contains_hashed(&self, value: Hashed<Value<'v>>) -> bool {
+        self.content.contains_hashed(value.as_ref())
+    }
+
+    /// Is every element of this set also in `other`?
+    pub fn is_subset(&self, other: &Set<'v>) -> bool {
+        self.len() <= other.len() && self.iter_hashed().all(|v| other.contains_hashed(v))
+    }
+
+    /// Reserve capacity to insert `additional` elements without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.content.reserve(additional);
+    }
+
+    /// Add a value to the set. Returns `false` if it was already present.
+    pub fn insert_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.insert_hashed(value)
+    }
+
+    /// Remove a value from the set. Returns `false` if it was not present.
+    pub fn remove_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.remove_hashed(value.as_ref())
+    }
+
+    /// Remove the first value inserted in the set, and return it.
+    pub fn pop_first(&mut self) -> Option<Value<'v>> {
+        let first = self.content.iter_hashed().next()?.copied();
+        self.content.remove_hashed(first.as_ref());
+        Some(first.into_key())
+    }
+
+    /// Only keep the values for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(Hashed<Value<'v>>) -> bool) {
+        let mut content = SmallSet::with_capacity(self.len());
+        for v in self.iter_hashed() {
+            if f(v) {
+                content.insert_hashed_unique_unchecked(v);
+            }
+        }
+        self.content = content;
+    }
+
+    /// Remove all elements from the set.
+    pub fn clear(&mut self) {
+        self.content.clear();
+    }
+}
+
+impl<'v> Freeze for SetGen<RefCell<Set<'v>>> {
+    type Frozen = SetGen<FrozenSetData>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSetData { content }))
+    }
+}
+
+pub(crate) trait SetLike<'v>: Debug + Allocative {
+    type ContentRef<'a>: Deref<Target = SmallSet<Value<'v>>>
+    where
+        Self: 'a,
+        'v: 'a;
+    fn content<'a>(&'a self) -> Self::ContentRef<'a>;
+    // These functions are unsafe for the same reason
+    // `StarlarkValue` iterator functions are unsafe.
+    unsafe fn iter_start(&self);
+    unsafe fn content_unchecked(&self) -> &SmallSet<Value<'v>>;
+    unsafe fn iter_stop(&self);
+}
+
+impl<'v> SetLike<'v> for RefCell<Set<'v>> {
+    type ContentRef<'a>
+        = Ref<'a, SmallSet<Value<'v>>>
+    where
+        Self: 'a,
+        'v: 'a;
+
+    fn content<'a>(&'a self) -> Ref<'a, SmallSet<Value<'v>>> {
+        Ref::map(self.borrow(), |x| &x.content)
+    }
+
+    #[inline]
+    unsafe fn iter_start(&self) {
+        mem::forget(self.borrow());
+    }
+
+    #[inline]
+    unsafe fn iter_stop(&self) {
+        unleak_borrow(self);
+    }
+
+    #[inline]
+    unsafe fn content_unchecked(&self) -> &SmallSet<Value<'v>> {
+        // SAFETY: this function contract is, caller must ensure that the value is borrowed.
+        &self.try_borrow_unguarded().ok().unwrap_unchecked().content
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSetData {
+    type ContentRef<'a>
+        = &'a SmallSet<Value<'v>>
+    where
+        Self: 'a,
+        'v: 'a;
+
+    fn content<'a>(&'a self) -> &'a SmallSet<Value<'v>> {
+        coerce(&self.content)
+    }
+
+    unsafe fn iter_start(&self) {}
+
+    unsafe fn iter_stop(&self) {}
+
+    unsafe fn content_unchecked(&self) -> &SmallSet<Value<'v>> {
+        coerce(&self.content)
+    }
+}
+
+pub(crate) fn set_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::set::set_methods)
+}
+
+impl<'v, T: SetLike<'v> + 'v> SetGen<T>
+where
+    Self: ProvidesStaticType<'v>,
+{
+    /// Build a new set from the elements of this set, using `f` to decide which elements of
+    /// `rhs` to combine with. Used for the binary operators.
+    fn bin_op(
+        &self,
+        op: &str,
+        rhs: Value<'v>,
+        heap: &'v Heap,
+        f: impl FnOnce(&mut Set<'v>, &Set<'v>),
+    ) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, op, rhs), Ok)?;
+        let mut res = Set::new(self.0.content().clone());
+        f(&mut res, &rhs);
+        Ok(heap.alloc(res))
+    }
+}
+
+#[starlark_value(type = Set::TYPE)]
+impl<'v, T: SetLike<'v> + 'v> StarlarkValue<'v> for SetGen<T>
+where
+    Self: ProvidesStaticType<'v>,
+{
+    type Canonical = FrozenSet;
+
+    fn get_methods() -> Option<&'static Methods> {
+        set_methods()
+    }
+
+    fn collect_repr(&self, r: &mut String) {
+        let content = self.0.content();
+        if content.is_empty() {
+            r.push_str("set()");
+            return;
+        }
+        r.push_str("set([");
+        for (i, v) in content.iter().enumerate() {
+            if i != 0 {
+                r.push_str(", ");
+            }
+            v.collect_repr(r);
+        }
+        r.push_str("])");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match SetRef::from_value(other) {
+            None => Ok(false),
+            Some(other) => {
+                let content = self.0.content();
+                Ok(content.len() == other.len()
+                    && content
+                        .iter_hashed()
+                        .all(|v| other.contains_hashed(v.copied())))
+            }
+        }
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self
+            .0
+            .content()
+            .contains_hashed(other.get_hashed()?.as_ref()))
+    }
+
+    unsafe fn iterate(&self, me: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.0.iter_start();
+        Ok(me)
+    }
+
+    unsafe fn iter_size_hint(&self, index: usize) -> (usize, Option<usize>) {
+        debug_assert!(index <= self.0.content().len());
+        let rem = self.0.content().len() - index;
+        (rem, Some(rem))
+    }
+
+    unsafe fn iter_next(&self, index: usize, _heap: &'v Heap) -> Option<Value<'v>> {
+        self.0.content_unchecked().get_index(index).copied()
+    }
+
+    unsafe fn iter_stop(&self) {
+        self.0.iter_stop();
+    }
+
+    fn bit_or(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.bin_op("|", rhs, heap, |res, rhs| {
+            for v in rhs.iter_hashed() {
+                res.insert_hashed(v);
+            }
+        })
+    }
+
+    fn bit_and(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.bin_op("&", rhs, heap, |res, rhs| {
+            res.retain(|v| rhs.contains_hashed(v))
+        })
+    }
+
+    fn bit_xor(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.bin_op("^", rhs, heap, |res, rhs| {
+            for v in rhs.iter_hashed() {
+                if !res.remove_hashed(v) {
+                    res.insert_hashed(v);
+                }
+            }
+        })
+    }
+
+    fn sub(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.bin_op("-", rhs, heap, |res, rhs| {
+            res.retain(|v| !rhs.contains_hashed(v))
+        })
+    }
+
+    fn typechecker_ty(&self) -> Option<Ty> {
+        Some(Ty::any_set())
+    }
+
+    fn get_type_starlark_repr() -> Ty {
+        Ty::any_set()
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.0.content().iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::assert::Assert;
+
+    #[test]
+    fn test_mutate_set() {
+        assert::is_true(
+            r#"
+x = set([1, 2])
+b1 = str(x) == "set([1, 2])"
+x.add(3)
+x.add(1)
+b2 = str(x) == "set([1, 2, 3])"
+x.discard(2)
+b3 = str(x) == "set([1, 3])"
+b1 and b2 and b3
+"#,
+        );
+    }
+
+    #[test]
+    fn test_repr() {
+        assert::eq("repr(set())", "'set()'");
+        assert::eq("repr(set([\"a\", (1, 2)]))", "'set([\"a\", (1, 2)])'");
+    }
+
+    #[test]
+    fn test_equals() {
+        assert::all_true(
+            r#"
+set([1, 2]) == set([2, 1])
+set([1, 2]) != set([1, 2, 3])
+set() != {}
+set() != []
+"#,
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        assert::all_true(
+            r#"
+set([1, 2]) | set([2, 3]) == set([1, 2, 3])
+set([1, 2]) & set([2, 3]) == set([2])
+set([1, 2]) ^ set([2, 3]) == set([1, 3])
+set([1, 2]) - set([2, 3]) == set([1])
+list(set([3, 1]) | set([2, 1])) == [3, 1, 2]
+2 in set([1, 2])
+3 not in set([1, 2])
+"#,
+        );
+        assert::fail("set([1]) | [2]", "not supported");
+        assert::fail("[] in set()", "not hashable");
+    }
+
+    #[test]
+    fn test_mutating_imports() {
+        let mut a = Assert::new();
+        a.module("x", "frozen_set = set([1, 2])");
+        a.fail("load('x','frozen_set')\nfrozen_set.add(3)", "Immutable");
+        a.is_true("load('x','frozen_set')\nfrozen_set | set([3]) == set([1, 2, 3])");
+    }
+
+    #[test]
+    fn test_mutation_during_iteration() {
+        assert::fail(
+            r#"
+def f():
+    x = set([1, 2])
+    for v in x:
+        x.add(v + 10)
+f()
+"#,
+            "mutate an iterable",
+        );
+    }
+}
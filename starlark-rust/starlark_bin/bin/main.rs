@@ -21,6 +21,7 @@
 use std::ffi::OsStr;
 use std::fmt;
 use std::fmt::Display;
+use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -31,6 +32,8 @@ use dupe::Dupe;
 use eval::Context;
 use itertools::Either;
 use itertools::Itertools;
+use starlark::analysis::apply_fixes;
+use starlark::analysis::AstModuleLint;
 use starlark::analysis::LintMessage;
 use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
@@ -42,8 +45,11 @@ use starlark::environment::Globals;
 use starlark::errors::EvalMessage;
 use starlark::errors::EvalSeverity;
 use starlark::read_line::ReadLine;
+use starlark::syntax::AstModule;
+use starlark::syntax::FormatOptions;
 use walkdir::WalkDir;
 
+use crate::eval::dialect;
 use crate::eval::ContextMode;
 
 mod bazel;
@@ -63,6 +69,9 @@ struct Args {
             "docs",
             "evaluate",
             "files",
+            "format",
+            "check_format",
+            "fix",
         ],
     )]
     lsp: bool,
@@ -80,6 +89,9 @@ struct Args {
             "prelude",
             "evaluate",
             "files",
+            "format",
+            "check_format",
+            "fix",
         ],
     )]
     dap: bool,
@@ -105,6 +117,27 @@ struct Args {
     )]
     docs: Option<ArgsDoc>,
 
+    #[arg(
+        long = "format",
+        help = "Format the files in place.",
+        conflicts_with_all = &["lsp", "dap", "check", "json", "docs", "evaluate", "check_format", "fix"],
+    )]
+    format: bool,
+
+    #[arg(
+        long = "check-format",
+        help = "Report the files which are not formatted, failing if there are any.",
+        conflicts_with_all = &["lsp", "dap", "check", "json", "docs", "evaluate", "format", "fix"],
+    )]
+    check_format: bool,
+
+    #[arg(
+        long = "fix",
+        help = "Apply the automatic fixes for lints to the files in place.",
+        conflicts_with_all = &["lsp", "dap", "check", "json", "docs", "evaluate", "format", "check_format"],
+    )]
+    fix: bool,
+
     #[arg(
         long = "extension",
         help = "File extension when searching directories."
@@ -226,6 +259,54 @@ fn drain(
     Ok(())
 }
 
+/// Format files in place, or if `check`, report which files are not formatted.
+fn format_files(files: impl Iterator<Item = PathBuf>, check: bool) -> anyhow::Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let name = file.to_string_lossy();
+        let content = fs::read_to_string(&file).with_context(|| format!("reading `{}`", name))?;
+        let ast = AstModule::parse(&name, content.clone(), &dialect())?;
+        let formatted = ast.format(&FormatOptions::for_filename(&name));
+        if formatted != content {
+            unformatted += 1;
+            if check {
+                println!("{}: not formatted", name);
+            } else {
+                fs::write(&file, formatted).with_context(|| format!("writing `{}`", name))?;
+            }
+        }
+    }
+    if check && unformatted > 0 {
+        return Err(anyhow::anyhow!("{} files are not formatted", unformatted));
+    }
+    Ok(())
+}
+
+/// Apply the automatic fixes for all lints in place.
+fn fix_files(files: impl Iterator<Item = PathBuf>) -> anyhow::Result<()> {
+    // Fixes which overlap are skipped, so re-lint a few times to pick them up.
+    const MAX_ROUNDS: usize = 5;
+    for file in files {
+        let name = file.to_string_lossy();
+        let original = fs::read_to_string(&file).with_context(|| format!("reading `{}`", name))?;
+        let mut content = original.clone();
+        for _ in 0..MAX_ROUNDS {
+            let ast = AstModule::parse(&name, content.clone(), &dialect())?;
+            let fixes: Vec<_> = ast.lint(None).into_iter().filter_map(|x| x.fix).collect();
+            let fixed = apply_fixes(&content, &fixes);
+            if fixed == content {
+                break;
+            }
+            content = fixed;
+        }
+        if content != original {
+            fs::write(&file, content).with_context(|| format!("writing `{}`", name))?;
+            println!("{}: fixed", name);
+        }
+    }
+    Ok(())
+}
+
 fn interactive(ctx: &Context) -> anyhow::Result<()> {
     let mut rl = ReadLine::new("STARLARK_RUST_HISTFILE")?;
     loop {
@@ -267,6 +348,13 @@ fn main() -> anyhow::Result<()> {
         let prelude = expand_dirs(ext, args.prelude).collect::<Vec<_>>();
         let print_non_none = !args.evaluate.is_empty() || is_interactive;
 
+        if args.format || args.check_format {
+            return format_files(expand_dirs(ext, args.files), args.check_format);
+        }
+        if args.fix {
+            return fix_files(expand_dirs(ext, args.files));
+        }
+
         // TODO: Remove this when extracting the Bazel binary to its own
         // repository, after the LspContext interface stabilizes.
         if args.bazel {
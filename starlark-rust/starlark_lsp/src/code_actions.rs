@@ -0,0 +1,79 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Code actions, offering the automatic fixes attached to lints.
+
+use std::collections::HashMap;
+
+use lsp_types::CodeAction;
+use lsp_types::CodeActionKind;
+use lsp_types::CodeActionOrCommand;
+use lsp_types::CodeActionParams;
+use lsp_types::Range;
+use lsp_types::TextEdit;
+use lsp_types::WorkspaceEdit;
+use starlark::analysis::AstModuleLint;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::server::Backend;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+
+impl<T: LspContext> Backend<T> {
+    /// Offer a quick fix for each fixable lint overlapping the requested range.
+    ///
+    /// Nothing is offered if the document does not currently parse, as the
+    /// edits would be relative to the last valid parse.
+    pub(crate) fn code_actions(
+        &self,
+        params: CodeActionParams,
+    ) -> anyhow::Result<Option<Vec<CodeActionOrCommand>>> {
+        let uri: LspUrl = params.text_document.uri.clone().try_into()?;
+        if self.parse_failed.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        let Some(module) = self.get_ast(&uri) else {
+            return Ok(None);
+        };
+        let codemap = module.ast.codemap();
+        let mut actions = Vec::new();
+        for lint in module.ast.lint(None) {
+            let Some(fix) = lint.fix else {
+                continue;
+            };
+            let range: Range = lint.location.resolve_span().into();
+            if range.end < params.range.start || params.range.end < range.start {
+                continue;
+            }
+            let edits = fix
+                .edits
+                .into_iter()
+                .map(|x| TextEdit::new(codemap.resolve_span(x.span).into(), x.replacement))
+                .collect();
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: fix.description,
+                kind: Some(CodeActionKind::QUICKFIX),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(
+                    params.text_document.uri.clone(),
+                    edits,
+                )]))),
+                ..CodeAction::default()
+            }));
+        }
+        Ok(Some(actions))
+    }
+}
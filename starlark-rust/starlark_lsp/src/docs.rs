@@ -15,6 +15,7 @@
  * limitations under the License.
  */
 
+use starlark::codemap::CodeMap;
 use starlark::docs::DocFunction;
 use starlark::docs::DocParam;
 use starlark::docs::DocProperty;
@@ -65,6 +66,53 @@ pub(crate) fn get_doc_item_for_def<P: AstPayload>(def: &DefP<P>) -> Option<DocFu
     }
 }
 
+/// Given the AST node for a `def` statement, return a `DocFunction` describing its
+/// full signature, including default values, `*args` and `**kwargs`, whether or not
+/// it has a docstring.
+pub(crate) fn get_doc_function_for_def<P: AstPayload>(
+    def: &DefP<P>,
+    codemap: &CodeMap,
+) -> DocFunction {
+    let params = def
+        .params
+        .iter()
+        .map(|param| match &param.node {
+            ParameterP::Normal(p, _) => DocParam::Arg {
+                name: p.ident.to_owned(),
+                docs: None,
+                typ: Ty::any(),
+                default_value: None,
+            },
+            ParameterP::WithDefaultValue(p, _, default) => DocParam::Arg {
+                name: p.ident.to_owned(),
+                docs: None,
+                typ: Ty::any(),
+                default_value: Some(codemap.source_span(default.span).to_owned()),
+            },
+            ParameterP::NoArgs => DocParam::NoArgs,
+            ParameterP::Args(p, _) => DocParam::Args {
+                name: format!("*{}", p.ident),
+                docs: None,
+                typ: Ty::any(),
+            },
+            ParameterP::KwArgs(p, _) => DocParam::Kwargs {
+                name: format!("**{}", p.ident),
+                docs: None,
+                typ: Ty::any(),
+            },
+        })
+        .collect();
+
+    DocFunction::from_docstring(
+        DocStringKind::Starlark,
+        params,
+        // TODO: Figure out how to get a `Ty` from the `def.return_type`.
+        Ty::any(),
+        peek_docstring(&def.body),
+        None,
+    )
+}
+
 pub(crate) fn get_doc_item_for_assign<P: AstPayload>(
     previous_node: &AstStmtP<P>,
     _assign: &AstAssignTargetP<P>,
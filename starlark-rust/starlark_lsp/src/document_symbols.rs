@@ -0,0 +1,286 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An outline of the symbols in a module: defs, loads, assignments and target definitions.
+
+use lsp_types::DocumentSymbol;
+use lsp_types::DocumentSymbolParams;
+use lsp_types::DocumentSymbolResponse;
+use lsp_types::SymbolKind;
+use starlark::codemap::CodeMap;
+use starlark::codemap::Span;
+use starlark_syntax::syntax::ast::ArgumentP;
+use starlark_syntax::syntax::ast::AssignP;
+use starlark_syntax::syntax::ast::AstExprP;
+use starlark_syntax::syntax::ast::AstLiteral;
+use starlark_syntax::syntax::ast::AstPayload;
+use starlark_syntax::syntax::ast::AstStmtP;
+use starlark_syntax::syntax::ast::ExprP;
+use starlark_syntax::syntax::ast::StmtP;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::server::Backend;
+use crate::server::LspContext;
+
+#[allow(deprecated)] // The `deprecated` field has to be set, even though it is deprecated.
+fn document_symbol(
+    codemap: &CodeMap,
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    range: Span,
+    selection_range: Span,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: codemap.resolve_span(range).into(),
+        selection_range: codemap.resolve_span(selection_range).into(),
+        children,
+    }
+}
+
+/// If the expression is a call with a literal `name` argument, e.g. `cxx_library(name = "foo")`,
+/// return a symbol for the target it defines.
+fn target_symbol<P: AstPayload>(
+    codemap: &CodeMap,
+    stmt: &AstStmtP<P>,
+    expr: &AstExprP<P>,
+) -> Option<DocumentSymbol> {
+    let ExprP::Call(function, args) = &expr.node else {
+        return None;
+    };
+    args.iter().find_map(|arg| match &arg.node {
+        ArgumentP::Named(arg_name, value) if arg_name.node == "name" => match &value.node {
+            ExprP::Literal(AstLiteral::String(name)) => Some(document_symbol(
+                codemap,
+                name.node.clone(),
+                Some(codemap.source_span(function.span).to_owned()),
+                SymbolKind::OBJECT,
+                stmt.span,
+                value.span,
+                None,
+            )),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Get the symbols defined in the given statement, nested according to where they are defined.
+///
+/// Target definitions are only included at the top level (including within `if` and `for`),
+/// as calls in function bodies are usually not defining targets.
+pub(crate) fn get_document_symbols<P: AstPayload>(
+    codemap: &CodeMap,
+    ast: &AstStmtP<P>,
+) -> Vec<DocumentSymbol> {
+    fn walk<P: AstPayload>(
+        codemap: &CodeMap,
+        stmt: &AstStmtP<P>,
+        top_level: bool,
+        symbols: &mut Vec<DocumentSymbol>,
+    ) {
+        match &stmt.node {
+            StmtP::Expression(expr) if top_level => {
+                symbols.extend(target_symbol(codemap, stmt, expr));
+            }
+            StmtP::Assign(AssignP { lhs, .. }) => lhs.visit_lvalue(|x| {
+                symbols.push(document_symbol(
+                    codemap,
+                    x.ident.clone(),
+                    None,
+                    SymbolKind::VARIABLE,
+                    stmt.span,
+                    x.span,
+                    None,
+                ))
+            }),
+            StmtP::Def(def) => {
+                let mut children = Vec::new();
+                walk(codemap, &def.body, false, &mut children);
+                symbols.push(document_symbol(
+                    codemap,
+                    def.name.ident.clone(),
+                    None,
+                    SymbolKind::FUNCTION,
+                    stmt.span,
+                    def.name.span,
+                    Some(children),
+                ));
+            }
+            StmtP::Load(load) => {
+                let children = load
+                    .args
+                    .iter()
+                    .map(|arg| {
+                        document_symbol(
+                            codemap,
+                            arg.local.ident.clone(),
+                            (arg.local.ident != arg.their.node).then(|| arg.their.node.clone()),
+                            SymbolKind::VARIABLE,
+                            arg.span(),
+                            arg.local.span,
+                            None,
+                        )
+                    })
+                    .collect();
+                symbols.push(document_symbol(
+                    codemap,
+                    load.module.node.clone(),
+                    None,
+                    SymbolKind::MODULE,
+                    stmt.span,
+                    load.module.span,
+                    Some(children),
+                ));
+            }
+            StmtP::Statements(_) | StmtP::If(..) | StmtP::IfElse(..) | StmtP::For(_) => {
+                stmt.visit_stmt(|x| walk(codemap, x, top_level, symbols))
+            }
+            _ => {}
+        }
+    }
+
+    let mut symbols = Vec::new();
+    walk(codemap, ast, true, &mut symbols);
+    symbols
+}
+
+impl<T: LspContext> Backend<T> {
+    /// Get an outline of the symbols in a document.
+    pub(crate) fn find_document_symbols(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> anyhow::Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri.try_into()?;
+        Ok(self.get_ast(&uri).map(|document| {
+            DocumentSymbolResponse::Nested(get_document_symbols(
+                document.ast.codemap(),
+                document.ast.statement(),
+            ))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Range;
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::definition::helpers::FixtureWithRanges;
+
+    #[test]
+    fn finds_document_symbols() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            <load>load(<foo_star>"foo.star"</foo_star>, <bar_arg><bar>"bar"</bar></bar_arg>, <baz_arg><baz>baz</baz> = "qux"</baz_arg>)</load>
+
+            <x_assign><x>X</x> = 1</x_assign>
+
+            <f_def>def <f>f</f>(a):
+                <y_assign><y>y</y> = a</y_assign>
+                cxx_library(name = "not_a_target")
+                return y
+
+            </f_def><lib>cxx_library(
+                name = <lib_name>"lib"</lib_name>,
+                srcs = ["lib.cpp"],
+            )</lib>
+
+            glob(["*.cpp"])
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+        let range = |name: &str| -> Range { parsed.resolved_span(name).into() };
+        #[allow(deprecated)]
+        let symbol = |name: &str,
+                      detail: Option<&str>,
+                      kind: SymbolKind,
+                      full: &str,
+                      selection: &str,
+                      children: Option<Vec<DocumentSymbol>>| DocumentSymbol {
+            name: name.to_owned(),
+            detail: detail.map(|x| x.to_owned()),
+            kind,
+            tags: None,
+            deprecated: None,
+            range: range(full),
+            selection_range: range(selection),
+            children,
+        };
+
+        let expected = vec![
+            symbol(
+                "foo.star",
+                None,
+                SymbolKind::MODULE,
+                "load",
+                "foo_star",
+                Some(vec![
+                    symbol("bar", None, SymbolKind::VARIABLE, "bar_arg", "bar", None),
+                    symbol(
+                        "baz",
+                        Some("qux"),
+                        SymbolKind::VARIABLE,
+                        "baz_arg",
+                        "baz",
+                        None,
+                    ),
+                ]),
+            ),
+            symbol("X", None, SymbolKind::VARIABLE, "x_assign", "x", None),
+            symbol(
+                "f",
+                None,
+                SymbolKind::FUNCTION,
+                "f_def",
+                "f",
+                Some(vec![symbol(
+                    "y",
+                    None,
+                    SymbolKind::VARIABLE,
+                    "y_assign",
+                    "y",
+                    None,
+                )]),
+            ),
+            symbol(
+                "lib",
+                Some("cxx_library"),
+                SymbolKind::OBJECT,
+                "lib",
+                "lib_name",
+                None,
+            ),
+        ];
+
+        assert_eq!(
+            expected,
+            get_document_symbols(module.ast.codemap(), module.ast.statement())
+        );
+        Ok(())
+    }
+}
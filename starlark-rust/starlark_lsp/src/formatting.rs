@@ -0,0 +1,56 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Whole document formatting.
+
+use lsp_types::DocumentFormattingParams;
+use lsp_types::TextEdit;
+use starlark_syntax::syntax::format::FormatOptions;
+use starlark_syntax::syntax::module::AstModuleFields;
+
+use crate::server::Backend;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+
+impl<T: LspContext> Backend<T> {
+    /// Format a document, as a single edit replacing all of its contents.
+    ///
+    /// Nothing is returned if the document does not currently parse,
+    /// as formatting the last valid parse would undo the edits since.
+    pub(crate) fn format_document(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let uri: LspUrl = params.text_document.uri.try_into()?;
+        if self.parse_failed.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        let Some(module) = self.get_ast(&uri) else {
+            return Ok(None);
+        };
+        let codemap = module.ast.codemap();
+        let options = FormatOptions::for_filename(&uri.path().to_string_lossy());
+        let formatted = module.ast.format(&options);
+        if formatted == codemap.source() {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: codemap.resolve_span(codemap.full_span()).into(),
+            new_text: formatted,
+        }]))
+    }
+}
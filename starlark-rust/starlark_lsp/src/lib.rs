@@ -19,14 +19,19 @@
 //! to the [Language Server Protocol](https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/).
 
 mod bind;
+mod code_actions;
 pub mod completion;
 mod definition;
 pub(crate) mod docs;
+mod document_symbols;
 pub mod error;
 mod exported;
+mod formatting;
 pub(crate) mod inspect;
 pub(crate) mod loaded;
+mod references;
 pub mod server;
+mod signature;
 mod symbols;
 #[cfg(all(test, not(windows)))]
 mod test;
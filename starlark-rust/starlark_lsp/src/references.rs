@@ -0,0 +1,893 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Finding references to symbols, and renaming them, including across `load()` statements.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use lsp_types::InitializeParams;
+use lsp_types::Location;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameParams;
+use lsp_types::TextEdit;
+use lsp_types::Url;
+use lsp_types::WorkspaceEdit;
+use starlark::codemap::Pos;
+use starlark::codemap::Span;
+use starlark_syntax::syntax::ast::AstNoPayload;
+use starlark_syntax::syntax::ast::LoadP;
+use starlark_syntax::syntax::ast::StmtP;
+use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
+
+use crate::bind::scope;
+use crate::bind::Assigner;
+use crate::bind::Bind;
+use crate::bind::Scope;
+use crate::definition::LspModule;
+use crate::server::Backend;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+use crate::server::KEYWORDS;
+
+/// Errors when renaming a symbol.
+#[derive(thiserror::Error, Debug)]
+enum RenameError {
+    /// The new name is not something that can be assigned to.
+    #[error("`{0}` is not a valid identifier")]
+    InvalidIdentifier(String),
+    /// The symbol is not defined in a starlark file, e.g. it is a builtin.
+    #[error("Cannot rename `{0}`, as it is not defined in a starlark file")]
+    NotDefinedInFile(String),
+    /// A file with references doesn't parse, so its edits would be relative to an older version.
+    #[error("Cannot rename while `{0}` has syntax errors")]
+    ParseFailed(LspUrl),
+    /// The new name would change what some variable refers to.
+    #[error("Cannot rename to `{0}`, as that name is already used in `{1}`")]
+    NameCollision(String, LspUrl),
+    /// The symbol is exported, and names starting with `_` can't be loaded from other modules.
+    #[error("Cannot rename `{0}` to `{1}`, as symbols starting with `_` are private to `{2}`")]
+    PrivateName(String, String, LspUrl),
+}
+
+/// The symbol at a given location in a module. See [`LspModule::find_symbol_at_location`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum SymbolAtLocation {
+    /// A variable which can only be referred to from within this module, e.g. a function
+    /// parameter, a private top-level variable, or a symbol loaded under a different name.
+    /// `binding` is the first assignment to the variable in the scope it is bound in.
+    Local { binding: Span, name: String },
+    /// A top-level symbol of this module, which other modules may load.
+    Exported { name: String },
+    /// A symbol loaded by a `load()` statement. `path` is the unresolved path in that statement,
+    /// and `name` is the name of the symbol in the loaded file.
+    Loaded { path: String, name: String },
+    /// A symbol which is not bound anywhere in this module, e.g. a builtin function.
+    Global { name: String },
+}
+
+/// A mention of a variable in a module, along with where that variable is bound.
+struct Occurrence<'a> {
+    name: &'a str,
+    span: Span,
+    /// The first assignment to the variable in the scope it is bound in, or `None` if the
+    /// variable is not bound anywhere in the module.
+    binding: Option<Span>,
+}
+
+/// Whether renaming the variable first assigned at `binding` to `new_name` would change what any
+/// mention of a variable in `scope` or its inner scopes refers to: either a mention of the renamed
+/// variable would find another binding of `new_name`, or a mention of `new_name` would find the
+/// renamed variable.
+fn rename_collides<'a>(
+    scope: &'a Scope,
+    parents: &mut Vec<&'a Scope>,
+    binding: Span,
+    new_name: &str,
+) -> bool {
+    parents.push(scope);
+    // The depth of the innermost scope that binds a name, and where that scope binds it.
+    let resolve = |parents: &[&Scope], name: &str| {
+        parents
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, s)| s.bound.get(name).map(|(_, span)| (depth, *span)))
+    };
+    let binding_depth = parents
+        .iter()
+        .rposition(|s| s.bound.values().any(|(_, span)| *span == binding));
+    let mut res = false;
+    for bind in &scope.inner {
+        let name = match bind {
+            Bind::Set(_, x) => x.ident.as_str(),
+            Bind::Get(x) => x.node.ident.as_str(),
+            Bind::GetDotted(x) => x.variable.node.ident.as_str(),
+            Bind::Scope(inner) => {
+                res = rename_collides(inner, parents, binding, new_name);
+                if res {
+                    break;
+                }
+                continue;
+            }
+            Bind::Flow => continue,
+        };
+        let new_name_binding = resolve(parents, new_name);
+        res = match resolve(parents, name) {
+            Some((depth, span)) if span == binding => {
+                new_name_binding.map_or(false, |(d, span)| d >= depth && span != binding)
+            }
+            _ if name == new_name => match binding_depth {
+                Some(binding_depth) => new_name_binding.map_or(true, |(d, _)| binding_depth >= d),
+                None => false,
+            },
+            _ => false,
+        };
+        if res {
+            break;
+        }
+    }
+    parents.pop();
+    res
+}
+
+/// Collect all the mentions of variables in `scope` and its inner scopes.
+fn occurrences<'a>(scope: &'a Scope, parents: &mut Vec<&'a Scope>, res: &mut Vec<Occurrence<'a>>) {
+    parents.push(scope);
+    for bind in &scope.inner {
+        let (name, span) = match bind {
+            Bind::Set(_, x) => (x.ident.as_str(), x.span),
+            Bind::Get(x) => (x.node.ident.as_str(), x.span),
+            Bind::GetDotted(x) => (x.variable.node.ident.as_str(), x.variable.span),
+            Bind::Scope(inner) => {
+                occurrences(inner, parents, res);
+                continue;
+            }
+            Bind::Flow => continue,
+        };
+        // The innermost scope that binds a name is the one it refers to.
+        let binding = parents
+            .iter()
+            .rev()
+            .find_map(|s| s.bound.get(name).map(|(_, span)| *span));
+        res.push(Occurrence {
+            name,
+            span,
+            binding,
+        });
+    }
+    parents.pop();
+}
+
+/// A `load()` of a particular symbol. See [`LspModule::find_loads_of_symbol`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct LoadOfSymbol {
+    /// The unresolved path in the `load()` statement.
+    pub(crate) path: String,
+    /// The location of the string naming the symbol in the loaded file.
+    pub(crate) their: Span,
+    /// If the symbol is bound under its own name, the location of that binding.
+    pub(crate) binding: Option<Span>,
+}
+
+impl LspModule {
+    fn pos_at_location(&self, line: u32, col: u32) -> Option<Pos> {
+        let line_span = self.ast.codemap().line_span_opt(line as usize)?;
+        Some(std::cmp::min(line_span.begin() + col, line_span.end()))
+    }
+
+    fn occurrences<'a>(scope: &'a Scope) -> Vec<Occurrence<'a>> {
+        let mut res = Vec::new();
+        occurrences(scope, &mut Vec::new(), &mut res);
+        res
+    }
+
+    /// Find the symbol at a given location, and the location of that mention of it.
+    ///
+    /// `line` and `col` are zero based indexes of a location in the module. Unlike
+    /// [`LspModule::find_definition_at_location`], this also finds symbols at the place
+    /// they are assigned, so that e.g. the name of a `def` can be renamed.
+    pub(crate) fn find_symbol_at_location(
+        &self,
+        line: u32,
+        col: u32,
+    ) -> Option<(Span, SymbolAtLocation)> {
+        let pos = self.pos_at_location(line, col)?;
+
+        // The name of a symbol in a `load()` statement is only a variable if it is not aliased.
+        for load in self.find_loads() {
+            for arg in &load.args {
+                if arg.their.span.contains(pos) && arg.their.span != arg.local.span {
+                    return Some((
+                        arg.their.span,
+                        SymbolAtLocation::Loaded {
+                            path: load.module.node.clone(),
+                            name: arg.their.node.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        let scope = scope(&self.ast);
+        let occurrence = Self::occurrences(&scope)
+            .into_iter()
+            .find(|x| x.span.contains(pos))?;
+        let name = occurrence.name.to_owned();
+        let symbol = match occurrence.binding {
+            None => SymbolAtLocation::Global { name },
+            Some(binding) => match scope.bound.get(occurrence.name) {
+                // Only top-level bindings can be used from other modules.
+                Some((assigner, span)) if *span == binding => match assigner {
+                    Assigner::Load { path, name: their } if their.span == binding => {
+                        SymbolAtLocation::Loaded {
+                            path: path.node.clone(),
+                            name: their.node.clone(),
+                        }
+                    }
+                    Assigner::Load { .. } => SymbolAtLocation::Local { binding, name },
+                    Assigner::Argument | Assigner::Assign if !name.starts_with('_') => {
+                        SymbolAtLocation::Exported { name }
+                    }
+                    Assigner::Argument | Assigner::Assign => {
+                        SymbolAtLocation::Local { binding, name }
+                    }
+                },
+                _ => SymbolAtLocation::Local { binding, name },
+            },
+        };
+        Some((occurrence.span, symbol))
+    }
+
+    /// Find all the mentions of variables matching a predicate, in source order.
+    fn find_occurrences(&self, pred: impl Fn(&Occurrence) -> bool) -> Vec<Span> {
+        let scope = scope(&self.ast);
+        // `x += 1` both reads and writes `x`, so is seen twice.
+        let mut seen = HashSet::new();
+        Self::occurrences(&scope)
+            .into_iter()
+            .filter(|x| pred(x) && seen.insert(x.span))
+            .map(|x| x.span)
+            .collect()
+    }
+
+    /// Find all the mentions of the variable first assigned at `binding`.
+    pub(crate) fn find_references_to_binding(&self, binding: Span) -> Vec<Span> {
+        self.find_occurrences(|x| x.binding == Some(binding))
+    }
+
+    /// Find all the mentions of a variable which is not bound in this module.
+    pub(crate) fn find_references_to_global(&self, name: &str) -> Vec<Span> {
+        self.find_occurrences(|x| x.binding.is_none() && x.name == name)
+    }
+
+    /// Whether renaming the variable first assigned at `binding` to `new_name` would change
+    /// what some variable in this module refers to.
+    pub(crate) fn rename_collides(&self, binding: Span, new_name: &str) -> bool {
+        rename_collides(&scope(&self.ast), &mut Vec::new(), binding, new_name)
+    }
+
+    /// Find the first top-level assignment to a symbol defined in this module.
+    pub(crate) fn find_exported_binding(&self, name: &str) -> Option<Span> {
+        match scope(&self.ast).bound.get(name) {
+            Some((Assigner::Argument | Assigner::Assign, span)) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// Find all the places a symbol with the given name is loaded from some other module.
+    pub(crate) fn find_loads_of_symbol(&self, name: &str) -> Vec<LoadOfSymbol> {
+        let mut res = Vec::new();
+        for load in self.find_loads() {
+            for arg in &load.args {
+                if arg.their.node == name {
+                    res.push(LoadOfSymbol {
+                        path: load.module.node.clone(),
+                        their: arg.their.span,
+                        binding: (arg.local.ident == name).then_some(arg.local.span),
+                    });
+                }
+            }
+        }
+        res
+    }
+
+    fn find_loads(&self) -> impl Iterator<Item = &LoadP<AstNoPayload>> {
+        top_level_stmts(self.ast.statement())
+            .into_iter()
+            .filter_map(|x| match &x.node {
+                StmtP::Load(load) => Some(load),
+                _ => None,
+            })
+    }
+}
+
+/// A symbol to find the references to, identified by where it is defined.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum ReferenceTarget {
+    /// A variable that can only be mentioned in the module where it is defined.
+    Local { uri: LspUrl, binding: Span },
+    /// A top-level symbol of the module at `uri`, which other modules may load.
+    Exported { uri: LspUrl, name: String },
+    /// A symbol which is not defined in any starlark file. Only mentions in the module at
+    /// `uri` are considered.
+    Global { uri: LspUrl, name: String },
+}
+
+/// A mention of a symbol that was found while looking for references.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Reference {
+    span: Span,
+    /// Whether this is where the symbol is defined, rather than just used.
+    is_declaration: bool,
+    /// Whether the reference is the string naming a symbol in a `load()` statement,
+    /// rather than an identifier.
+    is_load_string: bool,
+}
+
+/// The mentions of a symbol in one module, along with the module they were found in.
+struct ModuleReferences {
+    uri: LspUrl,
+    module: Arc<LspModule>,
+    /// Where the variables in this module which refer to the symbol are bound.
+    bindings: Vec<Span>,
+    references: Vec<Reference>,
+}
+
+fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|c| c == '_' || c.is_ascii_alphanumeric()) && !KEYWORDS.contains(&name)
+}
+
+impl<T: LspContext> Backend<T> {
+    /// Work out which symbol is at the given location, resolving symbols loaded from
+    /// other files to the file that defines them.
+    fn find_reference_target(
+        &self,
+        uri: &LspUrl,
+        line: u32,
+        character: u32,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Option<ReferenceTarget>> {
+        let Some(ast) = self.get_ast(uri) else {
+            return Ok(None);
+        };
+        let Some((_, symbol)) = ast.find_symbol_at_location(line, character) else {
+            return Ok(None);
+        };
+        let target = match symbol {
+            SymbolAtLocation::Local { binding, .. } => ReferenceTarget::Local {
+                uri: uri.clone(),
+                binding,
+            },
+            SymbolAtLocation::Exported { name } => ReferenceTarget::Exported {
+                uri: uri.clone(),
+                name,
+            },
+            SymbolAtLocation::Loaded { path, name } => ReferenceTarget::Exported {
+                uri: self.resolve_load_path(&path, uri, workspace_root)?,
+                name,
+            },
+            SymbolAtLocation::Global { name } => ReferenceTarget::Global {
+                uri: uri.clone(),
+                name,
+            },
+        };
+        Ok(Some(target))
+    }
+
+    /// The files which could load a symbol from another file: everything that is open,
+    /// plus anything else the context knows about.
+    fn files_to_search(&self, workspace_root: Option<&Path>) -> Vec<LspUrl> {
+        let mut seen = HashSet::new();
+        let open_files: Vec<LspUrl> = self
+            .last_valid_parse
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        let workspace_files = match self.context.get_workspace_files(workspace_root) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Error listing workspace files: {:#}", e);
+                Vec::new()
+            }
+        };
+        open_files
+            .into_iter()
+            .chain(workspace_files)
+            .filter(|uri| seen.insert(uri.clone()))
+            .collect()
+    }
+
+    /// Find all the mentions of a symbol, grouped by the module they are in.
+    fn find_references_to_target(
+        &self,
+        target: &ReferenceTarget,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Vec<ModuleReferences>> {
+        let mut res = Vec::new();
+        match target {
+            ReferenceTarget::Local { uri, binding } => {
+                if let Some(module) = self.get_ast_or_load_from_disk(uri)? {
+                    let references = module
+                        .find_references_to_binding(*binding)
+                        .into_iter()
+                        .map(|span| Reference {
+                            span,
+                            is_declaration: span == *binding,
+                            is_load_string: false,
+                        })
+                        .collect();
+                    res.push(ModuleReferences {
+                        uri: uri.clone(),
+                        module,
+                        bindings: vec![*binding],
+                        references,
+                    });
+                }
+            }
+            ReferenceTarget::Global { uri, name } => {
+                if let Some(module) = self.get_ast_or_load_from_disk(uri)? {
+                    let references = module
+                        .find_references_to_global(name)
+                        .into_iter()
+                        .map(|span| Reference {
+                            span,
+                            is_declaration: false,
+                            is_load_string: false,
+                        })
+                        .collect();
+                    res.push(ModuleReferences {
+                        uri: uri.clone(),
+                        module,
+                        bindings: Vec::new(),
+                        references,
+                    });
+                }
+            }
+            ReferenceTarget::Exported { uri, name } => {
+                if let Some(module) = self.get_ast_or_load_from_disk(uri)? {
+                    if let Some(binding) = module.find_exported_binding(name) {
+                        let references = module
+                            .find_references_to_binding(binding)
+                            .into_iter()
+                            .map(|span| Reference {
+                                span,
+                                is_declaration: span == binding,
+                                is_load_string: false,
+                            })
+                            .collect();
+                        res.push(ModuleReferences {
+                            uri: uri.clone(),
+                            module,
+                            bindings: vec![binding],
+                            references,
+                        });
+                    }
+                }
+
+                for file in self.files_to_search(workspace_root) {
+                    if &file == uri {
+                        continue;
+                    }
+                    // Files that can't be read or parsed just don't have any references.
+                    let Ok(Some(module)) = self.get_ast_or_load_from_disk(&file) else {
+                        continue;
+                    };
+                    let mut bindings = Vec::new();
+                    let mut references = Vec::new();
+                    for load in module.find_loads_of_symbol(name) {
+                        match self.context.resolve_load(&load.path, &file, workspace_root) {
+                            Ok(loaded) if &loaded == uri => {}
+                            _ => continue,
+                        }
+                        references.push(Reference {
+                            span: load.their,
+                            is_declaration: false,
+                            is_load_string: true,
+                        });
+                        if let Some(binding) = load.binding {
+                            bindings.push(binding);
+                            for span in module.find_references_to_binding(binding) {
+                                // Without an alias, the binding is the string in the `load()`.
+                                if span != load.their {
+                                    references.push(Reference {
+                                        span,
+                                        is_declaration: false,
+                                        is_load_string: false,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    if !references.is_empty() {
+                        res.push(ModuleReferences {
+                            uri: file,
+                            module,
+                            bindings,
+                            references,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Find all the references to the symbol at the given location, including those in
+    /// other files which load it.
+    ///
+    /// Nothing is returned if the document does not currently parse, as the location
+    /// would be looked up in the last valid parse.
+    pub(crate) fn find_references(
+        &self,
+        params: ReferenceParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        if self.parse_failed.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        let line = params.text_document_position.position.line;
+        let character = params.text_document_position.position.character;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+
+        let Some(target) =
+            self.find_reference_target(&uri, line, character, workspace_root.as_deref())?
+        else {
+            return Ok(None);
+        };
+        let mut locations = Vec::new();
+        for found in self.find_references_to_target(&target, workspace_root.as_deref())? {
+            let uri = Url::try_from(&found.uri)?;
+            let codemap = found.module.ast.codemap();
+            for reference in found.references {
+                if reference.is_declaration && !params.context.include_declaration {
+                    continue;
+                }
+                locations.push(Location {
+                    uri: uri.clone(),
+                    range: codemap.resolve_span(reference.span).into(),
+                });
+            }
+        }
+        Ok(Some(locations))
+    }
+
+    /// Rename the symbol at the given location, everywhere that it is referenced.
+    ///
+    /// Nothing is returned if the document does not currently parse, and renaming fails if
+    /// another file with references doesn't, as the edits would be relative to the last
+    /// valid parse. Renaming also fails if the new name is already used where it would
+    /// change what some variable refers to, or if it would make an exported symbol private.
+    pub(crate) fn rename_symbol(
+        &self,
+        params: RenameParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        if self.parse_failed.read().unwrap().contains(&uri) {
+            return Ok(None);
+        }
+        let line = params.text_document_position.position.line;
+        let character = params.text_document_position.position.character;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+        let new_name = params.new_name.as_str();
+
+        if !is_valid_identifier(new_name) {
+            return Err(RenameError::InvalidIdentifier(new_name.to_owned()).into());
+        }
+        let Some(target) =
+            self.find_reference_target(&uri, line, character, workspace_root.as_deref())?
+        else {
+            return Ok(None);
+        };
+        match &target {
+            ReferenceTarget::Global { name, .. } => {
+                return Err(RenameError::NotDefinedInFile(name.clone()).into());
+            }
+            ReferenceTarget::Exported { uri, name } if new_name.starts_with('_') => {
+                return Err(RenameError::PrivateName(
+                    name.clone(),
+                    new_name.to_owned(),
+                    uri.clone(),
+                )
+                .into());
+            }
+            ReferenceTarget::Local { .. } | ReferenceTarget::Exported { .. } => {}
+        }
+
+        let found = self.find_references_to_target(&target, workspace_root.as_deref())?;
+        for found in &found {
+            if self.parse_failed.read().unwrap().contains(&found.uri) {
+                return Err(RenameError::ParseFailed(found.uri.clone()).into());
+            }
+            if found
+                .bindings
+                .iter()
+                .any(|binding| found.module.rename_collides(*binding, new_name))
+            {
+                return Err(
+                    RenameError::NameCollision(new_name.to_owned(), found.uri.clone()).into(),
+                );
+            }
+        }
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for found in found {
+            let codemap = found.module.ast.codemap();
+            let edits = changes.entry(Url::try_from(&found.uri)?).or_default();
+            for reference in found.references {
+                let new_text = if reference.is_load_string {
+                    // Keep whichever quotes were used originally.
+                    let quote = codemap
+                        .source_span(reference.span)
+                        .chars()
+                        .next()
+                        .unwrap_or('"');
+                    format!("{quote}{new_name}{quote}")
+                } else {
+                    new_name.to_owned()
+                };
+                edits.push(TextEdit::new(
+                    codemap.resolve_span(reference.span).into(),
+                    new_text,
+                ));
+            }
+        }
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::codemap::ResolvedSpan;
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::definition::helpers::FixtureWithRanges;
+
+    fn resolve(module: &LspModule, spans: Vec<Span>) -> Vec<ResolvedSpan> {
+        spans
+            .into_iter()
+            .map(|span| module.ast.codemap().resolve_span(span))
+            .collect()
+    }
+
+    #[test]
+    fn finds_symbols_at_location() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("foo.star", "<bar>bar</bar>", <baz>baz</baz> = "<qux>qux</qux>")
+
+            def <f>f</f>(<x>x</x>):
+                return <y>y</y> + x
+
+            <z>_z</z> = 1
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let symbol_at = |name: &str| {
+            module
+                .find_symbol_at_location(parsed.begin_line(name), parsed.begin_column(name))
+                .map(|(_, symbol)| symbol)
+        };
+        let binding_of = |name: &str| {
+            module
+                .find_symbol_at_location(parsed.begin_line(name), parsed.begin_column(name))
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(
+            Some(SymbolAtLocation::Loaded {
+                path: "foo.star".to_owned(),
+                name: "bar".to_owned()
+            }),
+            symbol_at("bar")
+        );
+        assert_eq!(
+            Some(SymbolAtLocation::Loaded {
+                path: "foo.star".to_owned(),
+                name: "qux".to_owned()
+            }),
+            symbol_at("qux")
+        );
+        assert_eq!(
+            Some(SymbolAtLocation::Local {
+                binding: binding_of("baz"),
+                name: "baz".to_owned()
+            }),
+            symbol_at("baz")
+        );
+        assert_eq!(
+            Some(SymbolAtLocation::Exported {
+                name: "f".to_owned()
+            }),
+            symbol_at("f")
+        );
+        assert_eq!(
+            Some(SymbolAtLocation::Local {
+                binding: binding_of("x"),
+                name: "x".to_owned()
+            }),
+            symbol_at("x")
+        );
+        assert_eq!(
+            Some(SymbolAtLocation::Global {
+                name: "y".to_owned()
+            }),
+            symbol_at("y")
+        );
+        assert_eq!(
+            Some(SymbolAtLocation::Local {
+                binding: binding_of("z"),
+                name: "_z".to_owned()
+            }),
+            symbol_at("z")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_respecting_scopes() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            <x1>x</x1> = 1
+
+            def f(x):
+                return x
+
+            def g():
+                return <x2>x</x2> + [x for x in range(10)][0]
+
+            <x3>x</x3> += <x4>x</x4>
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let (source, symbol) = module
+            .find_symbol_at_location(parsed.begin_line("x2"), parsed.begin_column("x2"))
+            .unwrap();
+        assert_eq!(
+            parsed.resolved_span("x2"),
+            module.ast.codemap().resolve_span(source)
+        );
+        assert_eq!(
+            SymbolAtLocation::Exported {
+                name: "x".to_owned()
+            },
+            symbol
+        );
+
+        let binding = module.find_exported_binding("x").unwrap();
+        assert_eq!(
+            vec![
+                parsed.resolved_span("x1"),
+                parsed.resolved_span("x2"),
+                parsed.resolved_span("x3"),
+                parsed.resolved_span("x4"),
+            ],
+            resolve(&module, module.find_references_to_binding(binding))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn finds_loads_of_symbol() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("foo.star", <a>"a"</a>)
+            load("bar.star", b = <b>"a"</b>, "c")
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let loads = module.find_loads_of_symbol("a");
+        assert_eq!(
+            vec![
+                ("foo.star", parsed.resolved_span("a"), true),
+                ("bar.star", parsed.resolved_span("b"), false),
+            ],
+            loads
+                .iter()
+                .map(|load| (
+                    load.path.as_str(),
+                    module.ast.codemap().resolve_span(load.their),
+                    load.binding.is_some()
+                ))
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn detects_rename_collisions() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            <x>x</x> = 1
+            y = 2
+
+            def f(<a>a</a>):
+                b = 1
+                return a + x + len([])
+
+            def g(c):
+                return y
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+
+        let binding = |name: &str| {
+            module
+                .find_symbol_at_location(parsed.begin_line(name), parsed.begin_column(name))
+                .unwrap()
+                .0
+        };
+        let x = binding("x");
+        let a = binding("a");
+
+        // Another variable in the same scope.
+        assert!(module.rename_collides(x, "y"));
+        // A local variable which would shadow the renamed one.
+        assert!(module.rename_collides(x, "a"));
+        assert!(module.rename_collides(a, "b"));
+        // Mentions of other variables, or builtins, which would find the renamed one.
+        assert!(module.rename_collides(a, "x"));
+        assert!(module.rename_collides(a, "len"));
+        // Names which are only bound where the renamed variable isn't used.
+        assert!(!module.rename_collides(x, "c"));
+        assert!(!module.rename_collides(a, "c"));
+        assert!(!module.rename_collides(x, "z"));
+        assert!(!module.rename_collides(x, "x"));
+        Ok(())
+    }
+
+    #[test]
+    fn validates_identifiers() {
+        assert!(is_valid_identifier("foo"));
+        assert!(is_valid_identifier("_foo_1"));
+        assert!(!is_valid_identifier(""));
+        assert!(!is_valid_identifier("1foo"));
+        assert!(!is_valid_identifier("foo-bar"));
+        assert!(!is_valid_identifier("def"));
+    }
+}
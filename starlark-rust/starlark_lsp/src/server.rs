@@ -42,9 +42,17 @@ use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::CodeActionRequest;
 use lsp_types::request::Completion;
+use lsp_types::request::DocumentSymbolRequest;
+use lsp_types::request::Formatting;
 use lsp_types::request::GotoDefinition;
 use lsp_types::request::HoverRequest;
+use lsp_types::request::References;
+use lsp_types::request::Rename;
+use lsp_types::request::SignatureHelpRequest;
+use lsp_types::CodeActionParams;
+use lsp_types::CodeActionProviderCapability;
 use lsp_types::CompletionItem;
 use lsp_types::CompletionItemKind;
 use lsp_types::CompletionOptions;
@@ -55,6 +63,8 @@ use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::DocumentFormattingParams;
+use lsp_types::DocumentSymbolParams;
 use lsp_types::Documentation;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
@@ -74,7 +84,11 @@ use lsp_types::OneOf;
 use lsp_types::Position;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameParams;
 use lsp_types::ServerCapabilities;
+use lsp_types::SignatureHelpOptions;
+use lsp_types::SignatureHelpParams;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
 use lsp_types::TextEdit;
@@ -109,6 +123,16 @@ use crate::inspect::AstModuleInspect;
 use crate::inspect::AutocompleteType;
 use crate::symbols::find_symbols_at_location;
 
+/// The keywords and reserved words of the language, none of which are valid identifiers.
+pub(crate) const KEYWORDS: &[&str] = &[
+    // Actual keywords
+    "and", "else", "load", "break", "for", "not", "continue", "if", "or", "def", "in", "pass",
+    "elif", "return", "lambda", //
+    // Reserved words
+    "as", "import", "is", "class", "nonlocal", "del", "raise", "except", "try", "finally", "while",
+    "from", "with", "global", "yield",
+];
+
 /// The request to get the file contents for a starlark: URI
 struct StarlarkFileContentsRequest {}
 
@@ -341,9 +365,32 @@ pub trait LspContext {
         Ok(result)
     }
 
+    /// Get all the starlark files in the workspace, so that references to a symbol can be
+    /// found in files that are not open. Files that are open are always searched, so by
+    /// default nothing else is.
+    fn get_workspace_files(&self, workspace_root: Option<&Path>) -> anyhow::Result<Vec<LspUrl>> {
+        let _unused = workspace_root;
+        Ok(Vec::new())
+    }
+
     /// Get the preloaded environment for a particular file.
     fn get_environment(&self, uri: &LspUrl) -> DocModule;
 
+    /// Get the documentation for a global symbol, e.g. to offer signature help when calling it.
+    ///
+    /// By default this is looked up in [`LspContext::get_environment()`].
+    fn get_global_symbol_docs(
+        &self,
+        current_file: &LspUrl,
+        symbol: &str,
+    ) -> anyhow::Result<Option<DocMember>> {
+        Ok(self
+            .get_environment(current_file)
+            .members
+            .into_iter()
+            .find_map(|(name, member)| (name == symbol).then_some(member)))
+    }
+
     /// Get the LSPUrl for a global symbol if possible.
     ///
     /// The current file is provided in case different files have different global symbols
@@ -391,6 +438,8 @@ pub(crate) struct Backend<T: LspContext> {
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     pub(crate) last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// Open files whose current contents do not parse, so `last_valid_parse` is out of date.
+    pub(crate) parse_failed: RwLock<HashSet<LspUrl>>,
 }
 
 /// The logic implementations of stuff
@@ -428,11 +477,20 @@ impl<T: LspContext> Backend<T> {
                 ..Default::default()
             }),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
+            references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(vec!["(".to_owned(), ",".to_owned()]),
+                ..Default::default()
+            }),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
             ..ServerCapabilities::default()
         }
     }
 
-    fn get_ast(&self, uri: &LspUrl) -> Option<Arc<LspModule>> {
+    pub(crate) fn get_ast(&self, uri: &LspUrl) -> Option<Arc<LspModule>> {
         let last_valid_parse = self.last_valid_parse.read().unwrap();
         last_valid_parse.get(uri).duped()
     }
@@ -458,6 +516,9 @@ impl<T: LspContext> Backend<T> {
             let module = Arc::new(LspModule::new(ast));
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
             last_valid_parse.insert(uri.clone(), module);
+            self.parse_failed.write().unwrap().remove(&uri);
+        } else {
+            self.parse_failed.write().unwrap().insert(uri.clone());
         }
         self.publish_diagnostics(uri.try_into()?, eval_result.diagnostics, version);
         Ok(())
@@ -483,8 +544,10 @@ impl<T: LspContext> Backend<T> {
 
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
         {
+            let uri: LspUrl = params.text_document.uri.clone().try_into()?;
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            last_valid_parse.remove(&uri);
+            self.parse_failed.write().unwrap().remove(&uri);
         }
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
@@ -526,6 +589,56 @@ impl<T: LspContext> Backend<T> {
         self.send_response(new_response(id, self.hover_info(params, initialize_params)));
     }
 
+    /// Find all the references to the symbol at the current cursor, including in other
+    /// files which load it.
+    fn references(
+        &self,
+        id: RequestId,
+        params: ReferenceParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.find_references(params, initialize_params),
+        ));
+    }
+
+    /// Rename the symbol at the current cursor, including in other files which load it.
+    fn rename(&self, id: RequestId, params: RenameParams, initialize_params: &InitializeParams) {
+        self.send_response(new_response(
+            id,
+            self.rename_symbol(params, initialize_params),
+        ));
+    }
+
+    /// Offer the signature of the function being called at the current cursor.
+    fn signature_help(
+        &self,
+        id: RequestId,
+        params: SignatureHelpParams,
+        initialize_params: &InitializeParams,
+    ) {
+        self.send_response(new_response(
+            id,
+            self.get_signature_help(params, initialize_params),
+        ));
+    }
+
+    /// Offer an outline of the defs, loads, variables and targets in a file.
+    fn document_symbols(&self, id: RequestId, params: DocumentSymbolParams) {
+        self.send_response(new_response(id, self.find_document_symbols(params)));
+    }
+
+    /// Format the whole document.
+    fn formatting(&self, id: RequestId, params: DocumentFormattingParams) {
+        self.send_response(new_response(id, self.format_document(params)));
+    }
+
+    /// Offer quick fixes for the lints in a range.
+    fn code_action(&self, id: RequestId, params: CodeActionParams) {
+        self.send_response(new_response(id, self.code_actions(params)));
+    }
+
     /// Get the file contents of a starlark: URI.
     fn get_starlark_file_contents(&self, id: RequestId, params: StarlarkFileContentsParams) {
         let response: anyhow::Result<_> = match params.uri {
@@ -1005,17 +1118,8 @@ impl<T: LspContext> Backend<T> {
 
     /// Get completion items for each language keyword.
     pub(crate) fn get_keyword_completion_items() -> impl Iterator<Item = CompletionItem> {
-        [
-            // Actual keywords
-            "and", "else", "load", "break", "for", "not", "continue", "if", "or", "def", "in",
-            "pass", "elif", "return", "lambda", //
-            // Reserved words
-            "as", "import", "is", "class", "nonlocal", "del", "raise", "except", "try", "finally",
-            "while", "from", "with", "global", "yield",
-        ]
-        .into_iter()
-        .map(|keyword| CompletionItem {
-            label: keyword.to_owned(),
+        KEYWORDS.iter().map(|keyword| CompletionItem {
+            label: (*keyword).to_owned(),
             kind: Some(CompletionItemKind::KEYWORD),
             ..Default::default()
         })
@@ -1189,7 +1293,7 @@ impl<T: LspContext> Backend<T> {
         })
     }
 
-    fn get_workspace_root(
+    pub(crate) fn get_workspace_root(
         workspace_roots: Option<&Vec<WorkspaceFolder>>,
         target: &LspUrl,
     ) -> Option<PathBuf> {
@@ -1236,8 +1340,6 @@ impl<T: LspContext> Backend<T> {
         for msg in &self.connection.receiver {
             match msg {
                 Message::Request(req) => {
-                    // TODO(nmj): Also implement DocumentSymbols so that some logic can
-                    //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params, &initialize_params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
@@ -1246,6 +1348,18 @@ impl<T: LspContext> Backend<T> {
                         self.completion(req.id, params, &initialize_params);
                     } else if let Some(params) = as_request::<HoverRequest>(&req) {
                         self.hover(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<References>(&req) {
+                        self.references(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<Rename>(&req) {
+                        self.rename(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<SignatureHelpRequest>(&req) {
+                        self.signature_help(req.id, params, &initialize_params);
+                    } else if let Some(params) = as_request::<DocumentSymbolRequest>(&req) {
+                        self.document_symbols(req.id, params);
+                    } else if let Some(params) = as_request::<Formatting>(&req) {
+                        self.formatting(req.id, params);
+                    } else if let Some(params) = as_request::<CodeActionRequest>(&req) {
+                        self.code_action(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
                         return Ok(());
                     }
@@ -1309,6 +1423,7 @@ pub fn server_with_connection<T: LspContext>(
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        parse_failed: RwLock::default(),
     }
     .main_loop(initialization_params)?;
 
@@ -1388,21 +1503,43 @@ where
 //            some paths. Revisit later.
 #[cfg(all(test, not(windows)))]
 mod test {
+    use std::collections::HashMap;
     use std::path::Path;
     use std::path::PathBuf;
 
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::request::CodeActionRequest;
+    use lsp_types::request::DocumentSymbolRequest;
+    use lsp_types::request::Formatting;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::References;
+    use lsp_types::request::Rename;
+    use lsp_types::request::SignatureHelpRequest;
+    use lsp_types::CodeActionContext;
+    use lsp_types::CodeActionOrCommand;
+    use lsp_types::CodeActionParams;
+    use lsp_types::DocumentFormattingParams;
+    use lsp_types::DocumentSymbolParams;
+    use lsp_types::DocumentSymbolResponse;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::Location;
     use lsp_types::LocationLink;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::ReferenceContext;
+    use lsp_types::ReferenceParams;
+    use lsp_types::RenameParams;
+    use lsp_types::SignatureHelp;
+    use lsp_types::SignatureHelpParams;
+    use lsp_types::SymbolKind;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
+    use lsp_types::TextEdit;
     use lsp_types::Url;
+    use lsp_types::WorkspaceEdit;
     use starlark::codemap::ResolvedSpan;
     use starlark::wasm::is_wasm;
     use textwrap::dedent;
@@ -2502,4 +2639,546 @@ mod test {
         }
         Ok(())
     }
+
+    fn references_request(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+        include_declaration: bool,
+    ) -> Request {
+        server.new_request::<References>(ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration,
+            },
+        })
+    }
+
+    fn rename_request(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> Request {
+        server.new_request::<Rename>(RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            new_name: new_name.to_owned(),
+            work_done_progress_params: Default::default(),
+        })
+    }
+
+    /// Fixtures for a symbol `baz` defined in `bar.star`, loaded by `foo.star` directly,
+    /// and by `qux.star` under another name.
+    fn references_fixtures() -> anyhow::Result<(
+        (Url, FixtureWithRanges),
+        (Url, FixtureWithRanges),
+        (Url, FixtureWithRanges),
+    )> {
+        let bar_uri = temp_file_uri("bar.star");
+        let foo_uri = temp_file_uri("foo.star");
+        let qux_uri = temp_file_uri("qux.star");
+
+        let bar_contents = dedent(
+            r#"
+            def <baz_def>baz</baz_def>():
+                pass
+
+            def quz(baz):
+                return baz
+
+            <baz_use>baz</baz_use>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo_contents = dedent(
+            r#"
+            load("{load}", <baz_load>"baz"</baz_load>)
+            <baz_use>baz</baz_use>()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let qux_contents = dedent(
+            r#"
+            load("{load}", other = <baz_load>'baz'</baz_load>)
+            other()
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+
+        Ok((
+            (
+                bar_uri.clone(),
+                FixtureWithRanges::from_fixture(bar_uri.path(), &bar_contents)?,
+            ),
+            (
+                foo_uri.clone(),
+                FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?,
+            ),
+            (
+                qux_uri.clone(),
+                FixtureWithRanges::from_fixture(qux_uri.path(), &qux_contents)?,
+            ),
+        ))
+    }
+
+    #[test]
+    fn finds_references_across_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let ((bar_uri, bar), (foo_uri, foo), (qux_uri, qux)) = references_fixtures()?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(bar_uri.clone(), bar.program())?;
+        server.open_file(qux_uri.clone(), qux.program())?;
+        // Closed files are found through the context.
+        server.set_file_contents(PathBuf::from(foo_uri.path()), foo.program())?;
+
+        let location = |uri: &Url, fixture: &FixtureWithRanges, id: &str| Location {
+            uri: uri.clone(),
+            range: fixture.resolved_span(id).into(),
+        };
+
+        let request = references_request(
+            &mut server,
+            bar_uri.clone(),
+            bar.begin_line("baz_def"),
+            bar.begin_column("baz_def"),
+            true,
+        );
+        let req_id = server.send_request(request)?;
+        let mut response = server.get_response::<Vec<Location>>(req_id)?;
+        response.sort_by_key(|l| (l.uri.to_string(), l.range.start));
+
+        let mut expected = vec![
+            location(&bar_uri, &bar, "baz_def"),
+            location(&bar_uri, &bar, "baz_use"),
+            location(&foo_uri, &foo, "baz_load"),
+            location(&foo_uri, &foo, "baz_use"),
+            location(&qux_uri, &qux, "baz_load"),
+        ];
+        expected.sort_by_key(|l| (l.uri.to_string(), l.range.start));
+        assert_eq!(expected, response);
+
+        // The same references are found from a file that loads the symbol, except for
+        // the declaration when it is not requested.
+        server.open_file(foo_uri.clone(), foo.program())?;
+        let request = references_request(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("baz_use"),
+            foo.begin_column("baz_use"),
+            false,
+        );
+        let req_id = server.send_request(request)?;
+        let mut response = server.get_response::<Vec<Location>>(req_id)?;
+        response.sort_by_key(|l| (l.uri.to_string(), l.range.start));
+
+        expected.retain(|l| l != &location(&bar_uri, &bar, "baz_def"));
+        assert_eq!(expected, response);
+        Ok(())
+    }
+
+    #[test]
+    fn renames_across_files() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let ((bar_uri, bar), (foo_uri, foo), (qux_uri, qux)) = references_fixtures()?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.open_file(qux_uri.clone(), qux.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar.program())?;
+
+        let request = rename_request(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("baz_load"),
+            foo.begin_column("baz_load") + 1,
+            "renamed",
+        );
+        let req_id = server.send_request(request)?;
+        let mut response = server.get_response::<WorkspaceEdit>(req_id)?;
+        for edits in response.changes.iter_mut().flat_map(|x| x.values_mut()) {
+            edits.sort_by_key(|e| e.range.start);
+        }
+
+        let edit = |fixture: &FixtureWithRanges, id: &str, new_text: &str| {
+            TextEdit::new(fixture.resolved_span(id).into(), new_text.to_owned())
+        };
+        let expected = WorkspaceEdit::new(HashMap::from([
+            (
+                bar_uri,
+                vec![
+                    edit(&bar, "baz_def", "renamed"),
+                    edit(&bar, "baz_use", "renamed"),
+                ],
+            ),
+            (
+                foo_uri,
+                vec![
+                    edit(&foo, "baz_load", "\"renamed\""),
+                    edit(&foo, "baz_use", "renamed"),
+                ],
+            ),
+            (qux_uri, vec![edit(&qux, "baz_load", "'renamed'")]),
+        ]));
+        assert_eq!(expected, response);
+        Ok(())
+    }
+
+    #[test]
+    fn rename_rejects_invalid_names() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "x = 1\nprint(x)".to_owned())?;
+
+        for new_name in ["1x", "x-y", "lambda"] {
+            let request = rename_request(&mut server, foo_uri.clone(), 0, 0, new_name);
+            let req_id = server.send_request(request)?;
+            assert!(server.get_response::<WorkspaceEdit>(req_id).is_err());
+        }
+
+        // Builtins can't be renamed either.
+        let request = rename_request(&mut server, foo_uri, 1, 0, "y");
+        let req_id = server.send_request(request)?;
+        assert!(server.get_response::<WorkspaceEdit>(req_id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rename_rejects_names_in_use() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "x = 1\ny = 2\nprint(x, y)".to_owned())?;
+
+        // Both `y` and the builtin `print` would change meaning.
+        for new_name in ["y", "print"] {
+            let request = rename_request(&mut server, foo_uri.clone(), 0, 0, new_name);
+            let req_id = server.send_request(request)?;
+            assert!(server.get_response::<WorkspaceEdit>(req_id).is_err());
+        }
+
+        let request = rename_request(&mut server, foo_uri, 0, 0, "z");
+        let req_id = server.send_request(request)?;
+        assert!(server.get_response::<WorkspaceEdit>(req_id).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn rename_rejects_private_names_for_exported_symbols() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "x = 1\ndef f(a):\n    return a".to_owned())?;
+
+        // Other modules could no longer load `x`.
+        let request = rename_request(&mut server, foo_uri.clone(), 0, 0, "_x");
+        let req_id = server.send_request(request)?;
+        assert!(server.get_response::<WorkspaceEdit>(req_id).is_err());
+
+        let request = rename_request(&mut server, foo_uri, 1, 6, "_a");
+        let req_id = server.send_request(request)?;
+        assert!(server.get_response::<WorkspaceEdit>(req_id).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn references_ignore_documents_that_do_not_parse() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "x = 1\nprint(x)".to_owned())?;
+
+        // The last valid parse is out of date, so its locations would be wrong.
+        server.change_file(foo_uri.clone(), "y = 1\nx = (\nprint(x)".to_owned())?;
+        let request = references_request(&mut server, foo_uri.clone(), 0, 0, true);
+        let req_id = server.send_request(request)?;
+        assert_eq!(None, server.get_response::<Option<Vec<Location>>>(req_id)?);
+
+        let request = rename_request(&mut server, foo_uri, 0, 0, "z");
+        let req_id = server.send_request(request)?;
+        assert_eq!(None, server.get_response::<Option<WorkspaceEdit>>(req_id)?);
+        Ok(())
+    }
+
+    fn signature_help_request(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> Request {
+        server.new_request::<SignatureHelpRequest>(SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        })
+    }
+
+    #[test]
+    fn signature_help_for_loaded_function() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let bar_uri = temp_file_uri("bar.star");
+        let foo_uri = temp_file_uri("foo.star");
+
+        let bar_contents = dedent(
+            r#"
+            def baz(x, y = [], *args, **kwargs):
+                """Does baz things.
+
+                Args:
+                    y: The y value
+                """
+                pass
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo_contents = dedent(
+            r#"
+            load("{load}", "baz")
+            baz(1, <y>y</y> = [], <args> </args>)
+            "#,
+        )
+        .replace("{load}", bar_uri.path())
+        .trim()
+        .to_owned();
+        let foo = FixtureWithRanges::from_fixture(foo_uri.path(), &foo_contents)?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar_contents)?;
+
+        let request = signature_help_request(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("y"),
+            foo.begin_column("y"),
+        );
+        let req_id = server.send_request(request)?;
+        let response = server.get_response::<SignatureHelp>(req_id)?;
+
+        assert_eq!(1, response.signatures.len());
+        let signature = &response.signatures[0];
+        assert_eq!("baz(x, y = [], *args, **kwargs)", signature.label);
+        assert_eq!(Some(1), response.active_parameter);
+        assert_eq!(4, signature.parameters.as_ref().map_or(0, |p| p.len()));
+        assert!(
+            signature.parameters.as_ref().unwrap()[1]
+                .documentation
+                .is_some()
+        );
+
+        // After a named argument, the next parameter can't be known.
+        let request = signature_help_request(
+            &mut server,
+            foo_uri.clone(),
+            foo.begin_line("args"),
+            foo.begin_column("args"),
+        );
+        let req_id = server.send_request(request)?;
+        let response = server.get_response::<SignatureHelp>(req_id)?;
+        assert_eq!(None, response.active_parameter);
+
+        // There's no signature to show outside of a call.
+        let request = signature_help_request(&mut server, foo_uri, 0, 0);
+        let req_id = server.send_request(request)?;
+        assert_eq!(None, server.get_response::<Option<SignatureHelp>>(req_id)?);
+        Ok(())
+    }
+
+    #[test]
+    fn document_symbols_outline() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let contents = dedent(
+            r#"
+            load("bar.star", "baz")
+
+            def foo():
+                pass
+
+            baz(name = "target")
+            "#,
+        )
+        .trim()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), contents)?;
+
+        let request = server.new_request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri: foo_uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let req_id = server.send_request(request)?;
+        let response = server.get_response::<DocumentSymbolResponse>(req_id)?;
+
+        let DocumentSymbolResponse::Nested(symbols) = response else {
+            panic!("Expected nested document symbols, got {:?}", response);
+        };
+        assert_eq!(
+            vec![
+                ("bar.star", SymbolKind::MODULE),
+                ("foo", SymbolKind::FUNCTION),
+                ("target", SymbolKind::OBJECT),
+            ],
+            symbols
+                .iter()
+                .map(|s| (s.name.as_str(), s.kind))
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn formatting_whole_document() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "x=1\ny = [1,2]\n".to_owned())?;
+
+        let formatting_request = |server: &mut TestServer| {
+            server.new_request::<Formatting>(DocumentFormattingParams {
+                text_document: TextDocumentIdentifier {
+                    uri: foo_uri.clone(),
+                },
+                options: Default::default(),
+                work_done_progress_params: Default::default(),
+            })
+        };
+
+        let request = formatting_request(&mut server);
+        let req_id = server.send_request(request)?;
+        let expected = vec![TextEdit::new(
+            Range::new(Position::new(0, 0), Position::new(2, 0)),
+            "x = 1\ny = [1, 2]\n".to_owned(),
+        )];
+        assert_eq!(
+            Some(expected),
+            server.get_response::<Option<Vec<TextEdit>>>(req_id)?
+        );
+
+        // Already formatted, so nothing to do.
+        server.change_file(foo_uri.clone(), "x = 1\n".to_owned())?;
+        let request = formatting_request(&mut server);
+        let req_id = server.send_request(request)?;
+        assert_eq!(
+            Some(Vec::new()),
+            server.get_response::<Option<Vec<TextEdit>>>(req_id)?
+        );
+
+        // The last valid parse is out of date, so refuse to format.
+        server.change_file(foo_uri.clone(), "x = (\n".to_owned())?;
+        let request = formatting_request(&mut server);
+        let req_id = server.send_request(request)?;
+        assert_eq!(None, server.get_response::<Option<Vec<TextEdit>>>(req_id)?);
+        Ok(())
+    }
+
+    #[test]
+    fn code_action_fixes_lint() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        // Opening a file with lints fails, so open it empty and then add the lint.
+        server.open_file(foo_uri.clone(), String::new())?;
+        server.change_file(
+            foo_uri.clone(),
+            "def f(x):\n    return type(x) == list\n".to_owned(),
+        )?;
+
+        let code_action_request = |server: &mut TestServer, range: Range| {
+            server.new_request::<CodeActionRequest>(CodeActionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: foo_uri.clone(),
+                },
+                range,
+                context: CodeActionContext::default(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+        };
+
+        let request = code_action_request(
+            &mut server,
+            Range::new(Position::new(1, 11), Position::new(1, 11)),
+        );
+        let req_id = server.send_request(request)?;
+        let actions = server
+            .get_response::<Option<Vec<CodeActionOrCommand>>>(req_id)?
+            .unwrap();
+        let [CodeActionOrCommand::CodeAction(action)] = actions.as_slice() else {
+            panic!("Expected a single code action, got {:?}", actions);
+        };
+        assert_eq!("Replace `list` with `type([])`", action.title);
+        let expected = WorkspaceEdit::new(HashMap::from([(
+            foo_uri.clone(),
+            vec![TextEdit::new(
+                Range::new(Position::new(1, 22), Position::new(1, 26)),
+                "type([])".to_owned(),
+            )],
+        )]));
+        assert_eq!(Some(&expected), action.edit.as_ref());
+
+        // Nothing to fix on the first line.
+        let request = code_action_request(
+            &mut server,
+            Range::new(Position::new(0, 0), Position::new(0, 3)),
+        );
+        let req_id = server.send_request(request)?;
+        assert_eq!(
+            Some(Vec::new()),
+            server.get_response::<Option<Vec<CodeActionOrCommand>>>(req_id)?
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,548 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Signature help for function calls, driven by the `DocFunction` of the function being called.
+
+use std::path::Path;
+
+use lsp_types::Documentation;
+use lsp_types::InitializeParams;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
+use lsp_types::ParameterInformation;
+use lsp_types::ParameterLabel;
+use lsp_types::SignatureHelp;
+use lsp_types::SignatureHelpParams;
+use lsp_types::SignatureInformation;
+use starlark::codemap::CodeMap;
+use starlark::codemap::Pos;
+use starlark::codemap::ResolvedSpan;
+use starlark::codemap::Span;
+use starlark::docs::DocFunction;
+use starlark::docs::DocMember;
+use starlark::docs::DocParam;
+use starlark::docs::DocString;
+use starlark::typing::Ty;
+use starlark_syntax::syntax::ast::ArgumentP;
+use starlark_syntax::syntax::ast::AstExpr;
+use starlark_syntax::syntax::ast::AstNoPayload;
+use starlark_syntax::syntax::ast::AstStmt;
+use starlark_syntax::syntax::ast::DefP;
+use starlark_syntax::syntax::ast::ExprP;
+use starlark_syntax::syntax::ast::StmtP;
+use starlark_syntax::syntax::module::AstModuleFields;
+use starlark_syntax::syntax::top_level_stmts::top_level_stmts;
+
+use crate::definition::Definition;
+use crate::definition::IdentifierDefinition;
+use crate::definition::LspModule;
+use crate::docs::get_doc_function_for_def;
+use crate::server::Backend;
+use crate::server::LspContext;
+use crate::server::LspUrl;
+
+/// The argument of a function call that the cursor is in (or about to type).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum ActiveArgument {
+    /// The n-th positional argument, counting from zero.
+    Positional(usize),
+    /// A named argument, e.g. `name = "foo"`.
+    Named(String),
+    /// A `*args` argument.
+    Args,
+    /// A `**kwargs` argument.
+    Kwargs,
+}
+
+/// A function call that encloses a location. See [`LspModule::find_call_at_location`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct CallAtLocation {
+    /// The expression being called, e.g. `foo` or `native.foo`.
+    pub(crate) function: Span,
+    /// The argument the location is in. `None` if it cannot be determined,
+    /// e.g. after a named argument, where the next argument could be anything.
+    pub(crate) active_argument: Option<ActiveArgument>,
+}
+
+impl LspModule {
+    /// Find the innermost function call whose arguments contain the given location.
+    ///
+    /// `line` and `col` are zero based.
+    pub(crate) fn find_call_at_location(&self, line: u32, col: u32) -> Option<CallAtLocation> {
+        let line_span = self.ast.codemap().line_span_opt(line as usize)?;
+        let pos = std::cmp::min(line_span.begin() + col, line_span.end());
+
+        fn walk_stmt<'a>(stmt: &'a AstStmt, pos: Pos, res: &mut Option<&'a AstExpr>) {
+            stmt.visit_expr(|x| walk_expr(x, pos, res));
+            stmt.visit_stmt(|x| walk_stmt(x, pos, res));
+        }
+
+        fn walk_expr<'a>(expr: &'a AstExpr, pos: Pos, res: &mut Option<&'a AstExpr>) {
+            if !expr.span.contains(pos) {
+                return;
+            }
+            if let ExprP::Call(function, _) = &expr.node {
+                // Only inside the parentheses, not on the function name or after the `)`.
+                if function.span.end() < pos && pos < expr.span.end() {
+                    *res = Some(expr);
+                }
+            }
+            // Calls nested in the arguments are visited afterwards, so the innermost wins.
+            expr.visit_expr(|x| walk_expr(x, pos, res));
+        }
+
+        let mut call = None;
+        walk_stmt(self.ast.statement(), pos, &mut call);
+        let (function, args) = match &call?.node {
+            ExprP::Call(function, args) => (function, args),
+            _ => unreachable!("only calls are recorded"),
+        };
+
+        let argument_kind = |arg: &ArgumentP<AstNoPayload>, positional| match arg {
+            ArgumentP::Positional(_) => Some(ActiveArgument::Positional(positional)),
+            ArgumentP::Named(name, _) => Some(ActiveArgument::Named(name.node.clone())),
+            ArgumentP::Args(_) => Some(ActiveArgument::Args),
+            ArgumentP::KwArgs(_) => Some(ActiveArgument::Kwargs),
+        };
+
+        let mut positional = 0;
+        let mut previous = None;
+        for arg in args {
+            if arg.span.begin() > pos {
+                break;
+            }
+            if pos <= arg.span.end() {
+                return Some(CallAtLocation {
+                    function: function.span,
+                    active_argument: argument_kind(&arg.node, positional),
+                });
+            }
+            previous = Some((arg, positional));
+            if let ArgumentP::Positional(_) = arg.node {
+                positional += 1;
+            }
+        }
+
+        let active_argument = match previous {
+            None => Some(ActiveArgument::Positional(0)),
+            Some((arg, previous_positional)) => {
+                let between = self
+                    .ast
+                    .codemap()
+                    .source_span(Span::new(arg.span.end(), pos));
+                if !between.contains(',') {
+                    // Still at the end of the previous argument, e.g. `foo(x |)`.
+                    argument_kind(&arg.node, previous_positional)
+                } else if args
+                    .iter()
+                    .take_while(|x| x.span.end() < pos)
+                    .all(|x| matches!(x.node, ArgumentP::Positional(_)))
+                {
+                    Some(ActiveArgument::Positional(positional))
+                } else {
+                    None
+                }
+            }
+        };
+        Some(CallAtLocation {
+            function: function.span,
+            active_argument,
+        })
+    }
+
+    /// Find a `def` statement anywhere in the module, whose name is at the given location.
+    fn find_def_at_name_span(&self, name_span: ResolvedSpan) -> Option<&DefP<AstNoPayload>> {
+        fn walk<'a>(
+            codemap: &CodeMap,
+            stmt: &'a AstStmt,
+            name_span: ResolvedSpan,
+        ) -> Option<&'a DefP<AstNoPayload>> {
+            if let StmtP::Def(def) = &stmt.node {
+                if codemap.resolve_span(def.name.span) == name_span {
+                    return Some(def);
+                }
+            }
+            let mut res = None;
+            stmt.visit_stmt(|x| {
+                if res.is_none() {
+                    res = walk(codemap, x, name_span);
+                }
+            });
+            res
+        }
+
+        walk(self.ast.codemap(), self.ast.statement(), name_span)
+    }
+
+    /// Find a top-level `def` statement with the given name.
+    fn find_top_level_def(&self, name: &str) -> Option<&DefP<AstNoPayload>> {
+        top_level_stmts(self.ast.statement())
+            .into_iter()
+            .find_map(|x| match &x.node {
+                StmtP::Def(def) if def.name.ident == name => Some(def),
+                _ => None,
+            })
+    }
+}
+
+/// Render a `DocString` as markdown, for use in the signature help.
+fn render_doc_string(docs: &DocString) -> Documentation {
+    let value = match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    };
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+/// Find the index, within the parameters that are not `*` or `/` markers, of the
+/// parameter that the given argument is passed to.
+fn active_parameter(params: &[DocParam], argument: &ActiveArgument) -> Option<u32> {
+    let mut positional = 0;
+    let mut named_params = params
+        .iter()
+        .filter(|p| !matches!(p, DocParam::NoArgs | DocParam::OnlyPosBefore))
+        .enumerate();
+    let index = match argument {
+        ActiveArgument::Positional(n) => {
+            for p in params {
+                match p {
+                    DocParam::Arg { .. } if positional == *n => break,
+                    DocParam::Arg { .. } => positional += 1,
+                    DocParam::Args { .. } => break,
+                    DocParam::OnlyPosBefore => {}
+                    DocParam::NoArgs | DocParam::Kwargs { .. } => return None,
+                }
+            }
+            named_params.nth(positional).and_then(|(i, p)| match p {
+                DocParam::Arg { .. } | DocParam::Args { .. } => Some(i),
+                _ => None,
+            })?
+        }
+        ActiveArgument::Named(name) => {
+            let mut kwargs = None;
+            let mut found = None;
+            for (i, p) in named_params {
+                match p {
+                    DocParam::Arg { name: n, .. } if n == name => {
+                        found = Some(i);
+                        break;
+                    }
+                    DocParam::Kwargs { .. } => kwargs = Some(i),
+                    _ => {}
+                }
+            }
+            found.or(kwargs)?
+        }
+        ActiveArgument::Args => {
+            named_params
+                .find(|(_, p)| matches!(p, DocParam::Args { .. }))?
+                .0
+        }
+        ActiveArgument::Kwargs => {
+            named_params
+                .find(|(_, p)| matches!(p, DocParam::Kwargs { .. }))?
+                .0
+        }
+    };
+    Some(index as u32)
+}
+
+/// Build the signature for a function called `name`, e.g. `foo(x, y = 1, *args) -> int`.
+pub(crate) fn signature_information(
+    name: &str,
+    function: &DocFunction,
+    active_argument: Option<&ActiveArgument>,
+) -> SignatureInformation {
+    let mut label = format!("{}(", name);
+    let mut parameters = Vec::new();
+    for (i, param) in function.params.iter().enumerate() {
+        if i != 0 {
+            label.push_str(", ");
+        }
+        // Offsets are in UTF-16 code units, per the LSP spec.
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(&param.render_as_code());
+        let end = label.encode_utf16().count() as u32;
+        let docs = match param {
+            DocParam::Arg { docs, .. }
+            | DocParam::Args { docs, .. }
+            | DocParam::Kwargs { docs, .. } => docs,
+            DocParam::NoArgs | DocParam::OnlyPosBefore => continue,
+        };
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: docs.as_ref().map(render_doc_string),
+        });
+    }
+    label.push(')');
+    if function.ret.typ != Ty::any() {
+        label.push_str(&format!(" -> {}", function.ret.typ));
+    }
+
+    SignatureInformation {
+        label,
+        documentation: function.docs.as_ref().map(render_doc_string),
+        parameters: Some(parameters),
+        active_parameter: active_argument.and_then(|x| active_parameter(&function.params, x)),
+    }
+}
+
+impl<T: LspContext> Backend<T> {
+    /// Offer the signature of the function being called at the current cursor,
+    /// highlighting the parameter that is being typed.
+    pub(crate) fn get_signature_help(
+        &self,
+        params: SignatureHelpParams,
+        initialize_params: &InitializeParams,
+    ) -> anyhow::Result<Option<SignatureHelp>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let line = params.text_document_position_params.position.line;
+        let character = params.text_document_position_params.position.character;
+        let workspace_root =
+            Self::get_workspace_root(initialize_params.workspace_folders.as_ref(), &uri);
+
+        let Some(document) = self.get_ast(&uri) else {
+            return Ok(None);
+        };
+        let Some(call) = document.find_call_at_location(line, character) else {
+            return Ok(None);
+        };
+        let Some(function) =
+            self.find_called_function(&document, &uri, call.function, workspace_root.as_deref())?
+        else {
+            return Ok(None);
+        };
+
+        let name = document.ast.codemap().source_span(call.function);
+        let signature = signature_information(name, &function, call.active_argument.as_ref());
+        Ok(Some(SignatureHelp {
+            active_parameter: signature.active_parameter,
+            signatures: vec![signature],
+            active_signature: Some(0),
+        }))
+    }
+
+    /// Find the documentation of the function named by the expression at `function`,
+    /// whether it is defined in this file, loaded from another one, or is a global.
+    fn find_called_function(
+        &self,
+        document: &LspModule,
+        uri: &LspUrl,
+        function: Span,
+        workspace_root: Option<&Path>,
+    ) -> anyhow::Result<Option<DocFunction>> {
+        let function = document.ast.codemap().resolve_span(function);
+        let identifier = match document
+            .find_definition_at_location(function.begin.line as u32, function.begin.column as u32)
+        {
+            Definition::Identifier(identifier) => identifier,
+            // Attributes of structs don't have known signatures.
+            Definition::Dotted(_) => return Ok(None),
+        };
+
+        Ok(match identifier {
+            IdentifierDefinition::Location { destination, .. } => document
+                .find_def_at_name_span(destination)
+                .map(|def| get_doc_function_for_def(def, document.ast.codemap())),
+            IdentifierDefinition::LoadedLocation { path, name, .. } => {
+                let load_uri = self.resolve_load_path(&path, uri, workspace_root)?;
+                self.get_ast_or_load_from_disk(&load_uri)?.and_then(|ast| {
+                    ast.find_top_level_def(&name)
+                        .map(|def| get_doc_function_for_def(def, ast.ast.codemap()))
+                })
+            }
+            IdentifierDefinition::Unresolved { name, .. } => {
+                match self.context.get_global_symbol_docs(uri, &name)? {
+                    Some(DocMember::Function(function)) => Some(function),
+                    Some(DocMember::Property(_)) => None,
+                    // Globals implemented in starlark may have a file to look in.
+                    None => match self.context.get_url_for_global_symbol(uri, &name)? {
+                        Some(global_uri) => {
+                            self.get_ast_or_load_from_disk(&global_uri)?
+                                .and_then(|ast| {
+                                    ast.find_top_level_def(&name)
+                                        .map(|def| get_doc_function_for_def(def, ast.ast.codemap()))
+                                })
+                        }
+                        None => None,
+                    },
+                }
+            }
+            IdentifierDefinition::LoadPath { .. }
+            | IdentifierDefinition::StringLiteral { .. }
+            | IdentifierDefinition::NotFound => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark::docs::DocReturn;
+    use starlark::docs::DocStringKind;
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::definition::helpers::FixtureWithRanges;
+
+    #[test]
+    fn finds_calls_at_location() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            <f>foo</f>(<a>1</a>, <b>x = bar(<c>2</c>)</b>, <d>*</d>args)
+            <g>foo</g>(1,<e> </e>)
+            foo(1<h> </h>)
+            foo(x = 1,<i> </i>)
+            foo()<j> </j>
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let parsed = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = parsed.module()?;
+        let call_at = |name: &str| {
+            module
+                .find_call_at_location(parsed.begin_line(name), parsed.begin_column(name))
+                .map(|call| {
+                    (
+                        module.ast.codemap().resolve_span(call.function),
+                        call.active_argument,
+                    )
+                })
+        };
+
+        let foo = parsed.resolved_span("f");
+        assert_eq!(
+            Some((foo, Some(ActiveArgument::Positional(0)))),
+            call_at("a")
+        );
+        assert_eq!(
+            Some((foo, Some(ActiveArgument::Named("x".to_owned())))),
+            call_at("b")
+        );
+        assert_eq!(
+            Some(ActiveArgument::Positional(0)),
+            call_at("c").and_then(|(_, arg)| arg)
+        );
+        assert_ne!(Some(foo), call_at("c").map(|(span, _)| span));
+        assert_eq!(Some((foo, Some(ActiveArgument::Args))), call_at("d"));
+        assert_eq!(
+            Some((
+                parsed.resolved_span("g"),
+                Some(ActiveArgument::Positional(1))
+            )),
+            call_at("e")
+        );
+        assert_eq!(
+            Some(ActiveArgument::Positional(0)),
+            call_at("h").and_then(|(_, arg)| arg)
+        );
+        assert_eq!(Some(None), call_at("i").map(|(_, arg)| arg));
+        assert_eq!(None, call_at("f"));
+        assert_eq!(None, call_at("j"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn renders_signatures() {
+        let function = DocFunction::from_docstring(
+            DocStringKind::Starlark,
+            vec![
+                DocParam::Arg {
+                    name: "x".to_owned(),
+                    docs: None,
+                    typ: Ty::int(),
+                    default_value: None,
+                },
+                DocParam::NoArgs,
+                DocParam::Arg {
+                    name: "y".to_owned(),
+                    docs: None,
+                    typ: Ty::any(),
+                    default_value: Some("1".to_owned()),
+                },
+                DocParam::Kwargs {
+                    name: "**kwargs".to_owned(),
+                    docs: None,
+                    typ: Ty::any(),
+                },
+            ],
+            Ty::string(),
+            Some("Does things.\n\nArgs:\n    y: The y value"),
+            None,
+        );
+
+        let signature = signature_information(
+            "foo",
+            &function,
+            Some(&ActiveArgument::Named("y".to_owned())),
+        );
+        assert_eq!("foo(x: int, *, y = 1, **kwargs) -> str", signature.label);
+        assert_eq!(
+            vec![
+                ParameterLabel::LabelOffsets([4, 10]),
+                ParameterLabel::LabelOffsets([15, 20]),
+                ParameterLabel::LabelOffsets([22, 30]),
+            ],
+            signature
+                .parameters
+                .iter()
+                .flatten()
+                .map(|p| p.label.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(1), signature.active_parameter);
+        assert_eq!(
+            Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "The y value".to_owned(),
+            })),
+            signature.parameters.unwrap()[1].documentation
+        );
+        assert_eq!(
+            Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "Does things.".to_owned(),
+            })),
+            signature.documentation
+        );
+
+        let active = |arg| active_parameter(&function.params, &arg);
+        assert_eq!(Some(0), active(ActiveArgument::Positional(0)));
+        // `y` is keyword only.
+        assert_eq!(None, active(ActiveArgument::Positional(1)));
+        assert_eq!(Some(2), active(ActiveArgument::Named("z".to_owned())));
+        assert_eq!(Some(2), active(ActiveArgument::Kwargs));
+        assert_eq!(None, active(ActiveArgument::Args));
+
+        let no_return = DocFunction {
+            ret: DocReturn::default(),
+            ..function
+        };
+        assert_eq!(
+            "foo(x: int, *, y = 1, **kwargs)",
+            signature_information("foo", &no_return, None).label
+        );
+    }
+}
@@ -282,6 +282,16 @@ impl LspContext for TestServerContext {
         }
     }
 
+    fn get_workspace_files(&self, _workspace_root: Option<&Path>) -> anyhow::Result<Vec<LspUrl>> {
+        Ok(self
+            .file_contents
+            .read()
+            .unwrap()
+            .keys()
+            .map(|path| LspUrl::File(path.clone()))
+            .collect())
+    }
+
     fn get_url_for_global_symbol(
         &self,
         _current_file: &LspUrl,
@@ -93,6 +93,15 @@ impl<T> SmallSet<T> {
         Self(SmallMap::with_capacity(n))
     }
 
+    /// Reserve capacity for at least `additional` more elements to be inserted.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize)
+    where
+        T: Eq,
+    {
+        self.0.reserve(additional);
+    }
+
     /// Current capacity of the set.
     #[inline]
     pub fn capacity(&self) -> usize {
@@ -244,6 +253,18 @@ impl<T> SmallSet<T> {
         self.0.remove(key).is_some()
     }
 
+    /// Remove the element from the set if it is present.
+    ///
+    /// Time complexity of this operation is *O(N)* where *N* is the number of entries in the set.
+    #[inline]
+    pub fn remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> bool
+    where
+        Q: ?Sized + Equivalent<T>,
+        T: Eq,
+    {
+        self.0.remove_hashed(key).is_some()
+    }
+
     /// Insert entry if it doesn't exist.
     ///
     /// Return the resulting entry in the map.
@@ -0,0 +1,968 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Pretty printing of Starlark source code, see [`AstModule::format`].
+//!
+//! The parser drops comments, so they are recovered from the lexer and
+//! reattached to the closest statement or container element when printing.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use dupe::Dupe;
+
+use crate::codemap::CodeMap;
+use crate::codemap::Pos;
+use crate::codemap::Span;
+use crate::lexer::Lexer;
+use crate::lexer::Token;
+use crate::syntax::ast::Argument;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AssignTarget;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstAssignTarget;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::ForP;
+use crate::syntax::ast::LoadArgP;
+use crate::syntax::ast::Parameter;
+use crate::syntax::ast::Stmt;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+/// Options for [`AstModule::format`].
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// Format as a build file (`BUCK`, `BUILD`, `TARGETS`): top-level calls
+    /// which define a target put one argument per line with `name` first,
+    /// and lists with more than one element in those calls are split too.
+    pub build_file: bool,
+}
+
+impl FormatOptions {
+    /// Options appropriate for a file with the given name or path.
+    pub fn for_filename(filename: &str) -> FormatOptions {
+        let stem = Path::new(filename)
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(|x| x.split('.').next())
+            .unwrap_or_default();
+        FormatOptions {
+            build_file: matches!(stem, "BUCK" | "BUILD" | "TARGETS"),
+        }
+    }
+}
+
+impl AstModule {
+    /// Pretty print the module in a canonical layout.
+    ///
+    /// Comments are preserved, redundant parentheses are removed,
+    /// `load` symbols are sorted, and containers (calls, lists, dicts and so on)
+    /// are printed one element per line if they were split over lines in the source.
+    /// String and number literals are kept as written, except single quoted
+    /// strings which are switched to double quotes where that does not need escaping.
+    pub fn format(&self, options: &FormatOptions) -> String {
+        let mut formatter = Formatter::new(&self.codemap, &self.dialect, options);
+        let mut last = formatter.block(&self.statement, 0);
+        formatter.own_line_comments(self.codemap.full_span().end(), 0, &mut last);
+        formatter.out
+    }
+}
+
+// Binding strength of expressions, following the grammar.
+// An expression needs parentheses if it binds less tightly than its position requires.
+const PREC_TEST: u8 = 0;
+const PREC_IF: u8 = 1;
+const PREC_OR: u8 = 2;
+const PREC_AND: u8 = 3;
+const PREC_NOT: u8 = 4;
+const PREC_COMPARE: u8 = 5;
+const PREC_BIT_OR: u8 = 6;
+const PREC_BIT_XOR: u8 = 7;
+const PREC_BIT_AND: u8 = 8;
+const PREC_SHIFT: u8 = 9;
+const PREC_ARITH: u8 = 10;
+const PREC_PRODUCT: u8 = 11;
+const PREC_UNARY: u8 = 12;
+const PREC_PRIMARY: u8 = 13;
+
+fn bin_op_prec(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => PREC_OR,
+        BinOp::And => PREC_AND,
+        BinOp::Equal
+        | BinOp::NotEqual
+        | BinOp::Less
+        | BinOp::Greater
+        | BinOp::LessOrEqual
+        | BinOp::GreaterOrEqual
+        | BinOp::In
+        | BinOp::NotIn => PREC_COMPARE,
+        BinOp::BitOr => PREC_BIT_OR,
+        BinOp::BitXor => PREC_BIT_XOR,
+        BinOp::BitAnd => PREC_BIT_AND,
+        BinOp::LeftShift | BinOp::RightShift => PREC_SHIFT,
+        BinOp::Add | BinOp::Subtract => PREC_ARITH,
+        BinOp::Multiply | BinOp::Percent | BinOp::Divide | BinOp::FloorDivide => PREC_PRODUCT,
+    }
+}
+
+fn indentation(indent: usize) -> String {
+    "    ".repeat(indent)
+}
+
+fn flatten<'b>(stmt: &'b AstStmt, res: &mut Vec<&'b AstStmt>) {
+    match &stmt.node {
+        Stmt::Statements(xs) => xs.iter().for_each(|x| flatten(x, res)),
+        _ => res.push(stmt),
+    }
+}
+
+fn is_name_argument(arg: &AstArgument) -> bool {
+    matches!(&arg.node, Argument::Named(name, _) if name.node == "name")
+}
+
+struct Comment {
+    /// The comment text, including the `#`.
+    text: String,
+    /// Whether the comment is the only thing on its line.
+    own_line: bool,
+}
+
+/// Something printed between the brackets of a container.
+enum Element<'b> {
+    Expr(&'b AstExpr),
+    /// An argument, and whether the call defines a build target.
+    Argument(&'b AstArgument, bool),
+    Parameter(&'b AstParameter),
+    DictEntry(&'b AstExpr, &'b AstExpr),
+    LoadModule(&'b AstString),
+    LoadArg(&'b LoadArgP<AstNoPayload>),
+}
+
+impl<'b> Element<'b> {
+    fn span(&self) -> Span {
+        match self {
+            Element::Expr(x) => x.span,
+            Element::Argument(x, _) => x.span,
+            Element::Parameter(x) => x.span,
+            Element::DictEntry(k, v) => k.span.merge(v.span),
+            Element::LoadModule(x) => x.span,
+            Element::LoadArg(x) => x.span(),
+        }
+    }
+}
+
+struct Container<'b> {
+    /// Position of the opening bracket.
+    open: Pos,
+    /// Position just after the closing bracket.
+    close: Pos,
+    /// The opening and closing bracket, e.g. `"()"`.
+    brackets: &'static str,
+    /// Elements, in source order.
+    elements: Vec<Element<'b>>,
+    /// The order in which to print `elements`.
+    order: Vec<usize>,
+    force_multiline: bool,
+    /// A tuple, which needs a trailing comma if it has a single element.
+    tuple: bool,
+}
+
+impl<'b> Container<'b> {
+    fn new(open: Pos, close: Pos, brackets: &'static str, elements: Vec<Element<'b>>) -> Self {
+        Container {
+            open,
+            close,
+            brackets,
+            order: (0..elements.len()).collect(),
+            elements,
+            force_multiline: false,
+            tuple: false,
+        }
+    }
+}
+
+struct Formatter<'a> {
+    codemap: &'a CodeMap,
+    options: &'a FormatOptions,
+    /// Comments which have not been printed yet, by position.
+    comments: BTreeMap<Pos, Comment>,
+    out: String,
+    /// Number of `def` statements we are inside.
+    def_depth: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(codemap: &'a CodeMap, dialect: &Dialect, options: &'a FormatOptions) -> Self {
+        let source = codemap.source();
+        let mut comments = BTreeMap::new();
+        let lexer = Lexer::new(source, dialect, codemap.dupe());
+        for (begin, token, end) in lexer.flatten() {
+            if let Token::Comment(_) = token {
+                let pos = Pos::new(begin as u32);
+                let line_start = codemap.line_span(codemap.find_line(pos)).begin();
+                comments.insert(
+                    pos,
+                    Comment {
+                        text: source[begin..end].trim_end().to_owned(),
+                        own_line: source[line_start.get() as usize..begin].trim().is_empty(),
+                    },
+                );
+            }
+        }
+        Formatter {
+            codemap,
+            options,
+            comments,
+            out: String::new(),
+            def_depth: 0,
+        }
+    }
+
+    fn source(&self, begin: Pos, end: Pos) -> &'a str {
+        self.codemap.source_span(Span::new(begin, end))
+    }
+
+    fn line(&self, pos: Pos) -> usize {
+        self.codemap.find_line(pos)
+    }
+
+    fn column(&self, pos: Pos) -> usize {
+        (pos.get() - self.codemap.line_span(self.line(pos)).begin().get()) as usize
+    }
+
+    /// Is there an empty line strictly between the lines containing `after` and `before`.
+    fn has_blank_line(&self, after: Pos, before: Pos) -> bool {
+        (self.line(after) + 1..self.line(before))
+            .any(|line| self.codemap.source_line(line).trim().is_empty())
+    }
+
+    /// Skip over whitespace and comments.
+    fn skip_to_code(&self, pos: Pos) -> Pos {
+        let bytes = self.codemap.source().as_bytes();
+        let mut i = pos.get() as usize;
+        while i < bytes.len() {
+            match bytes[i] {
+                b' ' | b'\t' | b'\r' | b'\n' | b'\\' => i += 1,
+                b'#' => {
+                    while i < bytes.len() && bytes[i] != b'\n' {
+                        i += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        Pos::new(i as u32)
+    }
+
+    fn char_at(&self, pos: Pos) -> Option<char> {
+        self.codemap.source()[pos.get() as usize..].chars().next()
+    }
+
+    /// Find the position just after the closing bracket following `pos`,
+    /// skipping any trailing comma.
+    fn find_close(&self, pos: Pos, close: char) -> Pos {
+        let mut pos = self.skip_to_code(pos);
+        if self.char_at(pos) == Some(',') {
+            pos = self.skip_to_code(pos + 1);
+        }
+        if self.char_at(pos) == Some(close) {
+            pos + 1
+        } else {
+            pos
+        }
+    }
+
+    /// Is the (tuple) expression at `span` surrounded by parentheses in the source.
+    fn is_parenthesized(&self, span: Span) -> bool {
+        self.char_at(self.skip_to_code(span.end())) == Some(')')
+    }
+
+    fn has_comments(&self, begin: Pos, end: Pos) -> bool {
+        begin < end && self.comments.range(begin..end).next().is_some()
+    }
+
+    fn take_comments(&mut self, begin: Pos, end: Pos) -> Vec<(Pos, Comment)> {
+        if begin >= end {
+            return Vec::new();
+        }
+        let keys: Vec<Pos> = self.comments.range(begin..end).map(|(k, _)| *k).collect();
+        keys.into_iter()
+            .map(|k| (k, self.comments.remove(&k).unwrap()))
+            .collect()
+    }
+
+    /// Take a comment at the end of the line containing `after`,
+    /// if there is nothing but separators between them.
+    fn take_trailing_comment(&mut self, after: Pos) -> Option<(Pos, Comment)> {
+        let (&pos, comment) = self.comments.range(after..).next()?;
+        if comment.own_line
+            || self.line(pos) != self.line(after)
+            || !self
+                .source(after, pos)
+                .chars()
+                .all(|c| c.is_whitespace() || matches!(c, ',' | ';' | ')'))
+        {
+            return None;
+        }
+        Some((pos, self.comments.remove(&pos).unwrap()))
+    }
+
+    fn write_line(&mut self, indent: usize, text: &str) {
+        self.out.push_str(&indentation(indent));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Write an empty line if the source had one between `last` and `next`.
+    fn blank_line(&mut self, last: Option<Pos>, next: Pos) {
+        if let Some(last) = last {
+            if self.has_blank_line(last, next) {
+                self.out.push('\n');
+            }
+        }
+    }
+
+    /// Write all remaining comments before `end` on their own lines.
+    fn own_line_comments(&mut self, end: Pos, indent: usize, last: &mut Option<Pos>) {
+        for (pos, comment) in self.take_comments(Pos::new(0), end) {
+            self.blank_line(*last, pos);
+            self.write_line(indent, &comment.text);
+            *last = Some(pos + comment.text.len() as u32);
+        }
+    }
+
+    /// Write the comments following a block which are indented at least as far as the block,
+    /// as they are more likely to be about the block than the code after it.
+    fn block_end_comments(&mut self, column: usize, indent: usize, last: &mut Option<Pos>) {
+        let Some(mut cursor) = *last else {
+            return;
+        };
+        while let Some((&pos, comment)) = self.comments.range(cursor..).next() {
+            if !comment.own_line
+                || self.column(pos) < column
+                || !self.source(cursor, pos).trim().is_empty()
+            {
+                break;
+            }
+            let comment = self.comments.remove(&pos).unwrap();
+            self.blank_line(Some(cursor), pos);
+            self.write_line(indent, &comment.text);
+            cursor = pos + comment.text.len() as u32;
+        }
+        *last = Some(cursor);
+    }
+
+    /// Write a sequence of statements, returning the end of the last thing written.
+    fn block(&mut self, body: &AstStmt, indent: usize) -> Option<Pos> {
+        let mut stmts = Vec::new();
+        flatten(body, &mut stmts);
+        let mut last = None;
+        for stmt in &stmts {
+            self.own_line_comments(stmt.span.begin(), indent, &mut last);
+            self.blank_line(last, stmt.span.begin());
+            last = Some(self.stmt(stmt, indent));
+        }
+        if let Some(first) = stmts.first() {
+            self.block_end_comments(self.column(first.span.begin()), indent, &mut last);
+        }
+        last
+    }
+
+    /// Write a statement with a header line, e.g. `def` or `if`.
+    fn compound(
+        &mut self,
+        header_begin: Pos,
+        header: String,
+        body: &AstStmt,
+        indent: usize,
+    ) -> Pos {
+        // Comments after code on the header lines, most likely after the `:`.
+        // Own line comments are left for the body.
+        let keys: Vec<Pos> = self
+            .comments
+            .range(header_begin..body.span.begin())
+            .filter(|(_, c)| !c.own_line)
+            .map(|(k, _)| *k)
+            .collect();
+        let mut comments: Vec<Comment> = keys
+            .into_iter()
+            .map(|k| self.comments.remove(&k).unwrap())
+            .collect();
+        let trailing = comments.pop();
+        for comment in comments {
+            self.write_line(indent, &comment.text);
+        }
+        match trailing {
+            Some(c) => self.write_line(indent, &format!("{}  {}", header, c.text)),
+            None => self.write_line(indent, &header),
+        }
+        self.block(body, indent + 1).unwrap_or(body.span.end())
+    }
+
+    fn stmt(&mut self, stmt: &AstStmt, indent: usize) -> Pos {
+        match &stmt.node {
+            Stmt::Def(def) => {
+                let open = self.skip_to_code(def.name.span.end());
+                let close =
+                    self.find_close(def.params.last().map_or(open + 1, |p| p.span.end()), ')');
+                let params = self.container(
+                    Container::new(
+                        open,
+                        close,
+                        "()",
+                        def.params.iter().map(Element::Parameter).collect(),
+                    ),
+                    indent,
+                );
+                let return_type = match &def.return_type {
+                    Some(t) => format!(" -> {}", self.expr(&t.node.expr, indent, PREC_TEST)),
+                    None => String::new(),
+                };
+                let header = format!("def {}{}{}:", def.name.node.ident, params, return_type);
+                self.def_depth += 1;
+                let last = self.compound(stmt.span.begin(), header, &def.body, indent);
+                self.def_depth -= 1;
+                last
+            }
+            Stmt::If(..) | Stmt::IfElse(..) => self.if_stmt("if", stmt.span.begin(), stmt, indent),
+            Stmt::For(ForP { var, over, body }) => {
+                let header = format!(
+                    "for {} in {}:",
+                    self.target(var, indent, true),
+                    self.expr(over, indent, PREC_TEST)
+                );
+                self.compound(stmt.span.begin(), header, body, indent)
+            }
+            _ => {
+                let text = self.simple_stmt(stmt, indent);
+                // Comments inside the statement we could not attach anywhere else.
+                let mut last = None;
+                self.own_line_comments(stmt.span.end(), indent, &mut last);
+                match self.take_trailing_comment(stmt.span.end()) {
+                    Some((pos, c)) => {
+                        self.write_line(indent, &format!("{}  {}", text, c.text));
+                        pos + c.text.len() as u32
+                    }
+                    None => {
+                        self.write_line(indent, &text);
+                        stmt.span.end()
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write an `if` or `elif` statement, along with any `elif` or `else` branches.
+    fn if_stmt(&mut self, keyword: &str, header_begin: Pos, stmt: &AstStmt, indent: usize) -> Pos {
+        let (cond, then_branch, else_branch) = match &stmt.node {
+            Stmt::If(cond, then_branch) => (cond, &**then_branch, None),
+            Stmt::IfElse(cond, branches) => (cond, &branches.0, Some(&branches.1)),
+            _ => unreachable!("not an if statement"),
+        };
+        let header = format!("{} {}:", keyword, self.expr(cond, indent, PREC_TEST));
+        let last = self.compound(header_begin, header, then_branch, indent);
+        let Some(else_branch) = else_branch else {
+            return last;
+        };
+        let keyword_pos = self.skip_to_code(last);
+        let mut last = Some(last);
+        self.own_line_comments(keyword_pos, indent, &mut last);
+        let is_elif = self.codemap.source()[keyword_pos.get() as usize..].starts_with("elif");
+        match &else_branch.node {
+            Stmt::If(..) | Stmt::IfElse(..) if is_elif => {
+                self.if_stmt("elif", keyword_pos, else_branch, indent)
+            }
+            _ => self.compound(keyword_pos, "else:".to_owned(), else_branch, indent),
+        }
+    }
+
+    fn simple_stmt(&mut self, stmt: &AstStmt, indent: usize) -> String {
+        match &stmt.node {
+            Stmt::Break => "break".to_owned(),
+            Stmt::Continue => "continue".to_owned(),
+            Stmt::Pass => "pass".to_owned(),
+            Stmt::Return(None) => "return".to_owned(),
+            Stmt::Return(Some(e)) => format!("return {}", self.expr_list(e, indent)),
+            Stmt::Expression(e) => match &e.node {
+                Expr::Call(f, args)
+                    if self.options.build_file
+                        && self.def_depth == 0
+                        && args.iter().any(is_name_argument) =>
+                {
+                    self.call(e.span, f, args, indent, true)
+                }
+                _ => self.expr(e, indent, PREC_TEST),
+            },
+            Stmt::Assign(AssignP { lhs, ty, rhs }) => {
+                let lhs = self.target(lhs, indent, true);
+                let rhs = self.expr_list(rhs, indent);
+                match ty {
+                    Some(ty) => format!(
+                        "{}: {} = {}",
+                        lhs,
+                        self.expr(&ty.node.expr, indent, PREC_TEST),
+                        rhs
+                    ),
+                    None => format!("{} = {}", lhs, rhs),
+                }
+            }
+            Stmt::AssignModify(lhs, op, rhs) => format!(
+                "{}{}{}",
+                self.target(lhs, indent, false),
+                op,
+                self.expr_list(rhs, indent)
+            ),
+            Stmt::Load(load) => {
+                let open = self.skip_to_code(stmt.span.begin() + "load".len() as u32);
+                let mut elements = vec![Element::LoadModule(&load.module)];
+                elements.extend(load.args.iter().map(Element::LoadArg));
+                let mut container = Container::new(open, stmt.span.end(), "()", elements);
+                // Keep the module first, sort the symbols by the name they are bound to.
+                container.order[1..].sort_by_key(|&i| &load.args[i - 1].local.node.ident);
+                format!("load{}", self.container(container, indent))
+            }
+            Stmt::Statements(_) | Stmt::If(..) | Stmt::IfElse(..) | Stmt::For(_) | Stmt::Def(_) => {
+                unreachable!("not a simple statement")
+            }
+        }
+    }
+
+    /// Format an expression where a tuple does not need parentheses,
+    /// e.g. the right hand side of an assignment.
+    /// We keep the parentheses if the source had them.
+    fn expr_list(&mut self, e: &AstExpr, indent: usize) -> String {
+        match &e.node {
+            Expr::Tuple(xs) if !xs.is_empty() && !self.is_parenthesized(e.span) => {
+                let xs: Vec<String> = xs.iter().map(|x| self.expr(x, indent, PREC_TEST)).collect();
+                if xs.len() == 1 {
+                    format!("{},", xs[0])
+                } else {
+                    xs.join(", ")
+                }
+            }
+            _ => self.expr(e, indent, PREC_TEST),
+        }
+    }
+
+    /// Format an assignment target. At the `top` of an assignment or `for`,
+    /// tuples are kept without parentheses if the source had none.
+    fn target(&mut self, t: &AstAssignTarget, indent: usize, top: bool) -> String {
+        match &t.node {
+            AssignTarget::Tuple(xs) => {
+                let parts: Vec<String> = xs.iter().map(|x| self.target(x, indent, false)).collect();
+                let mut inner = parts.join(", ");
+                // Lists are also represented as tuples, but their span includes the bracket.
+                let is_list = match xs.first() {
+                    Some(x) => x.span.begin() != t.span.begin(),
+                    None => self.char_at(t.span.begin()) == Some('['),
+                };
+                if is_list {
+                    return format!("[{}]", inner);
+                }
+                if parts.len() == 1 {
+                    inner.push(',');
+                }
+                if top && !xs.is_empty() && !self.is_parenthesized(t.span) {
+                    inner
+                } else {
+                    format!("({})", inner)
+                }
+            }
+            AssignTarget::Index(x) => format!(
+                "{}[{}]",
+                self.expr(&x.0, indent, PREC_PRIMARY),
+                self.expr(&x.1, indent, PREC_TEST)
+            ),
+            AssignTarget::Dot(x, name) => {
+                format!("{}.{}", self.expr(x, indent, PREC_PRIMARY), name.node)
+            }
+            AssignTarget::Identifier(x) => x.node.ident.clone(),
+        }
+    }
+
+    fn string(&self, span: Span) -> String {
+        let s = self.codemap.source_span(span);
+        match s.strip_prefix('\'').and_then(|x| x.strip_suffix('\'')) {
+            Some(inner) if !s.starts_with("'''") && !inner.contains(['"', '\\']) => {
+                format!("\"{}\"", inner)
+            }
+            _ => s.to_owned(),
+        }
+    }
+
+    fn parameter(&mut self, p: &AstParameter, indent: usize) -> String {
+        let (prefix, name, ty, default) = match &p.node {
+            Parameter::Normal(name, ty) => ("", name, ty, None),
+            Parameter::WithDefaultValue(name, ty, default) => ("", name, ty, Some(default)),
+            Parameter::NoArgs => return "*".to_owned(),
+            Parameter::Args(name, ty) => ("*", name, ty, None),
+            Parameter::KwArgs(name, ty) => ("**", name, ty, None),
+        };
+        let mut res = format!("{}{}", prefix, name.node.ident);
+        if let Some(ty) = ty {
+            res.push_str(": ");
+            res.push_str(&self.expr(&ty.node.expr, indent, PREC_TEST));
+        }
+        if let Some(default) = default {
+            res.push_str(" = ");
+            res.push_str(&self.expr(default, indent, PREC_TEST));
+        }
+        res
+    }
+
+    fn element(&mut self, element: &Element, indent: usize) -> String {
+        match element {
+            Element::Expr(x) => self.expr(x, indent, PREC_TEST),
+            Element::Argument(arg, target) => match &arg.node {
+                Argument::Positional(x) => self.expr(x, indent, PREC_TEST),
+                Argument::Named(name, x) => {
+                    let value = match &x.node {
+                        Expr::List(xs) if *target && xs.len() > 1 => {
+                            self.list(x.span, xs, true, indent)
+                        }
+                        _ => self.expr(x, indent, PREC_TEST),
+                    };
+                    format!("{} = {}", name.node, value)
+                }
+                Argument::Args(x) => format!("*{}", self.expr(x, indent, PREC_TEST)),
+                Argument::KwArgs(x) => format!("**{}", self.expr(x, indent, PREC_TEST)),
+            },
+            Element::Parameter(p) => self.parameter(p, indent),
+            Element::DictEntry(k, v) => format!(
+                "{}: {}",
+                self.expr(k, indent, PREC_TEST),
+                self.expr(v, indent, PREC_TEST)
+            ),
+            Element::LoadModule(x) => self.string(x.span),
+            Element::LoadArg(x) => {
+                let their = self.string(x.their.span);
+                if x.local.node.ident == x.their.node {
+                    their
+                } else {
+                    format!("{} = {}", x.local.node.ident, their)
+                }
+            }
+        }
+    }
+
+    /// Format the elements between brackets, either all on one line,
+    /// or one per line with a trailing comma.
+    fn container(&mut self, c: Container, indent: usize) -> String {
+        let (open_bracket, close_bracket) = c.brackets.split_at(1);
+        let inner_begin = c.open + 1;
+        let multiline = c.force_multiline
+            || self.has_comments(inner_begin, c.close)
+            || c.elements
+                .first()
+                .map_or(false, |x| self.line(c.open) != self.line(x.span().begin()));
+
+        let mut res = open_bracket.to_owned();
+        if !multiline {
+            let parts: Vec<String> = c
+                .order
+                .iter()
+                .map(|&i| self.element(&c.elements[i], indent))
+                .collect();
+            res.push_str(&parts.join(", "));
+            if c.tuple && parts.len() == 1 {
+                res.push(',');
+            }
+            res.push_str(close_bracket);
+            return res;
+        }
+
+        // Attach comments to elements in source order, before they get reordered.
+        let mut leading = Vec::with_capacity(c.elements.len());
+        let mut trailing = Vec::with_capacity(c.elements.len());
+        let mut from = inner_begin;
+        for element in &c.elements {
+            let span = element.span();
+            leading.push((from, self.take_comments(from, span.begin())));
+            trailing.push(self.take_trailing_comment(span.end()));
+            from = span.end();
+        }
+
+        let inner_indent = indentation(indent + 1);
+        for (n, &i) in c.order.iter().enumerate() {
+            let (from, comments) = &leading[i];
+            let first = comments
+                .first()
+                .map_or(c.elements[i].span().begin(), |(pos, _)| *pos);
+            if n != 0 && self.has_blank_line(*from, first) {
+                res.push('\n');
+            }
+            for (_, comment) in comments {
+                res.push('\n');
+                res.push_str(&inner_indent);
+                res.push_str(&comment.text);
+            }
+            res.push('\n');
+            res.push_str(&inner_indent);
+            res.push_str(&self.element(&c.elements[i], indent + 1));
+            res.push(',');
+            if let Some((_, comment)) = &trailing[i] {
+                res.push_str("  ");
+                res.push_str(&comment.text);
+            }
+        }
+        // Comments after the last element.
+        for (_, comment) in self.take_comments(inner_begin, c.close) {
+            res.push('\n');
+            res.push_str(&inner_indent);
+            res.push_str(&comment.text);
+        }
+        res.push('\n');
+        res.push_str(&indentation(indent));
+        res.push_str(close_bracket);
+        res
+    }
+
+    fn list(&mut self, span: Span, xs: &[AstExpr], force_multiline: bool, indent: usize) -> String {
+        let mut container = Container::new(
+            span.begin(),
+            span.end(),
+            "[]",
+            xs.iter().map(Element::Expr).collect(),
+        );
+        container.force_multiline = force_multiline;
+        self.container(container, indent)
+    }
+
+    /// Format a call. For a call defining a build target, put each argument on its own line,
+    /// with `name` first.
+    fn call(
+        &mut self,
+        span: Span,
+        f: &AstExpr,
+        args: &[AstArgument],
+        indent: usize,
+        target: bool,
+    ) -> String {
+        let function = self.expr(f, indent, PREC_PRIMARY);
+        let open = match self.codemap.source()[f.span.end().get() as usize..].find('(') {
+            Some(i) => f.span.end() + i as u32,
+            None => f.span.end(),
+        };
+        let mut container = Container::new(
+            open,
+            span.end(),
+            "()",
+            args.iter().map(|x| Element::Argument(x, target)).collect(),
+        );
+        if target {
+            container.force_multiline = true;
+            // Named arguments all come before `*args` and `**kwargs`,
+            // so moving `name` to the first named argument keeps the call valid.
+            let first_named = args
+                .iter()
+                .position(|x| matches!(x.node, Argument::Named(..)));
+            if let (Some(first_named), Some(name)) =
+                (first_named, args.iter().position(is_name_argument))
+            {
+                let name = container.order.remove(name);
+                container.order.insert(first_named, name);
+            }
+        }
+        format!("{}{}", function, self.container(container, indent))
+    }
+
+    fn clauses(&mut self, for_: &ForClause, clauses: &[Clause], indent: usize) -> String {
+        let mut res = self.for_clause(for_, indent);
+        for clause in clauses {
+            match clause {
+                Clause::For(x) => res.push_str(&self.for_clause(x, indent)),
+                Clause::If(x) => {
+                    res.push_str(" if ");
+                    res.push_str(&self.expr(x, indent, PREC_OR));
+                }
+            }
+        }
+        res
+    }
+
+    fn for_clause(&mut self, for_: &ForClause, indent: usize) -> String {
+        format!(
+            " for {} in {}",
+            self.target(&for_.var, indent, true),
+            self.expr(&for_.over, indent, PREC_OR)
+        )
+    }
+
+    /// Format an expression, adding parentheses if it binds less tightly than `min_prec`.
+    fn expr(&mut self, e: &AstExpr, indent: usize, min_prec: u8) -> String {
+        let (prec, res) = match &e.node {
+            Expr::Tuple(xs) if xs.is_empty() => (PREC_PRIMARY, "()".to_owned()),
+            Expr::Tuple(xs) => {
+                let first = xs[0].span.begin();
+                let before = self.codemap.source()[..first.get() as usize].trim_end();
+                let open = if before.ends_with('(') {
+                    Pos::new(before.len() as u32 - 1)
+                } else {
+                    first
+                };
+                let mut container = Container::new(
+                    open,
+                    self.find_close(e.span.end(), ')'),
+                    "()",
+                    xs.iter().map(Element::Expr).collect(),
+                );
+                container.tuple = true;
+                (PREC_PRIMARY, self.container(container, indent))
+            }
+            Expr::Dot(x, name) => {
+                // `1.x` would lex as a float.
+                let min = match &x.node {
+                    Expr::Literal(AstLiteral::Int(_)) => PREC_PRIMARY + 1,
+                    _ => PREC_PRIMARY,
+                };
+                (
+                    PREC_PRIMARY,
+                    format!("{}.{}", self.expr(x, indent, min), name.node),
+                )
+            }
+            Expr::Call(f, args) => (PREC_PRIMARY, self.call(e.span, f, args, indent, false)),
+            Expr::Index(x) => (
+                PREC_PRIMARY,
+                format!(
+                    "{}[{}]",
+                    self.expr(&x.0, indent, PREC_PRIMARY),
+                    self.expr(&x.1, indent, PREC_TEST)
+                ),
+            ),
+            Expr::Index2(x) => (
+                PREC_PRIMARY,
+                format!(
+                    "{}[{}, {}]",
+                    self.expr(&x.0, indent, PREC_PRIMARY),
+                    self.expr(&x.1, indent, PREC_TEST),
+                    self.expr(&x.2, indent, PREC_TEST)
+                ),
+            ),
+            Expr::Slice(x, start, stop, step) => {
+                let mut res = format!("{}[", self.expr(x, indent, PREC_PRIMARY));
+                if let Some(start) = start {
+                    res.push_str(&self.expr(start, indent, PREC_TEST));
+                }
+                res.push(':');
+                if let Some(stop) = stop {
+                    res.push_str(&self.expr(stop, indent, PREC_TEST));
+                }
+                if let Some(step) = step {
+                    res.push(':');
+                    res.push_str(&self.expr(step, indent, PREC_TEST));
+                }
+                res.push(']');
+                (PREC_PRIMARY, res)
+            }
+            Expr::Identifier(x) => (PREC_PRIMARY, x.node.ident.clone()),
+            Expr::Lambda(lambda) => {
+                let params: Vec<String> = lambda
+                    .params
+                    .iter()
+                    .map(|p| self.parameter(p, indent))
+                    .collect();
+                let body = self.expr(&lambda.body, indent, PREC_TEST);
+                let res = if params.is_empty() {
+                    format!("lambda: {}", body)
+                } else {
+                    format!("lambda {}: {}", params.join(", "), body)
+                };
+                (PREC_TEST, res)
+            }
+            Expr::Literal(AstLiteral::String(x)) => (PREC_PRIMARY, self.string(x.span)),
+            Expr::Literal(AstLiteral::Ellipsis) => (PREC_PRIMARY, "...".to_owned()),
+            Expr::Literal(AstLiteral::Int(_) | AstLiteral::Float(_)) | Expr::FString(_) => {
+                (PREC_PRIMARY, self.codemap.source_span(e.span).to_owned())
+            }
+            Expr::Not(x) => (PREC_NOT, format!("not {}", self.expr(x, indent, PREC_NOT))),
+            Expr::Minus(x) => (PREC_UNARY, format!("-{}", self.expr(x, indent, PREC_UNARY))),
+            Expr::Plus(x) => (PREC_UNARY, format!("+{}", self.expr(x, indent, PREC_UNARY))),
+            Expr::BitNot(x) => (PREC_UNARY, format!("~{}", self.expr(x, indent, PREC_UNARY))),
+            Expr::Op(lhs, op, rhs) => {
+                let prec = bin_op_prec(*op);
+                // Comparisons do not chain, so need parentheses on both sides.
+                let lhs_prec = if prec == PREC_COMPARE { prec + 1 } else { prec };
+                (
+                    prec,
+                    format!(
+                        "{}{}{}",
+                        self.expr(lhs, indent, lhs_prec),
+                        op,
+                        self.expr(rhs, indent, prec + 1)
+                    ),
+                )
+            }
+            Expr::If(x) => {
+                let (cond, then_expr, else_expr) = &**x;
+                (
+                    PREC_IF,
+                    format!(
+                        "{} if {} else {}",
+                        self.expr(then_expr, indent, PREC_OR),
+                        self.expr(cond, indent, PREC_OR),
+                        self.expr(else_expr, indent, PREC_TEST)
+                    ),
+                )
+            }
+            Expr::List(xs) => (PREC_PRIMARY, self.list(e.span, xs, false, indent)),
+            Expr::Dict(xs) => (
+                PREC_PRIMARY,
+                self.container(
+                    Container::new(
+                        e.span.begin(),
+                        e.span.end(),
+                        "{}",
+                        xs.iter().map(|(k, v)| Element::DictEntry(k, v)).collect(),
+                    ),
+                    indent,
+                ),
+            ),
+            Expr::ListComprehension(x, for_, clauses) => (
+                PREC_PRIMARY,
+                format!(
+                    "[{}{}]",
+                    self.expr(x, indent, PREC_TEST),
+                    self.clauses(for_, clauses, indent)
+                ),
+            ),
+            Expr::DictComprehension(x, for_, clauses) => (
+                PREC_PRIMARY,
+                format!(
+                    "{{{}: {}{}}}",
+                    self.expr(&x.0, indent, PREC_TEST),
+                    self.expr(&x.1, indent, PREC_TEST),
+                    self.clauses(for_, clauses, indent)
+                ),
+            ),
+        };
+        if prec < min_prec {
+            format!("({})", res)
+        } else {
+            res
+        }
+    }
+}
@@ -0,0 +1,333 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::syntax::format::FormatOptions;
+use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+
+fn format_with(filename: &str, program: &str) -> String {
+    let options = FormatOptions::for_filename(filename);
+    let formatted = AstModule::parse(filename, program.to_owned(), &Dialect::Extended)
+        .unwrap()
+        .format(&options);
+    // Formatting must produce valid code, and be stable.
+    let again = AstModule::parse(filename, formatted.clone(), &Dialect::Extended)
+        .unwrap()
+        .format(&options);
+    assert_eq!(formatted, again, "formatting is not idempotent");
+    formatted
+}
+
+fn format(program: &str) -> String {
+    format_with("test.bzl", program)
+}
+
+#[test]
+fn test_for_filename() {
+    assert!(FormatOptions::for_filename("foo/BUCK").build_file);
+    assert!(FormatOptions::for_filename("BUCK.v2").build_file);
+    assert!(FormatOptions::for_filename("TARGETS").build_file);
+    assert!(!FormatOptions::for_filename("foo/defs.bzl").build_file);
+    assert!(!FormatOptions::for_filename("BUCKET").build_file);
+}
+
+#[test]
+fn test_empty() {
+    assert_eq!(format(""), "");
+    assert_eq!(format("\n\n"), "");
+    assert_eq!(format("# Just a comment\n"), "# Just a comment\n");
+}
+
+#[test]
+fn test_spacing() {
+    assert_eq!(format("x=1+2*3"), "x = 1 + 2 * 3\n");
+    assert_eq!(format("f(a,b=1,*c,**d)"), "f(a, b = 1, *c, **d)\n");
+    assert_eq!(format("x={'a':[1,2],}"), "x = {\"a\": [1, 2]}\n");
+    assert_eq!(
+        format("x=y[1:2] + y[::2] + y[a, b]"),
+        "x = y[1:2] + y[::2] + y[a, b]\n"
+    );
+    assert_eq!(format("x += -y"), "x += -y\n");
+    assert_eq!(format("x: int=1"), "x: int = 1\n");
+    assert_eq!(
+        format("x = [a for a in b if a]"),
+        "x = [a for a in b if a]\n"
+    );
+    assert_eq!(
+        format("x = {k:v for k, v in b}"),
+        "x = {k: v for k, v in b}\n"
+    );
+    assert_eq!(format("x = lambda a,b=1: a"), "x = lambda a, b = 1: a\n");
+    assert_eq!(format("a; b"), "a\nb\n");
+}
+
+#[test]
+fn test_parentheses() {
+    assert_eq!(format("x = (a)"), "x = a\n");
+    assert_eq!(format("x = (a - b) - c"), "x = a - b - c\n");
+    assert_eq!(format("x = a - (b - c)"), "x = a - (b - c)\n");
+    assert_eq!(format("x = (a + b) * c"), "x = (a + b) * c\n");
+    assert_eq!(format("x = not (a and b)"), "x = not (a and b)\n");
+    assert_eq!(format("x = (a == b) == c"), "x = (a == b) == c\n");
+    assert_eq!(format("x = -(a + b)"), "x = -(a + b)\n");
+    assert_eq!(
+        format("x = (a if b else c) if d else e"),
+        "x = (a if b else c) if d else e\n"
+    );
+    assert_eq!(format("x = (a + b).c"), "x = (a + b).c\n");
+    assert_eq!(format("x = (1).bit_length()"), "x = (1).bit_length()\n");
+    assert_eq!(format("f((a, b))"), "f((a, b))\n");
+    assert_eq!(format("x = y[(a, b)]"), "x = y[(a, b)]\n");
+}
+
+#[test]
+fn test_tuples() {
+    assert_eq!(format("x = 1, 2"), "x = 1, 2\n");
+    assert_eq!(format("x = (1, 2)"), "x = (1, 2)\n");
+    assert_eq!(format("x = 1,"), "x = 1,\n");
+    assert_eq!(format("x = ()"), "x = ()\n");
+    assert_eq!(format("a, b = b, a"), "a, b = b, a\n");
+    assert_eq!(format("(a, b) = x"), "(a, b) = x\n");
+    assert_eq!(format("[a, b] = x"), "[a, b] = x\n");
+    assert_eq!(
+        format("for a, b in x:\n  pass"),
+        "for a, b in x:\n    pass\n"
+    );
+    assert_eq!(format("f(x, (1,))"), "f(x, (1,))\n");
+    assert_eq!(
+        format("def f():\n  return a, b"),
+        "def f():\n    return a, b\n"
+    );
+}
+
+#[test]
+fn test_strings() {
+    assert_eq!(format("x = 'a'"), "x = \"a\"\n");
+    assert_eq!(format("x = 'a\"b'"), "x = 'a\"b'\n");
+    assert_eq!(format("x = 'a\\n'"), "x = 'a\\n'\n");
+    assert_eq!(format("x = r'a'"), "x = r'a'\n");
+    assert_eq!(format("x = '''a'''"), "x = '''a'''\n");
+    assert_eq!(format("x = 0x10 + 1.50"), "x = 0x10 + 1.50\n");
+}
+
+#[test]
+fn test_statements() {
+    let program = r#"
+def f(a,b:int=1,*args,**kwargs)->str:
+  """Docstring."""
+  if a: return 1
+  elif b:
+        return 2
+  else:
+    for x in args:
+      if x:
+          continue
+      break
+  return 3
+"#;
+    let expected = r#"def f(a, b: int = 1, *args, **kwargs) -> str:
+    """Docstring."""
+    if a:
+        return 1
+    elif b:
+        return 2
+    else:
+        for x in args:
+            if x:
+                continue
+            break
+    return 3
+"#;
+    assert_eq!(format(program), expected);
+}
+
+#[test]
+fn test_nested_else_if() {
+    let program = "if a:\n  pass\nelse:\n  if b:\n    pass\n";
+    assert_eq!(
+        format(program),
+        "if a:\n    pass\nelse:\n    if b:\n        pass\n"
+    );
+}
+
+#[test]
+fn test_blank_lines() {
+    let program = "x = 1\n\n\n\ny = 2\nz = 3\n\n\ndef f():\n    a = 1\n\n    b = 2\n";
+    let expected = "x = 1\n\ny = 2\nz = 3\n\ndef f():\n    a = 1\n\n    b = 2\n";
+    assert_eq!(format(program), expected);
+}
+
+#[test]
+fn test_load() {
+    assert_eq!(
+        format("load(':a.bzl', 'z', b = 'c', 'a')"),
+        "load(\":a.bzl\", \"a\", b = \"c\", \"z\")\n"
+    );
+    assert_eq!(
+        format("load(\n  ':a.bzl',\n  'z',\n  'a',\n)"),
+        "load(\n    \":a.bzl\",\n    \"a\",\n    \"z\",\n)\n"
+    );
+}
+
+#[test]
+fn test_multiline_containers() {
+    let program = r#"
+x = [1,
+  2]
+y = [
+  1, 2]
+f(a, [
+  1,
+  2,
+], b = {
+  "k": "v"})
+def g(
+    a, b):
+  pass
+"#;
+    let expected = r#"x = [1, 2]
+y = [
+    1,
+    2,
+]
+f(a, [
+    1,
+    2,
+], b = {
+    "k": "v",
+})
+def g(
+    a,
+    b,
+):
+    pass
+"#;
+    assert_eq!(format(program), expected);
+}
+
+#[test]
+fn test_comments() {
+    let program = r#"
+# Header.
+
+# About x.
+x = 1  # Trailing.
+
+def f(): # On the def.
+    # Leading.
+    y = [
+        # Before a.
+        a, # After a.
+
+        b,
+        # At the end.
+    ]
+    return y
+    # Still in f.
+
+# After f.
+z = f(a, # After a.
+      b)
+"#;
+    let expected = r#"# Header.
+
+# About x.
+x = 1  # Trailing.
+
+def f():  # On the def.
+    # Leading.
+    y = [
+        # Before a.
+        a,  # After a.
+
+        b,
+        # At the end.
+    ]
+    return y
+    # Still in f.
+
+# After f.
+z = f(
+    a,  # After a.
+    b,
+)
+"#;
+    assert_eq!(format(program), expected);
+}
+
+#[test]
+fn test_comments_in_if() {
+    let program = r#"
+if a:
+    pass
+    # In the if.
+# Before the else.
+else: # On the else.
+    pass
+"#;
+    let expected = r#"if a:
+    pass
+    # In the if.
+# Before the else.
+else:  # On the else.
+    pass
+"#;
+    assert_eq!(format(program), expected);
+}
+
+#[test]
+fn test_comments_not_lost() {
+    let program = "x = (1 +  # One.\n  2)\n";
+    assert_eq!(format(program), "# One.\nx = 1 + 2\n");
+}
+
+#[test]
+fn test_build_file() {
+    let program = r#"
+load("@prelude//:rules.bzl", "cxx_library")
+
+cxx_library(srcs = ["b.cpp", "a.cpp"], deps = [":dep"], name = "lib", visibility = ["PUBLIC"])
+
+glob(["*.cpp"])
+
+def macro(name):
+    cxx_library(name = name, srcs = ["a.cpp", "b.cpp"])
+"#;
+    let expected = r#"load("@prelude//:rules.bzl", "cxx_library")
+
+cxx_library(
+    name = "lib",
+    srcs = [
+        "b.cpp",
+        "a.cpp",
+    ],
+    deps = [":dep"],
+    visibility = ["PUBLIC"],
+)
+
+glob(["*.cpp"])
+
+def macro(name):
+    cxx_library(name = name, srcs = ["a.cpp", "b.cpp"])
+"#;
+    assert_eq!(format_with("BUCK", program), expected);
+    // Outside build files, calls keep their layout.
+    assert_eq!(
+        format("f(srcs = [\"a\", \"b\"], name = \"x\")"),
+        "f(srcs = [\"a\", \"b\"], name = \"x\")\n"
+    );
+}
@@ -97,6 +97,7 @@ const manualSidebar = [
           'users/build_observability/interactive_console',
           'users/build_observability/logging',
           'users/build_observability/build_report',
+          'users/build_observability/opentelemetry',
           isInternal() ? 'users/build_observability/observability' : [],
           isInternal() ? 'users/build_observability/scuba' : [],
           isInternal() ? 'users/build_observability/ods' : [],
@@ -110,6 +111,11 @@ const manualSidebar = [
           'users/advanced/deferred_materialization',
           'users/advanced/restarter',
           'users/advanced/in_memory_cache',
+          'users/advanced/local_action_cache',
+          'users/advanced/sandboxing',
+          'users/advanced/local_resource_limits',
+          'users/advanced/dice_memory_budget',
+          'users/advanced/persisted_package_listings',
           isInternal() ? 'users/advanced/offline_build_archives' : [],
           isInternal() ? 'users/advanced/vpnless' : [],
         ],